@@ -0,0 +1,216 @@
+// Optional encryption of everything the tracker keeps on disk.
+//
+// When it is on, encryption.json in the data directory holds an argon2id salt and its
+// parameters, plus a verifier: a known string sealed with the derived key, so a wrong
+// passphrase is told apart from a damaged file. Every file written through seal_text is
+// then `{"sealed":"<base64 nonce + ciphertext>"}`, sealed with XChaCha20-Poly1305.
+//
+// The key only ever lives in memory. After a restart the store is locked until
+// unlock_store supplies the passphrase, and nothing is loaded or saved before then, so
+// a locked store or a mistyped passphrase can never write over the encrypted files.
+// read_text takes plaintext and sealed files alike, which keeps a switch that was cut
+// short (some files converted, some not) readable.
+
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::{lock_or_recover, write_atomically, TrackerError};
+
+const SEALED_PREFIX: &str = "{\"sealed\":\"";
+const SEALED_SUFFIX: &str = "\"}";
+const VERIFIER: &[u8] = b"screen-time-tracker";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+const MIN_PASSPHRASE_CHARS: usize = 8;
+
+struct Store {
+    locked: bool, // encryption.json exists but no passphrase has been given yet
+    key: Option<[u8; 32]>,
+    seal_writes: bool, // false while disabling: sealed files are still read
+}
+
+static STORE: Mutex<Store> = Mutex::new(Store { locked: false, key: None, seal_writes: false });
+
+// Kept in encryption.json. Nothing here is secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyFile {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    verifier: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreStatus {
+    pub encrypted: bool,
+    pub locked: bool,
+}
+
+fn key_file_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("encryption.json")
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], m_cost: u32, t_cost: u32, p_cost: u32) -> Result<[u8; 32], TrackerError> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+        .map_err(|e| TrackerError::InvalidInput(format!("Bad key parameters in encryption.json: {}", e)))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| TrackerError::InvalidInput(format!("Could not derive the key: {}", e)))?;
+    Ok(key)
+}
+
+// Base64 of a fresh nonce followed by the ciphertext.
+pub(crate) fn seal(key: &[u8; 32], plaintext: &[u8]) -> String {
+    let cipher = XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(key));
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(cipher.encrypt(&nonce, plaintext).expect("XChaCha20-Poly1305 encryption does not fail"));
+    BASE64.encode(sealed)
+}
+
+// None for a wrong key or a damaged message alike.
+pub(crate) fn open(key: &[u8; 32], sealed: &str) -> Option<Vec<u8>> {
+    let bytes = BASE64.decode(sealed).ok()?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(key)).decrypt(XNonce::from_slice(nonce), ciphertext).ok()
+}
+
+// Called once at startup. True when the store is encrypted and must be unlocked before
+// anything is loaded.
+pub fn detect(data_dir: &Path) -> bool {
+    let locked = key_file_path(data_dir).exists();
+    lock_or_recover(&STORE).locked = locked;
+    locked
+}
+
+pub fn status() -> StoreStatus {
+    let store = lock_or_recover(&STORE);
+    StoreStatus { encrypted: store.locked || store.key.is_some(), locked: store.locked }
+}
+
+pub fn is_locked() -> bool {
+    lock_or_recover(&STORE).locked
+}
+
+// The key for `passphrase`, if it opens encryption.json's verifier.
+fn verify(data_dir: &Path, passphrase: &str) -> Result<[u8; 32], TrackerError> {
+    let file: KeyFile = serde_json::from_str(&std::fs::read_to_string(key_file_path(data_dir))?)?;
+    let salt = BASE64
+        .decode(&file.salt)
+        .map_err(|_| TrackerError::InvalidInput("encryption.json is damaged".to_string()))?;
+    let key = derive_key(passphrase, &salt, file.m_cost, file.t_cost, file.p_cost)?;
+    match open(&key, &file.verifier) {
+        Some(verifier) if verifier == VERIFIER => Ok(key),
+        _ => Err(TrackerError::WrongPassphrase),
+    }
+}
+
+pub fn unlock(data_dir: &Path, passphrase: &str) -> Result<(), TrackerError> {
+    let key = verify(data_dir, passphrase)?;
+    let mut store = lock_or_recover(&STORE);
+    store.key = Some(key);
+    store.locked = false;
+    store.seal_writes = true;
+    Ok(())
+}
+
+// Derive a key from a new passphrase, write encryption.json and start sealing writes.
+// The caller then rewrites every file.
+pub fn enable(data_dir: &Path, passphrase: &str) -> Result<(), TrackerError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(TrackerError::InvalidInput(format!(
+            "The passphrase needs at least {} characters",
+            MIN_PASSPHRASE_CHARS
+        )));
+    }
+    if status().encrypted {
+        return Err(TrackerError::InvalidInput("Encryption is already on".to_string()));
+    }
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let (m_cost, t_cost, p_cost) = (Params::DEFAULT_M_COST, Params::DEFAULT_T_COST, Params::DEFAULT_P_COST);
+    let key = derive_key(passphrase, &salt, m_cost, t_cost, p_cost)?;
+    let file = KeyFile { salt: BASE64.encode(salt), m_cost, t_cost, p_cost, verifier: seal(&key, VERIFIER) };
+    // Written before any file is sealed, so a sealed file never exists without it.
+    write_atomically(&key_file_path(data_dir), &serde_json::to_string_pretty(&file)?)?;
+    let mut store = lock_or_recover(&STORE);
+    store.key = Some(key);
+    store.seal_writes = true;
+    Ok(())
+}
+
+// Check the passphrase and stop sealing writes. Sealed files stay readable until the
+// caller has rewritten them and calls finish_disable, or cancel_disable on failure.
+pub fn begin_disable(data_dir: &Path, passphrase: &str) -> Result<(), TrackerError> {
+    let current = status();
+    if current.locked {
+        return Err(TrackerError::StoreLocked);
+    }
+    if !current.encrypted {
+        return Err(TrackerError::InvalidInput("Encryption is not on".to_string()));
+    }
+    verify(data_dir, passphrase)?;
+    lock_or_recover(&STORE).seal_writes = false;
+    Ok(())
+}
+
+pub fn cancel_disable() {
+    lock_or_recover(&STORE).seal_writes = true;
+}
+
+// Removed last, once no sealed file is left.
+pub fn finish_disable(data_dir: &Path) -> Result<(), TrackerError> {
+    std::fs::remove_file(key_file_path(data_dir))?;
+    lock_or_recover(&STORE).key = None;
+    Ok(())
+}
+
+// A file's text, opened first if it is sealed.
+pub fn read_text(path: &Path) -> std::io::Result<String> {
+    open_text(std::fs::read_to_string(path)?, &path.display().to_string())
+}
+
+// One journal line, opened first if it is sealed. Lines are sealed one by one (see
+// seal_text) so appending never rewrites the file.
+pub fn open_line(line: &str) -> std::io::Result<String> {
+    open_text(line.to_string(), "a journal line")
+}
+
+fn open_text(raw: String, source: &str) -> std::io::Result<String> {
+    let Some(body) = raw.strip_prefix(SEALED_PREFIX).and_then(|rest| rest.trim_end().strip_suffix(SEALED_SUFFIX)) else {
+        return Ok(raw);
+    };
+    let store = lock_or_recover(&STORE);
+    let key = store
+        .key
+        .as_ref()
+        .ok_or_else(|| Error::new(ErrorKind::PermissionDenied, "the data store is locked"))?;
+    let plaintext = open(key, body)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("could not decrypt {}", source)))?;
+    String::from_utf8(plaintext).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+// `text` as it should be written: sealed while encryption is on, unchanged otherwise.
+pub fn seal_text(text: String) -> std::io::Result<String> {
+    let store = lock_or_recover(&STORE);
+    if store.locked {
+        return Err(Error::new(ErrorKind::PermissionDenied, "the data store is locked"));
+    }
+    match (&store.key, store.seal_writes) {
+        (Some(key), true) => Ok(format!("{}{}{}", SEALED_PREFIX, seal(key, text.as_bytes()), SEALED_SUFFIX)),
+        _ => Ok(text),
+    }
+}
@@ -1,16 +1,22 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
-use tauri::{State, AppHandle, Manager, RunEvent, WebviewUrl, WebviewWindowBuilder, WindowEvent};
-use tauri::menu::{MenuBuilder, MenuItemBuilder};
+use tauri::{State, AppHandle, Emitter, Manager, RunEvent, WebviewUrl, WebviewWindowBuilder, WindowEvent};
+use tauri::menu::{MenuBuilder, MenuItem, MenuItemBuilder, PredefinedMenuItem};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
 use std::process::Command;
 use std::thread;
 use std::time::Duration;
 use std::fs;
 use std::path::{Path, PathBuf};
+use tracing::{debug, error, info, warn};
+use tauri_plugin_global_shortcut::{Shortcut, ShortcutEvent, ShortcutState};
+
+mod encryption;
+mod platform;
+mod sync;
 
 #[cfg(target_os = "macos")]
 use cocoa::base::{id, nil};
@@ -22,6 +28,106 @@ pub struct Lap {
     pub start_time: u64,
     pub end_time: Option<u64>,
     pub duration: Option<u64>, // in seconds
+    // Normalized (trimmed, lowercased) category tags, e.g. "work" or "meeting".
+    // Absent in state files written before tagging existed.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // The project that was active when the lap started. Absent in older state files.
+    #[serde(default)]
+    pub project: Option<String>,
+    // "#RRGGBB" for colour-coding the lap in the timeline; None means the default colour.
+    #[serde(default)]
+    pub color: Option<String>,
+    // Seconds spent in each frontmost application while this lap ran (macOS only).
+    #[serde(default)]
+    pub app_breakdown: HashMap<String, u64>,
+    // Distinct focused-window titles seen during the lap, in first-seen order. Only ever
+    // filled when the user opts in (TrackerConfig::capture_window_titles) and never
+    // exported unless explicitly asked for.
+    #[serde(default)]
+    pub window_titles: Vec<String>,
+    // Free-text note describing what the lap was spent on.
+    #[serde(default)]
+    pub note: Option<String>,
+    // A break lap inserted by detect_and_fill_gaps rather than tracked time. Kept in the
+    // timeline but never counted towards any total.
+    #[serde(default)]
+    pub is_synthetic: bool,
+    // What closed the lap. None while open, and for laps closed before this was recorded
+    // or recovered after a crash.
+    #[serde(default)]
+    pub end_reason: Option<LapEndReason>,
+    // Random UUID given when the lap is opened; identifies it across machines (see
+    // Lap::key). Empty for laps recorded before ids existed.
+    #[serde(default)]
+    pub id: String,
+    // Seconds cut from this lap because the clock jumped while it ran (a suspend the
+    // gap detector caught, or a wall-clock step), i.e. time between its end and "now"
+    // when it was closed. Never counted as work; kept to show what the heuristic dropped.
+    #[serde(default)]
+    pub excluded_seconds: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LapEndReason {
+    UserStopped,
+    ScreenLocked,
+    SystemSlept,
+    ClockChanged, // the wall clock was stepped (NTP, manual change) mid-lap
+    DisplaySlept, // screen went dark with the machine still running
+    UserLogout,
+    DayEnded,
+    AutoEnded,
+    LapAdded, // the next lap started: new lap, project switch, pomodoro phase
+}
+
+impl Lap {
+    // A freshly started lap: open until something closes it.
+    fn open(start_time: u64) -> Self {
+        Lap {
+            start_time,
+            end_time: None,
+            duration: None,
+            tags: Vec::new(),
+            project: None,
+            color: None,
+            app_breakdown: HashMap::new(),
+            window_titles: Vec::new(),
+            note: None,
+            is_synthetic: false,
+            end_reason: None,
+            id: uuid::Uuid::new_v4().to_string(),
+            excluded_seconds: 0,
+        }
+    }
+
+    // Record that the stretch from this lap's end to `now` was left out of it.
+    fn note_excluded(&mut self, now: u64) {
+        let excluded = now.saturating_sub(self.end_time.unwrap_or(now));
+        if excluded > 0 {
+            self.excluded_seconds += excluded;
+            info!(excluded_seconds = excluded, "⏭️ Excluded {}s from the lap that started at {}", excluded, self.start_time);
+        }
+    }
+
+    // The lap's identity when merging copies of a day. A lap without an id gets one
+    // derived from its day and start time, so every machine's copy of it agrees.
+    fn key(&self, date: &str) -> String {
+        if self.id.is_empty() {
+            uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, format!("{}/{}", date, self.start_time).as_bytes()).to_string()
+        } else {
+            self.id.clone()
+        }
+    }
+
+    // Duration that counts as tracked time: None while open and for synthetic break laps.
+    fn worked_duration(&self) -> Option<u64> {
+        if self.is_synthetic {
+            None
+        } else {
+            self.duration
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,25 +136,729 @@ pub struct DayRecord {
     pub total_duration: u64, // in seconds
     pub laps: Vec<Lap>,
     pub is_active: bool,
+    // Free-form per-day values (mood, energy, location...) set through set_day_metadata.
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    // Closed by the user's end_day (not a rollover or auto-end), so auto_start_day leaves
+    // the rest of the day alone. Cleared when the day is started again.
+    #[serde(default)]
+    pub ended_explicitly: bool,
 }
 
 
 pub struct AppState {
     pub current_session: Arc<Mutex<Option<CurrentSession>>>,
     pub day_records: Arc<Mutex<HashMap<String, DayRecord>>>,
+    // Snapshots taken before recent user actions, newest last. In-memory only: undo does
+    // not survive a restart.
+    pub undo_stack: Arc<Mutex<Vec<UndoEntry>>>,
+    pub config: Arc<Mutex<TrackerConfig>>,
+    // The running pomodoro, if any. In-memory only: a restart returns to free-running laps.
+    pub pomodoro: Arc<Mutex<Option<PomodoroState>>>,
+    // Liveness of the background threads, reported by get_health.
+    pub health: Arc<ThreadHealth>,
+    // Outcome of recent saves, reported by get_storage_info.
+    pub persistence: Arc<PersistenceHealth>,
+    // Pause/resume/lap history for the activity feed.
+    pub events: Arc<Mutex<EventLog>>,
+    // Stops the monitoring, status and autosave loops when the app quits.
+    pub shutdown: Arc<ShutdownToken>,
+    // Month archives ("YYYY-MM") read so far, for range queries that reach into them.
+    // A leaf lock: never take another lock while holding it.
+    pub archive_cache: Arc<Mutex<HashMap<String, Arc<Vec<DayRecord>>>>>,
+    // Per-day totals of settled history for get_statistics. A leaf lock, like archive_cache.
+    pub stats_cache: Arc<Mutex<Option<StatsCache>>>,
+    // The local HTTP status server while it is enabled. Never held while taking another lock.
+    pub status_server: Arc<Mutex<Option<StatusServer>>>,
+    // The state-tick subscription, if any. A leaf lock, like status_server.
+    pub state_subscription: Arc<Mutex<Option<StateSubscription>>>,
+    // Pending days and outcome of the last sync round. Its lock is taken after day_records.
+    pub sync: Arc<sync::SyncEngine>,
+    // The keep-display-awake assertion while one is held (see sync_display_assertion).
+    // A leaf lock.
+    pub display_assertion: Arc<Mutex<Option<usize>>>,
+    // screentime:// links that arrived while the store was locked, run by unlock_store.
+    // Taken before any other lock.
+    pub pending_deep_links: Arc<Mutex<Vec<String>>>,
+}
+
+#[derive(Default)]
+pub struct PersistenceHealth {
+    pub last_saved_at: AtomicU64, // unix seconds of the last successful save, 0 = none yet
+    pub last_alerted_at: AtomicU64, // 0 while saves are succeeding
+    pub last_error: Mutex<Option<String>>, // cleared by the next successful save
+    pub last_recovery: Mutex<Option<StateRecovery>>, // set if startup found state.json corrupt
+    // Set if startup ended the previous day; cleared by acknowledge_day_auto_closed.
+    pub day_auto_closed: Mutex<Option<DayAutoClosed>>,
+    // Fingerprint of every file in days/ as last written or loaded, keyed by date.
+    pub written_days: Mutex<HashMap<String, u64>>,
+    // days/ while its files are not yet read into day_records (see load_settled_days).
+    // Taken before day_records, never while holding it.
+    pub cold_days: Mutex<Option<PathBuf>>,
+    // Set, under the day_records lock, once load_settled_days has run.
+    pub settled_loaded: AtomicBool,
+    // Fingerprints of the days load_settled_days read, until a save whose snapshot
+    // includes them moves them into written_days. A leaf lock.
+    pub loaded_days: Mutex<HashMap<String, u64>>,
+    // Sequence number of the latest journal entry. Snapshots record the value they
+    // include, so replay skips what state.json already holds.
+    pub journal_seq: AtomicU64,
+    // Serialises appends with the compaction that follows each save. Taken after the
+    // session and records locks, never before them.
+    pub journal_lock: Mutex<()>,
+    // Bumped whenever a save writes or removes a settled day's file, i.e. whenever
+    // settled history changed. Caches over settled days compare against it.
+    pub settled_generation: AtomicU64,
+}
+
+impl PersistenceHealth {
+    fn note_success(&self, now: u64) {
+        self.last_saved_at.store(now, Ordering::Relaxed);
+        self.last_alerted_at.store(0, Ordering::Relaxed);
+        *lock_or_recover(&self.last_error) = None;
+    }
+}
+
+// Unix timestamps of each background loop's latest iteration (0 = never ran). A thread
+// that panicked stops beating, which is how get_health tells the UI it died.
+#[derive(Default)]
+pub struct ThreadHealth {
+    pub monitor_heartbeat: AtomicU64,
+    pub autosave_heartbeat: AtomicU64,
+}
+
+// Stops the background loops. Each loop sleeps through ShutdownToken::sleep, which
+// returns false (early, if need be) once shutdown is requested; threads started through
+// spawn are joined by shutdown.
+#[derive(Default)]
+pub struct ShutdownToken {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+    workers: Mutex<Vec<(String, thread::JoinHandle<()>)>>,
+    // For threads that block somewhere else (e.g. a message pump) and need a nudge.
+    on_shutdown: Mutex<Vec<Box<dyn FnOnce() + Send>>>,
+}
+
+impl ShutdownToken {
+    pub fn is_stopped(&self) -> bool {
+        *lock_or_recover(&self.stopped)
+    }
+
+    // Wait up to `duration`. False once shutdown has been requested: the loop should exit.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let stopped = lock_or_recover(&self.stopped);
+        let (stopped, _) = self
+            .wake
+            .wait_timeout_while(stopped, duration, |stopped| !*stopped)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        !*stopped
+    }
+
+    pub fn spawn(&self, name: &str, work: impl FnOnce() + Send + 'static) {
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(work)
+            .expect("failed to spawn background thread");
+        lock_or_recover(&self.workers).push((name.to_string(), handle));
+    }
+
+    pub fn on_shutdown(&self, hook: impl FnOnce() + Send + 'static) {
+        lock_or_recover(&self.on_shutdown).push(Box::new(hook));
+    }
+
+    // Signal every loop and wait up to `timeout` for them to finish. Returns the names of
+    // the threads still running (e.g. stuck in a slow platform probe); those are left
+    // detached.
+    pub fn shutdown(&self, timeout: Duration) -> Vec<String> {
+        *lock_or_recover(&self.stopped) = true;
+        self.wake.notify_all();
+        for hook in lock_or_recover(&self.on_shutdown).drain(..) {
+            hook();
+        }
+
+        let deadline = Instant::now() + timeout;
+        let workers: Vec<_> = lock_or_recover(&self.workers).drain(..).collect();
+        while Instant::now() < deadline && !workers.iter().all(|(_, handle)| handle.is_finished()) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut stragglers = Vec::new();
+        for (name, handle) in workers {
+            if handle.is_finished() {
+                let _ = handle.join();
+            } else {
+                stragglers.push(name);
+            }
+        }
+        stragglers
+    }
+}
+
+// When this process started; the grace period for loops that have not beaten yet.
+static APP_STARTED_AT: AtomicU64 = AtomicU64::new(0);
+
+// How many times a lock was found poisoned and taken over anyway (see lock_or_recover).
+static POISON_RECOVERIES: AtomicU64 = AtomicU64::new(0);
+
+// Take a lock even if a thread panicked while holding it. The data behind every lock
+// here stays consistent between statements, so the inner guard is still usable; the
+// poison is cleared so commands (which report StatePoisoned) recover too. Without this a
+// single panic would make the monitor and autosave loops panic on every later tick.
+fn lock_or_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        warn!("⚠️ Recovered a poisoned lock");
+        POISON_RECOVERIES.fetch_add(1, Ordering::Relaxed);
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+// User preferences and cached platform state, persisted alongside the history. Every
+// field must tolerate being absent from older state files (hence the struct-level
+// serde default, which is the Default impl below).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackerConfig {
+    // Whether macOS granted permission to post notifications; asked once, then cached.
+    pub notifications_authorized: bool,
+    // Alert once a stretch of work without any pause reaches this many seconds.
+    pub max_continuous_work_secs: Option<u64>,
+    // Opt-in: record the focused window's title on each lap. Titles can hold document
+    // names, URLs and message subjects, so this is off unless the user turns it on.
+    pub capture_window_titles: bool,
+    // Target tracked seconds per day. None means no goal.
+    pub daily_goal_secs: Option<u64>,
+    // Remind the user to take a break every this many minutes of a single lap. 0 = off.
+    pub break_reminder_minutes: u64,
+    // Turns off the "goal reached" notification.
+    pub goal_notification_muted: bool,
+    // Day key the goal notification was last sent for. Keyed by day rather than a plain
+    // flag so it lapses by itself when the session rolls over to a new day, and so a
+    // restart later the same day doesn't notify again.
+    pub goal_notified_day: Option<String>,
+    // Warn when a day's total passes this many seconds. None means no cap.
+    pub overtime_alert_seconds: Option<u64>,
+    // Day key the overtime alert was last sent for; see goal_notified_day.
+    pub overtime_notified_day: Option<String>,
+    // How long the monitoring loop sleeps between ticks, within POLL_INTERVAL_RANGE_MS.
+    pub monitoring_poll_interval_ms: u64,
+    // Poll more slowly while nothing is being tracked, and on battery power.
+    pub adaptive_polling: bool,
+    pub pomodoro: PomodoroConfig,
+    // Indent state.json for hand-reading; off writes it compact (much smaller).
+    pub use_pretty_json: bool,
+    // Notes given to laps by the hour they started, e.g. "Deep work" for 9-12.
+    pub auto_tag_rules: Vec<TaggingRule>,
+    // The expected working window in local hours, [start, end). Time outside it is
+    // reported as out-of-hours.
+    pub work_start_hour: u8,
+    pub work_end_hour: u8,
+    // First day of the week for weekly summaries, ISO-numbered from 0 = Monday to
+    // 6 = Sunday.
+    pub week_start_day: u8,
+    // Notify on unlock when the user was away at least this many seconds. None = off.
+    pub idle_gap_notification_secs: Option<u64>,
+    // Archive and drop days older than this many days on every autosave. None = keep all.
+    pub retention_days: Option<u32>,
+    // Archive each month once it is over (on autosave), keeping state.json to the
+    // current month. Range queries still see the archived days.
+    pub archive_completed_months: bool,
+    // "YYYY-MM" months brought back by unarchive_month. Autosave archiving (retention and
+    // archive_completed_months) leaves them live; an explicit archive_month clears it.
+    pub unarchived_months: Vec<String>,
+    // Billing rate per tracked hour for get_invoice_data, in `currency` (ISO 4217).
+    pub hourly_rate: Option<f64>,
+    pub currency: String,
+    // Saved day plans for start_day_from_template, unique by name.
+    pub session_templates: Vec<SessionTemplate>,
+    // Laps per worked hour that get_focus_score treats as fully distracted (score 0).
+    pub max_expected_fragmentation: f64,
+    // Linux only: seconds without input that count as away (see platform::linux).
+    pub idle_threshold_secs: u64,
+    // Pause tracking when running on battery below this charge (percent). None = off.
+    pub pause_on_battery_percent: Option<u8>,
+    // End a forgotten session when the local clock reaches this hour (0-23). None = off.
+    pub auto_end_day_at_hour: Option<u8>,
+    // Serve GET /status and GET /today on 127.0.0.1:status_server_port (for overlays).
+    pub status_server_enabled: bool,
+    pub status_server_port: u16,
+    // Send Access-Control-Allow-Origin: * so browser-based overlays on other origins can
+    // read the server. Off by default: with it on, any page open in a browser can.
+    pub status_server_allow_cors: bool,
+    // Days expected to be tracked, 0 = Monday through 6 = Sunday. Monday-Friday by default.
+    pub work_days: Vec<u8>,
+    // Self-hosted backend day records are mirrored to (see sync.rs). None = offline only.
+    pub sync: Option<sync::SyncConfig>,
+    // Global shortcuts ("CmdOrCtrl+Shift+T" style) that work with the window hidden: one
+    // pauses or resumes the timer, the other splits the lap. None = not registered.
+    pub toggle_shortcut: Option<String>,
+    pub lap_shortcut: Option<String>,
+    // Start the day on the first unlock or wake when no session is running, unless the
+    // user already ended today. With auto_start_weekdays_only, only on work_days.
+    pub auto_start_day: bool,
+    pub auto_start_weekdays_only: bool,
+    // Off by default. While a session is tracking (not paused), ask the OS to keep the
+    // display on, so a long read without input does not blank the screen and pause.
+    pub keep_display_awake: bool,
+    // On by default. When the display sleeps with the machine still running, close the
+    // lap and pause; off keeps tracking through a dark screen until an actual lock.
+    pub pause_on_display_sleep: bool,
+    // On by default. Closing the lid pauses like system sleep when the Mac is going to
+    // sleep because of it; docked in clamshell mode it stays awake and keeps tracking.
+    pub pause_on_lid_close: bool,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        TrackerConfig {
+            notifications_authorized: false,
+            max_continuous_work_secs: None,
+            capture_window_titles: false,
+            daily_goal_secs: None,
+            break_reminder_minutes: 0,
+            goal_notification_muted: false,
+            goal_notified_day: None,
+            overtime_alert_seconds: None,
+            overtime_notified_day: None,
+            monitoring_poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+            adaptive_polling: true,
+            pomodoro: PomodoroConfig::default(),
+            use_pretty_json: true,
+            auto_tag_rules: Vec::new(),
+            work_start_hour: 9,
+            work_end_hour: 17,
+            week_start_day: 0,
+            idle_gap_notification_secs: None,
+            retention_days: None,
+            archive_completed_months: false,
+            unarchived_months: Vec::new(),
+            hourly_rate: None,
+            currency: "USD".to_string(),
+            session_templates: Vec::new(),
+            max_expected_fragmentation: DEFAULT_MAX_EXPECTED_FRAGMENTATION,
+            idle_threshold_secs: DEFAULT_IDLE_THRESHOLD_SECS,
+            pause_on_battery_percent: None,
+            auto_end_day_at_hour: None,
+            status_server_enabled: false,
+            status_server_port: DEFAULT_STATUS_SERVER_PORT,
+            status_server_allow_cors: false,
+            work_days: vec![0, 1, 2, 3, 4],
+            sync: None,
+            toggle_shortcut: Some("CmdOrCtrl+Shift+T".to_string()),
+            lap_shortcut: Some("CmdOrCtrl+Shift+L".to_string()),
+            auto_start_day: false,
+            auto_start_weekdays_only: false,
+            keep_display_awake: false,
+            pause_on_display_sleep: true,
+            pause_on_lid_close: true,
+        }
+    }
+}
+
+// Once per second by default. Sub-second lock/sleep latency isn't needed for a time
+// tracker (a ~1s error at a lap boundary is negligible), and 1s halves the subprocess
+// spawns vs. the old 500ms.
+const DEFAULT_POLL_INTERVAL_MS: u64 = 1000;
+const POLL_INTERVAL_RANGE_MS: std::ops::RangeInclusive<u64> = 100..=5000;
+
+// Five minutes without input, a common screen-blank default.
+const DEFAULT_IDLE_THRESHOLD_SECS: u64 = 300;
+const MIN_IDLE_THRESHOLD_SECS: u64 = 60;
+
+// The longest break-reminder interval: a day.
+const MAX_BREAK_REMINDER_MINUTES: u64 = 24 * 60;
+
+// Adaptive polling: with no running lap there is only an unlock or a day change to
+// notice, so the loop relaxes to IDLE_POLL_INTERVAL; on battery every interval doubles,
+// up to the MAX_ADAPTIVE_POLL_INTERVAL ceiling.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(3);
+const MAX_ADAPTIVE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+impl TrackerConfig {
+    // The configured interval, clamped in case the state file was edited by hand.
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(
+            self.monitoring_poll_interval_ms
+                .clamp(*POLL_INTERVAL_RANGE_MS.start(), *POLL_INTERVAL_RANGE_MS.end()),
+        )
+    }
+
+    // How long the monitoring loop should sleep next. Never faster than configured.
+    fn effective_poll_interval(&self, tracking: bool, on_battery: bool) -> Duration {
+        let base = self.poll_interval();
+        if !self.adaptive_polling {
+            return base;
+        }
+        let mut interval = if tracking { base } else { base.max(IDLE_POLL_INTERVAL) };
+        if on_battery {
+            interval = (interval * 2).min(MAX_ADAPTIVE_POLL_INTERVAL);
+        }
+        interval.max(base)
+    }
+}
+
+// A lap that starts at or after start_hour and before end_hour (local time) and has no
+// note yet gets `name` as its note when it ends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaggingRule {
+    pub name: String,
+    pub start_hour: u8,
+    pub end_hour: u8,
+}
+
+fn validate_tagging_rules(rules: &[TaggingRule]) -> Result<(), String> {
+    let mut seen = std::collections::HashSet::new();
+    for rule in rules {
+        let name = rule.name.trim();
+        if name.is_empty() {
+            return Err("Rule names cannot be empty".to_string());
+        }
+        if rule.end_hour > 23 || rule.start_hour >= rule.end_hour {
+            return Err(format!("Rule '{}' needs start_hour < end_hour <= 23", name));
+        }
+        if !seen.insert(name.to_lowercase()) {
+            return Err(format!("Duplicate rule name '{}'", name));
+        }
+    }
+    Ok(())
+}
+
+// The first rule whose window contains the hour.
+fn matching_rule(rules: &[TaggingRule], hour: u32) -> Option<&TaggingRule> {
+    rules
+        .iter()
+        .find(|rule| (u32::from(rule.start_hour)..u32::from(rule.end_hour)).contains(&hour))
+}
+
+// Note a just-ended lap from the rules, unless the user already described it.
+fn auto_tag_lap(lap: &mut Lap, rules: &[TaggingRule]) {
+    if lap.note.is_some() {
+        return;
+    }
+    if let Some(rule) = matching_rule(rules, local_hour_of(lap.start_time)) {
+        lap.note = Some(rule.name.trim().to_string());
+    }
+}
+
+const POMODORO_WORK_TAG: &str = "pomodoro-work";
+const POMODORO_BREAK_TAG: &str = "pomodoro-break";
+const POMODORO_BREAK_NOTE: &str = "Pomodoro Break";
+
+// Interval lengths for pomodoro mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub work_seconds: u64,
+    pub short_break_seconds: u64,
+    pub long_break_seconds: u64,
+    // Every Nth break is a long one; 0 means never.
+    pub long_break_every: u32,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        PomodoroConfig {
+            work_seconds: 25 * 60,
+            short_break_seconds: 5 * 60,
+            long_break_seconds: 15 * 60,
+            long_break_every: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PomodoroPhase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+// A pomodoro cycle riding on the normal laps: every interval change closes the running
+// lap and opens a new one labelled with the interval.
+#[derive(Debug, Clone, Serialize)]
+pub struct PomodoroState {
+    pub phase: PomodoroPhase,
+    // Counts down only while a lap is running, so a lock or pause holds the clock.
+    pub phase_remaining_seconds: u64,
+    pub completed_work_intervals: u32,
+    pub config: PomodoroConfig, // as it was when the cycle started
+}
+
+impl PomodoroState {
+    fn new(config: PomodoroConfig) -> Self {
+        PomodoroState {
+            phase: PomodoroPhase::Work,
+            phase_remaining_seconds: config.work_seconds,
+            completed_work_intervals: 0,
+            config,
+        }
+    }
+
+    fn is_break(&self) -> bool {
+        self.phase != PomodoroPhase::Work
+    }
+
+    fn tag(&self) -> &'static str {
+        if self.is_break() {
+            POMODORO_BREAK_TAG
+        } else {
+            POMODORO_WORK_TAG
+        }
+    }
+
+    // Count down `elapsed` seconds of tracked time. Returns true when the interval changed.
+    fn advance(&mut self, elapsed: u64) -> bool {
+        self.phase_remaining_seconds = self.phase_remaining_seconds.saturating_sub(elapsed);
+        if self.phase_remaining_seconds > 0 {
+            return false;
+        }
+        if self.is_break() {
+            self.phase = PomodoroPhase::Work;
+            self.phase_remaining_seconds = self.config.work_seconds;
+        } else {
+            self.completed_work_intervals += 1;
+            let every = self.config.long_break_every;
+            if every > 0 && self.completed_work_intervals.is_multiple_of(every) {
+                self.phase = PomodoroPhase::LongBreak;
+                self.phase_remaining_seconds = self.config.long_break_seconds;
+            } else {
+                self.phase = PomodoroPhase::ShortBreak;
+                self.phase_remaining_seconds = self.config.short_break_seconds;
+            }
+        }
+        true
+    }
+}
+
+// Error returned by every command. Serialises as `{ "code": "...", "message": "..." }`:
+// `code` is stable for the frontend to branch on, `message` is the Display text.
+#[derive(Debug)]
+pub enum TrackerError {
+    NoActiveSession,
+    AlreadyTracking,
+    DayNotFound(String),
+    LapIndexOutOfRange { day: String, index: usize },
+    PersistenceError(std::io::Error),
+    StatePoisoned,
+    InvalidInput(String),
+    StoreLocked,
+    WrongPassphrase,
+}
+
+impl TrackerError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TrackerError::NoActiveSession => "no_active_session",
+            TrackerError::AlreadyTracking => "already_tracking",
+            TrackerError::DayNotFound(_) => "day_not_found",
+            TrackerError::LapIndexOutOfRange { .. } => "lap_index_out_of_range",
+            TrackerError::PersistenceError(_) => "persistence_error",
+            TrackerError::StatePoisoned => "state_poisoned",
+            TrackerError::InvalidInput(_) => "invalid_input",
+            TrackerError::StoreLocked => "store_locked",
+            TrackerError::WrongPassphrase => "wrong_passphrase",
+        }
+    }
+}
+
+impl std::fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrackerError::NoActiveSession => write!(f, "No active session. Start the day first."),
+            TrackerError::AlreadyTracking => write!(f, "Already tracking today's session"),
+            TrackerError::DayNotFound(day) => write!(f, "No record for {}", day),
+            TrackerError::LapIndexOutOfRange { day, index } => write!(f, "No lap {} on {}", index, day),
+            TrackerError::PersistenceError(e) => write!(f, "Could not read or write data: {}", e),
+            TrackerError::StatePoisoned => write!(f, "Internal state is unavailable after an earlier failure"),
+            TrackerError::InvalidInput(msg) => write!(f, "{}", msg),
+            TrackerError::StoreLocked => write!(f, "Your data is encrypted; unlock it with your passphrase first"),
+            TrackerError::WrongPassphrase => write!(f, "Wrong passphrase"),
+        }
+    }
+}
+
+impl std::error::Error for TrackerError {}
+
+impl Serialize for TrackerError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("TrackerError", 2)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for TrackerError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        TrackerError::StatePoisoned
+    }
+}
+
+impl From<std::io::Error> for TrackerError {
+    fn from(e: std::io::Error) -> Self {
+        TrackerError::PersistenceError(e)
+    }
+}
+
+impl From<serde_json::Error> for TrackerError {
+    fn from(e: serde_json::Error) -> Self {
+        TrackerError::PersistenceError(e.into())
+    }
+}
+
+// Validation helpers report plain messages; they surface as InvalidInput.
+impl From<String> for TrackerError {
+    fn from(msg: String) -> Self {
+        TrackerError::InvalidInput(msg)
+    }
+}
+
+pub struct UndoEntry {
+    state: PersistedState,
+    description: String,
 }
 
 pub type AppStateArc = Arc<AppState>;
 
 pub struct CurrentSession {
-    pub start_time: Instant,
     pub day_key: String,
-    pub current_lap_start: Instant,
     pub current_lap_start_timestamp: u64, // SystemTime timestamp for accurate tracking
-    pub accumulated_seconds: u64, // Accumulated seconds for current lap
-    pub last_activity_time: Instant, // To detect sleep/hibernate gaps
+    pub accumulated_seconds: u64, // Active seconds in the current lap, advanced only by the monitor tick
     pub is_paused: bool,
     pub user_paused: bool, // True if user manually paused, false if system paused (lock/sleep)
+    pub project: Option<String>, // Stamped onto every lap this session opens
+    // When the current unbroken stretch of work began (None while paused). Maintained by
+    // the monitoring loop; any pause — lock, sleep, or manual — ends the stretch.
+    pub continuous_work_start: Option<u64>, // unix seconds
+    pub continuous_work_alerted: bool, // The break alert already fired for this stretch
+    // Break reminders already sent for the running lap; reset whenever a lap starts.
+    pub break_reminders_sent: u64,
+    // Why and since when (unix seconds) the session is paused; both None while tracking.
+    pub paused_cause: Option<PauseCause>,
+    pub paused_since: Option<u64>,
+    pub overrides: DaySessionOverrides, // dropped with the session at end_day
+    // The day plan picked with start_day_from_template, if any.
+    pub template: Option<TemplateProgress>,
+}
+
+// A named day plan: each add_lap pre-fills the new lap's note from the next entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTemplate {
+    pub name: String,
+    pub laps: Vec<TemplateLap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateLap {
+    pub expected_duration_secs: u64,
+    pub note: String,
+}
+
+// How far through its template a session has got; persisted with the session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateProgress {
+    pub template: SessionTemplate,
+    pub next_lap: usize,
+}
+
+impl TemplateProgress {
+    // The note for the lap being opened, or None once the template has run out.
+    fn take_next_note(&mut self) -> Option<String> {
+        let entry = self.template.laps.get(self.next_lap)?;
+        self.next_lap += 1;
+        Some(entry.note.clone()).filter(|note| !note.is_empty())
+    }
+}
+
+const MAX_TEMPLATE_NAME_LEN: usize = 64;
+
+fn normalize_template(template: SessionTemplate) -> Result<SessionTemplate, String> {
+    let name = template.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Template name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_TEMPLATE_NAME_LEN {
+        return Err(format!("Template name cannot be longer than {} characters", MAX_TEMPLATE_NAME_LEN));
+    }
+    if template.laps.is_empty() {
+        return Err(format!("Template '{}' needs at least one lap", name));
+    }
+    let laps = template.laps.into_iter()
+        .map(|lap| TemplateLap { note: lap.note.trim().to_string(), ..lap })
+        .collect();
+    Ok(SessionTemplate { name, laps })
+}
+
+// Per-day settings chosen at start_day_with_config. Each one set takes precedence over
+// TrackerConfig until the day ends; Some(0) switches the goal or break alert off.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaySessionOverrides {
+    pub daily_goal_seconds: Option<u64>,
+    // Wall-clock jump between monitor ticks that counts as a suspend (default
+    // SUSPEND_GAP_THRESHOLD_SECS); raise it on a machine that stalls without sleeping.
+    pub gap_threshold_secs: Option<u64>,
+    pub max_continuous_work_secs: Option<u64>,
+}
+
+impl DaySessionOverrides {
+    fn daily_goal(&self, config: &TrackerConfig) -> Option<u64> {
+        self.daily_goal_seconds.or(config.daily_goal_secs).filter(|s| *s > 0)
+    }
+
+    fn max_continuous_work(&self, config: &TrackerConfig) -> Option<u64> {
+        self.max_continuous_work_secs.or(config.max_continuous_work_secs).filter(|s| *s > 0)
+    }
+
+    fn gap_threshold(&self) -> u64 {
+        self.gap_threshold_secs.unwrap_or(SUSPEND_GAP_THRESHOLD_SECS)
+    }
+}
+
+// What froze the timer, reported in CurrentStatus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PauseCause {
+    User,
+    Lock,
+    Sleep,
+    #[serde(rename = "display_sleep")]
+    DisplaySleep,
+    Logout,
+    Restart, // restored paused from a state file that recorded no cause
+}
+
+impl CurrentSession {
+    fn pause(&mut self, cause: PauseCause, at: u64) {
+        self.is_paused = true;
+        self.user_paused = cause == PauseCause::User;
+        self.paused_cause = Some(cause);
+        self.paused_since = Some(at);
+    }
+
+    fn resume(&mut self) {
+        self.is_paused = false;
+        self.user_paused = false;
+        self.paused_cause = None;
+        self.paused_since = None;
+    }
+
+    // Seconds since the running lap opened, by the wall clock. Instant stops while the
+    // machine sleeps; the unix clock does not, and it survives a restart. A clock set
+    // back past the lap start reads as zero rather than underflowing.
+    pub fn compute_accurate_elapsed(&self) -> u64 {
+        now_unix().saturating_sub(self.current_lap_start_timestamp)
+    }
+
+    // Open a lap carrying the session's active project.
+    fn open_lap(&mut self, start_time: u64) -> Lap {
+        self.break_reminders_sent = 0;
+        Lap {
+            project: self.project.clone(),
+            ..Lap::open(start_time)
+        }
+    }
 }
 
 impl AppState {
@@ -56,6 +866,20 @@ impl AppState {
         Self {
             current_session: Arc::new(Mutex::new(None)),
             day_records: Arc::new(Mutex::new(HashMap::new())),
+            undo_stack: Arc::new(Mutex::new(Vec::new())),
+            config: Arc::new(Mutex::new(TrackerConfig::default())),
+            pomodoro: Arc::new(Mutex::new(None)),
+            health: Arc::new(ThreadHealth::default()),
+            persistence: Arc::new(PersistenceHealth::default()),
+            events: Arc::new(Mutex::new(EventLog::default())),
+            shutdown: Arc::new(ShutdownToken::default()),
+            archive_cache: Arc::new(Mutex::new(HashMap::new())),
+            stats_cache: Arc::new(Mutex::new(None)),
+            status_server: Arc::new(Mutex::new(None)),
+            state_subscription: Arc::new(Mutex::new(None)),
+            sync: Arc::new(sync::SyncEngine::default()),
+            display_assertion: Arc::new(Mutex::new(None)),
+            pending_deep_links: Arc::new(Mutex::new(Vec::new())),
         }
     }
 }
@@ -71,6 +895,16 @@ struct PersistedSessionState {
     // Older state files won't have this field, so default to false on load.
     #[serde(default)]
     user_paused: bool,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    paused_cause: Option<PauseCause>,
+    #[serde(default)]
+    paused_since: Option<u64>,
+    #[serde(default)]
+    overrides: DaySessionOverrides,
+    #[serde(default)]
+    template: Option<TemplateProgress>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -86,6 +920,14 @@ struct PersistedState {
     // before migrations existed, which is exactly the data that needs them -> default 0.
     #[serde(default)]
     schema_version: u32,
+    #[serde(default)]
+    config: TrackerConfig,
+    // The activity feed, per day. Absent in files written before it existed.
+    #[serde(default)]
+    events: HashMap<String, Vec<TrackerEvent>>,
+    // The last journal entry reflected in this snapshot.
+    #[serde(default)]
+    journal_seq: u64,
 }
 
 // Bump when a new one-off migration is added in load_and_initialize.
@@ -96,8 +938,17 @@ const CURRENT_SCHEMA_VERSION: u32 = 1;
 // so a "day" matches the user's real day. The day_key is fixed when a session starts
 // and never changes while that session runs — this is what gives late-night work that
 // crosses midnight to the day it started on.
-fn local_date() -> String {
-    chrono::Local::now().format("%Y-%m-%d").to_string()
+fn today_local_key() -> String {
+    date_key_in(chrono::Utc::now(), &chrono::Local)
+}
+
+// The calendar date of an instant in a given timezone; split out so the conversion can
+// be tested against fixed offsets.
+fn date_key_in<Tz: chrono::TimeZone>(instant: chrono::DateTime<chrono::Utc>, tz: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    instant.with_timezone(tz).format("%Y-%m-%d").to_string()
 }
 
 fn now_unix() -> u64 {
@@ -184,6 +1035,8 @@ fn backfill_misattributed_laps(
             total_duration: 0,
             laps: Vec::new(),
             is_active: false,
+            metadata: HashMap::new(),
+            ended_explicitly: false,
         });
         record.laps.push(lap);
     }
@@ -192,7 +1045,7 @@ fn backfill_misattributed_laps(
     // laps at all (it only ever existed because of the misfiling).
     for record in records.values_mut() {
         record.laps.sort_by_key(|l| l.start_time);
-        record.total_duration = record.laps.iter().filter_map(|l| l.duration).sum();
+        record.total_duration = record.laps.iter().filter_map(Lap::worked_duration).sum();
     }
     records.retain(|key, record| {
         !record.laps.is_empty() || Some(key.as_str()) == session_day
@@ -201,6 +1054,25 @@ fn backfill_misattributed_laps(
     count
 }
 
+// Reset every total_duration that no longer matches the sum of its laps, the same sum
+// every lap edit computes. Returns the corrected dates in order.
+fn recompute_totals(records: &mut HashMap<String, DayRecord>) -> Vec<String> {
+    let mut corrected: Vec<String> = records
+        .values_mut()
+        .filter_map(|record| {
+            let total = record.laps.iter().filter_map(Lap::worked_duration).sum();
+            if record.total_duration == total {
+                return None;
+            }
+            warn!("🔧 {} total was {}s, laps add up to {}s", record.date, record.total_duration, total);
+            record.total_duration = total;
+            Some(record.date.clone())
+        })
+        .collect();
+    corrected.sort();
+    corrected
+}
+
 // Where the outgoing day ends and today begins.
 //
 // A session that worked straight through the night is split at the cutoff, so the
@@ -220,7 +1092,7 @@ fn rollover_boundary(working_through: bool, cutoff_ts: u64, now: u64) -> u64 {
 fn merge_records_into_previous(
     records: &mut HashMap<String, DayRecord>,
     date: &str,
-) -> Result<String, String> {
+) -> Result<String, TrackerError> {
     // Dates are "YYYY-MM-DD", so the lexicographic max below `date` is the nearest
     // earlier day that actually has a record (days the machine was off simply don't exist).
     let target = records
@@ -228,2045 +1100,11787 @@ fn merge_records_into_previous(
         .filter(|k| k.as_str() < date)
         .max()
         .cloned()
-        .ok_or_else(|| format!("No earlier day to merge {} into", date))?;
+        .ok_or_else(|| TrackerError::InvalidInput(format!("No earlier day to merge {} into", date)))?;
 
     let source = records
         .remove(date)
-        .ok_or_else(|| format!("No record for {}", date))?;
+        .ok_or_else(|| TrackerError::DayNotFound(date.to_string()))?;
 
     let dest = records
         .get_mut(&target)
-        .ok_or_else(|| format!("No record for {}", target))?;
+        .ok_or_else(|| TrackerError::DayNotFound(target.clone()))?;
 
     dest.laps.extend(source.laps);
     dest.laps.sort_by_key(|l| l.start_time);
-    dest.total_duration = dest.laps.iter().filter_map(|l| l.duration).sum();
+    dest.total_duration = dest.laps.iter().filter_map(Lap::worked_duration).sum();
     // The absorbing day inherits whether the merged day was still being tracked.
     dest.is_active = source.is_active;
 
     Ok(target)
 }
 
-// Today's DAY_CUTOFF_HOUR as a unix timestamp, in local time.
-fn cutoff_timestamp_today() -> u64 {
-    use chrono::Timelike;
-    chrono::Local::now()
-        .with_hour(DAY_CUTOFF_HOUR)
-        .and_then(|t| t.with_minute(0))
-        .and_then(|t| t.with_second(0))
-        .and_then(|t| t.with_nanosecond(0))
-        .map(|t| t.timestamp().max(0) as u64)
-        .unwrap_or_else(now_unix)
-}
+// Tags are free-form, but "Work", " work" and "WORK" are the same category — normalize
+// before storing or comparing so aggregation doesn't split one bucket into three.
+const MAX_TAGS_PER_LAP: usize = 8;
+const UNTAGGED: &str = "untagged";
 
-// Roll the session onto today if the rules above say the previous day is over.
-// Returns (previous_day_key, its final total) when a rollover happened, so the caller
-// can tell the user which day was just closed.
-//
-// Called from the unlock/wake path (the user is back after a break) and from the 30s
-// autosave tick (which is what catches a session that never paused at all).
-fn maybe_roll_over_day(app_handle: &AppHandle, state: &AppStateArc) -> Option<(String, u64)> {
-    let mut session_guard = state.current_session.lock().unwrap();
-    let mut records_guard = state.day_records.lock().unwrap();
+fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err("Tag cannot be empty".to_string());
+    }
+    Ok(tag)
+}
 
-    let session = session_guard.as_mut()?;
-    let today = local_date();
-    if session.day_key == today {
-        return None;
+// Insert a synthetic break lap into every gap longer than `min_gap_seconds` between one
+// lap's end and the next lap's start. Already-filled gaps are closed, so running this
+// again adds nothing. Returns the number of laps inserted.
+fn fill_gaps(record: &mut DayRecord, min_gap_seconds: u64, note: &str) -> usize {
+    record.laps.sort_by_key(|l| l.start_time);
+    let mut filled: Vec<Lap> = Vec::with_capacity(record.laps.len());
+    let mut inserted = 0;
+    for lap in record.laps.drain(..) {
+        if let Some(prev_end) = filled.last().and_then(|prev| prev.end_time) {
+            if lap.start_time > prev_end && lap.start_time - prev_end > min_gap_seconds {
+                filled.push(Lap {
+                    end_time: Some(lap.start_time),
+                    duration: Some(lap.start_time - prev_end),
+                    note: Some(note.to_string()),
+                    is_synthetic: true,
+                    ..Lap::open(prev_end)
+                });
+                inserted += 1;
+            }
+        }
+        filled.push(lap);
     }
+    record.laps = filled;
+    record.total_duration = record.laps.iter().filter_map(Lap::worked_duration).sum();
+    inserted
+}
 
-    let now = now_unix();
-    let last_lap = records_guard.get(&session.day_key).and_then(|r| r.laps.last());
-    // An open lap means the user is working right now, so there is no gap to measure.
-    let working_through = last_lap.map(|l| l.duration.is_none()).unwrap_or(false);
-    let gap = if working_through {
-        0
-    } else {
-        last_lap
-            .and_then(|l| l.end_time)
-            .map(|end| now.saturating_sub(end))
-            .unwrap_or(0)
-    };
+fn lap_at_mut<'a>(
+    records: &'a mut HashMap<String, DayRecord>,
+    date: &str,
+    index: usize,
+) -> Result<&'a mut Lap, TrackerError> {
+    records
+        .get_mut(date)
+        .ok_or_else(|| TrackerError::DayNotFound(date.to_string()))?
+        .laps
+        .get_mut(index)
+        .ok_or_else(|| TrackerError::LapIndexOutOfRange { day: date.to_string(), index })
+}
 
-    let past_cutoff = now >= cutoff_timestamp_today();
-    if !should_roll_over(true, gap, past_cutoff) {
-        // Still the same working day: the date rolled over, but the user is either mid-lap
-        // or back from a short break, and it isn't yet the cutoff. Late-night work stays
-        // with the day it started on.
-        return None;
+// Adding a tag the lap already has is a no-op, not an error.
+fn add_tag(lap: &mut Lap, tag: &str) -> Result<(), String> {
+    let tag = normalize_tag(tag)?;
+    if lap.tags.contains(&tag) {
+        return Ok(());
+    }
+    if lap.tags.len() >= MAX_TAGS_PER_LAP {
+        return Err(format!("A lap can have at most {} tags", MAX_TAGS_PER_LAP));
     }
+    lap.tags.push(tag);
+    Ok(())
+}
 
-    let boundary = rollover_boundary(working_through, cutoff_timestamp_today(), now);
+fn remove_tag(lap: &mut Lap, tag: &str) -> Result<(), String> {
+    let tag = normalize_tag(tag)?;
+    let before = lap.tags.len();
+    lap.tags.retain(|t| *t != tag);
+    if lap.tags.len() == before {
+        return Err(format!("Lap is not tagged '{}'", tag));
+    }
+    Ok(())
+}
 
-    let previous_day = session.day_key.clone();
-    if let Some(old_record) = records_guard.get_mut(&previous_day) {
-        finalize_dangling_lap(old_record, boundary);
-        old_record.is_active = false;
+// Completed-lap seconds per tag over an inclusive "YYYY-MM-DD" range. A lap with several
+// tags counts fully towards each of them; untagged laps are bucketed under UNTAGGED.
+fn time_by_tag(records: &HashMap<String, DayRecord>, from: &str, to: &str) -> HashMap<String, u64> {
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    for record in records.values() {
+        if record.date.as_str() < from || record.date.as_str() > to {
+            continue;
+        }
+        for lap in &record.laps {
+            let Some(duration) = lap.worked_duration() else { continue };
+            if lap.tags.is_empty() {
+                *totals.entry(UNTAGGED.to_string()).or_insert(0) += duration;
+            }
+            for tag in &lap.tags {
+                *totals.entry(tag.clone()).or_insert(0) += duration;
+            }
+        }
     }
-    let previous_total = records_guard
-        .get(&previous_day)
-        .map(|r| r.total_duration)
-        .unwrap_or(0);
+    totals
+}
 
-    // Open today. If the user worked through the boundary, today's first lap picks up
-    // exactly where the old day's last lap ended, so no time falls into the crack. If the
-    // session is paused (screen locked, machine asleep, or a manual pause), today starts
-    // with no open lap: the unlock/resume path will add one when the user actually
-    // returns, and a manual pause is still honoured across the rollover.
-    let resume_now = !session.is_paused;
-    let laps = if resume_now {
-        vec![Lap { start_time: boundary, end_time: None, duration: None }]
-    } else {
-        Vec::new()
-    };
+// Lap colours are stored as "#RRGGBB" in upper case, so "#ff5733" and "#FF5733" are the
+// same colour when filtering.
+fn normalize_color(color: &str) -> Result<String, String> {
+    let valid = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !valid {
+        return Err(format!("Invalid colour '{}': expected #RRGGBB", color));
+    }
+    Ok(color.to_ascii_uppercase())
+}
 
-    records_guard.insert(today.clone(), DayRecord {
-        date: today.clone(),
-        total_duration: 0,
-        laps,
-        is_active: true,
-    });
+// Every lap with the given colour as (day_key, lap_index, lap), oldest day first.
+fn laps_with_color(records: &HashMap<String, DayRecord>, color: &str) -> Vec<(String, usize, Lap)> {
+    let mut dates: Vec<&String> = records.keys().collect();
+    dates.sort();
+    dates
+        .into_iter()
+        .flat_map(|date| {
+            records[date]
+                .laps
+                .iter()
+                .enumerate()
+                .filter(|(_, lap)| lap.color.as_deref() == Some(color))
+                .map(move |(i, lap)| (date.clone(), i, lap.clone()))
+        })
+        .collect()
+}
 
-    session.day_key = today.clone();
-    if resume_now {
-        session.current_lap_start = Instant::now();
-        session.current_lap_start_timestamp = boundary;
-        session.accumulated_seconds = 0;
+// Per-app seconds for one day, most-used first (ties by name). Merges every lap's map.
+fn day_app_breakdown(record: &DayRecord) -> Vec<(String, u64)> {
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    for lap in &record.laps {
+        for (app, secs) in &lap.app_breakdown {
+            *totals.entry(app.clone()).or_insert(0) += secs;
+        }
     }
+    let mut ranked: Vec<(String, u64)> = totals.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
 
-    println!("📅 Rolled over {} -> {} (gap {}s, cutoff {})", previous_day, today, gap, past_cutoff);
+// The most-used apps over an inclusive "YYYY-MM-DD" range as (name, seconds, percent of
+// all app time in the range), most-used first. Names differing only in case are one app,
+// shown under whichever spelling has the most time. A still-open lap only counts when the
+// range includes `today` — it is live data, and a report for a past range must not move.
+// Days recorded before app sampling existed simply contribute nothing.
+fn top_apps(
+    records: &HashMap<String, DayRecord>,
+    from: &str,
+    to: &str,
+    today: &str,
+    limit: usize,
+) -> Vec<(String, u64, f64)> {
+    let include_open = from <= today && today <= to;
+    // lowercase name -> (spelling -> seconds)
+    let mut apps: HashMap<String, HashMap<String, u64>> = HashMap::new();
+    for record in records.values() {
+        if record.date.as_str() < from || record.date.as_str() > to {
+            continue;
+        }
+        for lap in &record.laps {
+            if lap.duration.is_none() && !include_open {
+                continue;
+            }
+            for (app, secs) in &lap.app_breakdown {
+                *apps.entry(app.to_lowercase()).or_default().entry(app.clone()).or_insert(0) += secs;
+            }
+        }
+    }
 
-    drop(session_guard);
-    drop(records_guard);
-    save_state(app_handle, state);
+    let mut ranked: Vec<(String, u64)> = apps
+        .into_values()
+        .map(|spellings| {
+            let total = spellings.values().sum();
+            let name = spellings
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                .map(|(name, _)| name)
+                .unwrap_or_default();
+            (name, total)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let grand_total: u64 = ranked.iter().map(|(_, secs)| secs).sum();
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(name, secs)| {
+            let percent = if grand_total == 0 { 0.0 } else { secs as f64 * 100.0 / grand_total as f64 };
+            (name, secs, percent)
+        })
+        .collect()
+}
 
-    Some((previous_day, previous_total))
+// Laps recorded without an active project are reported under this name.
+const NO_PROJECT: &str = "Unassigned";
+
+// Project names are kept as typed (unlike tags they are display names), but surrounding
+// whitespace is dropped and a blank name means "no project". Commas, quotes and line
+// breaks are rejected so a name can go into a CSV cell untouched.
+fn normalize_project(project: Option<String>) -> Result<Option<String>, String> {
+    let project = project.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(name) = &project {
+        if name.contains([',', '"', '\n', '\r']) {
+            return Err(format!("Project name '{}' cannot contain commas, quotes or line breaks", name));
+        }
+    }
+    Ok(project)
 }
 
-// Tell the user a day was closed behind their back. Deliberately a notification rather
-// than a modal: wake/unlock fires constantly (a lid opened for 30s, a 3am maintenance
-// wake), and a dialog that steals focus the moment you sit down is the wrong tax for a
-// background tracker. The automatic call is right nearly always; when it isn't, the day
-// can be merged back from the Reports view.
-fn notify_day_rolled_over(app_handle: &AppHandle, previous_day: &str, previous_total: u64) {
-    use tauri_plugin_notification::NotificationExt;
+// Completed-lap seconds per project over an inclusive "YYYY-MM-DD" range.
+fn project_totals(records: &HashMap<String, DayRecord>, from: &str, to: &str) -> HashMap<String, u64> {
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    for record in records.values() {
+        if record.date.as_str() < from || record.date.as_str() > to {
+            continue;
+        }
+        for lap in &record.laps {
+            let Some(duration) = lap.worked_duration() else { continue };
+            let project = lap.project.as_deref().unwrap_or(NO_PROJECT);
+            *totals.entry(project.to_string()).or_insert(0) += duration;
+        }
+    }
+    totals
+}
 
-    let hours = previous_total / 3600;
-    let minutes = (previous_total % 3600) / 60;
-    let body = format!("{} ended with {}h {}m tracked. Now tracking today.", previous_day, hours, minutes);
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectDayEntry {
+    pub date: String,
+    pub laps: Vec<Lap>,
+    pub day_total_seconds: u64,
+}
 
-    match app_handle
-        .notification()
-        .builder()
-        .title("Started a new day")
-        .body(&body)
-        .show()
-    {
-        Ok(_) => println!("✅ Rollover notification shown"),
-        Err(e) => eprintln!("❌ Failed to show rollover notification: {}", e),
-    }
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectSessions {
+    pub project: String,
+    pub total_seconds: u64,
+    pub lap_count: usize,
+    pub days: Vec<ProjectDayEntry>, // newest first
 }
 
-// Run the rollover check and notify if it fired.
-fn roll_over_day_if_due(app_handle: &AppHandle, state: &AppStateArc) {
-    if let Some((previous_day, previous_total)) = maybe_roll_over_day(app_handle, state) {
-        notify_day_rolled_over(app_handle, &previous_day, previous_total);
+// The laps behind one project's entry in project_totals: the same completed laps, with
+// NO_PROJECT standing for laps without a project, grouped by day.
+fn project_sessions(records: &HashMap<String, DayRecord>, project: &str, from: &str, to: &str) -> ProjectSessions {
+    let mut days: Vec<ProjectDayEntry> = records
+        .values()
+        .filter(|record| record.date.as_str() >= from && record.date.as_str() <= to)
+        .filter_map(|record| {
+            let laps: Vec<Lap> = record
+                .laps
+                .iter()
+                .filter(|lap| lap.worked_duration().is_some() && lap.project.as_deref().unwrap_or(NO_PROJECT) == project)
+                .cloned()
+                .collect();
+            let day_total_seconds = laps.iter().filter_map(Lap::worked_duration).sum();
+            (!laps.is_empty()).then(|| ProjectDayEntry { date: record.date.clone(), laps, day_total_seconds })
+        })
+        .collect();
+    days.sort_by(|a, b| b.date.cmp(&a.date));
+    ProjectSessions {
+        project: project.to_string(),
+        total_seconds: days.iter().map(|d| d.day_total_seconds).sum(),
+        lap_count: days.iter().map(|d| d.laps.len()).sum(),
+        days,
     }
 }
 
-// Close any still-open lap (duration == None) in a day record, ending it at `end_ts`
-// instead of "now". Used on startup to exclude time the machine spent powered off.
-// Recomputes the record's total_duration afterwards.
-fn finalize_dangling_lap(day_record: &mut DayRecord, end_ts: u64) {
-    if let Some(last_lap) = day_record.laps.last_mut() {
-        if last_lap.duration.is_none() {
-            // Guard against a heartbeat that is somehow before the lap start.
-            let end = end_ts.max(last_lap.start_time);
-            last_lap.end_time = Some(end);
-            last_lap.duration = Some(end - last_lap.start_time);
-        }
-    }
-    day_record.total_duration = day_record.laps.iter().filter_map(|lap| lap.duration).sum();
+// Invoices name laps without a project "General" rather than NO_PROJECT: the document
+// goes to a client, who has nothing to assign. Only the output line takes the label, so a
+// project literally called "General" still gets a line of its own.
+const INVOICE_GENERAL_PROJECT: &str = "General";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceProjectLine {
+    pub project: String,
+    pub billable_seconds: u64,
+    pub amount: f64,
 }
 
-// Get the path to the state file
-fn get_state_file_path(app_handle: &AppHandle) -> PathBuf {
-    let app_data_dir = app_handle.path().app_data_dir().unwrap();
-    fs::create_dir_all(&app_data_dir).ok();
-    app_data_dir.join("state.json")
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceLineItem {
+    pub date: String,
+    pub billable_seconds: u64,
+    pub amount: f64,
+    pub projects: Vec<InvoiceProjectLine>, // by project name
 }
 
-// Last known-good snapshot, rewritten on every successful load. It is the fallback if
-// state.json is ever found corrupt (see load_and_initialize).
-fn get_backup_file_path(app_handle: &AppHandle) -> PathBuf {
-    let app_data_dir = app_handle.path().app_data_dir().unwrap();
-    fs::create_dir_all(&app_data_dir).ok();
-    app_data_dir.join("state.backup.json")
+#[derive(Debug, Clone, Serialize)]
+pub struct InvoiceData {
+    pub total_billable_seconds: u64,
+    pub total_amount: f64,
+    pub currency: String,
+    pub line_items: Vec<InvoiceLineItem>, // one per day with tracked time, oldest first
 }
 
-// Read and parse a state file.
-//   None            -> the file does not exist (a genuine first run)
-//   Some(Err(..))   -> the file exists but is unreadable or unparseable (corruption)
-// The distinction matters: a missing file means "start fresh", but a corrupt file must
-// never be treated that way, or we would blank the history and save over the only copy.
-fn read_state_file(path: &Path) -> Option<Result<PersistedState, String>> {
-    if !path.exists() {
-        return None;
+fn round_to_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+// Billable (completed, non-synthetic) time over an inclusive "YYYY-MM-DD" range at
+// `hourly_rate`. Amounts are computed unrounded and only rounded for output, so the
+// total is not the sum of rounded lines.
+fn invoice_data(records: &HashMap<String, DayRecord>, from: &str, to: &str, hourly_rate: f64, currency: &str) -> InvoiceData {
+    let amount_for = |seconds: u64| seconds as f64 / 3600.0 * hourly_rate;
+    let mut days: BTreeMap<&str, BTreeMap<Option<&str>, u64>> = BTreeMap::new();
+    for record in records.values() {
+        if record.date.as_str() < from || record.date.as_str() > to {
+            continue;
+        }
+        for lap in &record.laps {
+            let Some(duration) = lap.worked_duration().filter(|d| *d > 0) else { continue };
+            let project = lap.project.as_deref();
+            *days.entry(&record.date).or_default().entry(project).or_insert(0) += duration;
+        }
     }
-    match fs::read_to_string(path) {
-        Ok(json) => Some(serde_json::from_str::<PersistedState>(&json).map_err(|e| e.to_string())),
-        Err(e) => Some(Err(e.to_string())),
+
+    let line_items: Vec<InvoiceLineItem> = days
+        .into_iter()
+        .map(|(date, projects)| {
+            let billable_seconds = projects.values().sum();
+            let mut projects: Vec<(Option<&str>, u64)> = projects.into_iter().collect();
+            // Named projects by name, then the unassigned time.
+            projects.sort_by_key(|(project, _)| project.is_none());
+            InvoiceLineItem {
+                date: date.to_string(),
+                billable_seconds,
+                amount: round_to_cents(amount_for(billable_seconds)),
+                projects: projects
+                    .into_iter()
+                    .map(|(project, seconds)| InvoiceProjectLine {
+                        project: project.unwrap_or(INVOICE_GENERAL_PROJECT).to_string(),
+                        billable_seconds: seconds,
+                        amount: round_to_cents(amount_for(seconds)),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    let total_billable_seconds = line_items.iter().map(|item| item.billable_seconds).sum();
+    InvoiceData {
+        total_billable_seconds,
+        total_amount: round_to_cents(amount_for(total_billable_seconds)),
+        currency: currency.to_string(),
+        line_items,
     }
 }
 
-// Save state to disk
-fn save_state(app_handle: &AppHandle, state: &AppStateArc) {
-    let session_guard = state.current_session.lock().unwrap();
-    let records_guard = state.day_records.lock().unwrap();
-    
-    let persisted_session = session_guard.as_ref().map(|session| {
-        PersistedSessionState {
-            day_key: session.day_key.clone(),
-            current_lap_start_timestamp: session.current_lap_start_timestamp,
-            accumulated_seconds: session.accumulated_seconds,
-            is_paused: session.is_paused,
-            user_paused: session.user_paused,
+// Personal records across the whole history, for the achievements display. Every field
+// is zero / empty when nothing has been tracked yet.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AllTimeBests {
+    pub longest_single_day_key: String,
+    pub longest_single_day_seconds: u64,
+    pub longest_single_lap_seconds: u64,
+    pub longest_single_lap_date: String,
+    pub longest_streak_days: u32,
+    pub longest_streak_start: String,
+    pub longest_streak_end: String,
+    pub most_laps_in_a_day: usize,
+    pub most_laps_date: String,
+}
+
+// Walks days oldest-first, so on a tie the earliest day keeps the record. A streak is a
+// run of consecutive calendar dates that each have at least one lap; a day the machine
+// was off simply has no record, which is what breaks the run.
+fn all_time_bests(records: &HashMap<String, DayRecord>) -> AllTimeBests {
+    let mut bests = AllTimeBests::default();
+    let mut dates: Vec<&String> = records.keys().collect();
+    dates.sort();
+
+    let mut streak: Option<(chrono::NaiveDate, chrono::NaiveDate, u32)> = None;
+
+    for date in dates {
+        let record = &records[date];
+
+        if record.total_duration > bests.longest_single_day_seconds {
+            bests.longest_single_day_seconds = record.total_duration;
+            bests.longest_single_day_key = date.clone();
+        }
+        for lap in &record.laps {
+            let duration = lap.worked_duration().unwrap_or(0);
+            if duration > bests.longest_single_lap_seconds {
+                bests.longest_single_lap_seconds = duration;
+                bests.longest_single_lap_date = date.clone();
+            }
+        }
+        if record.laps.len() > bests.most_laps_in_a_day {
+            bests.most_laps_in_a_day = record.laps.len();
+            bests.most_laps_date = date.clone();
         }
-    });
 
-    let persisted_state = PersistedState {
-        current_session: persisted_session,
-        day_records: records_guard.clone(),
-        last_heartbeat: now_unix(),
-        schema_version: CURRENT_SCHEMA_VERSION,
-    };
-    
-    let state_file = get_state_file_path(app_handle);
-    if let Ok(json) = serde_json::to_string_pretty(&persisted_state) {
-        // Write to a sibling temp file, then rename into place. rename(2) within a
-        // filesystem is atomic, so a crash or power cut mid-save can never leave a
-        // half-written state.json behind — the reader either sees the whole old file or
-        // the whole new one. A plain fs::write truncates first, and dying in that window
-        // used to leave a truncated file that the next launch could not parse.
-        let tmp_file = state_file.with_extension("json.tmp");
-        if fs::write(&tmp_file, &json).is_ok() && fs::rename(&tmp_file, &state_file).is_ok() {
-            println!("✅ State saved successfully");
-        } else {
-            eprintln!("❌ Failed to save state to {}", state_file.display());
-            fs::remove_file(&tmp_file).ok();
+        let Ok(parsed) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") else { continue };
+        if record.laps.is_empty() {
+            streak = None;
+            continue;
+        }
+        streak = match streak {
+            Some((start, end, len)) if end.succ_opt() == Some(parsed) => Some((start, parsed, len + 1)),
+            _ => Some((parsed, parsed, 1)),
+        };
+        if let Some((start, end, len)) = streak {
+            if len > bests.longest_streak_days {
+                bests.longest_streak_days = len;
+                bests.longest_streak_start = start.format("%Y-%m-%d").to_string();
+                bests.longest_streak_end = end.format("%Y-%m-%d").to_string();
+            }
         }
     }
+
+    bests
 }
 
-// Build a brand-new active session + day record for `today`, seeded with one open lap.
-// Used both for a genuinely fresh day and for a new day after an overnight shutdown.
-fn begin_fresh_day(records: &mut HashMap<String, DayRecord>, today: &str) -> CurrentSession {
-    let now = Instant::now();
-    let current_time = now_unix();
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Statistics {
+    pub total_tracked_seconds: u64,
+    pub tracked_days: u32, // days with any completed, non-synthetic time
+    pub average_seconds_per_tracked_day: u64,
+    pub best_day_key: Option<String>,
+    pub best_day_seconds: u64,
+    pub current_streak_days: u32, // ending today, or yesterday while today is still empty
+    pub longest_streak_days: u32,
+    pub this_week_seconds: u64, // weeks start on TrackerConfig::week_start_day
+    pub last_week_seconds: u64,
+    pub this_month_seconds: u64,
+    pub last_month_seconds: u64,
+}
 
-    records.insert(today.to_string(), DayRecord {
-        date: today.to_string(),
-        total_duration: 0,
-        laps: vec![Lap {
-            start_time: current_time,
-            end_time: None,
-            duration: None,
-        }],
-        is_active: true,
-    });
+// The settled part of get_statistics' input: worked seconds for every day before `today`
+// that is no longer changing, archives included. Valid while today and the persistence
+// settled_generation are unchanged.
+pub struct StatsCache {
+    today: String,
+    generation: u64,
+    daily: BTreeMap<String, u64>,
+}
 
-    CurrentSession {
-        start_time: now,
-        day_key: today.to_string(),
-        current_lap_start: now,
-        current_lap_start_timestamp: current_time,
-        accumulated_seconds: 0,
-        last_activity_time: now,
-        is_paused: false,
-        user_paused: false,
-    }
+fn worked_seconds(record: &DayRecord) -> u64 {
+    record.laps.iter().filter_map(Lap::worked_duration).sum()
 }
 
-// Load persisted state from disk and decide what today's session should be.
-//
-// Rules (all dates are LOCAL dates):
-//   * Fresh boot on a new day, or a day with no record yet -> auto-start a new active
-//     day in the background. The user can manually pause if they aren't working.
-//   * Machine restarted (e.g. power cut) on the SAME day the ongoing session belongs to
-//     -> continue that same day, appending a new lap (unless the user had manually paused,
-//     in which case we respect the pause and don't resume).
-//   * The ongoing session belongs to an EARLIER day -> apply the same rule the running app
-//     uses (should_roll_over). If the user was away long enough, or it is past the cutoff,
-//     that day is finalized and a fresh one starts for today. If not — the app just
-//     restarted a few minutes after midnight while they were still working — the earlier
-//     day CONTINUES, because late-night work belongs to the day it started on.
-//   * A day the user explicitly ended -> left alone; we do NOT auto-restart it.
-//
-// Any lap left open when the app last stopped is closed at `last_heartbeat` so that
-// time while the machine was powered off is never counted.
-fn load_and_initialize(app_handle: &AppHandle, state: &AppStateArc) {
-    let state_file = get_state_file_path(app_handle);
-    let backup_file = get_backup_file_path(app_handle);
-    let today = local_date();
+// Seconds the day's laps had cut out of them (see Lap::excluded_seconds).
+fn excluded_seconds(record: &DayRecord) -> u64 {
+    record.laps.iter().map(|lap| lap.excluded_seconds).sum()
+}
 
-    let persisted_state = match read_state_file(&state_file) {
-        Some(Ok(loaded)) => Some(loaded),
-        // The file is genuinely absent -> first ever run.
-        None => None,
-        Some(Err(err)) => {
-            // state.json exists but will not parse. This must NOT fall through to the
-            // "first run" path: that blanks day_records and immediately writes the empty
-            // state back, destroying every day the user ever tracked. Instead, quarantine
-            // the bad file (so it is still there to inspect or hand-recover) and fall back
-            // to the snapshot taken on the last successful load.
-            eprintln!("❌ state.json is corrupt: {}", err);
-            let quarantine = state_file.with_file_name(format!("state.corrupt-{}.json", now_unix()));
-            if fs::rename(&state_file, &quarantine).is_ok() {
-                eprintln!("   Corrupt file preserved at {}", quarantine.display());
-            }
-            match read_state_file(&backup_file) {
-                Some(Ok(recovered)) => {
-                    println!("✅ Recovered history from {}", backup_file.display());
-                    Some(recovered)
-                }
-                _ => {
-                    eprintln!("❌ No usable backup either; starting with empty history");
-                    None
-                }
-            }
-        }
+// Lifetime aggregates from per-day worked seconds. Streaks count consecutive dates with
+// time tracked; the best day is the earliest on a tie.
+fn statistics(daily: &BTreeMap<String, u64>, today: chrono::NaiveDate, week_start_day: u8) -> Statistics {
+    use chrono::Datelike;
+    let key = |d: chrono::NaiveDate| d.format("%Y-%m-%d").to_string();
+    let sum_between = |from: chrono::NaiveDate, to: chrono::NaiveDate| -> u64 {
+        daily.range(key(from)..=key(to)).map(|(_, secs)| secs).sum()
     };
 
-    let mut records_guard = state.day_records.lock().unwrap();
-    let mut session_guard = state.current_session.lock().unwrap();
+    let mut stats = Statistics::default();
+    let mut run: Option<(chrono::NaiveDate, u32)> = None;
+    for (date, &secs) in daily.iter().filter(|(_, secs)| **secs > 0) {
+        stats.total_tracked_seconds += secs;
+        stats.tracked_days += 1;
+        if secs > stats.best_day_seconds {
+            stats.best_day_seconds = secs;
+            stats.best_day_key = Some(date.clone());
+        }
+        let Ok(parsed) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") else { continue };
+        let len = match run {
+            Some((last, len)) if last.succ_opt() == Some(parsed) => len + 1,
+            _ => 1,
+        };
+        run = Some((parsed, len));
+        stats.longest_streak_days = stats.longest_streak_days.max(len);
+    }
+    if let Some((last, len)) = run {
+        if last == today || last.succ_opt() == Some(today) {
+            stats.current_streak_days = len;
+        }
+    }
+    if stats.tracked_days > 0 {
+        stats.average_seconds_per_tracked_day = stats.total_tracked_seconds / stats.tracked_days as u64;
+    }
 
-    let Some(persisted_state) = persisted_state else {
-        // No prior state at all -> very first run. Auto-start today in the background.
-        *records_guard = HashMap::new();
-        *session_guard = Some(begin_fresh_day(&mut records_guard, &today));
-        println!("✅ No prior state; auto-started a fresh day for {}", today);
-        drop(session_guard);
-        drop(records_guard);
-        save_state(app_handle, state);
-        return;
+    let into_week = (today.weekday().num_days_from_monday() + 7 - u32::from(week_start_day % 7)) % 7;
+    let week_start = today - chrono::Duration::days(into_week as i64);
+    stats.this_week_seconds = sum_between(week_start, week_start + chrono::Duration::days(6));
+    stats.last_week_seconds = sum_between(week_start - chrono::Duration::days(7), week_start - chrono::Duration::days(1));
+    let month_start = today.with_day(1).unwrap_or(today);
+    let last_month_end = month_start.pred_opt().unwrap_or(month_start);
+    stats.this_month_seconds = sum_between(month_start, today);
+    stats.last_month_seconds = sum_between(last_month_end.with_day(1).unwrap_or(last_month_end), last_month_end);
+    stats
+}
+
+// One calendar year for a contribution-style heatmap. `seconds` and `levels` hold one
+// entry per date from January 1st, in order, so the payload needs no date strings.
+#[derive(Debug, Clone, Serialize)]
+pub struct HeatmapData {
+    pub year: u16,
+    // Cells before January 1st in its first column, so columns line up with weeks that
+    // start on TrackerConfig::week_start_day.
+    pub leading_blank_days: u8,
+    pub seconds: Vec<u64>,
+    pub levels: Vec<u8>, // 0 = nothing tracked, 1-4 by quartile of the tracked days
+    pub thresholds: [u64; 3], // the quartiles separating levels 1|2, 2|3 and 3|4
+}
+
+// Levels come from the quartiles of the year's tracked days only: counting the empty
+// days would drag every quartile to zero, and a single long outlier then leaves the
+// ordinary days all at level 1. None for a year chrono cannot represent.
+fn heatmap(records: &HashMap<String, DayRecord>, year: u16, week_start_day: u8) -> Option<HeatmapData> {
+    use chrono::Datelike;
+    let first = chrono::NaiveDate::from_ymd_opt(year as i32, 1, 1)?;
+    let seconds: Vec<u64> = first
+        .iter_days()
+        .take_while(|date| date.year() == year as i32)
+        .map(|date| records.get(&date.format("%Y-%m-%d").to_string()).map(worked_seconds).unwrap_or(0))
+        .collect();
+
+    let mut tracked: Vec<u64> = seconds.iter().copied().filter(|secs| *secs > 0).collect();
+    tracked.sort_unstable();
+    let thresholds = if tracked.is_empty() {
+        [0; 3]
+    } else {
+        [25.0, 50.0, 75.0].map(|p| percentile_value(&tracked, p).round() as u64)
     };
+    let levels = seconds
+        .iter()
+        .map(|&secs| if secs == 0 { 0 } else { 1 + thresholds.iter().filter(|t| secs >= **t).count() as u8 })
+        .collect();
+
+    Some(HeatmapData {
+        year,
+        leading_blank_days: ((first.weekday().num_days_from_monday() + 7 - u32::from(week_start_day % 7)) % 7) as u8,
+        seconds,
+        levels,
+        thresholds,
+    })
+}
 
-    // Snapshot the history we just loaded, before this run starts mutating it. If a later
-    // write is ever cut short, this is what the recovery path above restores from.
-    if let Ok(json) = serde_json::to_string_pretty(&persisted_state) {
-        fs::write(&backup_file, json).ok();
+// Below this much completed work a day has too few laps for a focus score to mean much.
+const MIN_FOCUS_WORK_SECS: u64 = 5 * 60;
+const DEFAULT_MAX_EXPECTED_FRAGMENTATION: f64 = 6.0;
+
+// How broken up a day's work was. Only completed, non-synthetic laps count.
+#[derive(Debug, Clone, Serialize)]
+pub struct FocusScore {
+    pub day_key: String,
+    pub lap_count: usize,
+    pub average_lap_duration_seconds: u64,
+    pub longest_lap_seconds: u64,
+    pub fragmentation_ratio: f64, // laps per hour of work
+    pub focus_score: Option<f64>, // 0-100; None below MIN_FOCUS_WORK_SECS of work
+    pub explanation: Option<String>, // why focus_score is None
+}
+
+// Laps get_statistics and get_focus_score can be told to leave out, e.g. screen-lock
+// blips: {"end_reasons": ["ScreenLocked"], "shorter_than_secs": 120}.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LapFilter {
+    pub end_reasons: Vec<LapEndReason>,
+    pub shorter_than_secs: Option<u64>, // None = at any length
+}
+
+impl LapFilter {
+    fn excludes(&self, lap: &Lap) -> bool {
+        lap.end_reason.is_some_and(|reason| self.end_reasons.contains(&reason))
+            && self.shorter_than_secs.is_none_or(|max| lap.duration.unwrap_or(0) < max)
     }
 
-    *records_guard = persisted_state.day_records;
-    // Bound any lap that was still open at shutdown to the last heartbeat we recorded.
-    let heartbeat = if persisted_state.last_heartbeat > 0 {
-        persisted_state.last_heartbeat
+    // A copy of the record without the excluded laps.
+    fn apply(&self, record: &DayRecord) -> DayRecord {
+        let mut filtered = record.clone();
+        filtered.laps.retain(|lap| !self.excludes(lap));
+        filtered.total_duration = worked_seconds(&filtered);
+        filtered
+    }
+}
+
+fn focus_score(record: &DayRecord, max_expected_fragmentation: f64) -> FocusScore {
+    let durations: Vec<u64> = record.laps.iter().filter_map(Lap::worked_duration).collect();
+    let total: u64 = durations.iter().sum();
+    let lap_count = durations.len();
+    let fragmentation_ratio = if total == 0 { 0.0 } else { lap_count as f64 / (total as f64 / 3600.0) };
+    let (focus_score, explanation) = if total < MIN_FOCUS_WORK_SECS {
+        (None, Some(format!(
+            "Only {} min of work tracked; a focus score needs at least {} min",
+            total / 60,
+            MIN_FOCUS_WORK_SECS / 60
+        )))
     } else {
-        now_unix()
+        let score = 100.0 * (1.0 - fragmentation_ratio / max_expected_fragmentation).clamp(0.0, 1.0);
+        (Some(score), None)
     };
-    for record in records_guard.values_mut() {
-        finalize_dangling_lap(record, heartbeat);
+    FocusScore {
+        day_key: record.date.clone(),
+        lap_count,
+        average_lap_duration_seconds: if lap_count == 0 { 0 } else { total / lap_count as u64 },
+        longest_lap_seconds: durations.iter().copied().max().unwrap_or(0),
+        fragmentation_ratio,
+        focus_score,
+        explanation,
     }
+}
 
-    // One-off repair of history written before the day rolled over on its own. Runs once:
-    // save_state stamps CURRENT_SCHEMA_VERSION, and the pre-migration data is still on
-    // disk in state.backup.json (written just above) if this ever needs undoing.
-    if persisted_state.schema_version < 1 {
-        let session_day = persisted_state.current_session.as_ref().map(|s| s.day_key.as_str());
-        let moved = backfill_misattributed_laps(&mut records_guard, session_day);
-        if moved > 0 {
-            println!("🔧 Re-filed {} lap(s) that were recorded under the wrong day", moved);
-        } else {
-            println!("🔧 History checked: no misfiled laps to re-file");
+// Today's running total against yesterday and the days before it. Every delta is today
+// minus the baseline; a percentage is None when its baseline is zero.
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub today_seconds: u64,
+    pub yesterday_seconds: Option<u64>,
+    pub recent_average_seconds: Option<u64>,
+    pub vs_yesterday_seconds: Option<i64>,
+    pub vs_yesterday_percent: Option<f64>,
+    pub vs_recent_average_seconds: Option<i64>,
+    pub vs_recent_average_percent: Option<f64>,
+}
+
+// Days before today that the recent average covers.
+const COMPARISON_WINDOW_DAYS: u32 = 7;
+
+// `previous_days` is worked seconds per calendar day, oldest first, ending yesterday;
+// None where no record exists. The window starts at its first recorded day, so a new
+// user's average is over the days they have had rather than a mostly empty week, and
+// with nothing recorded at all there is no baseline to compare against.
+fn comparison(today_seconds: u64, previous_days: &[Option<u64>]) -> Comparison {
+    let baseline = match previous_days.iter().position(Option::is_some) {
+        Some(first) => &previous_days[first..],
+        None => &[],
+    };
+    let yesterday_seconds = (!baseline.is_empty()).then(|| previous_days.last().copied().flatten().unwrap_or(0));
+    let recent_average_seconds = (!baseline.is_empty())
+        .then(|| baseline.iter().map(|secs| secs.unwrap_or(0)).sum::<u64>() / baseline.len() as u64);
+    let delta = |base: Option<u64>| base.map(|b| today_seconds as i64 - b as i64);
+    let percent = |base: Option<u64>| base.filter(|b| *b > 0).map(|b| (today_seconds as f64 - b as f64) / b as f64 * 100.0);
+    Comparison {
+        today_seconds,
+        yesterday_seconds,
+        recent_average_seconds,
+        vs_yesterday_seconds: delta(yesterday_seconds),
+        vs_yesterday_percent: percent(yesterday_seconds),
+        vs_recent_average_seconds: delta(recent_average_seconds),
+        vs_recent_average_percent: percent(recent_average_seconds),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryBounds {
+    pub first_day: String,
+    pub last_day: String,
+    pub total_days_span: u32, // calendar days from first to last, both included
+    pub active_days_count: usize, // days with any tracked time
+}
+
+// None when there is no history at all. Keys are "YYYY-MM-DD", so min/max are the
+// earliest and latest dates without sorting.
+fn history_bounds(records: &HashMap<String, DayRecord>) -> Option<HistoryBounds> {
+    let first = records.keys().min()?;
+    let last = records.keys().max()?;
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok();
+    let span = match (parse(first), parse(last)) {
+        (Some(f), Some(l)) => (l - f).num_days() as u32 + 1,
+        _ => 0,
+    };
+    Some(HistoryBounds {
+        first_day: first.clone(),
+        last_day: last.clone(),
+        total_days_span: span,
+        active_days_count: records.values().filter(|r| r.total_duration > 0).count(),
+    })
+}
+
+// (percent of the goal reached, seconds still to go) for `worked` seconds against `goal`.
+// The percent keeps climbing past 100 once the goal is beaten; the remainder stops at 0.
+fn goal_progress(goal: Option<u64>, worked: u64) -> Option<(f64, u64)> {
+    let goal = goal.filter(|g| *g > 0)?;
+    Some((worked as f64 * 100.0 / goal as f64, goal.saturating_sub(worked)))
+}
+
+// Whether `worked` seconds on `day_key` should trigger the overtime alert now.
+fn overtime_alert_due(config: &TrackerConfig, day_key: &str, worked: u64, is_active: bool) -> bool {
+    match config.overtime_alert_seconds {
+        Some(cap) if cap > 0 => {
+            is_active && worked >= cap && config.overtime_notified_day.as_deref() != Some(day_key)
         }
+        _ => false,
     }
+}
 
-    // Whether the ongoing session's day is over, judged by the same rule the running app
-    // uses (see should_roll_over). The app being down is not itself evidence of a new day:
-    // launchd's KeepAlive can relaunch it seconds after a crash, and a plain "the date
-    // changed while we were down" test would split a night owl's session at midnight.
-    // What ends a day is a real break — measured here from the last heartbeat, which is
-    // also where any lap left open was just closed.
-    let downtime = now_unix().saturating_sub(heartbeat);
-    let past_cutoff = now_unix() >= cutoff_timestamp_today();
+// Whether `worked` seconds on `day_key` should trigger the goal notification now.
+fn goal_notification_due(config: &TrackerConfig, goal: Option<u64>, day_key: &str, worked: u64) -> bool {
+    match goal {
+        Some(goal) if goal > 0 => {
+            !config.goal_notification_muted
+                && worked >= goal
+                && config.goal_notified_day.as_deref() != Some(day_key)
+        }
+        _ => false,
+    }
+}
 
-    match persisted_state.current_session {
-        Some(ps)
-            if ps.day_key == today
-                || !should_roll_over(true, downtime, past_cutoff) =>
-        {
-            // Continue the ongoing session's day. Either it is still that day (a mid-day
-            // restart or power cut), or the date has changed but the user was working
-            // through it and the app merely restarted — in which case the work still
-            // belongs to the day it started on.
-            let day = ps.day_key.clone();
-            if let Some(record) = records_guard.get_mut(&day) {
-                record.is_active = true;
-            } else {
-                records_guard.insert(day.clone(), DayRecord {
-                    date: day.clone(),
-                    total_duration: 0,
-                    laps: Vec::new(),
-                    is_active: true,
-                });
-            }
+#[derive(Debug, Clone, Serialize)]
+pub struct WeekSummary {
+    // ISO week ("2026-W28") for Monday-first weeks, otherwise "start/end" (there is no
+    // ISO number for a week that starts on another day).
+    pub week_key: String,
+    pub week_start: String, // YYYY-MM-DD, on the configured week_start_day
+    pub week_end: String,   // YYYY-MM-DD, six days later
+    pub daily_totals: Vec<(String, u64)>, // all seven days, week_start first
+    pub total_seconds: u64,
+    pub days_tracked: u32,
+    // How many of the seven days reached the daily goal; None when no goal is set.
+    pub days_goal_met: Option<u32>,
+}
 
-            if ps.user_paused {
-                // User had manually paused before the restart -> respect it, stay paused.
-                let now = Instant::now();
-                *session_guard = Some(CurrentSession {
-                    start_time: now,
-                    day_key: day.clone(),
-                    current_lap_start: now,
-                    current_lap_start_timestamp: now_unix(),
-                    accumulated_seconds: ps.accumulated_seconds,
-                    last_activity_time: now,
-                    is_paused: true,
-                    user_paused: true,
-                });
-                println!("✅ Restored paused session for {} (user paused; not resuming)", day);
-            } else {
-                // Continue the existing day by appending a fresh lap.
-                let now = Instant::now();
-                let current_time = now_unix();
-                if let Some(record) = records_guard.get_mut(&day) {
-                    record.laps.push(Lap {
-                        start_time: current_time,
-                        end_time: None,
-                        duration: None,
-                    });
-                }
-                *session_guard = Some(CurrentSession {
-                    start_time: now,
-                    day_key: day.clone(),
-                    current_lap_start: now,
-                    current_lap_start_timestamp: current_time,
-                    accumulated_seconds: 0,
-                    last_activity_time: now,
-                    is_paused: false,
-                    user_paused: false,
-                });
-                if day == today {
-                    println!("✅ Continued ongoing day {} with a new lap (restart detected)", day);
-                } else {
-                    println!("✅ Continued {} past midnight (down {}s; not a new day yet)", day, downtime);
-                }
-            }
+// Totals for the week containing `date` that starts on `week_start_day` (0 = Monday),
+// from completed laps.
+fn week_summary(
+    records: &HashMap<String, DayRecord>,
+    date: chrono::NaiveDate,
+    week_start_day: u8,
+    goal: Option<u64>,
+) -> WeekSummary {
+    use chrono::Datelike;
+    let into_week = (date.weekday().num_days_from_monday() + 7 - u32::from(week_start_day % 7)) % 7;
+    let first = date - chrono::Duration::days(into_week as i64);
+    let daily_totals: Vec<(String, u64)> = (0..7)
+        .map(|offset| {
+            let key = (first + chrono::Duration::days(offset)).format("%Y-%m-%d").to_string();
+            let total = records
+                .get(&key)
+                .map(|r| r.laps.iter().filter_map(Lap::worked_duration).sum())
+                .unwrap_or(0);
+            (key, total)
+        })
+        .collect();
+
+    let goal = goal.filter(|g| *g > 0);
+    let week_key = if week_start_day == 0 {
+        let week = first.iso_week();
+        format!("{}-W{:02}", week.year(), week.week())
+    } else {
+        format!("{}/{}", daily_totals[0].0, daily_totals[6].0)
+    };
+    WeekSummary {
+        week_key,
+        week_start: daily_totals[0].0.clone(),
+        week_end: daily_totals[6].0.clone(),
+        total_seconds: daily_totals.iter().map(|(_, secs)| secs).sum(),
+        days_tracked: daily_totals.iter().filter(|(_, secs)| *secs > 0).count() as u32,
+        days_goal_met: goal.map(|g| daily_totals.iter().filter(|(_, secs)| *secs >= g).count() as u32),
+        daily_totals,
+    }
+}
+
+// How many laps MonthSummary::longest_laps lists.
+const MONTH_SUMMARY_LONGEST_LAPS: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthSummary {
+    pub month_key: String, // YYYY-MM
+    pub daily_totals: Vec<(String, u64)>, // every day of the month, in order
+    pub total_seconds: u64,
+    pub days_tracked: u32,
+    pub average_tracked_day_seconds: u64, // 0 when no day was tracked
+    pub best_day: Option<(String, u64)>, // the earliest on a tie
+    pub goal_seconds: Option<u64>,
+    // How many days reached the daily goal; None when no goal is set.
+    pub days_goal_met: Option<u32>,
+    pub longest_laps: Vec<(String, Lap)>, // (date, lap), longest first
+}
+
+// Totals for one calendar month from completed laps. None for an invalid month.
+fn month_summary(
+    records: &HashMap<String, DayRecord>,
+    year: i32,
+    month: u32,
+    goal: Option<u64>,
+) -> Option<MonthSummary> {
+    use chrono::Datelike;
+    let first = chrono::NaiveDate::from_ymd_opt(year, month, 1)?;
+    let days: Vec<String> = first
+        .iter_days()
+        .take_while(|date| date.month() == month)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .collect();
+    let daily_totals: Vec<(String, u64)> = days
+        .iter()
+        .map(|key| (key.clone(), records.get(key).map(worked_seconds).unwrap_or(0)))
+        .collect();
+
+    let mut longest_laps: Vec<(String, Lap)> = days
+        .iter()
+        .filter_map(|key| records.get(key))
+        .flat_map(|record| {
+            record.laps.iter().filter(|lap| lap.worked_duration().is_some()).map(|lap| (record.date.clone(), lap.clone()))
+        })
+        .collect();
+    // Stable sort, so equal laps stay in date order.
+    longest_laps.sort_by_key(|(_, lap)| std::cmp::Reverse(lap.worked_duration()));
+    longest_laps.truncate(MONTH_SUMMARY_LONGEST_LAPS);
+
+    let goal = goal.filter(|g| *g > 0);
+    let total_seconds: u64 = daily_totals.iter().map(|(_, secs)| secs).sum();
+    let days_tracked = daily_totals.iter().filter(|(_, secs)| *secs > 0).count() as u32;
+    Some(MonthSummary {
+        month_key: first.format("%Y-%m").to_string(),
+        total_seconds,
+        days_tracked,
+        average_tracked_day_seconds: if days_tracked == 0 { 0 } else { total_seconds / days_tracked as u64 },
+        best_day: daily_totals
+            .iter()
+            .filter(|(_, secs)| *secs > 0)
+            .fold(None, |best: Option<&(String, u64)>, day| match best {
+                Some(b) if b.1 >= day.1 => Some(b),
+                _ => Some(day),
+            })
+            .cloned(),
+        goal_seconds: goal,
+        days_goal_met: goal.map(|g| daily_totals.iter().filter(|(_, secs)| *secs >= g).count() as u32),
+        longest_laps,
+        daily_totals,
+    })
+}
+
+// Below this a day counts as untracked in a consistency report: a stray click, not work.
+const MIN_TRACKED_DAY_SECS: u64 = 60;
+const MAX_CONSISTENCY_WEEKS: u32 = 52;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsistencyReport {
+    pub total_workdays_in_period: u32,
+    pub tracked_workdays: u32,
+    pub consistency_percent: f64, // 0 when the period has no workdays
+    pub missed_days: Vec<String>, // oldest first
+    pub weeks_with_full_coverage: u32,
+    pub longest_tracked_run: u32, // in workdays; days off don't break a run
+    pub longest_missed_run: u32,
+}
+
+// The `weeks` weeks (starting on week_start_day) up to and including the one holding
+// `today`. Days after today are left out, and so is today itself until it is tracked,
+// so a morning not started yet isn't a miss. A partial current week is fully covered
+// when every workday so far is.
+fn consistency_report(
+    daily: &HashMap<String, u64>,
+    today: chrono::NaiveDate,
+    weeks: u32,
+    week_start_day: u8,
+    work_days: &[u8],
+) -> ConsistencyReport {
+    use chrono::Datelike;
+    let into_week = (today.weekday().num_days_from_monday() + 7 - u32::from(week_start_day % 7)) % 7;
+    let first = today - chrono::Duration::days(into_week as i64 + 7 * (weeks.max(1) as i64 - 1));
+
+    let mut report = ConsistencyReport {
+        total_workdays_in_period: 0,
+        tracked_workdays: 0,
+        consistency_percent: 0.0,
+        missed_days: Vec::new(),
+        weeks_with_full_coverage: 0,
+        longest_tracked_run: 0,
+        longest_missed_run: 0,
+    };
+    let (mut tracked_run, mut missed_run) = (0, 0);
+    let mut week_complete = None; // None until the week has a workday
+    for (index, date) in first.iter_days().take_while(|d| *d <= today).enumerate() {
+        if index % 7 == 0 {
+            report.weeks_with_full_coverage += u32::from(week_complete == Some(true));
+            week_complete = None;
         }
-        Some(ps) => {
-            // The session's day is genuinely over: the user was away long enough, or it is
-            // past the cutoff. End that day and start a fresh one for today.
-            if let Some(record) = records_guard.get_mut(&ps.day_key) {
-                record.is_active = false;
-            }
-            *session_guard = Some(begin_fresh_day(&mut records_guard, &today));
-            println!("✅ Ended previous day {} and auto-started a fresh day for {}", ps.day_key, today);
+        if !work_days.contains(&(date.weekday().num_days_from_monday() as u8)) {
+            continue;
         }
-        None => {
-            // No ongoing session was persisted (user had ended their day, or clean state).
-            match records_guard.get(&today) {
-                Some(record) if !record.is_active => {
-                    // User already ended today's day -> don't auto-restart it.
-                    println!("ℹ️ Today's day ({}) was already ended; not auto-starting", today);
-                }
-                _ => {
-                    *session_guard = Some(begin_fresh_day(&mut records_guard, &today));
-                    println!("✅ Auto-started a fresh day for {}", today);
-                }
-            }
+        let key = date.format("%Y-%m-%d").to_string();
+        let tracked = daily.get(&key).is_some_and(|secs| *secs >= MIN_TRACKED_DAY_SECS);
+        if date == today && !tracked {
+            continue;
+        }
+        report.total_workdays_in_period += 1;
+        if tracked {
+            report.tracked_workdays += 1;
+            tracked_run += 1;
+            missed_run = 0;
+        } else {
+            report.missed_days.push(key);
+            missed_run += 1;
+            tracked_run = 0;
         }
+        report.longest_tracked_run = report.longest_tracked_run.max(tracked_run);
+        report.longest_missed_run = report.longest_missed_run.max(missed_run);
+        week_complete = Some(week_complete.unwrap_or(true) && tracked);
     }
-
-    drop(session_guard);
-    drop(records_guard);
-    save_state(app_handle, state);
-    println!("✅ State loaded and initialized successfully");
+    report.weeks_with_full_coverage += u32::from(week_complete == Some(true));
+    if report.total_workdays_in_period > 0 {
+        report.consistency_percent = report.tracked_workdays as f64 / report.total_workdays_in_period as f64 * 100.0;
+    }
+    report
 }
 
-// Raw wall-clock elapsed since the lap started. Only valid for closing a lap that
-// was awake the whole time (lock, user pause, end-day). Laps interrupted by system
-// sleep must NOT be closed with this — use handle_system_suspend_direct, which
-// bounds the lap to a pre-sleep timestamp instead of "now".
-// Pass in the actual lap start time from records to ensure accuracy
-fn finalize_lap_duration(lap_start_time: u64) -> u64 {
-    let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-    current_time - lap_start_time
-}
+const MIN_RECORDS_FOR_PERCENTILES: usize = 4;
 
-// Get the actual start time of the active lap from records
-fn get_active_lap_start_time(day_record: &DayRecord) -> Option<u64> {
-    day_record.laps.iter()
-        .filter(|lap| lap.duration.is_none())
-        .last()
-        .map(|lap| lap.start_time)
+// Linearly interpolated value at `p` (0-100) of an ascending, non-empty slice.
+fn percentile_value(sorted: &[u64], p: f64) -> f64 {
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lo] as f64 + (sorted[hi] as f64 - sorted[lo] as f64) * (rank - lo as f64)
 }
 
-#[tauri::command]
-async fn start_day(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let today = local_date();
+// Day records whose total_duration lies between the `low` and `high` percentiles
+// (inclusive) of all recorded days, newest date first.
+fn records_in_duration_percentile(
+    records: &HashMap<String, DayRecord>,
+    low: f64,
+    high: f64,
+) -> Result<Vec<DayRecord>, String> {
+    if !(0.0..=100.0).contains(&low) || !(0.0..=100.0).contains(&high) {
+        return Err("Percentiles must be between 0 and 100".to_string());
+    }
+    if low >= high {
+        return Err(format!("percentile_low ({}) must be below percentile_high ({})", low, high));
+    }
+    if records.len() < MIN_RECORDS_FOR_PERCENTILES {
+        return Err(format!(
+            "Need at least {} tracked days to compute percentiles (found {})",
+            MIN_RECORDS_FOR_PERCENTILES,
+            records.len()
+        ));
+    }
 
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    // Check if already tracking today
-    if session_guard.is_some() {
-        return Err("Already tracking today's session".to_string());
+    let mut durations: Vec<u64> = records.values().map(|r| r.total_duration).collect();
+    durations.sort_unstable();
+    let (low_value, high_value) = (percentile_value(&durations, low), percentile_value(&durations, high));
+
+    let mut matching: Vec<DayRecord> = records
+        .values()
+        .filter(|r| (low_value..=high_value).contains(&(r.total_duration as f64)))
+        .cloned()
+        .collect();
+    matching.sort_by(|a, b| b.date.cmp(&a.date));
+    Ok(matching)
+}
+
+// Parse a UTC offset such as "+02:00", "-0530", "Z" or "UTC".
+fn parse_utc_offset(tz: &str) -> Result<chrono::FixedOffset, String> {
+    let tz = tz.trim();
+    if tz.eq_ignore_ascii_case("z") || tz.eq_ignore_ascii_case("utc") {
+        return Ok(chrono::FixedOffset::east_opt(0).unwrap());
     }
-    
-    let now = Instant::now();
-    let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-    
-    let session = CurrentSession {
-        start_time: now,
-        day_key: today.clone(),
-        current_lap_start: now,
-        current_lap_start_timestamp: current_time,
-        accumulated_seconds: 0,
-        last_activity_time: now,
-        is_paused: false,
-        user_paused: false,
+    let (sign, rest) = match tz.chars().next() {
+        Some('+') => (1, &tz[1..]),
+        Some('-') => (-1, &tz[1..]),
+        _ => return Err(format!("Invalid UTC offset '{}': expected e.g. +02:00", tz)),
     };
-    
-    *session_guard = Some(session);
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid UTC offset '{}': expected e.g. +02:00", tz));
+    }
+    let hours: i32 = digits[..2].parse().map_err(|e: std::num::ParseIntError| e.to_string())?;
+    let minutes: i32 = digits[2..].parse().map_err(|e: std::num::ParseIntError| e.to_string())?;
+    if minutes >= 60 {
+        return Err(format!("Invalid UTC offset '{}'", tz));
+    }
+    chrono::FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+        .ok_or_else(|| format!("Invalid UTC offset '{}'", tz))
+}
 
-    let new_lap = Lap {
-        start_time: current_time,
-        end_time: None,
-        duration: None,
+// Completed laps as Toggl Track time entries, oldest first. Timestamps are written as
+// RFC 3339 in `offset`; they denote the same instants whatever the offset, so it only
+// decides how they read. Open laps are skipped, which is why every duration is positive
+// (Toggl uses a negative duration for a running entry). Window titles are never exported.
+fn toggl_entries(
+    records: &HashMap<String, DayRecord>,
+    workspace_id: u64,
+    project_id: Option<u64>,
+    offset: chrono::FixedOffset,
+) -> Vec<serde_json::Value> {
+    let format_ts = |ts: u64| {
+        chrono::DateTime::from_timestamp(ts as i64, 0)
+            .map(|t| t.with_timezone(&offset).to_rfc3339())
+            .unwrap_or_default()
     };
 
-    // If a record already exists for today (e.g. the user ended their day earlier and is
-    // starting again), APPEND a new lap to it so previous laps are preserved. Otherwise
-    // create a fresh record for the day.
-    if let Some(existing) = records_guard.get_mut(&today) {
-        existing.laps.push(new_lap);
-        existing.is_active = true;
-    } else {
-        records_guard.insert(today.clone(), DayRecord {
-            date: today.clone(),
-            total_duration: 0,
-            laps: vec![new_lap],
-            is_active: true,
-        });
-    }
+    let mut laps: Vec<&Lap> = records
+        .values()
+        .flat_map(|r| r.laps.iter())
+        .filter(|lap| lap.worked_duration().is_some() && lap.end_time.is_some())
+        .collect();
+    laps.sort_by_key(|lap| lap.start_time);
+
+    laps.into_iter()
+        .map(|lap| {
+            serde_json::json!({
+                "start": format_ts(lap.start_time),
+                "stop": format_ts(lap.end_time.unwrap_or(lap.start_time)),
+                "duration": lap.duration.unwrap_or(0) as i64,
+                "description": lap.note.clone().unwrap_or_default(),
+                "workspace_id": workspace_id,
+                "project_id": project_id,
+                "tags": lap.tags,
+                "created_with": "screen-time-tracker",
+            })
+        })
+        .collect()
+}
 
-    Ok(format!("Started tracking for {}", today))
+// Duration as h:mm:ss, the format the Markdown report uses.
+fn format_hms(seconds: u64) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
 }
 
-#[tauri::command]
-async fn end_day(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<DayRecord, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    let session = session_guard.take().ok_or("No active session")?;
-    let day_key = session.day_key.clone();
-    
-    // Calculate final duration for current lap (excluding sleep/hibernate time)
-    let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-    
-    let result = if let Some(day_record) = records_guard.get_mut(&day_key) {
-        // Get actual lap start time from records
-        let lap_start_time = get_active_lap_start_time(day_record)
-            .unwrap_or(session.current_lap_start_timestamp);
-        let lap_duration = finalize_lap_duration(lap_start_time);
-        
-        // Update the last lap
-        if let Some(last_lap) = day_record.laps.last_mut() {
-            last_lap.end_time = Some(current_time);
-            last_lap.duration = Some(lap_duration);
-        }
-        
-        // Calculate total duration
-        day_record.total_duration = day_record.laps.iter()
-            .filter_map(|lap| lap.duration)
-            .sum();
-        
-        day_record.is_active = false;
-        
-        Ok(day_record.clone())
-    } else {
-        Err("Day record not found".to_string())
+// Text safe inside a Markdown table cell: pipes escaped, line breaks flattened.
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+const ANONYMIZE_BUCKET_SECS: u64 = 15 * 60;
+
+// Records as they may leave the machine when anonymized: no notes, tags, app breakdowns,
+// window titles, lap ids or day metadata. Each date becomes its index from
+// `range_start` ("day-0" is the first day) and lap times become seconds from the start
+// of the range, rounded to 15 minutes. Open laps are left out.
+fn anonymize_records<Tz: chrono::TimeZone>(records: &[DayRecord], range_start: chrono::NaiveDate, tz: &Tz) -> Vec<DayRecord> {
+    let anchor = local_hour_timestamp(range_start, 0, tz);
+    let bucket = |ts: u64| {
+        let offset = ts.saturating_sub(anchor);
+        (offset + ANONYMIZE_BUCKET_SECS / 2) / ANONYMIZE_BUCKET_SECS * ANONYMIZE_BUCKET_SECS
     };
-    
-    // Release locks before saving
-    drop(session_guard);
-    drop(records_guard);
-    
-    // Save state to disk
-    save_state(&app_handle, &state);
-    
-    result
+    let mut anonymized: Vec<(chrono::NaiveDate, DayRecord)> = records
+        .iter()
+        .filter_map(|record| {
+            let date = chrono::NaiveDate::parse_from_str(&record.date, "%Y-%m-%d").ok()?;
+            let laps = record
+                .laps
+                .iter()
+                .filter_map(|lap| {
+                    let (start, end) = (bucket(lap.start_time), bucket(lap.end_time?));
+                    Some(Lap {
+                        end_time: Some(end),
+                        duration: Some(end.saturating_sub(start)),
+                        project: lap.project.clone(),
+                        color: lap.color.clone(),
+                        is_synthetic: lap.is_synthetic,
+                        end_reason: lap.end_reason,
+                        id: String::new(),
+                        ..Lap::open(start)
+                    })
+                })
+                .collect();
+            let mut day = DayRecord {
+                date: format!("day-{}", (date - range_start).num_days()),
+                total_duration: 0,
+                laps,
+                is_active: false,
+                metadata: HashMap::new(),
+                ended_explicitly: false,
+            };
+            day.total_duration = worked_seconds(&day);
+            Some((date, day))
+        })
+        .collect();
+    anonymized.sort_by_key(|(date, _)| *date);
+    anonymized.into_iter().map(|(_, day)| day).collect()
 }
 
-#[tauri::command]
-async fn handle_screen_lock(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        // Skip if already paused (prevent duplicate events)
-        if session.is_paused {
-            return Ok("Already paused".to_string());
+// A Markdown report of the inclusive date range: a section per calendar day (including
+// days with nothing tracked) with a table of its completed laps, then a grand total.
+// Window titles get a column only when `include_titles` is set.
+fn markdown_report<Tz: chrono::TimeZone>(
+    records: &HashMap<String, DayRecord>,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+    include_titles: bool,
+    tz: &Tz,
+) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    use std::fmt::Write;
+    let mut md = String::new();
+    let mut grand_total = 0;
+    let _ = writeln!(md, "# Time report: {} to {}", from.format("%Y-%m-%d"), to.format("%Y-%m-%d"));
+    for date in from.iter_days().take_while(|date| *date <= to) {
+        let key = date.format("%Y-%m-%d").to_string();
+        let _ = writeln!(md, "\n## {}\n", date.format("%A, %Y-%m-%d"));
+        let laps: Vec<&Lap> = records
+            .get(&key)
+            .map(|record| record.laps.iter().filter(|lap| lap.worked_duration().is_some()).collect())
+            .unwrap_or_default();
+        if laps.is_empty() {
+            let _ = writeln!(md, "No activity");
+            continue;
         }
-        
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Get actual lap start time from records
-        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
-            get_active_lap_start_time(day_record)
-                .unwrap_or(session.current_lap_start_timestamp)
+        if include_titles {
+            let _ = writeln!(md, "| Start | Duration | Note | Tags | Windows |\n|---|---|---|---|---|");
         } else {
-            session.current_lap_start_timestamp
-        };
-        
-        let lap_duration = finalize_lap_duration(lap_start_time);
-        
-        // End current lap
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            if let Some(last_lap) = day_record.laps.last_mut() {
-                last_lap.end_time = Some(current_time);
-                last_lap.duration = Some(lap_duration);
+            let _ = writeln!(md, "| Start | Duration | Note | Tags |\n|---|---|---|---|");
+        }
+        let mut day_total = 0;
+        for lap in laps {
+            let duration = lap.worked_duration().unwrap_or(0);
+            day_total += duration;
+            let start = chrono::DateTime::from_timestamp(lap.start_time as i64, 0)
+                .map(|t| t.with_timezone(tz).format("%H:%M").to_string())
+                .unwrap_or_default();
+            let note = lap.note.as_deref().filter(|n| !n.trim().is_empty()).map(markdown_cell);
+            let _ = write!(
+                md,
+                "| {} | {} | {} | {} |",
+                start,
+                format_hms(duration),
+                note.as_deref().unwrap_or("—"),
+                markdown_cell(&lap.tags.join(";"))
+            );
+            if include_titles {
+                let _ = write!(md, " {} |", markdown_cell(&lap.window_titles.join(";")));
             }
+            md.push('\n');
         }
-        
-        // Mark as paused by system
-        session.is_paused = true;
-        session.user_paused = false; // System paused
-        
-        Ok("Screen locked - timer paused".to_string())
-    } else {
-        Ok("No active session".to_string())
+        let _ = writeln!(md, "\n**Day total:** {}", format_hms(day_total));
+        grand_total += day_total;
     }
+    let _ = writeln!(md, "\n---\n\n**Grand total:** {}", format_hms(grand_total));
+    md
 }
 
-#[tauri::command]
-async fn handle_screen_unlock(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        // Skip if already active (prevent duplicate events)
-        if !session.is_paused {
-            return Ok("Already active".to_string());
+// One A4 page for a MonthSummary: headline numbers, a bar per day (with the goal as a
+// line across the chart), a totals table and the longest laps. Helvetica is built into
+// every PDF reader, so no font is embedded. Window titles are listed under each long
+// lap only when `include_titles` is set.
+fn month_pdf<Tz: chrono::TimeZone>(summary: &MonthSummary, include_titles: bool, tz: &Tz) -> Result<Vec<u8>, String>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    use printpdf::{BuiltinFont, Color, Mm, PdfDocument, Rect, Rgb};
+    let month = chrono::NaiveDate::parse_from_str(&format!("{}-01", summary.month_key), "%Y-%m-%d")
+        .map(|first| first.format("%B %Y").to_string())
+        .unwrap_or_else(|_| summary.month_key.clone());
+    let (doc, page, layer) = PdfDocument::new(format!("Time report {}", summary.month_key), Mm(210.0), Mm(297.0), "Report");
+    let layer = doc.get_page(page).get_layer(layer);
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let text = |s: &str, size: f32, x: f32, y: f32, font| layer.use_text(s, size, Mm(x), Mm(y), font);
+    let fill = |r: f32, g: f32, b: f32| layer.set_fill_color(Color::Rgb(Rgb::new(r, g, b, None)));
+    let hours_minutes = |secs: u64| format!("{}h {:02}m", secs / 3600, secs % 3600 / 60);
+
+    text(&format!("Time report: {}", month), 20.0, 20.0, 272.0, &bold);
+    text(
+        &format!("{} tracked over {} days", hours_minutes(summary.total_seconds), summary.days_tracked),
+        11.0,
+        20.0,
+        263.0,
+        &regular,
+    );
+
+    // Daily bar chart, scaled to the busiest day (or the goal, if that is higher).
+    let (chart_left, chart_bottom, chart_width, chart_height) = (20.0, 190.0, 170.0, 60.0);
+    let peak = summary
+        .daily_totals
+        .iter()
+        .map(|(_, secs)| *secs)
+        .chain(summary.goal_seconds)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let slot = chart_width / summary.daily_totals.len().max(1) as f32;
+    for (index, (date, secs)) in summary.daily_totals.iter().enumerate() {
+        let x = chart_left + index as f32 * slot;
+        if *secs > 0 {
+            let height = chart_height * *secs as f32 / peak as f32;
+            let met = summary.goal_seconds.is_some_and(|g| *secs >= g);
+            if met { fill(0.2, 0.6, 0.35) } else { fill(0.3, 0.45, 0.8) }
+            layer.add_rect(Rect::new(Mm(x + slot * 0.15), Mm(chart_bottom), Mm(x + slot * 0.85), Mm(chart_bottom + height)));
         }
-        
-        // Only auto-start if user didn't manually pause
-        if !session.user_paused {
-            let now = Instant::now();
-            let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-            
-            // Start new lap
-            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-                day_record.laps.push(Lap {
-                    start_time: current_time,
-                    end_time: None,
-                    duration: None,
-                });
-                
-            }
-            
-            // Reset lap tracking
-            session.current_lap_start = now;
-            session.current_lap_start_timestamp = current_time;
-            session.accumulated_seconds = 0;
-            session.last_activity_time = now;
-            session.is_paused = false;
-            
-            Ok("Screen unlocked - new lap started".to_string())
-        } else {
-            Ok("Screen unlocked - session remains paused (user paused)".to_string())
+        fill(0.0, 0.0, 0.0);
+        text(date.get(8..).unwrap_or(""), 6.0, x + slot * 0.2, chart_bottom - 5.0, &regular);
+    }
+    fill(0.0, 0.0, 0.0);
+    layer.add_rect(Rect::new(Mm(chart_left), Mm(chart_bottom - 0.2), Mm(chart_left + chart_width), Mm(chart_bottom)));
+    if let Some(goal) = summary.goal_seconds {
+        let y = chart_bottom + chart_height * goal as f32 / peak as f32;
+        fill(0.85, 0.3, 0.25);
+        layer.add_rect(Rect::new(Mm(chart_left), Mm(y - 0.15), Mm(chart_left + chart_width), Mm(y + 0.15)));
+        fill(0.0, 0.0, 0.0);
+    }
+    text(&format!("Peak {}", hours_minutes(peak)), 7.0, chart_left, chart_bottom + chart_height + 3.0, &regular);
+
+    // Totals table
+    text("Totals", 13.0, 20.0, 172.0, &bold);
+    let best = summary
+        .best_day
+        .as_ref()
+        .map(|(date, secs)| format!("{} ({})", date, hours_minutes(*secs)))
+        .unwrap_or_else(|| "—".to_string());
+    let goal_line = match (summary.goal_seconds, summary.days_goal_met) {
+        (Some(goal), Some(met)) => format!("{} of {} tracked days reached {}", met, summary.days_tracked, hours_minutes(goal)),
+        _ => "No daily goal set".to_string(),
+    };
+    let rows = [
+        ("Total tracked", hours_minutes(summary.total_seconds)),
+        ("Days tracked", format!("{} of {}", summary.days_tracked, summary.daily_totals.len())),
+        ("Average per tracked day", hours_minutes(summary.average_tracked_day_seconds)),
+        ("Best day", best),
+        ("Goal attainment", goal_line),
+    ];
+    for (row, (label, value)) in rows.iter().enumerate() {
+        let y = 163.0 - row as f32 * 7.0;
+        text(label, 10.0, 20.0, y, &regular);
+        text(value, 10.0, 80.0, y, &bold);
+    }
+
+    // Longest laps
+    text("Longest laps", 13.0, 20.0, 120.0, &bold);
+    let mut y = 111.0;
+    for (label, x) in [("Date", 20.0), ("Start", 48.0), ("Duration", 64.0), ("Note", 86.0), ("Tags", 150.0)] {
+        text(label, 9.0, x, y, &bold);
+    }
+    for (date, lap) in &summary.longest_laps {
+        y -= 7.0;
+        let start = chrono::DateTime::from_timestamp(lap.start_time as i64, 0)
+            .map(|t| t.with_timezone(tz).format("%H:%M").to_string())
+            .unwrap_or_default();
+        let note: String = lap.note.as_deref().unwrap_or("—").chars().take(40).collect();
+        text(date, 9.0, 20.0, y, &regular);
+        text(&start, 9.0, 48.0, y, &regular);
+        text(&format_hms(lap.worked_duration().unwrap_or(0)), 9.0, 64.0, y, &regular);
+        text(&note, 9.0, 86.0, y, &regular);
+        text(&lap.tags.join(";"), 9.0, 150.0, y, &regular);
+        if include_titles && !lap.window_titles.is_empty() {
+            y -= 5.0;
+            let titles: String = lap.window_titles.join(";").chars().take(110).collect();
+            text(&titles, 7.0, 86.0, y, &regular);
         }
-    } else {
-        Ok("No active session".to_string())
     }
+
+    doc.save_to_bytes().map_err(|e| e.to_string())
 }
 
-#[tauri::command]
-async fn get_current_status(state: State<'_, AppStateArc>) -> Result<Option<CurrentStatus>, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        // Calculate total duration from completed laps only
-        let mut total_duration = 0u64;
-        if let Some(day_record) = records_guard.get(&session.day_key) {
-            // Sum all completed laps
-            total_duration = day_record.laps.iter()
-                .filter_map(|lap| lap.duration)
-                .sum();
-        }
-        
-        if session.is_paused {
-            // Session is paused - show only completed laps, no current lap time
-            Ok(Some(CurrentStatus {
-                day_key: session.day_key.clone(),
-                current_lap_duration: 0, // No current lap when paused
-                current_lap_start_timestamp: session.current_lap_start_timestamp,
-                total_session_duration: total_duration, // Only completed laps
-                is_active: false, // Not actively tracking
-            }))
-        } else {
-            // Session is active - use session's current_lap_start_timestamp as source of truth
-            let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-            let current_lap_seconds = current_time - session.current_lap_start_timestamp;
-            
-            // IMPORTANT: total_session_duration should be ONLY completed laps
-            // Frontend will add current_lap_duration for smooth display
-            Ok(Some(CurrentStatus {
-                day_key: session.day_key.clone(),
-                current_lap_duration: current_lap_seconds,
-                current_lap_start_timestamp: session.current_lap_start_timestamp,
-                total_session_duration: total_duration, // Only completed laps, NOT including current lap
-                is_active: true,
-            }))
-        }
-    } else {
-        Ok(None)
-    }
+// Where a copied day summary is going to be pasted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryFormat {
+    #[default]
+    Text,
+    Markdown,
+    Slack,
+}
+
+// One line for a standup note, e.g. "2024-06-03 — 6h 12m across 9 laps, first activity
+// 08:41, last 17:55", from completed laps. Markdown and Slack get the date in bold.
+fn day_summary_line<Tz: chrono::TimeZone>(record: &DayRecord, format: SummaryFormat, tz: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let date = match format {
+        SummaryFormat::Text => record.date.clone(),
+        SummaryFormat::Markdown => format!("**{}**", record.date),
+        SummaryFormat::Slack => format!("*{}*", record.date),
+    };
+    let laps: Vec<&Lap> = record.laps.iter().filter(|lap| lap.worked_duration().is_some()).collect();
+    let (Some(first), Some(last)) = (laps.iter().map(|lap| lap.start_time).min(), laps.iter().filter_map(|lap| lap.end_time).max()) else {
+        return format!("{} — nothing tracked", date);
+    };
+    let clock = |ts: u64| {
+        chrono::DateTime::from_timestamp(ts as i64, 0)
+            .map(|t| t.with_timezone(tz).format("%H:%M").to_string())
+            .unwrap_or_default()
+    };
+    format!(
+        "{} — {} across {} lap{}, first activity {}, last {}",
+        date,
+        format_hours_minutes(worked_seconds(record)),
+        laps.len(),
+        if laps.len() == 1 { "" } else { "s" },
+        clock(first),
+        clock(last)
+    )
 }
 
 #[derive(Debug, Clone, Serialize)]
-pub struct CurrentStatus {
-    pub day_key: String,
-    pub current_lap_duration: u64,
-    pub current_lap_start_timestamp: u64, // For frontend smooth display
-    pub total_session_duration: u64,
-    pub is_active: bool,
+pub struct HourBucket {
+    pub hour: u8, // local hour of day, 0-23
+    pub total_seconds: u64,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct ProductivityReport {
+    pub peak_hour: u8,
+    pub peak_hour_total_seconds: u64,
+    pub hourly_buckets: Vec<HourBucket>, // all 24 hours, midnight first
+    // [start, end) hours of the best 4-hour block; it does not wrap past midnight.
+    pub recommended_focus_window: (u8, u8),
+}
 
-#[tauri::command]
-async fn get_current_day_laps(state: State<'_, AppStateArc>) -> Result<Vec<Lap>, String> {
-    let session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_ref() {
-        if let Some(day_record) = records_guard.get(&session.day_key) {
-            Ok(day_record.laps.clone())
-        } else {
-            Ok(Vec::new())
+const FOCUS_WINDOW_HOURS: usize = 4;
+const MIN_DAYS_FOR_PRODUCTIVITY_REPORT: usize = 3;
+
+// Completed-lap seconds per hour of the day in `tz`, over an inclusive "YYYY-MM-DD"
+// range. A lap is cut at each hour boundary it crosses, so 09:40-11:10 puts 20 minutes
+// in 9, 60 in 10 and 10 in 11.
+fn hourly_totals<Tz: chrono::TimeZone>(
+    records: &HashMap<String, DayRecord>,
+    from: &str,
+    to: &str,
+    tz: &Tz,
+) -> [u64; 24] {
+    use chrono::Timelike;
+    let mut buckets = [0u64; 24];
+    for record in records.values() {
+        if record.date.as_str() < from || record.date.as_str() > to {
+            continue;
+        }
+        for lap in &record.laps {
+            let (Some(end), Some(_)) = (lap.end_time, lap.worked_duration()) else { continue };
+            let mut t = lap.start_time;
+            while t < end {
+                let Some(local) = tz.timestamp_opt(t as i64, 0).single() else { break };
+                let into_hour = (local.minute() * 60 + local.second()) as u64;
+                let slice = (3600 - into_hour).min(end - t);
+                buckets[local.hour() as usize] += slice;
+                t += slice;
+            }
         }
-    } else {
-        Ok(Vec::new())
     }
+    buckets
 }
 
-// Return every stored day record, most recent day first, so the frontend can render
-// the full per-day history (each day with all of its laps and total duration).
-#[tauri::command]
-async fn get_all_day_records(state: State<'_, AppStateArc>) -> Result<Vec<DayRecord>, String> {
-    let records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    let mut records: Vec<DayRecord> = records_guard.values().cloned().collect();
-    // Dates are "YYYY-MM-DD" so lexicographic sort == chronological sort.
-    records.sort_by(|a, b| b.date.cmp(&a.date));
-    Ok(records)
+// The parts of [start, end) that fall outside the local-hour window [start_hour,
+// end_hour), cut at hour boundaries like hourly_totals and merged back when adjacent.
+fn out_of_hours_segments<Tz: chrono::TimeZone>(
+    start: u64,
+    end: u64,
+    start_hour: u8,
+    end_hour: u8,
+    tz: &Tz,
+) -> Vec<(u64, u64)> {
+    use chrono::Timelike;
+    let mut segments: Vec<(u64, u64)> = Vec::new();
+    let mut t = start;
+    while t < end {
+        let Some(local) = tz.timestamp_opt(t as i64, 0).single() else { break };
+        let into_hour = (local.minute() * 60 + local.second()) as u64;
+        let slice = (3600 - into_hour).min(end - t);
+        let in_hours = (u32::from(start_hour)..u32::from(end_hour)).contains(&local.hour());
+        if !in_hours {
+            match segments.last_mut() {
+                Some(last) if last.1 == t => last.1 = t + slice,
+                _ => segments.push((t, t + slice)),
+            }
+        }
+        t += slice;
+    }
+    segments
 }
 
-// Undo an automatic rollover: fold `date`'s laps back into the day before it. The
-// rollover rules get the call right nearly always, but the 00:00-06:00 window is
-// genuinely ambiguous — a 5h break before an early start reads exactly like a late night —
-// and only the user knows which it was. This is the escape hatch, so the ambiguity costs
-// one click rather than a dialog every morning.
-#[tauri::command]
-async fn merge_day_into_previous(
-    app_handle: AppHandle,
-    state: State<'_, AppStateArc>,
-    date: String,
-) -> Result<String, String> {
-    let state_arc = state.inner().clone();
-    let target = {
-        let mut session_guard = state_arc.current_session.lock().map_err(|e| e.to_string())?;
-        let mut records_guard = state_arc.day_records.lock().map_err(|e| e.to_string())?;
-
-        let target = merge_records_into_previous(&mut records_guard, &date)?;
+// Worked seconds of a day inside and outside the work window, completed laps only.
+fn work_window_split<Tz: chrono::TimeZone>(record: &DayRecord, config: &TrackerConfig, tz: &Tz) -> (u64, u64) {
+    let mut in_hours = 0;
+    let mut out_of_hours = 0;
+    for lap in &record.laps {
+        let (Some(end), Some(worked)) = (lap.end_time, lap.worked_duration()) else { continue };
+        let outside: u64 = out_of_hours_segments(lap.start_time, end, config.work_start_hour, config.work_end_hour, tz)
+            .iter()
+            .map(|(s, e)| e - s)
+            .sum();
+        let outside = outside.min(worked);
+        out_of_hours += outside;
+        in_hours += worked - outside;
+    }
+    (in_hours, out_of_hours)
+}
 
-        // If the day being merged away is the one currently being tracked, the live session
-        // has to follow it, or the next lap would recreate the record we just removed.
-        if let Some(session) = session_guard.as_mut() {
-            if session.day_key == date {
-                session.day_key = target.clone();
-            }
+fn productivity_report(buckets: [u64; 24]) -> ProductivityReport {
+    // Earliest hour wins ties, for both the peak and the window.
+    let mut peak_hour = 0;
+    for (hour, secs) in buckets.iter().enumerate() {
+        if *secs > buckets[peak_hour] {
+            peak_hour = hour;
+        }
+    }
+    let mut best_start = 0;
+    let mut best_total = 0;
+    for start in 0..=(24 - FOCUS_WINDOW_HOURS) {
+        let total: u64 = buckets[start..start + FOCUS_WINDOW_HOURS].iter().sum();
+        if total > best_total {
+            best_total = total;
+            best_start = start;
         }
+    }
 
-        drop(session_guard);
-        drop(records_guard);
-        println!("↩️ Merged {} into {}", date, target);
-        target
+    ProductivityReport {
+        peak_hour: peak_hour as u8,
+        peak_hour_total_seconds: buckets[peak_hour],
+        hourly_buckets: buckets
+            .iter()
+            .enumerate()
+            .map(|(hour, secs)| HourBucket { hour: hour as u8, total_seconds: *secs })
+            .collect(),
+        recommended_focus_window: (best_start as u8, (best_start + FOCUS_WINDOW_HOURS) as u8),
+    }
+}
+
+// `hour`:00 on `date` in `tz`, as a unix timestamp. Resolved by the zone's own rules,
+// never by counting 3600s from midnight: across a DST switch a local day is 23 or 25
+// hours. An hour that happens twice (fall back) means its first occurrence; one that is
+// skipped (spring forward) means the moment the clocks jump past it.
+fn local_hour_timestamp<Tz: chrono::TimeZone>(date: chrono::NaiveDate, hour: u32, tz: &Tz) -> u64 {
+    use chrono::{LocalResult, Offset};
+    let naive = date.and_hms_opt(hour, 0, 0).unwrap_or_else(|| date.and_time(chrono::NaiveTime::MIN));
+    let ts = match tz.from_local_datetime(&naive) {
+        LocalResult::Single(t) | LocalResult::Ambiguous(t, _) => t.timestamp(),
+        LocalResult::None => {
+            // In the gap: the jump happens at this wall time under the offset before it.
+            let before = tz
+                .offset_from_local_datetime(&(naive - chrono::Duration::hours(3)))
+                .earliest()
+                .map_or(0, |offset| offset.fix().local_minus_utc());
+            naive.and_utc().timestamp() - i64::from(before)
+        }
     };
+    ts.max(0) as u64
+}
 
-    save_state(&app_handle, &state_arc);
-    Ok(format!("Merged {} into {}", date, target))
+// The DAY_CUTOFF_HOUR of the local date `now` falls on in `tz`.
+fn cutoff_timestamp_in<Tz: chrono::TimeZone>(now: chrono::DateTime<chrono::Utc>, tz: &Tz) -> u64 {
+    local_hour_timestamp(now.with_timezone(tz).date_naive(), DAY_CUTOFF_HOUR, tz)
 }
 
-#[tauri::command]
-async fn add_lap(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        let now = Instant::now();
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Only finalize the last lap if it's still active (no duration set)
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            if let Some(last_lap) = day_record.laps.last_mut() {
-                // Only finalize if this lap is still active (duration is None)
-                if last_lap.duration.is_none() {
-                    let lap_start_time = last_lap.start_time;
-                    let lap_duration = finalize_lap_duration(lap_start_time);
-                    
-                    if lap_duration > 1 {
-                        last_lap.end_time = Some(current_time);
-                        last_lap.duration = Some(lap_duration);
-                    }
-                }
-            }
-            
-            // Start new lap
-            day_record.laps.push(Lap {
-                start_time: current_time,
-                end_time: None,
-                duration: None,
-            });
-            
-        }
-        
-        // Reset current lap tracking and resume session
-        session.current_lap_start = now;
-        session.current_lap_start_timestamp = current_time;
-        session.accumulated_seconds = 0;
-        session.last_activity_time = now;
-        session.is_paused = false; // Resume the session
-        session.user_paused = false; // Clear user pause flag
-        Ok("New lap added successfully - session resumed".to_string())
+// Today's DAY_CUTOFF_HOUR as a unix timestamp, in local time.
+fn cutoff_timestamp_today() -> u64 {
+    cutoff_timestamp_in(chrono::Utc::now(), &chrono::Local)
+}
+
+// Roll the session onto today if the rules above say the previous day is over.
+// Returns (previous_day_key, its final total) when a rollover happened, so the caller
+// can tell the user which day was just closed.
+//
+// Called from the unlock/wake path (the user is back after a break) and from the 30s
+// autosave tick (which is what catches a session that never paused at all).
+fn maybe_roll_over_day(app_handle: &AppHandle, state: &AppStateArc) -> Option<(String, u64)> {
+    let mut session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+
+    let session = session_guard.as_mut()?;
+    let today = today_local_key();
+    // An earlier date than the session's (clock set back, a flight west) never rolls
+    // the day back.
+    if today <= session.day_key {
+        return None;
+    }
+
+    let now = now_unix();
+    let last_lap = records_guard.get(&session.day_key).and_then(|r| r.laps.last());
+    // An open lap means the user is working right now, so there is no gap to measure.
+    let working_through = last_lap.map(|l| l.duration.is_none()).unwrap_or(false);
+    let gap = if working_through {
+        0
+    } else {
+        last_lap
+            .and_then(|l| l.end_time)
+            .map(|end| now.saturating_sub(end))
+            .unwrap_or(0)
+    };
+
+    let past_cutoff = now >= cutoff_timestamp_today();
+    if !should_roll_over(true, gap, past_cutoff) {
+        // Still the same working day: the date rolled over, but the user is either mid-lap
+        // or back from a short break, and it isn't yet the cutoff. Late-night work stays
+        // with the day it started on.
+        return None;
+    }
+
+    let boundary = rollover_boundary(working_through, cutoff_timestamp_today(), now);
+
+    let previous_day = session.day_key.clone();
+    if let Some(old_record) = records_guard.get_mut(&previous_day) {
+        finalize_dangling_lap(old_record, boundary, Some(LapEndReason::DayEnded));
+        old_record.is_active = false;
+    }
+    let previous_total = records_guard
+        .get(&previous_day)
+        .map(|r| r.total_duration)
+        .unwrap_or(0);
+
+    // Open today. If the user worked through the boundary, today's first lap picks up
+    // exactly where the old day's last lap ended, so no time falls into the crack. If the
+    // session is paused (screen locked, machine asleep, or a manual pause), today starts
+    // with no open lap: the unlock/resume path will add one when the user actually
+    // returns, and a manual pause is still honoured across the rollover.
+    let resume_now = !session.is_paused;
+    let laps = if resume_now {
+        vec![session.open_lap(boundary)]
     } else {
-        Err("No active session".to_string())
+        Vec::new()
+    };
+
+    records_guard.insert(today.clone(), DayRecord {
+        date: today.clone(),
+        total_duration: 0,
+        laps,
+        is_active: true,
+        metadata: HashMap::new(),
+        ended_explicitly: false,
+    });
+
+    session.day_key = today.clone();
+    if resume_now {
+        session.current_lap_start_timestamp = boundary;
+        session.accumulated_seconds = 0;
     }
+
+    info!("📅 Rolled over {} -> {} (gap {}s, cutoff {})", previous_day, today, gap, past_cutoff);
+
+    drop(session_guard);
+    drop(records_guard);
+    save_state(app_handle, state);
+
+    Some((previous_day, previous_total))
 }
 
-#[tauri::command]
-async fn stop_lap(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        if session.is_paused {
-            return Err("Session is already paused".to_string());
-        }
-        
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Get actual lap start time from records
-        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
-            get_active_lap_start_time(day_record)
-                .unwrap_or(session.current_lap_start_timestamp)
+// Tell the user a day was closed behind their back. Deliberately a notification rather
+// than a modal: wake/unlock fires constantly (a lid opened for 30s, a 3am maintenance
+// wake), and a dialog that steals focus the moment you sit down is the wrong tax for a
+// background tracker. The automatic call is right nearly always; when it isn't, the day
+// can be merged back from the Reports view.
+fn notify_day_rolled_over(app_handle: &AppHandle, state: &AppStateArc, previous_day: &str, previous_total: u64) {
+    let hours = previous_total / 3600;
+    let minutes = (previous_total % 3600) / 60;
+    let body = format!("{} ended with {}h {}m tracked. Now tracking today.", previous_day, hours, minutes);
+
+    send_alert(app_handle, state, "Started a new day", &body);
+}
+
+// Run the rollover check and notify if it fired.
+fn roll_over_day_if_due(app_handle: &AppHandle, state: &AppStateArc) {
+    if let Some((previous_day, previous_total)) = maybe_roll_over_day(app_handle, state) {
+        notify_day_rolled_over(app_handle, state, &previous_day, previous_total);
+    }
+}
+
+// Deliver a desktop alert (rollover, goals, break reminders). A bundled macOS app posts
+// through UNUserNotificationCenter, asking for permission the first time and posting
+// once it is granted. Anything else — a `tauri dev` binary outside an .app bundle, other
+// platforms — goes through the notification plugin.
+fn send_alert(app_handle: &AppHandle, state: &AppStateArc, title: &str, body: &str) {
+    deliver_alert(app_handle, state, title, body, None);
+}
+
+// send_alert, plus a "Review" button on macOS that emits review-gap with `review_gap`
+// seconds (see install_notification_actions). The plugin fallback has no buttons.
+fn deliver_alert(app_handle: &AppHandle, state: &AppStateArc, title: &str, body: &str, review_gap: Option<u64>) {
+    #[cfg(target_os = "macos")]
+    {
+        let authorized = lock_or_recover(&state.config).notifications_authorized;
+        let delivered = if authorized {
+            post_macos_notification(title, body, review_gap)
         } else {
-            session.current_lap_start_timestamp
-        };
-        
-        let lap_duration = finalize_lap_duration(lap_start_time);
-        
-        // If lap is very short (< 3 seconds), remove it instead of keeping it
-        if lap_duration < 3 {
-            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-                // Remove the last lap if it's too short
-                if let Some(last_lap) = day_record.laps.last() {
-                    if last_lap.duration.is_none() {
-                        // This is the active lap, remove it
-                        day_record.laps.pop();
-                        
-                        // Mark session as paused by user and reset accumulated time
-                        session.is_paused = true;
-                        session.user_paused = true; // User manually paused
-                        session.accumulated_seconds = 0;
-                        
-                        return Ok("Very short lap removed - session paused".to_string());
-                    }
-                }
-            }
-        }
-        
-        // End current lap normally
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            if let Some(last_lap) = day_record.laps.last_mut() {
-                if last_lap.duration.is_none() {
-                    last_lap.end_time = Some(current_time);
-                    last_lap.duration = Some(lap_duration);
+            let (handle, state) = (app_handle.clone(), state.clone());
+            let (title, body) = (title.to_string(), body.to_string());
+            request_macos_notification_authorization(move |granted| {
+                cache_notification_permission(&handle, &state, granted);
+                if granted {
+                    post_macos_notification(&title, &body, review_gap);
+                } else {
+                    alert_undelivered(&handle, &title, &body);
                 }
-            }
+            })
+        };
+        if delivered {
+            info!("✅ Notification posted: {}", title);
+            return;
         }
-        
-        // Mark session as paused by user (not ended)
-        session.is_paused = true;
-        session.user_paused = true; // User manually paused
-        
-        Ok("Lap stopped - session paused".to_string())
-    } else {
-        Err("No active session".to_string())
     }
-}
+    #[cfg(not(target_os = "macos"))]
+    let _ = (state, review_gap);
 
+    use tauri_plugin_notification::NotificationExt;
+    match app_handle.notification().builder().title(title).body(body).show() {
+        Ok(_) => info!("✅ Notification shown: {}", title),
+        Err(e) => error!("❌ Failed to show notification: {}", e),
+    }
+}
 
+// Set once the denied-permission warning has been logged; it is not repeated per alert.
+static NOTIFICATIONS_DENIED_LOGGED: AtomicBool = AtomicBool::new(false);
 
-#[tauri::command]
-async fn check_screen_lock_state() -> Result<bool, String> {
-    // Use the same method as the monitoring function
-    check_screen_lock_state_sync()
+// The user turned notifications off for the app: hand the alert to any open window
+// instead (alert-undelivered), so it can be shown in-app.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn alert_undelivered(app_handle: &AppHandle, title: &str, body: &str) {
+    if !NOTIFICATIONS_DENIED_LOGGED.swap(true, Ordering::Relaxed) {
+        warn!("🔕 Notification permission is denied; alerts go to the app window instead");
+    }
+    let _ = app_handle.emit("alert-undelivered", serde_json::json!({ "title": title, "body": body }));
 }
 
-#[tauri::command]
-async fn test_screen_lock_detection() -> Result<String, String> {
-    // Test all detection methods
-    let mut results = Vec::new();
-    
-    // Method 1: Display sleep check
-    let display_output = Command::new("sh")
-        .arg("-c")
-        .arg("pmset -g ps")
-        .output()
-        .map_err(|e| e.to_string())?;
-    let display_str = String::from_utf8_lossy(&display_output.stdout);
-    results.push(format!("Power state: {}", display_str.trim()));
-    
-    // Method 2: Screen saver check
-    let screensaver_output = Command::new("sh")
-        .arg("-c")
-        .arg("ps aux | grep -E 'ScreenSaverEngine' | grep -v grep")
-        .output()
-        .map_err(|e| e.to_string())?;
-    results.push(format!("Screen saver: {}", if screensaver_output.stdout.is_empty() { "Not running" } else { "Running" }));
-    
-    // Method 3: Login window check
-    let login_output = Command::new("sh")
-        .arg("-c")
-        .arg("ps aux | grep -E 'loginwindow' | grep -v grep | wc -l")
-        .output()
-        .map_err(|e| e.to_string())?;
-    let login_count = String::from_utf8_lossy(&login_output.stdout).trim().parse::<i32>().unwrap_or(0);
-    results.push(format!("Login windows: {}", login_count));
-    
-    // Method 4: Current detection result
-    let current_result = check_screen_lock_state_sync();
-    results.push(format!("Detection result: {}", if current_result.unwrap_or(false) { "LOCKED" } else { "UNLOCKED" }));
-    
-    Ok(results.join("\n"))
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn cache_notification_permission(app_handle: &AppHandle, state: &AppStateArc, granted: bool) {
+    lock_or_recover(&state.config).notifications_authorized = granted;
+    save_state(app_handle, state);
 }
 
+#[cfg(target_os = "macos")]
+#[link(name = "UserNotifications", kind = "framework")]
+extern "C" {}
 
-#[tauri::command]
-async fn handle_system_sleep(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Get actual lap start time from records
-        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
-            get_active_lap_start_time(day_record)
-                .unwrap_or(session.current_lap_start_timestamp)
-        } else {
-            session.current_lap_start_timestamp
-        };
-        
-        let lap_duration = finalize_lap_duration(lap_start_time);
-        
-        // End current lap
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            if let Some(last_lap) = day_record.laps.last_mut() {
-                last_lap.end_time = Some(current_time);
-                last_lap.duration = Some(lap_duration);
-            }
-        }
-        
-        // Mark session as paused
-        session.is_paused = true;
-        
-        Ok("System sleep detected - lap paused".to_string())
+// The shared UNUserNotificationCenter, if this process may use it. The framework throws
+// (taking the whole process down) when the binary is not inside an .app bundle, so a
+// missing bundle identifier means "don't touch it".
+#[cfg(target_os = "macos")]
+unsafe fn macos_notification_center() -> Option<id> {
+    let bundle: id = msg_send![class!(NSBundle), mainBundle];
+    let bundle_id: id = msg_send![bundle, bundleIdentifier];
+    if bundle_id == nil {
+        return None;
+    }
+    let center_class = objc::runtime::Class::get("UNUserNotificationCenter")?;
+    let center: id = msg_send![center_class, currentNotificationCenter];
+    if center == nil {
+        None
     } else {
-        Ok("No active session".to_string())
+        Some(center)
     }
 }
 
-#[tauri::command]
-async fn handle_system_wake(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        let now = Instant::now();
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Start new lap
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            day_record.laps.push(Lap {
-                start_time: current_time,
-                end_time: None,
-                duration: None,
-            });
-            
-        }
-        
-        // Reset lap tracking
-        session.current_lap_start = now;
-        session.current_lap_start_timestamp = current_time;
-        session.accumulated_seconds = 0;
-        session.last_activity_time = now;
-        session.is_paused = false; // Resume the session
-        
-        Ok("System wake detected - new lap started".to_string())
-    } else {
-        Ok("No active session".to_string())
+// Ask for alert + sound permission. macOS only prompts the first time; later calls
+// report the user's stored choice. `on_result` runs on a framework queue once the user
+// answers. Returns false if the notification center is unavailable.
+#[cfg(target_os = "macos")]
+fn request_macos_notification_authorization(on_result: impl Fn(bool) + Send + 'static) -> bool {
+    use block::ConcreteBlock;
+    use objc::runtime::{BOOL, YES};
+
+    // UNAuthorizationOptionSound | UNAuthorizationOptionAlert
+    const OPTIONS: usize = (1 << 1) | (1 << 2);
+
+    unsafe {
+        let Some(center) = macos_notification_center() else { return false };
+        let block = ConcreteBlock::new(move |granted: BOOL, _error: id| on_result(granted == YES));
+        // The center copies the completion handler, so ours can be dropped afterwards.
+        let block = block.copy();
+        let block_ptr = &*block as *const _ as *const std::ffi::c_void;
+        let _: () = msg_send![center, requestAuthorizationWithOptions: OPTIONS
+                                                    completionHandler: block_ptr];
     }
+    true
 }
 
-#[tauri::command]
-async fn handle_user_logout(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Get actual lap start time from records
-        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
-            get_active_lap_start_time(day_record)
-                .unwrap_or(session.current_lap_start_timestamp)
-        } else {
-            session.current_lap_start_timestamp
+// Post a notification immediately, in the idle-gap category (with its Review button)
+// when `review_gap` is set. Returns false if the notification center is unavailable, so
+// the caller can fall back to another delivery path.
+#[cfg(target_os = "macos")]
+fn post_macos_notification(title: &str, body: &str, review_gap: Option<u64>) -> bool {
+    use cocoa::foundation::NSString;
+    use objc::runtime::Class;
+
+    unsafe {
+        let Some(center) = macos_notification_center() else { return false };
+        let (Some(content_class), Some(request_class)) = (
+            Class::get("UNMutableNotificationContent"),
+            Class::get("UNNotificationRequest"),
+        ) else {
+            return false;
         };
-        
-        let lap_duration = finalize_lap_duration(lap_start_time);
-        
-        // End current lap
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            if let Some(last_lap) = day_record.laps.last_mut() {
-                last_lap.end_time = Some(current_time);
-                last_lap.duration = Some(lap_duration);
-            }
-        }
-        
-        // Mark session as paused
-        session.is_paused = true;
-        
-        Ok("User logout detected - lap paused".to_string())
-    } else {
-        Ok("No active session".to_string())
-    }
-}
 
-#[tauri::command]
-async fn handle_user_login(state: State<'_, AppStateArc>) -> Result<String, String> {
-    let mut session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-    let mut records_guard = state.day_records.lock().map_err(|e| e.to_string())?;
-    
-    if let Some(session) = session_guard.as_mut() {
-        let now = Instant::now();
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Start new lap
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            day_record.laps.push(Lap {
-                start_time: current_time,
-                end_time: None,
-                duration: None,
-            });
-            
+        let content: id = msg_send![content_class, new];
+        let ns_title = NSString::alloc(nil).init_str(title);
+        let ns_body = NSString::alloc(nil).init_str(body);
+        // A request with an identifier already on screen replaces it, so make each unique.
+        let identifier = NSString::alloc(nil).init_str(&format!("screen-time-{}-{}", now_unix(), title));
+        let _: () = msg_send![content, setTitle: ns_title];
+        let _: () = msg_send![content, setBody: ns_body];
+        if let Some(gap) = review_gap {
+            let category = NSString::alloc(nil).init_str(IDLE_GAP_CATEGORY);
+            let key = NSString::alloc(nil).init_str(GAP_SECONDS_KEY);
+            let value: id = msg_send![class!(NSNumber), numberWithUnsignedLongLong: gap];
+            let user_info: id = msg_send![class!(NSDictionary), dictionaryWithObject: value forKey: key];
+            let _: () = msg_send![content, setCategoryIdentifier: category];
+            let _: () = msg_send![content, setUserInfo: user_info];
+            let _: () = msg_send![category, release];
+            let _: () = msg_send![key, release];
+        }
+        let request: id = msg_send![request_class, requestWithIdentifier: identifier
+                                                                 content: content
+                                                                 trigger: nil];
+        let _: () = msg_send![center, addNotificationRequest: request withCompletionHandler: nil];
+
+        // The request copies what it needs; release our +1 references.
+        for obj in [ns_title, ns_body, identifier, content] {
+            let _: () = msg_send![obj, release];
         }
-        
-        // Reset lap tracking
-        session.current_lap_start = now;
-        session.current_lap_start_timestamp = current_time;
-        session.accumulated_seconds = 0;
-        session.last_activity_time = now;
-        session.is_paused = false; // Resume the session
-        
-        Ok("User login detected - new lap started".to_string())
-    } else {
-        Ok("No active session".to_string())
     }
+    true
 }
 
-// If two consecutive iterations of the 1s monitoring loop are separated by more
-// than this many seconds of wall-clock time, the process was suspended in between
-// (system sleep) rather than merely scheduled late.
-const SUSPEND_GAP_THRESHOLD_SECS: u64 = 10;
+#[cfg(target_os = "macos")]
+const IDLE_GAP_CATEGORY: &str = "idle-gap";
+#[cfg(target_os = "macos")]
+const REVIEW_GAP_ACTION: &str = "review-gap";
+#[cfg(target_os = "macos")]
+const GAP_SECONDS_KEY: &str = "gap_seconds";
 
-// Wall-clock timestamp of the last NSWorkspace willSleep notification, 0 once
-// consumed. Lets the monitoring loop resync its lock-state machine even after a
-// sleep too short to trip the gap check above (otherwise a <10s sleep that wakes
-// to an unlocked screen would leave the session paused with nothing to resume it).
-static SLEEP_NOTIFIED_AT: AtomicU64 = AtomicU64::new(0);
+// Where the notification delegate emits to. Set once, by install_notification_actions.
+#[cfg(target_os = "macos")]
+static NOTIFICATION_APP: std::sync::OnceLock<AppHandle> = std::sync::OnceLock::new();
 
-// Register for NSWorkspace's willSleep notification so the open lap is closed at
-// the exact moment the machine goes to sleep. The polling thread can't do this:
-// it is frozen during sleep and only learns about it after wake (see the gap
-// detector in start_system_monitoring, which remains as a safety net).
-// No didWake observer is needed — on wake, the monitoring loop's gap detector and
-// lock-state check decide whether to resume immediately (woke unlocked) or wait
-// for the real unlock (woke locked). Resuming blindly on didWake would create a
-// phantom 1-2s lap whenever the Mac wakes to a locked screen.
+// Register the idle-gap category and its "Review" button, and a delegate that turns a
+// press of it into a review-gap event. Without a delegate, button presses are dropped.
 #[cfg(target_os = "macos")]
-fn register_sleep_observer(app_handle: AppHandle, state: AppStateArc) {
-    use block::ConcreteBlock;
+fn install_notification_actions(app_handle: &AppHandle) {
     use cocoa::foundation::NSString;
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Object, Sel};
+
+    extern "C" fn did_receive_response(_this: &Object, _sel: Sel, _center: id, response: id, completion: id) {
+        unsafe {
+            let action: id = msg_send![response, actionIdentifier];
+            let action: *const i8 = msg_send![action, UTF8String];
+            if !action.is_null() && std::ffi::CStr::from_ptr(action).to_str() == Ok(REVIEW_GAP_ACTION) {
+                let notification: id = msg_send![response, notification];
+                let request: id = msg_send![notification, request];
+                let content: id = msg_send![request, content];
+                let user_info: id = msg_send![content, userInfo];
+                let key = NSString::alloc(nil).init_str(GAP_SECONDS_KEY);
+                let value: id = msg_send![user_info, objectForKey: key];
+                let _: () = msg_send![key, release];
+                let gap_seconds: u64 = if value == nil { 0 } else { msg_send![value, unsignedLongLongValue] };
+                if let Some(app) = NOTIFICATION_APP.get() {
+                    open_main_window(app);
+                    let _ = app.emit("review-gap", serde_json::json!({ "gap_seconds": gap_seconds }));
+                }
+            }
+            // The framework waits for this before it considers the response handled.
+            let completion = &*(completion as *const block::Block<(), ()>);
+            completion.call(());
+        }
+    }
 
+    let _ = NOTIFICATION_APP.set(app_handle.clone());
     unsafe {
-        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
-        let center: id = msg_send![workspace, notificationCenter];
-        let name = NSString::alloc(nil).init_str("NSWorkspaceWillSleepNotification");
+        let Some(center) = macos_notification_center() else { return };
+        let (Some(action_class), Some(category_class)) =
+            (Class::get("UNNotificationAction"), Class::get("UNNotificationCategory"))
+        else {
+            return;
+        };
+        // UNNotificationActionOptionForeground: pressing Review brings the app forward.
+        const FOREGROUND: usize = 1 << 2;
+        let action_id = NSString::alloc(nil).init_str(REVIEW_GAP_ACTION);
+        let action_title = NSString::alloc(nil).init_str("Review");
+        let category_id = NSString::alloc(nil).init_str(IDLE_GAP_CATEGORY);
+        let action: id = msg_send![action_class, actionWithIdentifier: action_id title: action_title options: FOREGROUND];
+        let actions: id = msg_send![class!(NSArray), arrayWithObject: action];
+        let no_intents: id = msg_send![class!(NSArray), array];
+        let category: id = msg_send![category_class, categoryWithIdentifier: category_id
+                                                                     actions: actions
+                                                           intentIdentifiers: no_intents
+                                                                     options: 0usize];
+        let categories: id = msg_send![class!(NSSet), setWithObject: category];
+        let _: () = msg_send![center, setNotificationCategories: categories];
+        for obj in [action_id, action_title, category_id] {
+            let _: () = msg_send![obj, release];
+        }
 
-        let block = ConcreteBlock::new(move |_notification: id| {
-            println!("💤 NSWorkspaceWillSleepNotification - closing open lap before suspend");
-            let now = now_unix();
-            handle_system_suspend_direct(&app_handle, &state, now);
-            SLEEP_NOTIFIED_AT.store(now, Ordering::Relaxed);
-        });
-        // The notification center copies the block, but keep our copy alive too:
-        // the observer is registered once and never removed.
-        let block = block.copy();
-        let block_ptr = &*block as *const _ as *const std::ffi::c_void;
-        let _observer: id = msg_send![center, addObserverForName: name
-                                                          object: nil
-                                                           queue: nil
-                                                      usingBlock: block_ptr];
-        std::mem::forget(block);
+        let Some(mut decl) = ClassDecl::new("ScreenTimeNotificationDelegate", class!(NSObject)) else { return };
+        decl.add_method(
+            sel!(userNotificationCenter:didReceiveNotificationResponse:withCompletionHandler:),
+            did_receive_response as extern "C" fn(&Object, Sel, id, id, id),
+        );
+        let delegate: id = msg_send![decl.register(), new];
+        // The center holds its delegate weakly; this one lives as long as the process.
+        let _: () = msg_send![center, setDelegate: delegate];
     }
 }
 
-// System monitoring functions
-fn start_system_monitoring(app_handle: AppHandle, state: AppStateArc) {
-    let state_clone = state.clone();
-    let app_handle_clone = app_handle.clone();
-    
-    thread::spawn(move || {
-        let mut last_screen_lock_state = false;
-        let mut lock_detection_count = 0;
-        let mut unlock_detection_count = 0;
-        let mut last_iteration_ts = now_unix();
-
-        loop {
-            // Suspend (system sleep) detection. Polling can never observe the sleep
-            // transition itself: macOS freezes this thread along with the process, so
-            // the first thing we see is the wake. What we CAN observe is the aftermath —
-            // wall-clock time jumping far beyond the 1s we slept between iterations.
-            // Treat a large jump as "the process was suspended" and retroactively close
-            // the open lap at the last pre-gap timestamp, so none of the asleep interval
-            // is counted as active time. The NSWorkspaceWillSleepNotification observer
-            // normally closes the lap first (at the exact sleep moment); this is the
-            // safety net for a missed notification.
-            let iteration_ts = now_unix();
-            let gap_detected =
-                iteration_ts.saturating_sub(last_iteration_ts) > SUSPEND_GAP_THRESHOLD_SECS;
-            if gap_detected {
-                println!("💤 Suspend gap detected ({}s) - closing lap at pre-gap timestamp",
-                         iteration_ts - last_iteration_ts);
-                handle_system_suspend_direct(&app_handle_clone, &state_clone, last_iteration_ts);
-            }
-            last_iteration_ts = iteration_ts;
+// The away time to report when tracking resumes at `resumed_at`, if the previous lap
+// ended at least `threshold` seconds before it.
+fn idle_gap_to_report(previous_end: Option<u64>, resumed_at: u64, threshold: Option<u64>) -> Option<u64> {
+    let gap = resumed_at.saturating_sub(previous_end?);
+    threshold.filter(|t| *t > 0 && gap >= *t).map(|_| gap)
+}
 
-            // Sleeps shorter than the gap threshold don't produce a detectable gap, but
-            // the willSleep observer still closed the lap. Consume its marker once enough
-            // wall clock has passed that the suspend really happened, so short sleeps go
-            // through the same resync below.
-            let sleep_notified_ts = SLEEP_NOTIFIED_AT.load(Ordering::Relaxed);
-            let slept_via_notification =
-                sleep_notified_ts != 0 && iteration_ts >= sleep_notified_ts + 2;
-            if slept_via_notification {
-                SLEEP_NOTIFIED_AT.store(0, Ordering::Relaxed);
-            }
+fn notify_idle_gap(app_handle: &AppHandle, state: &AppStateArc, gap: u64) {
+    let body = format!("You were away for {}m — timer resumed", gap / 60);
+    deliver_alert(app_handle, state, "Welcome back", &body, Some(gap));
+}
 
-            if gap_detected || slept_via_notification {
-                // Force the lock-state machine to "locked" so the normal unlock path
-                // below starts a fresh lap — whether the Mac woke to a locked screen
-                // (lap resumes at the real unlock) or an already-unlocked one (the
-                // very next check sees "unlocked" and resumes immediately).
-                last_screen_lock_state = true;
-                lock_detection_count = 0;
-                unlock_detection_count = 0;
-            }
+// Set (or clear, with None or 0) the away time that earns a notification on return.
+#[tauri::command]
+async fn set_idle_gap_notification(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    seconds: Option<u64>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.idle_gap_notification_secs = seconds.filter(|s| *s > 0);
+    save_state(&app_handle, &state);
+    Ok(())
+}
 
-            // Check screen lock state
-            match check_screen_lock_state_sync() {
-                Ok(is_locked) => {
-                    // Debounce: require 2 consecutive detections before changing state
-                    if is_locked {
-                        lock_detection_count += 1;
-                        unlock_detection_count = 0;
-                    } else {
-                        unlock_detection_count += 1;
-                        lock_detection_count = 0;
-                    }
-                    
-                    // Only change state after 1 consecutive detection (less strict)
-                    if is_locked && lock_detection_count >= 1 && !last_screen_lock_state {
-                        // Screen just got locked - handle directly
-                        println!("🔒 Screen lock detected!");
-                        handle_screen_lock_direct(&app_handle_clone, &state_clone);
-                        last_screen_lock_state = true;
-                    } else if !is_locked && unlock_detection_count >= 1 && last_screen_lock_state {
-                        // Screen just got unlocked - handle directly
-                        println!("🔓 Screen unlock detected!");
-                        handle_screen_unlock_direct(&app_handle_clone, &state_clone);
-                        last_screen_lock_state = false;
-                    }
-                }
-                Err(e) => eprintln!("Error checking screen lock state: {}", e),
-            }
+// Linux only. The X11 watcher picks the new value up on its next poll; a Wayland
+// compositor keeps the old one until the app restarts.
+#[tauri::command]
+async fn set_idle_threshold(app_handle: AppHandle, state: State<'_, AppStateArc>, seconds: u64) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if seconds < MIN_IDLE_THRESHOLD_SECS {
+        return Err(TrackerError::InvalidInput(format!(
+            "Idle threshold must be at least {}s",
+            MIN_IDLE_THRESHOLD_SECS
+        )));
+    }
+    state.config.lock()?.idle_threshold_secs = seconds;
+    save_state(&app_handle, &state);
+    Ok(())
+}
 
-            // Poll once per second. Sub-second lock/sleep latency isn't needed for a time
-            // tracker (a ~1s error at a lap boundary is negligible), and 1s halves the
-            // subprocess spawns vs. the old 500ms.
-            thread::sleep(Duration::from_millis(1000));
-        }
-    });
+// Set (or clear, with None or 0) how long unbroken work may run before a break alert.
+#[tauri::command]
+async fn set_max_continuous_work_alert(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    seconds: Option<u64>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    {
+        let mut config = state.config.lock()?;
+        config.max_continuous_work_secs = seconds.filter(|s| *s > 0);
+    }
+    save_state(&app_handle, &state);
+    Ok(())
 }
 
-// Direct handlers that don't need State wrapper
-fn handle_screen_lock_direct(app_handle: &AppHandle, state: &AppStateArc) {
-    let mut session_guard = state.current_session.lock().unwrap();
-    let mut records_guard = state.day_records.lock().unwrap();
+// Set the daily target in seconds; 0 clears it.
+#[tauri::command]
+async fn set_daily_goal(app_handle: AppHandle, state: State<'_, AppStateArc>, seconds: u64) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    {
+        let mut config = state.config.lock()?;
+        config.daily_goal_secs = Some(seconds).filter(|s| *s > 0);
+    }
+    info!("🎯 Daily goal set to {}s", seconds);
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Start alternating work / break laps on top of the running session, using the
+// configured PomodoroConfig. The current lap is closed so the first work interval gets
+// a lap of its own.
+#[tauri::command]
+async fn start_pomodoro(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<PomodoroState, TrackerError> {
+    ensure_unlocked()?;
+    let config = state.config.lock()?.pomodoro.clone();
+    let pomodoro = PomodoroState::new(config);
+    {
+        let mut pomodoro_guard = state.pomodoro.lock()?;
+        let mut session_guard = state.current_session.lock()?;
+        let mut records_guard = state.day_records.lock()?;
+        let session = session_guard.as_mut().ok_or(TrackerError::NoActiveSession)?;
+        roll_to_next_lap(session, &mut records_guard, now_unix());
+        label_pomodoro_lap(session, &mut records_guard, &pomodoro);
+        *pomodoro_guard = Some(pomodoro.clone());
+    }
+    info!(
+        "🍅 Pomodoro started: {}s work / {}s break",
+        pomodoro.config.work_seconds, pomodoro.config.short_break_seconds
+    );
+    save_state(&app_handle, &state);
+    let _ = app_handle.emit("pomodoro-interval-changed", &pomodoro);
+    Ok(pomodoro)
+}
+
+// Replace the time-of-day note rules. Laps that already ended are left as they are.
+#[tauri::command]
+async fn set_auto_tag_rules(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    rules: Vec<TaggingRule>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    validate_tagging_rules(&rules)?;
+    {
+        let mut config = state.config.lock()?;
+        config.auto_tag_rules = rules;
+    }
+    info!("🏷️ Auto-tag rules updated");
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_auto_tag_rules(state: State<'_, AppStateArc>) -> Result<Vec<TaggingRule>, TrackerError> {
+    Ok(state.config.lock()?.auto_tag_rules.clone())
+}
+
+// Add a template, or replace the one with the same name.
+#[tauri::command]
+async fn save_template(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    template: SessionTemplate,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let template = normalize_template(template)?;
+    {
+        let mut config = state.config.lock()?;
+        let name = template.name.clone();
+        match config.session_templates.iter_mut().find(|t| t.name == name) {
+            Some(existing) => *existing = template,
+            None => config.session_templates.push(template),
+        }
+        info!("📋 Saved session template '{}'", name);
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_templates(state: State<'_, AppStateArc>) -> Result<Vec<SessionTemplate>, TrackerError> {
+    Ok(state.config.lock()?.session_templates.clone())
+}
+
+// A session already following the template keeps its own copy.
+#[tauri::command]
+async fn delete_template(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    name: String,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    // Stored names are trimmed by save_template, so match on the trimmed form.
+    let name = name.trim();
+    {
+        let mut config = state.config.lock()?;
+        let before = config.session_templates.len();
+        config.session_templates.retain(|t| t.name != name);
+        if config.session_templates.len() == before {
+            return Err(TrackerError::InvalidInput(format!("No template named '{}'", name)));
+        }
+    }
+    info!("🗑️ Deleted session template '{}'", name);
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// start_day, then follow the named template: the first lap takes its first note and
+// each add_lap the next one.
+#[tauri::command]
+async fn start_day_from_template(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    template_name: String,
+) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let template_name = template_name.trim();
+    let template = state.config.lock()?.session_templates.iter()
+        .find(|t| t.name == template_name)
+        .cloned()
+        .ok_or_else(|| TrackerError::InvalidInput(format!("No template named '{}'", template_name)))?;
+    let message = start_day_direct(&app_handle, &state, None, DaySessionOverrides::default())?;
+    {
+        let mut session_guard = state.current_session.lock()?;
+        let mut records_guard = state.day_records.lock()?;
+        if let Some(session) = session_guard.as_mut() {
+            let mut progress = TemplateProgress { template, next_lap: 0 };
+            let note = progress.take_next_note();
+            session.template = Some(progress);
+            if let Some(lap) = records_guard.get_mut(&session.day_key).and_then(|r| r.laps.last_mut()) {
+                lap.note = note;
+            }
+        }
+    }
+    info!("📋 Day started from template '{}'", template_name);
+    save_state(&app_handle, &state);
+    Ok(message)
+}
+
+// Interval lengths used by the next start_pomodoro; a running cycle keeps its own.
+#[tauri::command]
+async fn set_pomodoro_config(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    pomodoro: PomodoroConfig,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if pomodoro.work_seconds == 0 || pomodoro.short_break_seconds == 0 || pomodoro.long_break_seconds == 0 {
+        return Err(TrackerError::InvalidInput("Pomodoro intervals must be longer than zero".to_string()));
+    }
+    {
+        let mut config = state.config.lock()?;
+        config.pomodoro = pomodoro;
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Stop the pomodoro cycle. The running lap keeps going as an ordinary lap.
+#[tauri::command]
+async fn cancel_pomodoro(state: State<'_, AppStateArc>) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let mut pomodoro_guard = state.pomodoro.lock()?;
+    if pomodoro_guard.take().is_none() {
+        return Err(TrackerError::InvalidInput("No pomodoro running".to_string()));
+    }
+    info!("🍅 Pomodoro cancelled");
+    Ok(())
+}
+
+const LOG_FILE_PREFIX: &str = "tracker";
+// Rotation is daily; beyond these caps the oldest files are deleted.
+const MAX_LOG_FILES: usize = 14;
+const MAX_LOG_DIR_BYTES: u64 = 20 * 1024 * 1024;
+
+// Keeps the background log writer alive (and flushing) for the life of the app.
+struct LogGuard(#[allow(dead_code)] tracing_appender::non_blocking::WorkerGuard);
+
+fn get_log_dir(app_handle: &AppHandle) -> Result<PathBuf, TrackerError> {
+    let dir = get_app_data_dir(app_handle)?.join("logs");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// Which log files to delete so the newest ones fit in `max_bytes`. The newest file is
+// always kept, whatever its size. `files` is (path, modified, size).
+fn logs_over_budget(mut files: Vec<(PathBuf, SystemTime, u64)>, max_bytes: u64) -> Vec<PathBuf> {
+    files.sort_by_key(|f| std::cmp::Reverse(f.1));
+    let mut kept = 0u64;
+    let mut excess = Vec::new();
+    for (i, (path, _, size)) in files.into_iter().enumerate() {
+        kept += size;
+        if i > 0 && kept > max_bytes {
+            excess.push(path);
+        }
+    }
+    excess
+}
+
+fn prune_log_dir(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let files: Vec<(PathBuf, SystemTime, u64)> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(LOG_FILE_PREFIX))
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((e.path(), meta.modified().ok()?, meta.len()))
+        })
+        .collect();
+    for path in logs_over_budget(files, MAX_LOG_DIR_BYTES) {
+        fs::remove_file(path).ok();
+    }
+}
+
+// Log to <app_data_dir>/logs/tracker.YYYY-MM-DD.log, plus the console in debug builds
+// (a release app launched from Finder has no console). RUST_LOG overrides the levels.
+// Returns the writer guard, or None if no log file could be opened.
+fn init_logging(app_handle: &AppHandle) -> Option<tracing_appender::non_blocking::WorkerGuard> {
+    use tracing_appender::rolling::{RollingFileAppender, Rotation};
+    use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info,screen_time_tracker_lib=debug"));
+    let console = cfg!(debug_assertions).then(fmt::layer);
+
+    // Nothing is listening yet, so failures here can only go to stderr.
+    let appender = get_log_dir(app_handle)
+        .map_err(|e| e.to_string())
+        .and_then(|dir| {
+            prune_log_dir(&dir);
+            RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(LOG_FILE_PREFIX)
+                .filename_suffix("log")
+                .max_log_files(MAX_LOG_FILES)
+                .build(&dir)
+                .map_err(|e| e.to_string())
+        });
+    let (file, guard) = match appender {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(fmt::layer().with_ansi(false).with_writer(writer)), Some(guard))
+        }
+        Err(e) => {
+            eprintln!("❌ File logging unavailable: {}", e);
+            (None, None)
+        }
+    };
+
+    tracing_subscriber::registry().with(filter).with(console).with(file).try_init().ok();
+    guard
+}
+
+// The directory holding the log files, for an "open logs" action when time goes missing.
+#[tauri::command]
+async fn get_log_path(app_handle: AppHandle) -> Result<String, TrackerError> {
+    Ok(get_log_dir(&app_handle)?.to_string_lossy().to_string())
+}
+
+// Size of state.json in bytes (0 before the first save).
+#[tauri::command]
+async fn get_state_file_size(app_handle: AppHandle) -> Result<u64, TrackerError> {
+    let state_file = get_state_file_path(&app_handle)?;
+    Ok(fs::metadata(&state_file).map(|m| m.len()).unwrap_or(0))
+}
+
+// Rewrite state.json without indentation, atomically, and return its new size. The
+// content is unchanged. With use_pretty_json still on, the next save indents it again;
+// turn that off with set_pretty_json to keep the file compact.
+#[tauri::command]
+async fn compact_state_file(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<u64, TrackerError> {
+    ensure_unlocked()?;
+    let before = get_state_file_size(app_handle.clone()).await?;
+    write_state(&app_handle, &state, true)?;
+    state.persistence.note_success(now_unix());
+    let after = get_state_file_size(app_handle).await?;
+    info!("🗜️ Compacted state.json: {} -> {} bytes", before, after);
+    Ok(after)
+}
+
+#[tauri::command]
+async fn set_pretty_json(app_handle: AppHandle, state: State<'_, AppStateArc>, enabled: bool) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.use_pretty_json = enabled;
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// How often the monitoring loop wakes while tracking, in milliseconds (100-5000). Longer
+// intervals save power at the cost of slower lock detection and coarser lap boundaries.
+#[tauri::command]
+async fn set_monitoring_poll_interval(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    ms: u64,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if !POLL_INTERVAL_RANGE_MS.contains(&ms) {
+        return Err(TrackerError::InvalidInput(format!(
+            "Poll interval must be between {} and {} ms",
+            POLL_INTERVAL_RANGE_MS.start(),
+            POLL_INTERVAL_RANGE_MS.end()
+        )));
+    }
+    {
+        let mut config = state.config.lock()?;
+        config.monitoring_poll_interval_ms = ms;
+    }
+    info!("⏱️ Monitoring poll interval set to {}ms", ms);
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Turn the idle / battery slow-down of the monitoring loop on or off.
+#[tauri::command]
+async fn set_adaptive_polling(app_handle: AppHandle, state: State<'_, AppStateArc>, enabled: bool) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.adaptive_polling = enabled;
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Set (or clear, with None) the local hour at which a running session is ended.
+#[tauri::command]
+async fn set_auto_end_day_at(app_handle: AppHandle, state: State<'_, AppStateArc>, hour: Option<u8>) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if hour.is_some_and(|h| h > 23) {
+        return Err(TrackerError::InvalidInput("hour must be between 0 and 23".to_string()));
+    }
+    state.config.lock()?.auto_end_day_at_hour = hour;
+    info!("🌙 Auto end of day set to {:?}", hour);
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Set (or clear, with None) the battery charge below which tracking pauses while
+// unplugged. Plugging back in resumes it.
+#[tauri::command]
+async fn set_pause_on_battery_below(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    percent: Option<u8>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if percent.is_some_and(|p| p > 100) {
+        return Err(TrackerError::InvalidInput("percent must be between 0 and 100".to_string()));
+    }
+    state.config.lock()?.pause_on_battery_percent = percent;
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Remind every `minutes` (1 up to a day) of a single running lap; None turns reminders off.
+#[tauri::command]
+async fn set_break_reminder_minutes(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    minutes: Option<u64>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if minutes.is_some_and(|m| m == 0 || m > MAX_BREAK_REMINDER_MINUTES) {
+        return Err(TrackerError::InvalidInput(format!(
+            "Break reminders must be between 1 and {} minutes apart",
+            MAX_BREAK_REMINDER_MINUTES
+        )));
+    }
+    {
+        let mut config = state.config.lock()?;
+        config.break_reminder_minutes = minutes.unwrap_or(0);
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Warn once the day's total passes `seconds`; 0 removes the cap.
+#[tauri::command]
+async fn set_overtime_alert(app_handle: AppHandle, state: State<'_, AppStateArc>, seconds: u64) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    {
+        let mut config = state.config.lock()?;
+        config.overtime_alert_seconds = Some(seconds).filter(|s| *s > 0);
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Turn the "daily goal reached" notification on or off.
+#[tauri::command]
+async fn set_goal_notification_enabled(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    enabled: bool,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    {
+        let mut config = state.config.lock()?;
+        config.goal_notification_muted = !enabled;
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Keep the display on while tracking (see TrackerConfig::keep_display_awake). The
+// monitor loop takes or drops the assertion on its next tick.
+#[tauri::command]
+async fn set_keep_display_awake(app_handle: AppHandle, state: State<'_, AppStateArc>, enabled: bool) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.keep_display_awake = enabled;
+    info!("☀️ Keep display awake {}", if enabled { "enabled" } else { "disabled" });
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Whether the display going to sleep pauses tracking (see
+// TrackerConfig::pause_on_display_sleep).
+#[tauri::command]
+async fn set_pause_on_display_sleep(app_handle: AppHandle, state: State<'_, AppStateArc>, enabled: bool) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.pause_on_display_sleep = enabled;
+    info!("🖥️ Pause on display sleep {}", if enabled { "enabled" } else { "disabled" });
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Whether closing the lid pauses tracking (see TrackerConfig::pause_on_lid_close).
+#[tauri::command]
+async fn set_pause_on_lid_close(app_handle: AppHandle, state: State<'_, AppStateArc>, enabled: bool) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.pause_on_lid_close = enabled;
+    info!("💻 Pause on lid close {}", if enabled { "enabled" } else { "disabled" });
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Turn starting the day on the first unlock or wake on or off.
+#[tauri::command]
+async fn set_auto_start_day(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    enabled: bool,
+    weekdays_only: bool,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    {
+        let mut config = state.config.lock()?;
+        config.auto_start_day = enabled;
+        config.auto_start_weekdays_only = weekdays_only;
+    }
+    info!("🌅 Auto-start on unlock {}", if enabled { "enabled" } else { "disabled" });
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Opt in to (or out of) window-title capture. Turning it off stops new captures but keeps
+// what was already recorded; purge_window_titles removes that.
+#[tauri::command]
+async fn set_capture_window_titles(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    enabled: bool,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    {
+        let mut config = state.config.lock()?;
+        config.capture_window_titles = enabled;
+    }
+    info!("🪟 Window title capture {}", if enabled { "enabled" } else { "disabled" });
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Clear the window titles of every lap in `laps`; returns how many had any.
+fn clear_window_titles<'a>(laps: impl IntoIterator<Item = &'a mut Lap>) -> usize {
+    let mut cleared = 0;
+    for lap in laps {
+        if !lap.window_titles.is_empty() {
+            lap.window_titles.clear();
+            cleared += 1;
+        }
+    }
+    cleared
+}
+
+// Parse a stored JSON file, let `scrub` edit it, and write it back (sealed as before)
+// only if anything changed.
+fn scrub_json_file<T: Serialize + serde::de::DeserializeOwned>(
+    path: &Path,
+    scrub: impl FnOnce(&mut T) -> usize,
+) -> Result<(), TrackerError> {
+    let mut value: T = serde_json::from_str(&encryption::read_text(path)?)?;
+    if scrub(&mut value) > 0 {
+        write_atomically(path, &encryption::seal_text(serde_json::to_string_pretty(&value)?)?)?;
+    }
+    Ok(())
+}
+
+// Scrub every captured window title from the whole history: the live days, undo
+// snapshots, the day files' backups, archives, state backups and the journal. Returns
+// how many live laps had any.
+#[tauri::command]
+async fn purge_window_titles(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<usize, TrackerError> {
+    ensure_unlocked()?;
+    let scrubbed = clear_window_titles(all_day_records(&state)?.values_mut().flat_map(|r| r.laps.iter_mut()));
+    for entry in state.undo_stack.lock()?.iter_mut() {
+        clear_window_titles(entry.state.day_records.values_mut().flat_map(|r| r.laps.iter_mut()));
+    }
+    // The save compacts the journal and backs up each rewritten day file, so the backups
+    // below are scrubbed after it.
+    write_state(&app_handle, &state, false)?;
+
+    for path in day_file_paths(&get_days_dir(&app_handle)?).iter().map(|path| day_backup_path(path)) {
+        if path.exists() {
+            scrub_json_file(&path, |record: &mut DayRecord| clear_window_titles(&mut record.laps))?;
+        }
+    }
+    for year_month in archived_months(&app_handle)? {
+        let path = get_archive_file_path(&app_handle, &year_month)?;
+        scrub_json_file(&path, |records: &mut Vec<DayRecord>| {
+            clear_window_titles(records.iter_mut().flat_map(|r| r.laps.iter_mut()))
+        })?;
+        state.archive_cache.lock()?.remove(&year_month);
+    }
+    let state_file = get_state_file_path(&app_handle)?;
+    for path in recovery_candidates(&state_file, &get_backup_file_path(&app_handle)?) {
+        if path.exists() {
+            scrub_json_file(&path, |persisted: &mut PersistedState| {
+                clear_window_titles(persisted.day_records.values_mut().flat_map(|r| r.laps.iter_mut()))
+            })?;
+        }
+    }
+    // Entries appended since the save above are still in the journal.
+    {
+        let journal_file = get_journal_file_path(&app_handle)?;
+        let _journal = lock_or_recover(&state.persistence.journal_lock);
+        if let Ok(contents) = fs::read_to_string(&journal_file) {
+            let (mut entries, _) = parse_journal(&contents);
+            if clear_window_titles(entries.iter_mut().filter_map(|entry| entry.event.lap.as_mut())) > 0 {
+                let mut text = String::new();
+                for entry in &entries {
+                    text.push_str(&journal_line(entry)?);
+                    text.push('\n');
+                }
+                write_atomically(&journal_file, &text)?;
+            }
+        }
+    }
+    info!("🧹 Purged window titles from {} lap(s)", scrubbed);
+    state.persistence.note_success(now_unix());
+    Ok(scrubbed)
+}
+
+// Let the frontend ask for notification permission up front (e.g. from settings) rather
+// than on the first alert. Resolves once the user answers the system prompt.
+#[tauri::command]
+async fn request_notification_permission(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+) -> Result<bool, TrackerError> {
+    ensure_unlocked()?;
+    #[cfg(target_os = "macos")]
+    {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let tx = Mutex::new(Some(tx));
+        if !request_macos_notification_authorization(move |granted| {
+            if let Some(tx) = tx.lock().ok().and_then(|mut t| t.take()) {
+                let _ = tx.send(granted);
+            }
+        }) {
+            return Err(TrackerError::InvalidInput("Notifications are unavailable outside the installed app".to_string()));
+        }
+        let granted = rx.await.map_err(|e| TrackerError::InvalidInput(e.to_string()))?;
+        cache_notification_permission(&app_handle, &state, granted);
+        Ok(granted)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (app_handle, state);
+        Ok(false)
+    }
+}
+
+// Whether an unlock or wake should start the day: the setting is on, nothing is being
+// tracked, today was not ended by hand, and (if asked) today is a work day.
+// `weekday` counts from 0 = Monday, as work_days does.
+fn auto_start_due(config: &TrackerConfig, has_session: bool, today: Option<&DayRecord>, weekday: u8) -> bool {
+    config.auto_start_day
+        && !has_session
+        && !today.is_some_and(|record| record.ended_explicitly)
+        && (!config.auto_start_weekdays_only || config.work_days.contains(&weekday))
+}
+
+// Start the day through start_day_direct if auto_start_due says so; the first lap opens
+// now, at the unlock. Returns whether it did.
+fn auto_start_day_if_due(app_handle: &AppHandle, state: &AppStateArc) -> bool {
+    let today = today_local_key();
+    let weekday = chrono::Datelike::weekday(&chrono::Local::now().date_naive()).num_days_from_monday() as u8;
+    let due = {
+        let has_session = lock_or_recover(&state.current_session).is_some();
+        let records_guard = lock_or_recover(&state.day_records);
+        auto_start_due(&lock_or_recover(&state.config), has_session, records_guard.get(&today), weekday)
+    };
+    if !due {
+        return false;
+    }
+    match start_day_direct(app_handle, state, None, DaySessionOverrides::default()) {
+        Ok(_) => {
+            info!("🌅 Day started automatically for {}", today);
+            true
+        }
+        Err(e) => {
+            warn!("Could not start the day automatically: {}", e);
+            false
+        }
+    }
+}
+
+// Close any still-open lap (duration == None) in a day record, ending it at `end_ts`
+// instead of "now". Used on startup to exclude time the machine spent powered off.
+// Recomputes the record's total_duration afterwards.
+fn finalize_dangling_lap(day_record: &mut DayRecord, end_ts: u64, reason: Option<LapEndReason>) {
+    if let Some(last_lap) = day_record.laps.last_mut() {
+        if last_lap.duration.is_none() {
+            // Guard against a heartbeat that is somehow before the lap start.
+            let end = end_ts.max(last_lap.start_time);
+            last_lap.end_time = Some(end);
+            last_lap.duration = Some(end - last_lap.start_time);
+            last_lap.end_reason = reason;
+        }
+    }
+    day_record.total_duration = day_record.laps.iter().filter_map(Lap::worked_duration).sum();
+}
+
+// Close the lap the persisted session had open when the app went down. It ends at the
+// last monitor tick (lap start + accumulated_seconds), not the heartbeat: the tick
+// advances only while active, so idle time before the crash is not credited. Falls back
+// to the heartbeat when nothing was accumulated (a lap restored by the journal).
+fn close_interrupted_lap(records: &mut HashMap<String, DayRecord>, ps: &PersistedSessionState, heartbeat: u64) {
+    let Some(record) = records.get_mut(&ps.day_key) else { return };
+    let end = if ps.accumulated_seconds > 0 {
+        (ps.current_lap_start_timestamp + ps.accumulated_seconds).min(heartbeat)
+    } else {
+        heartbeat
+    };
+    finalize_dangling_lap(record, end, None);
+}
+
+// Get the path to the state file
+// The per-user data directory, created if missing. Fails if the platform cannot name
+// one or it cannot be created (read-only volume, permissions).
+fn get_app_data_dir(app_handle: &AppHandle) -> Result<PathBuf, TrackerError> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| TrackerError::PersistenceError(std::io::Error::other(e.to_string())))?;
+    fs::create_dir_all(&app_data_dir)?;
+    Ok(app_data_dir)
+}
+
+fn get_state_file_path(app_handle: &AppHandle) -> Result<PathBuf, TrackerError> {
+    Ok(get_app_data_dir(app_handle)?.join("state.json"))
+}
+
+// Last known-good snapshot, rewritten on every successful load. It is the fallback if
+// state.json is ever found corrupt (see load_and_initialize).
+fn get_backup_file_path(app_handle: &AppHandle) -> Result<PathBuf, TrackerError> {
+    Ok(get_app_data_dir(app_handle)?.join("state.backup.json"))
+}
+
+// One file per settled day ("YYYY-MM-DD.json"), kept out of state.json so the
+// autosave only rewrites the days that are still changing.
+fn get_days_dir(app_handle: &AppHandle) -> Result<PathBuf, TrackerError> {
+    Ok(get_app_data_dir(app_handle)?.join("days"))
+}
+
+// Session events since the last snapshot, one JSON line each (see append_journal).
+fn get_journal_file_path(app_handle: &AppHandle) -> Result<PathBuf, TrackerError> {
+    Ok(get_app_data_dir(app_handle)?.join("journal.jsonl"))
+}
+
+// Archived history for one calendar month ("YYYY-MM"), written by archive_old_records.
+fn get_archive_file_path(app_handle: &AppHandle, year_month: &str) -> Result<PathBuf, TrackerError> {
+    Ok(get_app_data_dir(app_handle)?.join(format!("archive-{}.json", year_month)))
+}
+
+// Day records dated before `cutoff` ("YYYY-MM-DD"), grouped by "YYYY-MM" and sorted by
+// date within each month. The live session's day, and any day still marked active, is
+// never archived, however old.
+fn archivable_records(
+    records: &HashMap<String, DayRecord>,
+    cutoff: &str,
+    session_day: Option<&str>,
+) -> BTreeMap<String, Vec<DayRecord>> {
+    let mut by_month: BTreeMap<String, Vec<DayRecord>> = BTreeMap::new();
+    for (key, record) in records {
+        if key.as_str() >= cutoff || Some(key.as_str()) == session_day || record.is_active || key.len() < 7 {
+            continue;
+        }
+        by_month.entry(key[..7].to_string()).or_default().push(record.clone());
+    }
+    for month in by_month.values_mut() {
+        month.sort_by(|a, b| a.date.cmp(&b.date));
+    }
+    by_month
+}
+
+// Fold `incoming` into an existing month archive. A day archived twice keeps the newer copy.
+fn merge_archive(existing: Vec<DayRecord>, incoming: Vec<DayRecord>) -> Vec<DayRecord> {
+    let mut by_date: BTreeMap<String, DayRecord> =
+        existing.into_iter().map(|r| (r.date.clone(), r)).collect();
+    for record in incoming {
+        by_date.insert(record.date.clone(), record);
+    }
+    by_date.into_values().collect()
+}
+
+// Two laps from different machines covering the same wall-clock time. Indexes are into
+// the merged day's laps.
+#[derive(Debug, Clone, Serialize)]
+pub struct LapConflict {
+    pub date: String,
+    pub local_index: usize,
+    pub local_id: String,
+    pub imported_index: usize,
+    pub imported_id: String,
+    pub overlap_seconds: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub days_added: usize,
+    pub days_merged: usize,
+    pub laps_added: usize,
+    pub laps_replaced: usize, // same lap, and the imported copy ended later
+    pub conflicts: Vec<LapConflict>,
+    pub days_skipped: Vec<String>, // the running session's day, which only this machine writes
+    pub open_laps_dropped: usize,  // laps still running on the other machine
+}
+
+// Drop the laps `record` still has running: an export taken mid-lap has no end for them,
+// and importing one would leave a second open lap, or a day that never stops growing.
+fn drop_open_laps(record: &mut DayRecord) -> usize {
+    let before = record.laps.len();
+    record.laps.retain(|lap| lap.end_time.is_some());
+    before - record.laps.len()
+}
+
+// Whether `a` ended after `b`. An open lap counts as the latest.
+fn ends_later(a: &Lap, b: &Lap) -> bool {
+    match (a.end_time, b.end_time) {
+        (None, Some(_)) => true,
+        (Some(a_end), Some(b_end)) => a_end > b_end,
+        _ => false,
+    }
+}
+
+// Fold an imported copy of a day into the local one, keyed on Lap::key. A lap both have
+// keeps whichever copy ended later; the rest are unioned in start order. Imported laps
+// that overlap a local one are flagged in the report, never dropped. Local metadata wins.
+fn merge_imported_day(local: &mut DayRecord, incoming: DayRecord, report: &mut ImportReport) {
+    let date = local.date.clone();
+    let mut imported = std::collections::HashSet::new();
+    for lap in incoming.laps {
+        let key = lap.key(&date);
+        match local.laps.iter_mut().find(|existing| existing.key(&date) == key) {
+            Some(existing) => {
+                if ends_later(&lap, existing) {
+                    *existing = lap;
+                    report.laps_replaced += 1;
+                }
+            }
+            None => {
+                imported.insert(key);
+                local.laps.push(lap);
+                report.laps_added += 1;
+            }
+        }
+    }
+    for (key, value) in incoming.metadata {
+        local.metadata.entry(key).or_insert(value);
+    }
+    local.laps.sort_by_key(|lap| lap.start_time);
+    local.total_duration = worked_seconds(local);
+
+    let keys: Vec<String> = local.laps.iter().map(|lap| lap.key(&date)).collect();
+    let window = |lap: &Lap| (lap.start_time, lap.end_time.unwrap_or(lap.start_time));
+    for (local_index, local_lap) in local.laps.iter().enumerate() {
+        if local_lap.is_synthetic || imported.contains(&keys[local_index]) {
+            continue;
+        }
+        for (imported_index, imported_lap) in local.laps.iter().enumerate() {
+            if imported_lap.is_synthetic || !imported.contains(&keys[imported_index]) {
+                continue;
+            }
+            let ((a_start, a_end), (b_start, b_end)) = (window(local_lap), window(imported_lap));
+            let overlap_seconds = a_end.min(b_end).saturating_sub(a_start.max(b_start));
+            if overlap_seconds > 0 {
+                report.conflicts.push(LapConflict {
+                    date: date.clone(),
+                    local_index,
+                    local_id: keys[local_index].clone(),
+                    imported_index,
+                    imported_id: keys[imported_index].clone(),
+                    overlap_seconds,
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapKind {
+    Overlap,
+    Duplicate, // both laps start at the same second, as two sessions started together do
+}
+
+// Two completed laps of one day covering the same time. lap_a started first (or with
+// lap_b, for a duplicate); indexes are into the day's laps.
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlapReport {
+    pub day_key: String,
+    pub lap_a_index: usize,
+    pub lap_b_index: usize,
+    pub overlap_seconds: u64,
+    pub kind: OverlapKind,
+}
+
+// Overlaps among a day's completed laps in one sort and a sweep. Each lap is reported at
+// most once: as a duplicate of the lap sorted just before it when both start together,
+// otherwise against the earlier lap reaching furthest past its start. Synthetic break
+// laps are never tracked time, so they never conflict.
+fn overlapping_laps(record: &DayRecord) -> Vec<OverlapReport> {
+    let mut laps: Vec<(usize, u64, u64)> = record
+        .laps
+        .iter()
+        .enumerate()
+        .filter(|(_, lap)| !lap.is_synthetic)
+        .filter_map(|(index, lap)| Some((index, lap.start_time, lap.end_time?)))
+        .collect();
+    laps.sort_by_key(|&(index, start, end)| (start, end, index));
+
+    let mut reports = Vec::new();
+    let mut previous: Option<(usize, u64, u64)> = None;
+    let mut furthest: Option<(usize, u64, u64)> = None;
+    for (index, start, end) in laps {
+        let conflict = match (previous, furthest) {
+            (Some(prev @ (_, prev_start, _)), _) if prev_start == start => Some((prev, OverlapKind::Duplicate)),
+            (_, Some(far @ (_, _, far_end))) if start < far_end => Some((far, OverlapKind::Overlap)),
+            _ => None,
+        };
+        if let Some(((a_index, _, a_end), kind)) = conflict {
+            reports.push(OverlapReport {
+                day_key: record.date.clone(),
+                lap_a_index: a_index,
+                lap_b_index: index,
+                overlap_seconds: end.min(a_end).saturating_sub(start),
+                kind,
+            });
+        }
+        if furthest.is_none_or(|(_, _, a_end)| end > a_end) {
+            furthest = Some((index, start, end));
+        }
+        previous = Some((index, start, end));
+    }
+    reports
+}
+
+// Laps that overlap within a day, as left behind by two sessions running at once, across
+// the live and archived history. Read-only: nothing is changed, so the user can decide
+// which lap to keep.
+#[tauri::command]
+async fn detect_overlapping_laps(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<Vec<OverlapReport>, TrackerError> {
+    let history = history_in_range(&app_handle, &state, "0000-01-01", "9999-12-31")?;
+    let mut days: Vec<&DayRecord> = history.values().collect();
+    days.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(days.into_iter().flat_map(overlapping_laps).collect())
+}
+
+fn read_archive(path: &Path) -> Result<Vec<DayRecord>, String> {
+    let json = encryption::read_text(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+// Read and parse a state file.
+//   None            -> the file does not exist (a genuine first run)
+//   Some(Err(..))   -> the file exists but is unreadable or unparseable (corruption)
+// The distinction matters: a missing file means "start fresh", but a corrupt file must
+// never be treated that way, or we would blank the history and save over the only copy.
+fn read_state_file(path: &Path) -> Option<Result<PersistedState, String>> {
+    if !path.exists() {
+        return None;
+    }
+    match encryption::read_text(path) {
+        Ok(json) => Some(serde_json::from_str::<PersistedState>(&json).map_err(|e| e.to_string())),
+        Err(e) => Some(Err(e.to_string())),
+    }
+}
+
+// A state file that exists and parses, or None for anything else.
+fn try_load_persisted_state(path: &Path) -> Option<PersistedState> {
+    read_state_file(path).and_then(Result::ok)
+}
+
+// Older load-time snapshots kept besides state.backup.json: state.json.1 (newest) to .3.
+const BACKUP_GENERATIONS: u32 = 3;
+
+fn backup_generation_path(state_file: &Path, generation: u32) -> PathBuf {
+    state_file.with_extension(format!("json.{}", generation))
+}
+
+// Where to look, newest first, when state.json is corrupt.
+fn recovery_candidates(state_file: &Path, backup_file: &Path) -> Vec<PathBuf> {
+    std::iter::once(backup_file.to_path_buf())
+        .chain((1..=BACKUP_GENERATIONS).map(|n| backup_generation_path(state_file, n)))
+        .collect()
+}
+
+// Shift every snapshot down one generation, dropping the oldest, so the backup can be
+// rewritten.
+fn rotate_backups(state_file: &Path, backup_file: &Path) {
+    for n in (1..BACKUP_GENERATIONS).rev() {
+        let from = backup_generation_path(state_file, n);
+        if from.exists() {
+            fs::rename(&from, backup_generation_path(state_file, n + 1)).ok();
+        }
+    }
+    if backup_file.exists() {
+        fs::rename(backup_file, backup_generation_path(state_file, 1)).ok();
+    }
+}
+
+// What happened when state.json was found corrupt at startup. Emitted as
+// state-recovery (source is set) or state-recovery-failed (history started empty).
+#[derive(Debug, Clone, Serialize)]
+pub struct StateRecovery {
+    pub source: Option<String>, // the snapshot history was restored from
+    pub quarantined: Option<String>, // where the corrupt state.json was moved
+}
+
+// A previous day that load_and_initialize ended because its session was over. Emitted
+// as day-auto-closed so the UI can say when that day's last lap was cut off; the event
+// fires before any window listens, so it is also kept for get_day_auto_closed.
+#[derive(Debug, Clone, Serialize)]
+pub struct DayAutoClosed {
+    pub day_key: String,
+    pub closed_at: Option<u64>, // end of the day's last lap, None if it had no laps
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateFileCheck {
+    pub path: String,
+    pub exists: bool,
+    pub valid: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub files: Vec<StateFileCheck>, // state.json, then each backup newest first
+    // Set when this run had to recover (the startup events fire before any window listens).
+    pub last_recovery: Option<StateRecovery>,
+}
+
+// Whether state.json and each backup still parse, and whether startup had to recover.
+#[tauri::command]
+async fn check_state_file_integrity(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+) -> Result<IntegrityReport, TrackerError> {
+    let state_file = get_state_file_path(&app_handle)?;
+    let backup_file = get_backup_file_path(&app_handle)?;
+    let files = std::iter::once(state_file.clone())
+        .chain(recovery_candidates(&state_file, &backup_file))
+        .map(|path| StateFileCheck {
+            exists: path.exists(),
+            valid: try_load_persisted_state(&path).is_some(),
+            path: path.to_string_lossy().to_string(),
+        })
+        .collect();
+    Ok(IntegrityReport {
+        files,
+        last_recovery: lock_or_recover(&state.persistence.last_recovery).clone(),
+    })
+}
+
+// The day startup closed automatically, until the UI acknowledges it.
+#[tauri::command]
+async fn get_day_auto_closed(state: State<'_, AppStateArc>) -> Result<Option<DayAutoClosed>, TrackerError> {
+    Ok(state.persistence.day_auto_closed.lock()?.clone())
+}
+
+#[tauri::command]
+async fn acknowledge_day_auto_closed(state: State<'_, AppStateArc>) -> Result<(), TrackerError> {
+    *state.persistence.day_auto_closed.lock()? = None;
+    Ok(())
+}
+
+// What the next launch would start from, as read from disk.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecoveryPreview {
+    pub has_session: bool,
+    pub session_day_key: Option<String>,
+    pub session_accumulated_seconds: Option<u64>,
+    pub day_records_count: usize, // state.json's days and the settled ones in days/
+    pub file_size_bytes: u64,     // state.json plus every day file
+    pub last_modified: u64, // unix seconds
+    pub schema_version: u32,
+}
+
+// `day_files` are the dates with a file in days/; a day also in state.json counts once.
+fn recovery_preview(persisted: &PersistedState, day_files: &[String], file_size_bytes: u64, last_modified: u64) -> RecoveryPreview {
+    let session = persisted.current_session.as_ref();
+    let settled_only = day_files.iter().filter(|date| !persisted.day_records.contains_key(*date)).count();
+    RecoveryPreview {
+        has_session: session.is_some(),
+        session_day_key: session.map(|s| s.day_key.clone()),
+        session_accumulated_seconds: session.map(|s| s.accumulated_seconds),
+        day_records_count: persisted.day_records.len() + settled_only,
+        file_size_bytes,
+        last_modified,
+        schema_version: persisted.schema_version,
+    }
+}
+
+// Read state.json (plus the journal entries written after it, as startup would) and list
+// days/ without touching the in-memory state, so the user can check what a restart would load.
+#[tauri::command]
+async fn get_session_recovery_info(app_handle: AppHandle) -> Result<RecoveryPreview, TrackerError> {
+    let state_file = get_state_file_path(&app_handle)?;
+    let metadata = fs::metadata(&state_file)?;
+    let mut persisted: PersistedState = serde_json::from_str(&encryption::read_text(&state_file)?)?;
+    if let Ok(contents) = get_journal_file_path(&app_handle).and_then(|path| Ok(fs::read_to_string(path)?)) {
+        replay_journal(&mut persisted, &parse_journal(&contents).0);
+    }
+    let last_modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs());
+    let paths = day_file_paths(&get_days_dir(&app_handle)?);
+    let size = metadata.len() + paths.iter().map(|path| fs::metadata(path).map_or(0, |m| m.len())).sum::<u64>();
+    let day_files: Vec<String> = paths.iter().filter_map(|path| day_file_date(path)).collect();
+    Ok(recovery_preview(&persisted, &day_files, size, last_modified))
+}
+
+// Bulk repair for totals that drifted from their laps. get_current_status already sums
+// the laps itself, so the running day needs nothing beyond the corrected record.
+#[tauri::command]
+async fn recompute_all_totals(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+) -> Result<Vec<String>, TrackerError> {
+    ensure_unlocked()?;
+    let corrected = recompute_totals(&mut *all_day_records(&state)?);
+    info!("🔧 Recomputed totals, {} day(s) corrected", corrected.len());
+    save_state(&app_handle, &state);
+    Ok(corrected)
+}
+
+// Capture the in-memory state in its persisted form. Takes both locks, so callers must
+// not be holding either.
+fn snapshot_state(state: &AppStateArc) -> PersistedState {
+    let session_guard = lock_or_recover(&state.current_session);
+    let records_guard = lock_or_recover(&state.day_records);
+    persisted_form(state, session_guard.as_ref(), records_guard.clone())
+}
+
+// Before an action is made undoable: the snapshot must hold every day, or undoing would
+// drop the settled days loaded in the meantime.
+fn undo_snapshot(state: &AppStateArc) -> PersistedState {
+    load_settled_days(state);
+    snapshot_state(state)
+}
+
+// `session` and `day_records` as persisted, with the config and event log. Called with
+// the session and records locks held.
+fn persisted_form(state: &AppStateArc, session: Option<&CurrentSession>, day_records: HashMap<String, DayRecord>) -> PersistedState {
+    let config = lock_or_recover(&state.config).clone();
+    let events = lock_or_recover(&state.events).by_day.clone();
+    // Read while the session and records are locked. An entry appended just after is
+    // replayed over a snapshot that may already hold it, which replay tolerates.
+    let journal_seq = state.persistence.journal_seq.load(Ordering::Relaxed);
+
+    let persisted_session = session.map(|session| {
+        PersistedSessionState {
+            day_key: session.day_key.clone(),
+            current_lap_start_timestamp: session.current_lap_start_timestamp,
+            accumulated_seconds: session.accumulated_seconds,
+            is_paused: session.is_paused,
+            user_paused: session.user_paused,
+            project: session.project.clone(),
+            paused_cause: session.paused_cause,
+            paused_since: session.paused_since,
+            overrides: session.overrides.clone(),
+            template: session.template.clone(),
+        }
+    });
+
+    PersistedState {
+        current_session: persisted_session,
+        day_records,
+        last_heartbeat: now_unix(),
+        schema_version: CURRENT_SCHEMA_VERSION,
+        config,
+        events,
+        journal_seq,
+    }
+}
+
+// Rebuild a live session from its persisted form. The continuous-work stretch is not
+// persisted, so it restarts with the next monitor tick.
+fn restore_session(ps: &PersistedSessionState) -> CurrentSession {
+    CurrentSession {
+        day_key: ps.day_key.clone(),
+        current_lap_start_timestamp: ps.current_lap_start_timestamp,
+        accumulated_seconds: ps.accumulated_seconds,
+        is_paused: ps.is_paused,
+        user_paused: ps.user_paused,
+        project: ps.project.clone(),
+        continuous_work_start: None,
+        continuous_work_alerted: false,
+        break_reminders_sent: 0,
+        paused_cause: ps.paused_cause.or(ps.is_paused.then_some(PauseCause::Restart)),
+        paused_since: ps.paused_since,
+        overrides: ps.overrides.clone(),
+        template: ps.template.clone(),
+    }
+}
+
+// How many actions undo_last_action can step back through.
+const MAX_UNDO_DEPTH: usize = 10;
+
+// Record the state from before a successful user action so it can be undone. Callers
+// take the snapshot before mutating and push it only once the action has succeeded, so
+// a rejected command never leaves a no-op entry on the stack.
+fn push_undo(state: &AppStateArc, snapshot: PersistedState, description: &str) {
+    {
+        let mut stack = lock_or_recover(&state.undo_stack);
+        stack.push(UndoEntry { state: snapshot, description: description.to_string() });
+        if stack.len() > MAX_UNDO_DEPTH {
+            stack.remove(0);
+        }
+    }
+}
+
+// Save state to disk
+// Callers never handle the error themselves: a failure is recorded on
+// AppState::persistence and reported to the user by note_save_failure.
+fn save_state(app_handle: &AppHandle, state: &AppStateArc) {
+    let started = Instant::now();
+    match write_state(app_handle, state, false) {
+        Ok(()) => {
+            debug!(elapsed_ms = started.elapsed().as_millis() as u64, "✅ State saved successfully");
+            state.persistence.note_success(now_unix());
+        }
+        Err(e) => {
+            error!("❌ Failed to save state: {}", e);
+            note_save_failure(app_handle, state, &e);
+        }
+    }
+}
+
+// Write to a sibling temp file, then rename into place. rename(2) within a filesystem is
+// atomic, so a crash or power cut mid-save can never leave a half-written file behind —
+// the reader either sees the whole old file or the whole new one. A plain fs::write
+// truncates first, and dying in that window used to leave a truncated state.json that
+// the next launch could not parse. The temp file is synced before the rename, so a power
+// cut cannot leave the new name pointing at data that never reached the disk.
+fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    let tmp_file = path.with_extension("json.tmp");
+    let written = fs::File::create(&tmp_file)
+        .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&tmp_file, path));
+    if written.is_err() {
+        fs::remove_file(&tmp_file).ok();
+    }
+    // The rename itself is durable once the directory is synced (not possible on Windows).
+    #[cfg(unix)]
+    if written.is_ok() {
+        if let Some(dir) = path.parent() {
+            fs::File::open(dir).and_then(|dir| dir.sync_all()).ok();
+        }
+    }
+    written
+}
+
+// Days that are still changing live in state.json: today, the session's day, and any
+// day not yet ended. Every other day is settled and lives in its own file under days/.
+fn is_hot_day(record: &DayRecord, today: &str, session_day: Option<&str>) -> bool {
+    record.is_active || record.date == today || Some(record.date.as_str()) == session_day
+}
+
+// A cheap identity for a day's content, to tell whether its file is already current.
+// Hashes the serialized form without building the string.
+fn day_fingerprint(record: &DayRecord) -> u64 {
+    use std::hash::Hasher;
+    struct HashWriter(std::collections::hash_map::DefaultHasher);
+    impl std::io::Write for HashWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut writer = HashWriter(Default::default());
+    serde_json::to_writer(&mut writer, record).ok();
+    writer.0.finish()
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct DayWritePlan {
+    write: Vec<(String, u64)>, // settled days whose file is missing or out of date
+    remove: Vec<String>,       // day files for days that are gone or hot again
+}
+
+fn plan_day_writes(
+    records: &HashMap<String, DayRecord>,
+    is_hot: impl Fn(&DayRecord) -> bool,
+    written: &HashMap<String, u64>,
+) -> DayWritePlan {
+    let mut plan = DayWritePlan::default();
+    for (date, record) in records {
+        if is_hot(record) {
+            continue;
+        }
+        let fingerprint = day_fingerprint(record);
+        if written.get(date) != Some(&fingerprint) {
+            plan.write.push((date.clone(), fingerprint));
+        }
+    }
+    for date in written.keys() {
+        if records.get(date).is_none_or(&is_hot) {
+            plan.remove.push(date.clone());
+        }
+    }
+    plan
+}
+
+// The copy of a day file from before its last rewrite or removal: YYYY-MM-DD.json.bak.
+fn day_backup_path(path: &Path) -> PathBuf {
+    path.with_extension("json.bak")
+}
+
+fn read_day_file(path: &Path) -> Result<DayRecord, String> {
+    let json = encryption::read_text(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+// Every parsable day file, keyed by date. A file that won't parse is logged and left in
+// place for inspection, and its backup is read instead.
+fn read_day_files(days_dir: &Path) -> HashMap<String, DayRecord> {
+    let mut days = HashMap::new();
+    for path in day_file_paths(days_dir) {
+        let record = read_day_file(&path).or_else(|e| {
+            warn!("⚠️ Unreadable day file {}: {}", path.display(), e);
+            read_day_file(&day_backup_path(&path))
+        });
+        match record {
+            Ok(record) => {
+                days.insert(record.date.clone(), record);
+            }
+            Err(e) => warn!("⚠️ Skipping {}, its backup is unreadable too: {}", path.display(), e),
+        }
+    }
+    days
+}
+
+// Every YYYY-MM-DD.json in days/, without reading them.
+fn day_file_paths(days_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(days_dir) else { return Vec::new() };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("json"))
+        .collect()
+}
+
+fn day_file_date(path: &Path) -> Option<String> {
+    path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string)
+}
+
+// Read days/ into day_records the first time anything needs past history. Startup only
+// lists the files, so a long history costs nothing until the history is opened. A day
+// already in memory keeps that copy: state.json's is never older than its file.
+fn load_settled_days(state: &AppStateArc) {
+    let mut cold = lock_or_recover(&state.persistence.cold_days);
+    let Some(days_dir) = cold.take() else { return };
+    let started = Instant::now();
+    let days = read_day_files(&days_dir);
+    let mut records_guard = lock_or_recover(&state.day_records);
+    let mut loaded = lock_or_recover(&state.persistence.loaded_days);
+    for (date, record) in days {
+        if let std::collections::hash_map::Entry::Vacant(slot) = records_guard.entry(date.clone()) {
+            loaded.insert(date, day_fingerprint(&record));
+            slot.insert(record);
+        }
+    }
+    state.persistence.settled_loaded.store(true, Ordering::Relaxed);
+    state.persistence.settled_generation.fetch_add(1, Ordering::Relaxed);
+    info!("📂 Loaded {} settled day(s) in {}ms", loaded.len(), started.elapsed().as_millis());
+}
+
+// At startup, a day file for a day already in memory is stale. Noting it as written, with
+// no fingerprint, has the first save rewrite it, or remove it if the day is hot.
+fn note_shadowed_day_files(state: &AppStateArc, records: &HashMap<String, DayRecord>, day_files: &[String]) {
+    let mut written = lock_or_recover(&state.persistence.written_days);
+    for date in day_files.iter().filter(|date| records.contains_key(*date)) {
+        written.entry(date.clone()).or_insert(0);
+    }
+}
+
+// day_records with every settled day loaded, for anything that reads past days. The hot
+// paths (the monitor tick, saves, the running day's laps) lock day_records directly.
+fn all_day_records(state: &AppStateArc) -> Result<MutexGuard<'_, HashMap<String, DayRecord>>, TrackerError> {
+    load_settled_days(state);
+    Ok(state.day_records.lock()?)
+}
+
+fn to_json<T: Serialize>(value: &T, pretty: bool) -> Result<String, serde_json::Error> {
+    if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+// Pretty-printed per TrackerConfig::use_pretty_json unless `force_compact`. Settled days
+// are written to days/ only when they changed since the last save, so the regular
+// autosave rewrites state.json with the hot days and session alone. A day file's
+// previous version is kept as its .bak, both on rewrite and on removal.
+fn write_state(app_handle: &AppHandle, state: &AppStateArc, force_compact: bool) -> Result<(), TrackerError> {
+    // Nothing was loaded, so there is nothing to save, and the encrypted files must stay.
+    if encryption::is_locked() {
+        return Err(TrackerError::StoreLocked);
+    }
+    let state_file = get_state_file_path(app_handle)?;
+    let days_dir = get_days_dir(app_handle)?;
+    let today = today_local_key();
+
+    // Held for the whole write so two saves never interleave their day files.
+    let mut written = lock_or_recover(&state.persistence.written_days);
+    // Serialized under the session and records locks, which are released before any file
+    // is touched: a slow disk never holds up the monitor or a command.
+    let (state_json, day_files, removed, journal_seq) = {
+        let session_guard = lock_or_recover(&state.current_session);
+        let records_guard = lock_or_recover(&state.day_records);
+        // Only a snapshot that includes the loaded days may count their files as current.
+        if state.persistence.settled_loaded.load(Ordering::Relaxed) {
+            written.extend(lock_or_recover(&state.persistence.loaded_days).drain());
+        }
+        let session_day = session_guard.as_ref().map(|s| s.day_key.as_str());
+        let is_hot = |record: &DayRecord| is_hot_day(record, &today, session_day);
+        let plan = plan_day_writes(&records_guard, is_hot, &written);
+        let hot = records_guard.iter().filter(|(_, record)| is_hot(record)).map(|(date, record)| (date.clone(), record.clone()));
+        let persisted_state = persisted_form(state, session_guard.as_ref(), hot.collect());
+        let pretty = persisted_state.config.use_pretty_json && !force_compact;
+        let mut day_files = Vec::with_capacity(plan.write.len());
+        for (date, fingerprint) in plan.write {
+            day_files.push((to_json(&records_guard[&date], pretty)?, date, fingerprint));
+        }
+        (to_json(&persisted_state, pretty)?, day_files, plan.remove, persisted_state.journal_seq)
+    };
+
+    if !day_files.is_empty() || !removed.is_empty() {
+        state.persistence.settled_generation.fetch_add(1, Ordering::Relaxed);
+    }
+    if !day_files.is_empty() {
+        fs::create_dir_all(&days_dir)?;
+    }
+    for (json, date, fingerprint) in day_files {
+        let path = days_dir.join(format!("{}.json", date));
+        // A torn file never replaces the backup it was being read from.
+        if read_day_file(&path).is_ok() {
+            fs::copy(&path, day_backup_path(&path))?;
+        }
+        write_atomically(&path, &encryption::seal_text(json)?)?;
+        written.insert(date, fingerprint);
+    }
+
+    write_atomically(&state_file, &encryption::seal_text(state_json)?)?;
+
+    // Only once state.json no longer needs them (a day that is hot again is in it now).
+    for date in removed {
+        let path = days_dir.join(format!("{}.json", date));
+        if read_day_file(&path).is_ok() {
+            fs::rename(&path, day_backup_path(&path)).ok();
+        } else {
+            fs::remove_file(&path).ok();
+        }
+        written.remove(&date);
+    }
+    drop(written);
+
+    // Everything up to journal_seq is in the snapshot now.
+    if let Err(e) = compact_journal(app_handle, state, journal_seq) {
+        warn!("⚠️ Could not compact the journal: {}", e);
+    }
+    Ok(())
+}
+
+// Minimum gap between two "could not save" alerts while saves keep failing; the
+// autosave loop would otherwise raise one every 30 seconds.
+const PERSISTENCE_ALERT_INTERVAL_SECS: u64 = 60 * 60;
+
+// Whether a failed save should alert. `last_alerted_at` is 0 when the current run of
+// failures has not alerted yet (a successful save resets it).
+fn persistence_alert_due(last_alerted_at: u64, now: u64) -> bool {
+    last_alerted_at == 0 || now.saturating_sub(last_alerted_at) >= PERSISTENCE_ALERT_INTERVAL_SECS
+}
+
+fn note_save_failure(app_handle: &AppHandle, state: &AppStateArc, error: &TrackerError) {
+    let now = now_unix();
+    *lock_or_recover(&state.persistence.last_error) = Some(error.to_string());
+    if !persistence_alert_due(state.persistence.last_alerted_at.load(Ordering::Relaxed), now) {
+        return;
+    }
+    // Mark before alerting: the alert path can itself save (notification permission).
+    state.persistence.last_alerted_at.store(now, Ordering::Relaxed);
+    let _ = app_handle.emit("persistence-error", error);
+    send_alert(
+        app_handle,
+        state,
+        "Tracking data is not being saved",
+        &format!("{}. Free up disk space or check permissions.", error),
+    );
+}
+
+// Build a brand-new active session + day record for `today`, seeded with one open lap.
+// Used both for a genuinely fresh day and for a new day after an overnight shutdown.
+fn begin_fresh_day(records: &mut HashMap<String, DayRecord>, today: &str) -> CurrentSession {
+    let current_time = now_unix();
+
+    records.insert(today.to_string(), DayRecord {
+        date: today.to_string(),
+        total_duration: 0,
+        laps: vec![Lap::open(current_time)],
+        is_active: true,
+        metadata: HashMap::new(),
+        ended_explicitly: false,
+    });
+
+    CurrentSession {
+        day_key: today.to_string(),
+        current_lap_start_timestamp: current_time,
+        accumulated_seconds: 0,
+        is_paused: false,
+        user_paused: false,
+        project: None,
+        continuous_work_start: None,
+        continuous_work_alerted: false,
+        break_reminders_sent: 0,
+        paused_cause: None,
+        paused_since: None,
+        overrides: DaySessionOverrides::default(),
+        template: None,
+    }
+}
+
+// Load persisted state from disk and decide what today's session should be.
+//
+// Rules (all dates are LOCAL dates):
+//   * Fresh boot on a new day, or a day with no record yet -> auto-start a new active
+//     day in the background. The user can manually pause if they aren't working.
+//   * Machine restarted (e.g. power cut) on the SAME day the ongoing session belongs to
+//     -> continue that same day, appending a new lap (unless the user had manually paused,
+//     in which case we respect the pause and don't resume).
+//   * The ongoing session belongs to an EARLIER day -> apply the same rule the running app
+//     uses (should_roll_over). If the user was away long enough, or it is past the cutoff,
+//     that day is finalized and a fresh one starts for today. If not — the app just
+//     restarted a few minutes after midnight while they were still working — the earlier
+//     day CONTINUES, because late-night work belongs to the day it started on.
+//   * A day the user explicitly ended -> left alone; we do NOT auto-restart it.
+//
+// Any lap left open when the app last stopped is closed at `last_heartbeat` so that
+// time while the machine was powered off is never counted.
+fn load_and_initialize(app_handle: &AppHandle, state: &AppStateArc) {
+    let (state_file, backup_file) = match (get_state_file_path(app_handle), get_backup_file_path(app_handle)) {
+        (Ok(state_file), Ok(backup_file)) => (state_file, backup_file),
+        (Err(e), _) | (_, Err(e)) => {
+            // Nothing to load from; run with empty history. Saves will fail the same way
+            // and save_state tells the user.
+            error!("❌ No usable data directory, starting without history: {}", e);
+            return;
+        }
+    };
+    let today = today_local_key();
+
+    let persisted_state = match read_state_file(&state_file) {
+        Some(Ok(loaded)) => Some(loaded),
+        // The file is genuinely absent -> first ever run.
+        None => None,
+        Some(Err(err)) => {
+            // state.json exists but will not parse. This must NOT fall through to the
+            // "first run" path: that blanks day_records and immediately writes the empty
+            // state back, destroying every day the user ever tracked. Instead, quarantine
+            // the bad file (so it is still there to inspect or hand-recover) and fall back
+            // to the snapshots of earlier successful loads, newest first.
+            error!("❌ state.json is corrupt: {}", err);
+            let quarantine = state_file.with_file_name(format!("state.corrupt-{}.json", now_unix()));
+            let quarantined = fs::rename(&state_file, &quarantine).is_ok().then(|| {
+                error!("   Corrupt file preserved at {}", quarantine.display());
+                quarantine.to_string_lossy().to_string()
+            });
+            let recovered = recovery_candidates(&state_file, &backup_file)
+                .into_iter()
+                .find_map(|path| try_load_persisted_state(&path).map(|loaded| (path, loaded)));
+            let outcome = StateRecovery {
+                source: recovered.as_ref().map(|(path, _)| path.to_string_lossy().to_string()),
+                quarantined,
+            };
+            match &outcome.source {
+                Some(source) => {
+                    info!("✅ Recovered history from {}", source);
+                    let _ = app_handle.emit("state-recovery", &outcome);
+                }
+                None => {
+                    error!("❌ No usable backup either; starting with empty history");
+                    let _ = app_handle.emit("state-recovery-failed", &outcome);
+                }
+            }
+            *lock_or_recover(&state.persistence.last_recovery) = Some(outcome);
+            recovered.map(|(_, loaded)| loaded)
+        }
+    };
+
+    // Settled days stay in days/ until something reads past history (load_settled_days),
+    // even when state.json is missing or unrecoverable: they never depended on it. The
+    // one-off migration below re-files laps across every day, so it reads them all now.
+    let days_dir = get_days_dir(app_handle).ok();
+    let day_files: Vec<String> =
+        days_dir.as_deref().map(day_file_paths).unwrap_or_default().iter().filter_map(|path| day_file_date(path)).collect();
+    let migrating = persisted_state.as_ref().is_some_and(|persisted| persisted.schema_version < 1);
+    let settled_days = match &days_dir {
+        Some(days_dir) if migrating => read_day_files(days_dir),
+        _ => HashMap::new(),
+    };
+    if migrating || day_files.is_empty() {
+        state.persistence.settled_loaded.store(true, Ordering::Relaxed);
+        *lock_or_recover(&state.persistence.written_days) =
+            settled_days.iter().map(|(date, record)| (date.clone(), day_fingerprint(record))).collect();
+    } else {
+        *lock_or_recover(&state.persistence.cold_days) = days_dir;
+    }
+
+    let mut records_guard = lock_or_recover(&state.day_records);
+    let mut session_guard = lock_or_recover(&state.current_session);
+
+    let Some(mut persisted_state) = persisted_state else {
+        // No prior state at all -> very first run. Auto-start today in the background.
+        *records_guard = settled_days;
+        *session_guard = Some(begin_fresh_day(&mut records_guard, &today));
+        note_shadowed_day_files(state, &records_guard, &day_files);
+        info!("✅ No prior state; auto-started a fresh day for {}", today);
+        drop(session_guard);
+        drop(records_guard);
+        save_state(app_handle, state);
+        return;
+    };
+
+    // Bring the snapshot up to date with the session events journaled after it, cutting
+    // off a torn last line so later appends start on a clean line.
+    if let Ok(journal_file) = get_journal_file_path(app_handle) {
+        if let Ok(contents) = fs::read_to_string(&journal_file) {
+            let (entries, valid_len) = parse_journal(&contents);
+            if valid_len < contents.len() {
+                warn!("⚠️ Truncating {} torn byte(s) from the journal", contents.len() - valid_len);
+                if let Ok(file) = fs::OpenOptions::new().write(true).open(&journal_file) {
+                    file.set_len(valid_len as u64).ok();
+                }
+            }
+            let replayed = replay_journal(&mut persisted_state, &entries);
+            if replayed > 0 {
+                info!("📜 Replayed {} journal entr(ies) newer than state.json", replayed);
+            }
+            let last_seq = entries.iter().map(|entry| entry.seq).max().unwrap_or(0);
+            state
+                .persistence
+                .journal_seq
+                .store(last_seq.max(persisted_state.journal_seq), Ordering::Relaxed);
+        }
+    }
+
+    // Snapshot the history we just loaded, before this run starts mutating it. If a later
+    // write is ever cut short, this is what the recovery path above restores from. The
+    // previous snapshots move down a generation first, in case this one is already bad.
+    rotate_backups(&state_file, &backup_file);
+    if let Ok(json) = serde_json::to_string_pretty(&persisted_state).map_err(std::io::Error::from).and_then(encryption::seal_text) {
+        fs::write(&backup_file, json).ok();
+    }
+
+    // state.json is written after the day files, so its copy of a day is never older.
+    *records_guard = settled_days;
+    records_guard.extend(persisted_state.day_records);
+    note_shadowed_day_files(state, &records_guard, &day_files);
+    *lock_or_recover(&state.config) = persisted_state.config;
+    sync::adopt_legacy_token(app_handle, state);
+    *lock_or_recover(&state.events) = EventLog::from_days(persisted_state.events);
+    // Bound any lap that was still open at shutdown to the last heartbeat we recorded.
+    let heartbeat = if persisted_state.last_heartbeat > 0 {
+        persisted_state.last_heartbeat
+    } else {
+        now_unix()
+    };
+    if let Some(ps) = &persisted_state.current_session {
+        close_interrupted_lap(&mut records_guard, ps, heartbeat);
+    }
+    for record in records_guard.values_mut() {
+        finalize_dangling_lap(record, heartbeat, None);
+    }
+
+    // One-off repair of history written before the day rolled over on its own. Runs once:
+    // save_state stamps CURRENT_SCHEMA_VERSION, and the pre-migration data is still on
+    // disk in state.backup.json (written just above) if this ever needs undoing.
+    if persisted_state.schema_version < 1 {
+        let session_day = persisted_state.current_session.as_ref().map(|s| s.day_key.as_str());
+        let moved = backfill_misattributed_laps(&mut records_guard, session_day);
+        if moved > 0 {
+            info!("🔧 Re-filed {} lap(s) that were recorded under the wrong day", moved);
+        } else {
+            info!("🔧 History checked: no misfiled laps to re-file");
+        }
+    }
+
+    // Whether the ongoing session's day is over, judged by the same rule the running app
+    // uses (see should_roll_over). The app being down is not itself evidence of a new day:
+    // launchd's KeepAlive can relaunch it seconds after a crash, and a plain "the date
+    // changed while we were down" test would split a night owl's session at midnight.
+    // What ends a day is a real break — measured here from the last heartbeat, which is
+    // also where any lap left open was just closed.
+    let downtime = now_unix().saturating_sub(heartbeat);
+    let past_cutoff = now_unix() >= cutoff_timestamp_today();
+
+    let mut auto_closed = None;
+    match persisted_state.current_session {
+        Some(ps)
+            if ps.day_key == today
+                || !should_roll_over(true, downtime, past_cutoff) =>
+        {
+            // Continue the ongoing session's day. Either it is still that day (a mid-day
+            // restart or power cut), or the date has changed but the user was working
+            // through it and the app merely restarted — in which case the work still
+            // belongs to the day it started on.
+            let day = ps.day_key.clone();
+            if let Some(record) = records_guard.get_mut(&day) {
+                record.is_active = true;
+            } else {
+                records_guard.insert(day.clone(), DayRecord {
+                    date: day.clone(),
+                    total_duration: 0,
+                    laps: Vec::new(),
+                    is_active: true,
+                    metadata: HashMap::new(),
+                    ended_explicitly: false,
+                });
+            }
+
+            if ps.user_paused {
+                // User had manually paused before the restart -> respect it, stay paused.
+                *session_guard = Some(CurrentSession {
+                    day_key: day.clone(),
+                    current_lap_start_timestamp: now_unix(),
+                    // The interrupted lap is closed and in the total; resume opens a new one.
+                    accumulated_seconds: 0,
+                    is_paused: true,
+                    user_paused: true,
+                    project: ps.project.clone(),
+                    continuous_work_start: None,
+                    continuous_work_alerted: false,
+                    break_reminders_sent: 0,
+                    paused_cause: Some(ps.paused_cause.unwrap_or(PauseCause::User)),
+                    paused_since: ps.paused_since.or(Some(now_unix())),
+                    overrides: ps.overrides.clone(),
+                    template: ps.template.clone(),
+                });
+                info!("✅ Restored paused session for {} (user paused; not resuming)", day);
+            } else {
+                // Continue the existing day by appending a fresh lap.
+                let current_time = now_unix();
+                let mut session = CurrentSession {
+                    day_key: day.clone(),
+                    current_lap_start_timestamp: current_time,
+                    accumulated_seconds: 0,
+                    is_paused: false,
+                    user_paused: false,
+                    project: ps.project.clone(),
+                    continuous_work_start: None,
+                    continuous_work_alerted: false,
+                    break_reminders_sent: 0,
+                    paused_cause: None,
+                    paused_since: None,
+                    overrides: ps.overrides.clone(),
+                    template: ps.template.clone(),
+                };
+                if let Some(record) = records_guard.get_mut(&day) {
+                    record.laps.push(session.open_lap(current_time));
+                }
+                *session_guard = Some(session);
+                if day == today {
+                    info!("✅ Continued ongoing day {} with a new lap (restart detected)", day);
+                } else {
+                    info!("✅ Continued {} past midnight (down {}s; not a new day yet)", day, downtime);
+                }
+            }
+        }
+        Some(ps) => {
+            // The session's day is genuinely over: the user was away long enough, or it is
+            // past the cutoff. End that day and start a fresh one for today.
+            // Its open lap was closed above, at the last tick; the total includes it.
+            if let Some(record) = records_guard.get_mut(&ps.day_key) {
+                record.is_active = false;
+                auto_closed = Some(DayAutoClosed {
+                    day_key: ps.day_key.clone(),
+                    closed_at: record.laps.iter().filter_map(|lap| lap.end_time).max(),
+                });
+            }
+            *session_guard = Some(begin_fresh_day(&mut records_guard, &today));
+            info!("✅ Ended previous day {} and auto-started a fresh day for {}", ps.day_key, today);
+        }
+        None => {
+            // No ongoing session was persisted (user had ended their day, or clean state).
+            match records_guard.get(&today) {
+                Some(record) if !record.is_active => {
+                    // User already ended today's day -> don't auto-restart it.
+                    info!("ℹ️ Today's day ({}) was already ended; not auto-starting", today);
+                }
+                _ => {
+                    *session_guard = Some(begin_fresh_day(&mut records_guard, &today));
+                    info!("✅ Auto-started a fresh day for {}", today);
+                }
+            }
+        }
+    }
+
+    drop(session_guard);
+    drop(records_guard);
+    if let Some(closed) = auto_closed {
+        let _ = app_handle.emit("day-auto-closed", &closed);
+        *lock_or_recover(&state.persistence.day_auto_closed) = Some(closed);
+    }
+    save_state(app_handle, state);
+    info!("✅ State loaded and initialized successfully");
+}
+
+// Raw wall-clock elapsed since the lap started. Only valid for closing a lap that
+// was awake the whole time (lock, user pause, end-day). Laps interrupted by system
+// sleep must NOT be closed with this — use handle_system_suspend_direct, which
+// bounds the lap to a pre-sleep timestamp instead of "now".
+// Pass in the actual lap start time from records to ensure accuracy
+fn finalize_lap_duration(lap_start_time: u64) -> u64 {
+    let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    current_time - lap_start_time
+}
+
+// Close the running lap at `now` and open the next one in the same step, so no second
+// falls between them. A running lap of a second or less is dropped rather than closed:
+// it is a double-click, not work, and leaving it open beside the new lap used to give
+// the day two open laps. Leaves the session active on the new lap.
+fn roll_to_next_lap(session: &mut CurrentSession, records: &mut HashMap<String, DayRecord>, now: u64) {
+    if let Some(day_record) = records.get_mut(&session.day_key) {
+        if let Some(last_lap) = day_record.laps.last_mut() {
+            if last_lap.duration.is_none() {
+                let lap_duration = now.saturating_sub(last_lap.start_time);
+                if lap_duration > 1 {
+                    last_lap.end_time = Some(now);
+                    last_lap.duration = Some(lap_duration);
+                    last_lap.end_reason = Some(LapEndReason::LapAdded);
+                } else {
+                    day_record.laps.pop();
+                }
+            }
+        }
+        day_record.laps.push(session.open_lap(now));
+        day_record.total_duration = day_record.laps.iter().filter_map(Lap::worked_duration).sum();
+    }
+
+    session.current_lap_start_timestamp = now;
+    session.accumulated_seconds = 0;
+    session.resume();
+}
+
+// Get the actual start time of the active lap from records
+fn get_active_lap_start_time(day_record: &DayRecord) -> Option<u64> {
+    day_record.laps.iter()
+        .filter(|lap| lap.duration.is_none())
+        .last()
+        .map(|lap| lap.start_time)
+}
+
+#[tauri::command]
+async fn start_day(app_handle: AppHandle, state: State<'_, AppStateArc>, project: Option<String>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    start_day_direct(&app_handle, &state, project, DaySessionOverrides::default())
+}
+
+// start_day with per-day settings that replace the configured ones until end_day.
+#[tauri::command]
+async fn start_day_with_config(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    project: Option<String>,
+    overrides: DaySessionOverrides,
+) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    if overrides.gap_threshold_secs.is_some_and(|secs| secs < SUSPEND_GAP_THRESHOLD_SECS) {
+        return Err(TrackerError::InvalidInput(format!(
+            "Gap threshold must be at least {}s",
+            SUSPEND_GAP_THRESHOLD_SECS
+        )));
+    }
+    start_day_direct(&app_handle, &state, project, overrides)
+}
+
+// The command bodies below take a plain &AppStateArc so the tray menu can share them.
+fn start_day_direct(
+    app_handle: &AppHandle,
+    state: &AppStateArc,
+    project: Option<String>,
+    overrides: DaySessionOverrides,
+) -> Result<String, TrackerError> {
+    let today = today_local_key();
+    let project = normalize_project(project)?;
+    let snapshot = undo_snapshot(state);
+
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    // Check if already tracking today
+    if session_guard.is_some() {
+        return Err(TrackerError::AlreadyTracking);
+    }
+    
+    let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    
+    let mut session = CurrentSession {
+        day_key: today.clone(),
+        current_lap_start_timestamp: current_time,
+        accumulated_seconds: 0,
+        is_paused: false,
+        user_paused: false,
+        project,
+        continuous_work_start: None,
+        continuous_work_alerted: false,
+        break_reminders_sent: 0,
+        paused_cause: None,
+        paused_since: None,
+        overrides,
+        template: None,
+    };
+    
+    let new_lap = session.open_lap(current_time);
+    *session_guard = Some(session);
+
+    // If a record already exists for today (e.g. the user ended their day earlier and is
+    // starting again), APPEND a new lap to it so previous laps are preserved. Otherwise
+    // create a fresh record for the day.
+    if let Some(existing) = records_guard.get_mut(&today) {
+        existing.laps.push(new_lap);
+        existing.is_active = true;
+        existing.ended_explicitly = false;
+    } else {
+        records_guard.insert(today.clone(), DayRecord {
+            date: today.clone(),
+            total_duration: 0,
+            laps: vec![new_lap],
+            is_active: true,
+            metadata: HashMap::new(),
+            ended_explicitly: false,
+        });
+    }
+
+    emit_session_event(app_handle, "session-started", &today, records_guard.get(&today).and_then(|r| r.laps.last()).cloned(), None);
+    push_undo(state, snapshot, "Start day");
+    Ok(format!("Started tracking for {}", today))
+}
+
+#[tauri::command]
+async fn end_day(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<DayRecord, TrackerError> {
+    ensure_unlocked()?;
+    end_day_direct(&app_handle, &state)
+}
+
+fn end_day_direct(app_handle: &AppHandle, state: &AppStateArc) -> Result<DayRecord, TrackerError> {
+    let snapshot = undo_snapshot(state);
+    let rules = state.config.lock()?.auto_tag_rules.clone();
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    let session = session_guard.take().ok_or(TrackerError::NoActiveSession)?;
+    let day_key = session.day_key.clone();
+    
+    // Calculate final duration for current lap (excluding sleep/hibernate time)
+    let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    
+    let result = if let Some(day_record) = records_guard.get_mut(&day_key) {
+        // Get actual lap start time from records
+        let lap_start_time = get_active_lap_start_time(day_record)
+            .unwrap_or(session.current_lap_start_timestamp);
+        let lap_duration = finalize_lap_duration(lap_start_time);
+        
+        // Update the last lap
+        if let Some(last_lap) = day_record.laps.last_mut() {
+            last_lap.end_time = Some(current_time);
+            last_lap.duration = Some(lap_duration);
+            last_lap.end_reason = Some(LapEndReason::DayEnded);
+            auto_tag_lap(last_lap, &rules);
+        }
+        
+        // Calculate total duration
+        day_record.total_duration = day_record.laps.iter()
+            .filter_map(Lap::worked_duration)
+            .sum();
+        
+        day_record.is_active = false;
+        day_record.ended_explicitly = true;
+        
+        Ok(day_record.clone())
+    } else {
+        Err(TrackerError::DayNotFound(day_key.clone()))
+    };
+    
+    // Release locks before saving
+    drop(session_guard);
+    drop(records_guard);
+    
+    push_undo(state, snapshot, "End day");
+    if result.is_ok() {
+        emit_session_event(app_handle, "day-ended", &day_key, None, None);
+    }
+
+    // Save state to disk
+    save_state(app_handle, state);
+    state.sync.request();
+    
+    result
+}
+
+// Only on the tick that crosses into the hour, so a day started again after the
+// auto end runs on until the next day.
+fn auto_end_due(previous_hour: Option<u32>, hour: u32, end_at: Option<u8>) -> bool {
+    end_at.is_some_and(|h| u32::from(h) == hour) && previous_hour.is_some_and(|p| p != hour)
+}
+
+// End the day for auto_end_day_at_hour. The caller has already closed the running lap
+// through handle_screen_lock_direct, so unlike end_day no lap is closed here; when
+// `closed_running_lap` says that lap was running until now, its end reason becomes
+// AutoEnded. The record is marked auto_ended and sent with day-auto-ended.
+fn auto_end_day_direct(app_handle: &AppHandle, state: &AppStateArc, closed_running_lap: bool) -> Result<DayRecord, TrackerError> {
+    let snapshot = undo_snapshot(state);
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+
+    let session = session_guard.take().ok_or(TrackerError::NoActiveSession)?;
+    let record = records_guard.get_mut(&session.day_key).ok_or_else(|| TrackerError::DayNotFound(session.day_key.clone()))?;
+    if closed_running_lap {
+        if let Some(last_lap) = record.laps.last_mut() {
+            last_lap.end_reason = Some(LapEndReason::AutoEnded);
+        }
+    }
+    record.total_duration = record.laps.iter().filter_map(Lap::worked_duration).sum();
+    record.is_active = false;
+    record.metadata.insert("auto_ended".to_string(), "true".to_string());
+    let record = record.clone();
+
+    drop(session_guard);
+    drop(records_guard);
+
+    push_undo(state, snapshot, "Auto end day");
+    emit_session_event(app_handle, "day-ended", &record.date, None, None);
+    let _ = app_handle.emit("day-auto-ended", &record);
+    info!("🌙 Auto-ended {} with {}s tracked", record.date, record.total_duration);
+    save_state(app_handle, state);
+    state.sync.request();
+    Ok(record)
+}
+
+#[tauri::command]
+async fn handle_screen_lock(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        // Skip if already paused (prevent duplicate events)
+        if session.is_paused {
+            return Ok("Already paused".to_string());
+        }
+        
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        
+        // Get actual lap start time from records
+        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
+            get_active_lap_start_time(day_record)
+                .unwrap_or(session.current_lap_start_timestamp)
+        } else {
+            session.current_lap_start_timestamp
+        };
+        
+        let lap_duration = finalize_lap_duration(lap_start_time);
+        
+        // End current lap
+        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+            if let Some(last_lap) = day_record.laps.last_mut() {
+                last_lap.end_time = Some(current_time);
+                last_lap.duration = Some(lap_duration);
+                last_lap.end_reason = Some(LapEndReason::ScreenLocked);
+                emit_session_event(&app_handle, "lap-ended", &session.day_key, Some(last_lap.clone()), None);
+            }
+        }
+        
+        // Mark as paused by system
+        session.pause(PauseCause::Lock, now_unix());
+        emit_session_event(&app_handle, "session-paused", &session.day_key, None, Some("lock"));
+        
+        Ok("Screen locked - timer paused".to_string())
+    } else {
+        Ok("No active session".to_string())
+    }
+}
+
+#[tauri::command]
+async fn handle_screen_unlock(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        // Skip if already active (prevent duplicate events)
+        if !session.is_paused {
+            return Ok("Already active".to_string());
+        }
+        
+        // Only auto-start if user didn't manually pause
+        if !session.user_paused {
+            let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            
+            // Start new lap
+            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+                day_record.laps.push(session.open_lap(current_time));
+                let opened = day_record.laps.last().cloned();
+                emit_session_event(&app_handle, "session-resumed", &session.day_key, opened, None);
+            }
+            
+            // Reset lap tracking
+            session.current_lap_start_timestamp = current_time;
+            session.accumulated_seconds = 0;
+            session.resume();
+            
+            Ok("Screen unlocked - new lap started".to_string())
+        } else {
+            Ok("Screen unlocked - session remains paused (user paused)".to_string())
+        }
+    } else {
+        Ok("No active session".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_current_status(state: State<'_, AppStateArc>) -> Result<Option<CurrentStatus>, TrackerError> {
+    let config = state.config.lock()?.clone();
+    let pomodoro = state.pomodoro.lock()?.clone();
+    let session_guard = state.current_session.lock()?;
+    let records_guard = state.day_records.lock()?;
+    Ok(session_guard.as_ref().map(|session| current_status(session, &records_guard, &config, pomodoro, seconds_since_last_save(&state))))
+}
+
+fn current_status(
+    session: &CurrentSession,
+    records: &HashMap<String, DayRecord>,
+    config: &TrackerConfig,
+    pomodoro: Option<PomodoroState>,
+    seconds_since_last_save: u64,
+) -> CurrentStatus {
+    let goal = session.overrides.daily_goal(config);
+    let overtime = config
+        .overtime_alert_seconds
+        .map(|cap| worked_today(session, records, now_unix()) >= cap)
+        .unwrap_or(false);
+
+    // Calculate total duration from completed laps only
+    let mut total_duration = 0u64;
+    if let Some(day_record) = records.get(&session.day_key) {
+        // Sum all completed laps
+        total_duration = day_record.laps.iter()
+            .filter_map(Lap::worked_duration)
+            .sum();
+    }
+    let current_lap_note = records.get(&session.day_key).and_then(running_lap).and_then(|lap| lap.note.clone());
+    let excluded_seconds_today = records.get(&session.day_key).map_or(0, excluded_seconds);
+
+    if session.is_paused {
+        // Session is paused - show only completed laps, no current lap time
+        let progress = goal_progress(goal, total_duration);
+        CurrentStatus {
+            day_key: session.day_key.clone(),
+            current_lap_duration: 0, // No current lap when paused
+            current_lap_start_timestamp: session.current_lap_start_timestamp,
+            total_session_duration: total_duration, // Only completed laps
+            is_active: false, // Not actively tracking
+            active_project: session.project.clone(),
+            current_continuous_work_seconds: 0,
+            goal_seconds: goal,
+            goal_progress_percent: progress.map(|(percent, _)| percent),
+            goal_remaining_seconds: progress.map(|(_, remaining)| remaining),
+            overtime,
+            pomodoro_state: pomodoro.clone(),
+            paused_cause: session.paused_cause,
+            paused_since: session.paused_since,
+            user_paused: session.user_paused,
+            effective_max_continuous_work_secs: session.overrides.max_continuous_work(config),
+            effective_gap_threshold_secs: session.overrides.gap_threshold(),
+            current_lap_note,
+            seconds_since_last_save,
+            excluded_seconds_today,
+        }
+    } else {
+        // Session is active - use session's current_lap_start_timestamp as source of truth
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let current_lap_seconds = session.compute_accurate_elapsed();
+        // Goal progress counts the running lap too, or it would sit still all lap.
+        let progress = goal_progress(goal, total_duration + current_lap_seconds);
+
+        // IMPORTANT: total_session_duration should be ONLY completed laps
+        // Frontend will add current_lap_duration for smooth display
+        CurrentStatus {
+            day_key: session.day_key.clone(),
+            current_lap_duration: current_lap_seconds,
+            current_lap_start_timestamp: session.current_lap_start_timestamp,
+            total_session_duration: total_duration, // Only completed laps, NOT including current lap
+            is_active: true,
+            active_project: session.project.clone(),
+            current_continuous_work_seconds: session
+                .continuous_work_start
+                .map(|t| current_time.saturating_sub(t))
+                .unwrap_or(0),
+            goal_seconds: goal,
+            goal_progress_percent: progress.map(|(percent, _)| percent),
+            goal_remaining_seconds: progress.map(|(_, remaining)| remaining),
+            overtime,
+            pomodoro_state: pomodoro.clone(),
+            paused_cause: session.paused_cause,
+            paused_since: session.paused_since,
+            user_paused: session.user_paused,
+            effective_max_continuous_work_secs: session.overrides.max_continuous_work(config),
+            effective_gap_threshold_secs: session.overrides.gap_threshold(),
+            current_lap_note,
+            seconds_since_last_save,
+            excluded_seconds_today,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CurrentStatus {
+    pub day_key: String,
+    pub current_lap_duration: u64,
+    pub current_lap_start_timestamp: u64, // For frontend smooth display
+    pub total_session_duration: u64,
+    pub is_active: bool,
+    pub active_project: Option<String>,
+    pub current_continuous_work_seconds: u64,
+    // All None when no daily goal is set.
+    pub goal_seconds: Option<u64>,
+    pub goal_progress_percent: Option<f64>,
+    pub goal_remaining_seconds: Option<u64>,
+    // The day's total has passed the overtime cap (always false without one).
+    pub overtime: bool,
+    pub pomodoro_state: Option<PomodoroState>,
+    // Why the timer is frozen: user, lock, sleep, logout or restart. None while tracking.
+    pub paused_cause: Option<PauseCause>,
+    pub paused_since: Option<u64>,
+    pub user_paused: bool,
+    // The settings in force for this session, start_day_with_config overrides included
+    // (goal_seconds above is the effective goal too).
+    pub effective_max_continuous_work_secs: Option<u64>,
+    pub effective_gap_threshold_secs: u64,
+    pub current_lap_note: Option<String>, // None while paused
+    // What a crash right now would lose; more than twice the autosave interval means
+    // saves are falling behind.
+    pub seconds_since_last_save: u64,
+    pub excluded_seconds_today: u64, // cut from the day's laps by gap detection
+}
+
+
+#[tauri::command]
+async fn get_current_day_laps(state: State<'_, AppStateArc>) -> Result<Vec<Lap>, TrackerError> {
+    let session_guard = state.current_session.lock()?;
+    let records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_ref() {
+        if let Some(day_record) = records_guard.get(&session.day_key) {
+            Ok(day_record.laps.clone())
+        } else {
+            Ok(Vec::new())
+        }
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TodaySummary {
+    pub current_status: Option<CurrentStatus>,
+    pub laps: Vec<Lap>,
+    // total_duration recomputed from completed laps, as end_day would store it.
+    pub day_record: Option<DayRecord>,
+    pub goal_seconds: Option<u64>,
+    pub goal_reached: bool,
+    pub idle_seconds: f64, // since the last keyboard/mouse input (0 where unsupported)
+    // Completed-lap time split by the work window (set_tracking_boundary).
+    pub in_hours_seconds: u64,
+    pub out_of_hours_seconds: u64,
+    pub excluded_seconds: u64, // see Lap::excluded_seconds
+}
+
+// Everything the dashboard shows, in one round trip.
+#[tauri::command]
+async fn get_today_summary(state: State<'_, AppStateArc>) -> Result<TodaySummary, TrackerError> {
+    let config = state.config.lock()?.clone();
+    let pomodoro = state.pomodoro.lock()?.clone();
+    let session_guard = state.current_session.lock()?;
+    let records_guard = state.day_records.lock()?;
+
+    let current_status = session_guard
+        .as_ref()
+        .map(|session| current_status(session, &records_guard, &config, pomodoro, seconds_since_last_save(&state)));
+    let day_record = session_guard
+        .as_ref()
+        .and_then(|session| records_guard.get(&session.day_key))
+        .map(|record| DayRecord {
+            total_duration: record.laps.iter().filter_map(Lap::worked_duration).sum(),
+            ..record.clone()
+        });
+    let goal_reached = current_status
+        .as_ref()
+        .and_then(|status| status.goal_remaining_seconds)
+        .map(|remaining| remaining == 0)
+        .unwrap_or(false);
+    let (in_hours_seconds, out_of_hours_seconds) = day_record
+        .as_ref()
+        .map(|record| work_window_split(record, &config, &chrono::Local))
+        .unwrap_or_default();
+    let excluded_seconds = day_record.as_ref().map_or(0, excluded_seconds);
+
+    Ok(TodaySummary {
+        laps: day_record.as_ref().map(|r| r.laps.clone()).unwrap_or_default(),
+        current_status,
+        day_record,
+        goal_seconds: session_guard
+            .as_ref()
+            .map_or(config.daily_goal_secs, |session| session.overrides.daily_goal(&config)),
+        goal_reached,
+        idle_seconds: system_idle_seconds(),
+        in_hours_seconds,
+        out_of_hours_seconds,
+        excluded_seconds,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UptimeStats {
+    pub app_uptime_seconds: u64,
+    pub tracked_seconds_since_launch: u64,
+    pub coverage_percent: f64, // 0 in the first second after launch
+}
+
+// Tracked seconds between `since` and `now`: the part of each lap that falls after
+// `since`, with the running lap counted up to `now`. Synthetic break laps never count.
+fn tracked_since<'a>(laps: impl IntoIterator<Item = &'a Lap>, since: u64, now: u64) -> u64 {
+    laps.into_iter()
+        .filter(|lap| !lap.is_synthetic)
+        .map(|lap| {
+            let from = lap.start_time.max(since);
+            match (lap.end_time, lap.duration) {
+                (None, _) => now.saturating_sub(from),
+                (Some(end), Some(duration)) => end.saturating_sub(from).min(duration),
+                (Some(_), None) => 0,
+            }
+        })
+        .sum()
+}
+
+// How much of the time since the app was opened was tracked. Every live day counts, so
+// a launch before midnight still covers yesterday's laps.
+#[tauri::command]
+async fn get_app_uptime_vs_tracked_ratio(state: State<'_, AppStateArc>) -> Result<UptimeStats, TrackerError> {
+    let launched_at = APP_STARTED_AT.load(Ordering::Relaxed);
+    let now = now_unix();
+    let tracked = tracked_since(state.day_records.lock()?.values().flat_map(|r| &r.laps), launched_at, now);
+    let uptime = now.saturating_sub(launched_at);
+    Ok(UptimeStats {
+        app_uptime_seconds: uptime,
+        tracked_seconds_since_launch: tracked,
+        coverage_percent: if uptime == 0 { 0.0 } else { tracked as f64 * 100.0 / uptime as f64 },
+    })
+}
+
+// Set the expected work window in local hours; end_hour 24 means midnight.
+#[tauri::command]
+async fn set_tracking_boundary(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    start_hour: u8,
+    end_hour: u8,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if start_hour >= end_hour || end_hour > 24 {
+        return Err(TrackerError::InvalidInput("Work hours need start_hour < end_hour <= 24".to_string()));
+    }
+    {
+        let mut config = state.config.lock()?;
+        config.work_start_hour = start_hour;
+        config.work_end_hour = end_hour;
+    }
+    info!("🕘 Work window set to {}:00-{}:00", start_hour, end_hour);
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// The out-of-hours portions of a day's completed laps, each as a lap trimmed to that
+// portion (a lap running 17:30-19:00 with a 9-18 window comes back as 18:00-19:00).
+#[tauri::command]
+async fn get_out_of_hours_laps(state: State<'_, AppStateArc>, day_key: String) -> Result<Vec<Lap>, TrackerError> {
+    let config = state.config.lock()?.clone();
+    let records_guard = all_day_records(&state)?;
+    let record = records_guard.get(&day_key).ok_or(TrackerError::DayNotFound(day_key))?;
+    let laps = record
+        .laps
+        .iter()
+        .filter(|lap| lap.worked_duration().is_some())
+        .flat_map(|lap| {
+            let end = lap.end_time.unwrap_or(lap.start_time);
+            out_of_hours_segments(lap.start_time, end, config.work_start_hour, config.work_end_hour, &chrono::Local)
+                .into_iter()
+                .map(move |(start, end)| Lap {
+                    start_time: start,
+                    end_time: Some(end),
+                    duration: Some(end - start),
+                    ..lap.clone()
+                })
+        })
+        .collect();
+    Ok(laps)
+}
+
+// Whether there is a session for today's date that is tracking right now.
+#[tauri::command]
+async fn is_tracking_today(state: State<'_, AppStateArc>) -> Result<bool, TrackerError> {
+    let session_guard = state.current_session.lock()?;
+    Ok(session_guard
+        .as_ref()
+        .map(|session| session.day_key == today_local_key() && !session.is_paused)
+        .unwrap_or(false))
+}
+
+// Return every stored day record, most recent day first, so the frontend can render
+// the full per-day history (each day with all of its laps and total duration).
+#[tauri::command]
+async fn get_all_day_records(state: State<'_, AppStateArc>) -> Result<Vec<DayRecord>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    let mut records: Vec<DayRecord> = records_guard.values().cloned().collect();
+    // Dates are "YYYY-MM-DD" so lexicographic sort == chronological sort.
+    records.sort_by(|a, b| b.date.cmp(&a.date));
+    Ok(records)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryDay {
+    #[serde(flatten)]
+    pub record: DayRecord,
+    pub project_breakdown: HashMap<String, u64>,
+}
+
+// Like get_all_day_records (newest first), with each day's seconds per project.
+#[tauri::command]
+async fn get_history(state: State<'_, AppStateArc>) -> Result<Vec<HistoryDay>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    let mut history: Vec<HistoryDay> = records_guard
+        .values()
+        .map(|record| HistoryDay {
+            project_breakdown: project_totals(&records_guard, &record.date, &record.date),
+            record: record.clone(),
+        })
+        .collect();
+    history.sort_by(|a, b| b.record.date.cmp(&a.record.date));
+    Ok(history)
+}
+
+// Undo an automatic rollover: fold `date`'s laps back into the day before it. The
+// rollover rules get the call right nearly always, but the 00:00-06:00 window is
+// genuinely ambiguous — a 5h break before an early start reads exactly like a late night —
+// and only the user knows which it was. This is the escape hatch, so the ambiguity costs
+// one click rather than a dialog every morning.
+#[tauri::command]
+async fn merge_day_into_previous(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    date: String,
+) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let state_arc = state.inner().clone();
+    let target = {
+        let mut session_guard = state_arc.current_session.lock()?;
+        let mut records_guard = all_day_records(&state_arc)?;
+
+        let target = merge_records_into_previous(&mut records_guard, &date)?;
+
+        // If the day being merged away is the one currently being tracked, the live session
+        // has to follow it, or the next lap would recreate the record we just removed.
+        if let Some(session) = session_guard.as_mut() {
+            if session.day_key == date {
+                session.day_key = target.clone();
+            }
+        }
+
+        drop(session_guard);
+        drop(records_guard);
+        info!("↩️ Merged {} into {}", date, target);
+        target
+    };
+
+    save_state(&app_handle, &state_arc);
+    Ok(format!("Merged {} into {}", date, target))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveReport {
+    pub days_archived: usize,
+    pub files: Vec<String>, // archive files created or updated, oldest month first
+    pub seconds_archived: u64, // tracked time in the archived days
+}
+
+// Move every archivable day before `cutoff` out of the live state into per-month
+// archive-YYYY-MM.json files. Archives are written before anything is removed: a failed
+// write leaves the live records untouched. The caller saves.
+fn archive_days_before(app_handle: &AppHandle, state: &AppStateArc, cutoff: &str) -> Result<ArchiveReport, TrackerError> {
+    let by_month = archivable_months(state, cutoff)?;
+    let report = write_archives(app_handle, state, by_month)?;
+    if report.days_archived > 0 {
+        info!("🗄️ Archived {} day(s) older than {} into {} file(s)", report.days_archived, cutoff, report.files.len());
+    }
+    Ok(report)
+}
+
+fn archivable_months(state: &AppStateArc, cutoff: &str) -> Result<BTreeMap<String, Vec<DayRecord>>, TrackerError> {
+    let session_guard = state.current_session.lock()?;
+    let records_guard = all_day_records(state)?;
+    Ok(archivable_records(&records_guard, cutoff, session_guard.as_ref().map(|s| s.day_key.as_str())))
+}
+
+// Fold each month's days into its archive file, then drop them from the live records.
+fn write_archives(
+    app_handle: &AppHandle,
+    state: &AppStateArc,
+    by_month: BTreeMap<String, Vec<DayRecord>>,
+) -> Result<ArchiveReport, TrackerError> {
+    let mut report = ArchiveReport { days_archived: 0, files: Vec::new(), seconds_archived: 0 };
+    let mut archived_days: Vec<String> = Vec::new();
+    for (year_month, records) in by_month {
+        let path = get_archive_file_path(app_handle, &year_month)?;
+        let existing = if path.exists() { read_archive(&path)? } else { Vec::new() };
+        archived_days.extend(records.iter().map(|r| r.date.clone()));
+        report.seconds_archived += records.iter().map(|r| r.total_duration).sum::<u64>();
+        let merged = merge_archive(existing, records);
+        let json = serde_json::to_string_pretty(&merged)?;
+        fs::write(&path, encryption::seal_text(json)?)?;
+        state.archive_cache.lock()?.remove(&year_month);
+        report.files.push(path.to_string_lossy().to_string());
+    }
+
+    {
+        let mut records_guard = all_day_records(state)?;
+        for day in &archived_days {
+            records_guard.remove(day);
+        }
+    }
+    report.days_archived = archived_days.len();
+    Ok(report)
+}
+
+// Validate a year and month and format them as "YYYY-MM".
+fn year_month_key(year: i32, month: u32) -> Result<String, TrackerError> {
+    chrono::NaiveDate::from_ymd_opt(year, month, 1)
+        .map(|first| first.format("%Y-%m").to_string())
+        .ok_or_else(|| TrackerError::InvalidInput(format!("{}-{} is not a valid month", year, month)))
+}
+
+// Every month that has an archive file, oldest first.
+fn archived_months(app_handle: &AppHandle) -> Result<Vec<String>, TrackerError> {
+    let mut months: Vec<String> = fs::read_dir(get_app_data_dir(app_handle)?)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let month = name.strip_prefix("archive-")?.strip_suffix(".json")?;
+            (month.len() == 7).then(|| month.to_string())
+        })
+        .collect();
+    months.sort();
+    Ok(months)
+}
+
+// One month's archived days, read from disk the first time and cached after that.
+fn archived_month(app_handle: &AppHandle, state: &AppStateArc, year_month: &str) -> Result<Arc<Vec<DayRecord>>, TrackerError> {
+    let mut cache = state.archive_cache.lock()?;
+    if let Some(days) = cache.get(year_month) {
+        return Ok(days.clone());
+    }
+    let path = get_archive_file_path(app_handle, year_month)?;
+    let days = Arc::new(if path.exists() { read_archive(&path)? } else { Vec::new() });
+    cache.insert(year_month.to_string(), days.clone());
+    Ok(days)
+}
+
+// The days in the inclusive "YYYY-MM-DD" range, live and archived alike, for history
+// queries. Only the archives of months the range touches are read. A day both live and
+// archived (unarchived and edited since) is taken from the live records.
+fn history_in_range(app_handle: &AppHandle, state: &AppStateArc, from: &str, to: &str) -> Result<HashMap<String, DayRecord>, TrackerError> {
+    let in_range = |date: &str| date >= from && date <= to;
+    let (from_month, to_month) = (from.get(..7).unwrap_or(""), to.get(..7).unwrap_or(to));
+    let mut history = HashMap::new();
+    for year_month in archived_months(app_handle)? {
+        if year_month.as_str() < from_month || year_month.as_str() > to_month {
+            continue;
+        }
+        for record in archived_month(app_handle, state, &year_month)?.iter() {
+            if in_range(&record.date) {
+                history.insert(record.date.clone(), record.clone());
+            }
+        }
+    }
+    let records_guard = all_day_records(state)?;
+    history.extend(records_guard.iter().filter(|(date, _)| in_range(date)).map(|(k, v)| (k.clone(), v.clone())));
+    Ok(history)
+}
+
+// How many days in the inclusive range exist only in an archive, for bulk edits that
+// change live days alone and say what they left out.
+fn archived_only_days(app_handle: &AppHandle, state: &AppStateArc, from: &str, to: &str) -> Result<usize, TrackerError> {
+    let history = history_in_range(app_handle, state, from, to)?;
+    let records_guard = all_day_records(state)?;
+    Ok(history.keys().filter(|date| !records_guard.contains_key(*date)).count())
+}
+
+// Move one month's days into archive-YYYY-MM.json. The live session's day and any day
+// still marked active stay put.
+#[tauri::command]
+async fn archive_month(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    year: i32,
+    month: u32,
+) -> Result<ArchiveReport, TrackerError> {
+    ensure_unlocked()?;
+    let year_month = year_month_key(year, month)?;
+    let mut by_month = archivable_months(&state, "9999-12-31")?;
+    by_month.retain(|key, _| *key == year_month);
+    let report = write_archives(&app_handle, &state, by_month)?;
+    info!("🗄️ Archived {} day(s) of {}", report.days_archived, year_month);
+    state.config.lock()?.unarchived_months.retain(|month| *month != year_month);
+    save_state(&app_handle, &state);
+    Ok(report)
+}
+
+// Bring an archived month back into the live history and delete its archive file.
+// Returns how many days were restored; a day that is also live keeps the live copy.
+// Autosave archiving then leaves the month live (see TrackerConfig::unarchived_months).
+#[tauri::command]
+async fn unarchive_month(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    year: i32,
+    month: u32,
+) -> Result<usize, TrackerError> {
+    ensure_unlocked()?;
+    let year_month = year_month_key(year, month)?;
+    let path = get_archive_file_path(&app_handle, &year_month)?;
+    if !path.exists() {
+        return Err(TrackerError::InvalidInput(format!("No archive for {}", year_month)));
+    }
+    let archived = read_archive(&path)?;
+    let mut restored = Vec::new();
+    {
+        let mut records_guard = all_day_records(&state)?;
+        for record in archived {
+            if !records_guard.contains_key(&record.date) {
+                restored.push(record.date.clone());
+                records_guard.insert(record.date.clone(), record);
+            }
+        }
+    }
+    let newly_pinned = {
+        let mut config = state.config.lock()?;
+        let pinned = !config.unarchived_months.contains(&year_month);
+        if pinned {
+            config.unarchived_months.push(year_month.clone());
+        }
+        pinned
+    };
+    // The archive goes only once the days are safely written back. If they can't be, undo
+    // the restore: the archive still holds them.
+    if let Err(e) = write_state(&app_handle, &state, false) {
+        let mut records_guard = all_day_records(&state)?;
+        for date in &restored {
+            records_guard.remove(date);
+        }
+        if newly_pinned {
+            state.config.lock()?.unarchived_months.retain(|month| *month != year_month);
+        }
+        return Err(e);
+    }
+    state.persistence.note_success(now_unix());
+    let restored = restored.len();
+    fs::remove_file(&path)?;
+    state.archive_cache.lock()?.remove(&year_month);
+    info!("🗄️ Restored {} day(s) of {} from the archive", restored, year_month);
+    Ok(restored)
+}
+
+// Lifetime totals, streaks and period comparisons for the dashboard, archives included.
+// Settled days are summed once and cached (see StatsCache); only the days still
+// changing are summed on every call. A `filter` bypasses the cache.
+#[tauri::command]
+async fn get_statistics(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    filter: Option<LapFilter>,
+) -> Result<Statistics, TrackerError> {
+    let today = chrono::Local::now().date_naive();
+    let today_key = today.format("%Y-%m-%d").to_string();
+    if let Some(filter) = filter {
+        let history = history_in_range(&app_handle, &state, "", &today_key)?;
+        let daily: BTreeMap<String, u64> =
+            history.values().map(|record| (record.date.clone(), worked_seconds(&filter.apply(record)))).collect();
+        let week_start_day = state.config.lock()?.week_start_day;
+        return Ok(statistics(&daily, today, week_start_day));
+    }
+    let generation = state.persistence.settled_generation.load(Ordering::Relaxed);
+    let session_day = state.current_session.lock()?.as_ref().map(|s| s.day_key.clone());
+    let is_hot = |record: &DayRecord| is_hot_day(record, &today_key, session_day.as_deref());
+
+    let cached = state
+        .stats_cache
+        .lock()?
+        .as_ref()
+        .filter(|cache| cache.today == today_key && cache.generation == generation)
+        .map(|cache| cache.daily.clone());
+    let mut daily = match cached {
+        Some(daily) => daily,
+        None => {
+            let history = history_in_range(&app_handle, &state, "", &today_key)?;
+            let daily: BTreeMap<String, u64> = history
+                .values()
+                .filter(|record| record.date < today_key && !is_hot(record))
+                .map(|record| (record.date.clone(), worked_seconds(record)))
+                .collect();
+            *state.stats_cache.lock()? = Some(StatsCache { today: today_key.clone(), generation, daily: daily.clone() });
+            daily
+        }
+    };
+    {
+        let records_guard = all_day_records(&state)?;
+        for record in records_guard.values().filter(|record| is_hot(record)) {
+            daily.insert(record.date.clone(), worked_seconds(record));
+        }
+    }
+    let week_start_day = state.config.lock()?.week_start_day;
+    Ok(statistics(&daily, today, week_start_day))
+}
+
+// Per-day totals and intensity levels for every date of `year`, archives included.
+#[tauri::command]
+async fn get_heatmap(state: State<'_, AppStateArc>, app_handle: AppHandle, year: u16) -> Result<HeatmapData, TrackerError> {
+    let history = history_in_range(&app_handle, &state, &format!("{:04}-01-01", year), &format!("{:04}-12-31", year))?;
+    let week_start_day = state.config.lock()?.week_start_day;
+    heatmap(&history, year, week_start_day).ok_or_else(|| TrackerError::InvalidInput(format!("{} is not a supported year", year)))
+}
+
+// Every day in the inclusive range ("YYYY-MM-DD"), including archived ones, oldest first.
+#[tauri::command]
+async fn get_records_between(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    from_date: String,
+    to_date: String,
+) -> Result<Vec<DayRecord>, TrackerError> {
+    let mut records: Vec<DayRecord> = history_in_range(&app_handle, &state, &from_date, &to_date)?.into_values().collect();
+    records.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(records)
+}
+
+// Merge days exported on another machine into the history: `path` holds a JSON array of
+// day records, as in an archive file or from get_records_between. Days new here are
+// added; days both have are merged by merge_imported_day. The running session's day is
+// skipped and laps still open in the export are dropped, both noted in the report. Undoable.
+#[tauri::command]
+async fn import_days(state: State<'_, AppStateArc>, app_handle: AppHandle, path: String) -> Result<ImportReport, TrackerError> {
+    ensure_unlocked()?;
+    let incoming: Vec<DayRecord> = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    for record in &incoming {
+        chrono::NaiveDate::parse_from_str(&record.date, "%Y-%m-%d")
+            .map_err(|_| TrackerError::InvalidInput(format!("'{}' is not a valid day", record.date)))?;
+    }
+    let (Some(from), Some(to)) = (incoming.iter().map(|r| r.date.clone()).min(), incoming.iter().map(|r| r.date.clone()).max()) else {
+        return Ok(ImportReport::default());
+    };
+    let mut archived = history_in_range(&app_handle, &state, &from, &to)?;
+
+    let snapshot = undo_snapshot(&state);
+    let session_day = state.current_session.lock()?.as_ref().map(|s| s.day_key.clone());
+    let mut report = ImportReport::default();
+    {
+        let mut records_guard = all_day_records(&state)?;
+        for mut record in incoming {
+            if Some(&record.date) == session_day.as_ref() {
+                report.days_skipped.push(record.date);
+                continue;
+            }
+            report.open_laps_dropped += drop_open_laps(&mut record);
+            // A day only in an archive is merged from there and comes back live.
+            match records_guard.remove(&record.date).or_else(|| archived.remove(&record.date)) {
+                Some(mut local) => {
+                    merge_imported_day(&mut local, record, &mut report);
+                    report.days_merged += 1;
+                    records_guard.insert(local.date.clone(), local);
+                }
+                None => {
+                    record.is_active = false;
+                    record.total_duration = worked_seconds(&record);
+                    report.days_added += 1;
+                    report.laps_added += record.laps.len();
+                    records_guard.insert(record.date.clone(), record);
+                }
+            }
+        }
+    }
+    push_undo(&state, snapshot, "Import");
+    save_state(&app_handle, &state);
+    info!(
+        "📥 Imported {}: {} day(s) added, {} merged, {} conflict(s), {} skipped",
+        path, report.days_added, report.days_merged, report.conflicts.len(), report.days_skipped.len()
+    );
+    Ok(report)
+}
+
+// "YYYY-MM-DD" for `days` days before today.
+fn days_ago_key(days: u32) -> String {
+    (chrono::Local::now().date_naive() - chrono::Duration::days(days as i64))
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+// Move every day older than `older_than_days` out of the live state into per-month
+// archive-YYYY-MM.json files, so state.json stays small.
+#[tauri::command]
+async fn archive_old_records(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    older_than_days: u32,
+) -> Result<ArchiveReport, TrackerError> {
+    ensure_unlocked()?;
+    let report = archive_days_before(&app_handle, &state, &days_ago_key(older_than_days))?;
+    save_state(&app_handle, &state);
+    Ok(report)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeReport {
+    pub days_removed: usize,
+    pub hours_removed: f64,
+    pub archive_files: Vec<String>, // where the removed days were exported first
+}
+
+// Remove every day before `date` (YYYY-MM-DD) from the live history, exporting them to
+// the month archives first. Refuses a date after today; today and the session's day are
+// never removed. Saves straight away, so the days leave state.json and days/ now.
+#[tauri::command]
+async fn purge_records_before(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    date: String,
+) -> Result<PurgeReport, TrackerError> {
+    ensure_unlocked()?;
+    chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|e| TrackerError::InvalidInput(e.to_string()))?;
+    if date > today_local_key() {
+        return Err(TrackerError::InvalidInput("Cannot purge today or later".to_string()));
+    }
+    let report = archive_days_before(&app_handle, &state, &date)?;
+    save_state(&app_handle, &state);
+    Ok(PurgeReport {
+        days_removed: report.days_archived,
+        hours_removed: report.seconds_archived as f64 / 3600.0,
+        archive_files: report.files,
+    })
+}
+
+// Keep only the last `days` days in the live history (older ones are archived on each
+// autosave); None or 0 keeps everything.
+#[tauri::command]
+async fn set_retention_days(app_handle: AppHandle, state: State<'_, AppStateArc>, days: Option<u32>) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.retention_days = days.filter(|d| *d > 0);
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// The date before which autosave archives days, if either setting asks it to: the first
+// of this month for archive_completed_months, `retention_days` ago for retention. When
+// both are on, the later cutoff covers both.
+fn retention_cutoff(today: chrono::NaiveDate, retention_days: Option<u32>, completed_months: bool) -> Option<String> {
+    let month_start = completed_months.then(|| today.format("%Y-%m-01").to_string());
+    let retention = retention_days.map(|days| (today - chrono::Duration::days(days as i64)).format("%Y-%m-%d").to_string());
+    month_start.into_iter().chain(retention).max()
+}
+
+// The autosave side of retention_days and archive_completed_months.
+fn enforce_retention(app_handle: &AppHandle, state: &AppStateArc) {
+    let (retention_days, completed_months) = {
+        let config = lock_or_recover(&state.config);
+        (config.retention_days, config.archive_completed_months)
+    };
+    let today = chrono::Local::now().date_naive();
+    let Some(cutoff) = retention_cutoff(today, retention_days, completed_months) else { return };
+    let unarchived = lock_or_recover(&state.config).unarchived_months.clone();
+    let archived = archivable_months(state, &cutoff).and_then(|mut by_month| {
+        by_month.retain(|month, _| !unarchived.contains(month));
+        write_archives(app_handle, state, by_month)
+    });
+    match archived {
+        Ok(report) if report.days_archived > 0 => {
+            info!("🗄️ Archived {} day(s) older than {} into {} file(s)", report.days_archived, cutoff, report.files.len());
+        }
+        Ok(_) => {}
+        Err(e) => warn!("⚠️ Retention archive failed, keeping the days: {}", e),
+    }
+}
+
+// Turn automatic archiving of finished months on or off.
+#[tauri::command]
+async fn set_archive_completed_months(app_handle: AppHandle, state: State<'_, AppStateArc>, enabled: bool) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    state.config.lock()?.archive_completed_months = enabled;
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageInfo {
+    pub data_dir: String,
+    pub state_file: String,
+    pub state_file_size_bytes: u64, // 0 when nothing has been written yet
+    pub last_saved_at: Option<u64>, // unix seconds of the last successful save this run
+    pub last_error: Option<String>, // why saving is currently failing, if it is
+}
+
+// Where the data lives and whether it is actually being written, for users checking up
+// on lost time.
+#[tauri::command]
+async fn get_storage_info(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<StorageInfo, TrackerError> {
+    let data_dir = get_app_data_dir(&app_handle)?;
+    let state_file = get_state_file_path(&app_handle)?;
+    let last_saved_at = state.persistence.last_saved_at.load(Ordering::Relaxed);
+    Ok(StorageInfo {
+        data_dir: data_dir.to_string_lossy().to_string(),
+        state_file_size_bytes: fs::metadata(&state_file).map(|m| m.len()).unwrap_or(0),
+        state_file: state_file.to_string_lossy().to_string(),
+        last_saved_at: (last_saved_at > 0).then_some(last_saved_at),
+        last_error: lock_or_recover(&state.persistence.last_error).clone(),
+    })
+}
+
+// The archived days of one month ("YYYY-MM"), oldest first.
+#[tauri::command]
+async fn load_archive(state: State<'_, AppStateArc>, app_handle: AppHandle, year_month: String) -> Result<Vec<DayRecord>, TrackerError> {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", year_month), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid month '{}': expected YYYY-MM", year_month))?;
+    if !get_archive_file_path(&app_handle, &year_month)?.exists() {
+        return Err(TrackerError::InvalidInput(format!("No archive for {}", year_month)));
+    }
+    Ok(archived_month(&app_handle, &state, &year_month)?.to_vec())
+}
+
+// Classify a lap (work, meeting, ...). `index` is the lap's position in that day's record.
+#[tauri::command]
+async fn tag_lap(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    date: String,
+    index: usize,
+    tag: String,
+) -> Result<Vec<String>, TrackerError> {
+    ensure_unlocked()?;
+    let tags = {
+        let mut records_guard = all_day_records(&state)?;
+        let lap = lap_at_mut(&mut records_guard, &date, index)?;
+        add_tag(lap, &tag)?;
+        lap.tags.clone()
+    };
+    save_state(&app_handle, &state);
+    Ok(tags)
+}
+
+#[tauri::command]
+async fn untag_lap(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    date: String,
+    index: usize,
+    tag: String,
+) -> Result<Vec<String>, TrackerError> {
+    ensure_unlocked()?;
+    let tags = {
+        let mut records_guard = all_day_records(&state)?;
+        let lap = lap_at_mut(&mut records_guard, &date, index)?;
+        remove_tag(lap, &tag)?;
+        lap.tags.clone()
+    };
+    save_state(&app_handle, &state);
+    Ok(tags)
+}
+
+// Account for unexplained time between laps by filling each gap longer than
+// `min_gap_seconds` with a break lap noted `fill_note` ("Break" by default). The
+// inserted laps are marked is_synthetic and do not count towards any total.
+#[tauri::command]
+async fn detect_and_fill_gaps(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    day_key: String,
+    min_gap_seconds: u64,
+    fill_note: Option<String>,
+) -> Result<DayRecord, TrackerError> {
+    ensure_unlocked()?;
+    let note = fill_note
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Break".to_string());
+    let record = {
+        let mut records_guard = all_day_records(&state)?;
+        let record = records_guard
+            .get_mut(&day_key)
+            .ok_or_else(|| TrackerError::DayNotFound(day_key.clone()))?;
+        let inserted = fill_gaps(record, min_gap_seconds, &note);
+        info!("🧩 Filled {} gap(s) on {}", inserted, day_key);
+        record.clone()
+    };
+    save_state(&app_handle, &state);
+    Ok(record)
+}
+
+// Colour a lap for the timeline, or clear its colour with None.
+#[tauri::command]
+async fn set_lap_color(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    day_key: String,
+    lap_index: usize,
+    color: Option<String>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let color = color.as_deref().map(normalize_color).transpose()?;
+    {
+        let mut records_guard = all_day_records(&state)?;
+        lap_at_mut(&mut records_guard, &day_key, lap_index)?.color = color;
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_laps_by_color(
+    state: State<'_, AppStateArc>,
+    color: String,
+) -> Result<Vec<(String, usize, Lap)>, TrackerError> {
+    let color = normalize_color(&color)?;
+    let records_guard = all_day_records(&state)?;
+    Ok(laps_with_color(&records_guard, &color))
+}
+
+// Seconds per tag across the inclusive date range `from`..=`to` ("YYYY-MM-DD").
+#[tauri::command]
+async fn get_time_by_tag(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    from: String,
+    to: String,
+) -> Result<HashMap<String, u64>, TrackerError> {
+    let history = history_in_range(&app_handle, &state, &from, &to)?;
+    Ok(time_by_tag(&history, &from, &to))
+}
+
+// The open lap of a day record: the last one, if it has no end yet.
+fn running_lap(record: &DayRecord) -> Option<&Lap> {
+    record.laps.last().filter(|lap| lap.end_time.is_none())
+}
+
+// Set (or clear, with a blank note) the note on the running lap. Saved by the next
+// autosave; a lap that is still running has nothing else worth saving straight away.
+#[tauri::command]
+async fn annotate_current_lap(state: State<'_, AppStateArc>, note: String) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    let session = session_guard.as_ref().ok_or(TrackerError::NoActiveSession)?;
+    let lap = records_guard
+        .get_mut(&session.day_key)
+        .and_then(|record| record.laps.last_mut())
+        .filter(|lap| lap.end_time.is_none())
+        .ok_or_else(|| TrackerError::InvalidInput("No lap is running".to_string()))?;
+    let note = note.trim();
+    lap.note = (!note.is_empty()).then(|| note.to_string());
+    Ok(())
+}
+
+// Switch (or clear, with None/blank) the active project. While a lap is running it is
+// split here: the running lap closes now under the old project and a new one opens under
+// the new project, so per-project totals are exact. While paused there is no running
+// lap, so only the project for the next lap changes.
+#[tauri::command]
+async fn set_active_project(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    name: Option<String>,
+) -> Result<Option<String>, TrackerError> {
+    ensure_unlocked()?;
+    let project = normalize_project(name)?;
+    {
+        let mut session_guard = state.current_session.lock()?;
+        let mut records_guard = state.day_records.lock()?;
+        let session = session_guard.as_mut().ok_or(TrackerError::NoActiveSession)?;
+        if session.project == project {
+            return Ok(project);
+        }
+        session.project = project.clone();
+        if !session.is_paused {
+            roll_to_next_lap(session, &mut records_guard, now_unix());
+        }
+    }
+    save_state(&app_handle, &state);
+    Ok(project)
+}
+
+// Seconds per project across the inclusive date range ("YYYY-MM-DD"); either end may be
+// left open. Laps without a project are reported under "Unassigned".
+#[tauri::command]
+async fn get_project_totals(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    from_date: Option<String>,
+    to_date: Option<String>,
+) -> Result<HashMap<String, u64>, TrackerError> {
+    let from = from_date.unwrap_or_default();
+    let to = to_date.unwrap_or_else(|| "9999-12-31".to_string());
+    let history = history_in_range(&app_handle, &state, &from, &to)?;
+    Ok(project_totals(&history, &from, &to))
+}
+
+// The laps behind get_project_totals for one project, by day, newest first. "Unassigned"
+// asks for the laps without a project.
+#[tauri::command]
+async fn get_sessions_for_project(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    project: String,
+    from_date: Option<String>,
+    to_date: Option<String>,
+) -> Result<ProjectSessions, TrackerError> {
+    let from = from_date.unwrap_or_default();
+    let to = to_date.unwrap_or_else(|| "9999-12-31".to_string());
+    let history = history_in_range(&app_handle, &state, &from, &to)?;
+    Ok(project_sessions(&history, project.trim(), &from, &to))
+}
+
+// Set the billing rate used by get_invoice_data. `currency` is a three-letter ISO 4217
+// code such as "USD" or "EUR".
+#[tauri::command]
+async fn set_hourly_rate(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    rate: f64,
+    currency: String,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if !rate.is_finite() || rate < 0.0 {
+        return Err(TrackerError::InvalidInput("The hourly rate must be a non-negative number".to_string()));
+    }
+    let currency = currency.trim().to_uppercase();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(TrackerError::InvalidInput(format!("'{}' is not a three-letter currency code", currency)));
+    }
+    {
+        let mut config = state.config.lock()?;
+        config.hourly_rate = Some(rate);
+        config.currency = currency;
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Billable time and amounts per day and project over the inclusive date range.
+#[tauri::command]
+async fn get_invoice_data(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    from_date: String,
+    to_date: String,
+) -> Result<InvoiceData, TrackerError> {
+    let (rate, currency) = {
+        let config = state.config.lock()?;
+        (config.hourly_rate, config.currency.clone())
+    };
+    let rate = rate.ok_or_else(|| TrackerError::InvalidInput("Set an hourly rate first".to_string()))?;
+    let history = history_in_range(&app_handle, &state, &from_date, &to_date)?;
+    Ok(invoice_data(&history, &from_date, &to_date, rate, &currency))
+}
+
+// Re-file one completed or running lap under another project (None clears it). Unlike
+// set_active_project this never splits anything: it corrects the record after the fact.
+#[tauri::command]
+async fn set_project_for_lap(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    day_key: String,
+    lap_index: usize,
+    project: Option<String>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let project = normalize_project(project)?;
+    {
+        let mut records_guard = all_day_records(&state)?;
+        lap_at_mut(&mut records_guard, &day_key, lap_index)?.project = project;
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BulkUpdateReport {
+    pub days_updated: usize,
+    pub laps_updated: usize,
+    pub laps_skipped: usize, // kept their project, already had this one, or still running
+    pub archived_days_skipped: usize, // archived days in the range; see unarchive_month
+}
+
+// Give `project` to the laps of every day in the inclusive "YYYY-MM-DD" range. A lap that
+// already has a project keeps it unless `overwrite_existing`; the running lap is never
+// touched, and synthetic break laps are not counted at all.
+fn assign_project_in_range(
+    records: &mut HashMap<String, DayRecord>,
+    from: &str,
+    to: &str,
+    project: &Option<String>,
+    overwrite_existing: bool,
+) -> BulkUpdateReport {
+    let mut report = BulkUpdateReport::default();
+    for record in records.values_mut().filter(|r| r.date.as_str() >= from && r.date.as_str() <= to) {
+        let mut changed = false;
+        for lap in record.laps.iter_mut().filter(|lap| !lap.is_synthetic) {
+            let keep = lap.end_time.is_none()
+                || lap.project == *project
+                || (lap.project.is_some() && !overwrite_existing);
+            if keep {
+                report.laps_skipped += 1;
+            } else {
+                lap.project = project.clone();
+                report.laps_updated += 1;
+                changed = true;
+            }
+        }
+        report.days_updated += usize::from(changed);
+    }
+    report
+}
+
+// set_project_for_lap over a whole date range. Only live days change; archived days in
+// the range are counted in archived_days_skipped. None or blank clears the project.
+// Undoable.
+#[tauri::command]
+async fn set_lap_project_bulk(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    from_date: String,
+    to_date: String,
+    project: Option<String>,
+    overwrite_existing: bool,
+) -> Result<BulkUpdateReport, TrackerError> {
+    ensure_unlocked()?;
+    let project = normalize_project(project)?;
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|e| TrackerError::InvalidInput(e.to_string()));
+    if parse(&from_date)? > parse(&to_date)? {
+        return Err(TrackerError::InvalidInput("from_date must not be after to_date".to_string()));
+    }
+    let archived_days_skipped = archived_only_days(&app_handle, &state, &from_date, &to_date)?;
+    let snapshot = undo_snapshot(&state);
+    let report = {
+        let mut records_guard = all_day_records(&state)?;
+        BulkUpdateReport {
+            archived_days_skipped,
+            ..assign_project_in_range(&mut records_guard, &from_date, &to_date, &project, overwrite_existing)
+        }
+    };
+    if report.laps_updated > 0 {
+        push_undo(&state, snapshot, "Set project for date range");
+        save_state(&app_handle, &state);
+    }
+    info!("🏷️ Set project on {} lap(s) across {} day(s)", report.laps_updated, report.days_updated);
+    Ok(report)
+}
+
+// trim_laps_below_threshold never removes laps this long or longer, whatever it is asked.
+const MAX_TRIM_THRESHOLD_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TrimReport {
+    pub laps_found: usize,
+    pub laps_removed: usize,
+    pub days_affected: Vec<String>, // sorted
+    pub seconds_removed: u64,
+    pub archived_days_skipped: usize, // archived days are not trimmed; see unarchive_month
+}
+
+// Find the completed laps shorter than `min_seconds` (clamped to MAX_TRIM_THRESHOLD_SECS)
+// and, unless `dry_run`, remove them and recompute each affected day's total. Running
+// and synthetic break laps are left alone.
+fn trim_short_laps(records: &mut HashMap<String, DayRecord>, min_seconds: u64, dry_run: bool) -> TrimReport {
+    let min_seconds = min_seconds.min(MAX_TRIM_THRESHOLD_SECS);
+    let is_short = |lap: &Lap| !lap.is_synthetic && lap.end_time.is_some() && lap.duration.unwrap_or(0) < min_seconds;
+    let mut report = TrimReport::default();
+    for record in records.values_mut() {
+        let short: Vec<u64> = record.laps.iter().filter(|lap| is_short(lap)).map(|lap| lap.duration.unwrap_or(0)).collect();
+        if short.is_empty() {
+            continue;
+        }
+        report.laps_found += short.len();
+        report.seconds_removed += short.iter().sum::<u64>();
+        report.days_affected.push(record.date.clone());
+        if !dry_run {
+            record.laps.retain(|lap| !is_short(lap));
+            record.total_duration = worked_seconds(record);
+            report.laps_removed += short.len();
+        }
+    }
+    report.days_affected.sort();
+    report
+}
+
+// Bulk cleanup of the 1-2 second laps an accidental unlock/lock leaves behind, across
+// every live day; archived days are only counted, in archived_days_skipped. With dry_run
+// nothing changes and laps_removed stays 0. Undoable.
+#[tauri::command]
+async fn trim_laps_below_threshold(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    min_seconds: u64,
+    dry_run: bool,
+) -> Result<TrimReport, TrackerError> {
+    ensure_unlocked()?;
+    let archived_days_skipped = archived_only_days(&app_handle, &state, "0000-01-01", "9999-12-31")?;
+    let snapshot = undo_snapshot(&state);
+    let report = {
+        let mut records_guard = all_day_records(&state)?;
+        TrimReport { archived_days_skipped, ..trim_short_laps(&mut records_guard, min_seconds, dry_run) }
+    };
+    if report.laps_removed > 0 {
+        push_undo(&state, snapshot, "Trim short laps");
+        save_state(&app_handle, &state);
+        info!("✂️ Trimmed {} short lap(s) ({}s) across {} day(s)", report.laps_removed, report.seconds_removed, report.days_affected.len());
+    }
+    Ok(report)
+}
+
+// Where a day's time went, by frontmost application, most-used first.
+#[tauri::command]
+async fn get_app_breakdown(state: State<'_, AppStateArc>, date: String) -> Result<Vec<(String, u64)>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    let record = records_guard.get(&date).ok_or_else(|| TrackerError::DayNotFound(date.clone()))?;
+    Ok(day_app_breakdown(record))
+}
+
+// The top `limit` apps across the inclusive date range, for the dashboard pie chart.
+#[tauri::command]
+async fn get_top_apps(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    from: String,
+    to: String,
+    limit: usize,
+) -> Result<Vec<(String, u64, f64)>, TrackerError> {
+    let history = history_in_range(&app_handle, &state, &from, &to)?;
+    Ok(top_apps(&history, &from, &to, &today_local_key(), limit))
+}
+
+// The week containing `date` (YYYY-MM-DD), or the current week, starting on the
+// configured week_start_day.
+#[tauri::command]
+async fn get_week_summary(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    date: Option<String>,
+) -> Result<WeekSummary, TrackerError> {
+    let date = date.unwrap_or_else(today_local_key);
+    let date = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|e| TrackerError::InvalidInput(e.to_string()))?;
+    let (week_start_day, goal) = {
+        let config = state.config.lock()?;
+        (config.week_start_day, config.daily_goal_secs)
+    };
+    // Whichever day the week starts on, it lies within six days either side of `date`.
+    let key = |d: chrono::NaiveDate| d.format("%Y-%m-%d").to_string();
+    let (from, to) = (key(date - chrono::Duration::days(6)), key(date + chrono::Duration::days(6)));
+    let history = history_in_range(&app_handle, &state, &from, &to)?;
+    Ok(week_summary(&history, date, week_start_day, goal))
+}
+
+// How regularly the workdays of the last `weeks` weeks (1-52) were tracked.
+#[tauri::command]
+async fn get_tracking_consistency_report(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    weeks: u32,
+) -> Result<ConsistencyReport, TrackerError> {
+    if !(1..=MAX_CONSISTENCY_WEEKS).contains(&weeks) {
+        return Err(TrackerError::InvalidInput(format!("weeks must be between 1 and {}", MAX_CONSISTENCY_WEEKS)));
+    }
+    let (week_start_day, work_days) = {
+        let config = state.config.lock()?;
+        (config.week_start_day, config.work_days.clone())
+    };
+    let today = chrono::Local::now().date_naive();
+    // The period starts at most six days before `weeks` whole weeks ago.
+    let from = (today - chrono::Duration::weeks(weeks as i64) - chrono::Duration::days(6)).format("%Y-%m-%d").to_string();
+    let history = history_in_range(&app_handle, &state, &from, &today_local_key())?;
+    let daily: HashMap<String, u64> = history.iter().map(|(date, record)| (date.clone(), worked_seconds(record))).collect();
+    Ok(consistency_report(&daily, today, weeks, week_start_day, &work_days))
+}
+
+// The weekdays (0 = Monday through 6 = Sunday) expected to be tracked.
+#[tauri::command]
+async fn set_work_days(app_handle: AppHandle, state: State<'_, AppStateArc>, days: Vec<u8>) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if days.iter().any(|d| *d > 6) {
+        return Err(TrackerError::InvalidInput("days must be 0 (Monday) to 6 (Sunday)".to_string()));
+    }
+    let mut days = days;
+    days.sort_unstable();
+    days.dedup();
+    state.config.lock()?.work_days = days;
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_focus_score(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    day_key: String,
+    filter: Option<LapFilter>,
+) -> Result<FocusScore, TrackerError> {
+    let max_expected_fragmentation = state.config.lock()?.max_expected_fragmentation;
+    let history = history_in_range(&app_handle, &state, &day_key, &day_key)?;
+    let record = history.get(&day_key).ok_or(TrackerError::DayNotFound(day_key))?;
+    let record = filter.unwrap_or_default().apply(record);
+    Ok(focus_score(&record, max_expected_fragmentation))
+}
+
+// Today counts its live lap the way get_current_status does.
+#[tauri::command]
+async fn get_comparison(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<Comparison, TrackerError> {
+    let today = today_local_key();
+    let history = history_in_range(&app_handle, &state, &days_ago_key(COMPARISON_WINDOW_DAYS), &days_ago_key(1))?;
+    let previous_days: Vec<Option<u64>> = (1..=COMPARISON_WINDOW_DAYS)
+        .rev()
+        .map(|days| history.get(&days_ago_key(days)).map(worked_seconds))
+        .collect();
+    let today_seconds = {
+        let session_guard = state.current_session.lock()?;
+        let records_guard = all_day_records(&state)?;
+        match session_guard.as_ref().filter(|s| s.day_key == today) {
+            Some(session) => worked_today(session, &records_guard, now_unix()),
+            None => records_guard.get(&today).map(worked_seconds).unwrap_or(0),
+        }
+    };
+    Ok(comparison(today_seconds, &previous_days))
+}
+
+// Laps per worked hour that scores 0 in get_focus_score.
+#[tauri::command]
+async fn set_max_expected_fragmentation(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    laps_per_hour: f64,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if !laps_per_hour.is_finite() || laps_per_hour <= 0.0 {
+        return Err(TrackerError::InvalidInput("laps_per_hour must be a positive number".to_string()));
+    }
+    state.config.lock()?.max_expected_fragmentation = laps_per_hour;
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// 0 = Monday through 6 = Sunday.
+#[tauri::command]
+async fn set_week_start_day(app_handle: AppHandle, state: State<'_, AppStateArc>, day: u8) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if day > 6 {
+        return Err(TrackerError::InvalidInput("day must be 0 (Monday) to 6 (Sunday)".to_string()));
+    }
+    state.config.lock()?.week_start_day = day;
+    info!("📅 Weeks now start on day {} (0 = Monday)", day);
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Days whose tracked total falls between two percentiles (0-100) of all days, e.g.
+// 25 and 75 for the "typical" days. Newest first.
+#[tauri::command]
+async fn get_sessions_by_duration_percentile(
+    state: State<'_, AppStateArc>,
+    percentile_low: f64,
+    percentile_high: f64,
+) -> Result<Vec<DayRecord>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    Ok(records_in_duration_percentile(&records_guard, percentile_low, percentile_high)?)
+}
+
+// Exports are read by other tools, so they are never sealed. With encryption on, one is
+// only written when the caller passes plaintext: true.
+fn check_plaintext_export(plaintext: Option<bool>) -> Result<(), TrackerError> {
+    if encryption::status().encrypted && plaintext != Some(true) {
+        return Err(TrackerError::InvalidInput(
+            "Your data is encrypted; pass plaintext: true to write this export unencrypted".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Write every completed lap to `output_path` as a JSON array of Toggl Track time entries.
+// `timezone` is a UTC offset like "+02:00" for the written timestamps; the machine's
+// current offset when omitted. With `anonymize` the laps go through anonymize_records,
+// so the timestamps count from 1970-01-01 as the first tracked day. Returns the number
+// of entries written.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // each one is a named field of the command's JSON arguments
+async fn export_to_toggl_json(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    output_path: String,
+    workspace_id: u64,
+    project_id: Option<u64>,
+    timezone: Option<String>,
+    plaintext: Option<bool>,
+    anonymize: Option<bool>,
+) -> Result<usize, TrackerError> {
+    check_plaintext_export(plaintext)?;
+    let offset = match timezone {
+        Some(tz) => parse_utc_offset(&tz)?,
+        None => *chrono::Local::now().offset(),
+    };
+    let mut history = history_in_range(&app_handle, &state, "", "9999-12-31")?;
+    let first_day = history.keys().min().and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+    if let (true, Some(first_day)) = (anonymize.unwrap_or(false), first_day) {
+        let records: Vec<DayRecord> = history.into_values().collect();
+        history = anonymize_records(&records, first_day, &offset).into_iter().map(|r| (r.date.clone(), r)).collect();
+    }
+    let entries = toggl_entries(&history, workspace_id, project_id, offset);
+    let json = serde_json::to_string_pretty(&entries)?;
+    fs::write(&output_path, json)?;
+    info!("📤 Exported {} Toggl entries to {}", entries.len(), output_path);
+    Ok(entries.len())
+}
+
+// Write the inclusive date range to `output_path` as a JSON array of day records, the
+// format import_days reads. Window titles are left out unless `include_titles` is true;
+// `anonymize` applies anonymize_records, counting days from `from_date`. Returns the
+// number of days written.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_to_json(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    output_path: String,
+    from_date: String,
+    to_date: String,
+    include_titles: Option<bool>,
+    anonymize: Option<bool>,
+    plaintext: Option<bool>,
+) -> Result<usize, TrackerError> {
+    check_plaintext_export(plaintext)?;
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|e| TrackerError::InvalidInput(e.to_string()));
+    let (from, to) = (parse(&from_date)?, parse(&to_date)?);
+    if from > to {
+        return Err(TrackerError::InvalidInput("from_date must not be after to_date".to_string()));
+    }
+    let mut records: Vec<DayRecord> = history_in_range(&app_handle, &state, &from_date, &to_date)?.into_values().collect();
+    records.sort_by(|a, b| a.date.cmp(&b.date));
+    if anonymize.unwrap_or(false) {
+        records = anonymize_records(&records, from, &chrono::Local);
+    } else if !include_titles.unwrap_or(false) {
+        records.iter_mut().flat_map(|r| r.laps.iter_mut()).for_each(|lap| lap.window_titles.clear());
+    }
+    fs::write(&output_path, serde_json::to_string_pretty(&records)?)?;
+    info!("📤 Exported {} day(s) as JSON to {}", records.len(), output_path);
+    Ok(records.len())
+}
+
+// A day record as pretty JSON that parses back into a DayRecord. Without
+// `include_metadata` the metadata key is left out, which reads back as empty.
+fn day_export_json(record: &DayRecord, include_metadata: bool) -> Result<String, serde_json::Error> {
+    let mut value = serde_json::to_value(record)?;
+    if !include_metadata {
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("metadata");
+        }
+    }
+    serde_json::to_string_pretty(&value)
+}
+
+// One day as self-contained JSON for sharing, returned rather than written to a file.
+// Window titles are left out unless `include_titles` is true; `anonymize` applies
+// anonymize_records, so the day becomes "day-0". With encryption on, `plaintext` must be
+// true, as for the file exports.
+#[tauri::command]
+async fn export_day_to_json(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    day_key: String,
+    include_metadata: bool,
+    include_titles: Option<bool>,
+    anonymize: Option<bool>,
+    plaintext: Option<bool>,
+) -> Result<String, TrackerError> {
+    check_plaintext_export(plaintext)?;
+    let mut record = history_in_range(&app_handle, &state, &day_key, &day_key)?
+        .remove(&day_key)
+        .ok_or_else(|| TrackerError::DayNotFound(day_key.clone()))?;
+    if anonymize.unwrap_or(false) {
+        let date = chrono::NaiveDate::parse_from_str(&day_key, "%Y-%m-%d").map_err(|e| TrackerError::InvalidInput(e.to_string()))?;
+        record = anonymize_records(&[record], date, &chrono::Local)
+            .pop()
+            .ok_or_else(|| TrackerError::InvalidInput(format!("{} has no completed laps to export", day_key)))?;
+    } else if !include_titles.unwrap_or(false) {
+        record.laps.iter_mut().for_each(|lap| lap.window_titles.clear());
+    }
+    Ok(day_export_json(&record, include_metadata)?)
+}
+
+// The longest range a Markdown report covers: a year, leap day included. The whole report
+// is also returned for a preview, so it is kept to a size the webview can show.
+const MAX_REPORT_RANGE_DAYS: i64 = 366;
+
+// Write the inclusive date range (at most MAX_REPORT_RANGE_DAYS) to `output_path` as a
+// Markdown report and return the same text for a preview. Window titles are left out
+// unless `include_titles` is true.
+#[tauri::command]
+async fn export_to_markdown(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    output_path: String,
+    from_date: String,
+    to_date: String,
+    include_titles: Option<bool>,
+    plaintext: Option<bool>,
+) -> Result<String, TrackerError> {
+    check_plaintext_export(plaintext)?;
+    let parse = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|e| TrackerError::InvalidInput(e.to_string()));
+    let (from, to) = (parse(&from_date)?, parse(&to_date)?);
+    if from > to {
+        return Err(TrackerError::InvalidInput("from_date must not be after to_date".to_string()));
+    }
+    if (to - from).num_days() >= MAX_REPORT_RANGE_DAYS {
+        return Err(TrackerError::InvalidInput(format!("A report covers at most {} days", MAX_REPORT_RANGE_DAYS)));
+    }
+    let history = history_in_range(&app_handle, &state, &from_date, &to_date)?;
+    let markdown = markdown_report(&history, from, to, include_titles.unwrap_or(false), &chrono::Local);
+    fs::write(&output_path, &markdown)?;
+    info!("📤 Exported a Markdown report for {} to {} to {}", from_date, to_date, output_path);
+    Ok(markdown)
+}
+
+// Put a one-line summary of `date` on the clipboard and return it. `format` is "text"
+// (the default), "markdown" or "slack".
+#[tauri::command]
+async fn copy_day_summary(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    date: String,
+    format: Option<SummaryFormat>,
+) -> Result<String, TrackerError> {
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+    let history = history_in_range(&app_handle, &state, &date, &date)?;
+    let record = history.get(&date).ok_or_else(|| TrackerError::DayNotFound(date.clone()))?;
+    let summary = day_summary_line(record, format.unwrap_or_default(), &chrono::Local);
+    app_handle
+        .clipboard()
+        .write_text(summary.clone())
+        .map_err(|e| TrackerError::InvalidInput(format!("Could not write to the clipboard: {}", e)))?;
+    info!("📋 Copied the {} summary to the clipboard", date);
+    Ok(summary)
+}
+
+#[tauri::command]
+async fn get_month_summary(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    year: i32,
+    month: u32,
+) -> Result<MonthSummary, TrackerError> {
+    let month_key = year_month_key(year, month)?;
+    let goal = state.config.lock()?.daily_goal_secs;
+    let history = history_in_range(&app_handle, &state, &format!("{}-01", month_key), &format!("{}-31", month_key))?;
+    month_summary(&history, year, month, goal).ok_or_else(|| TrackerError::InvalidInput(format!("{} is not a valid month", month_key)))
+}
+
+// Write a one-page PDF of get_month_summary's numbers to `path` and return the path.
+// Window titles are left out unless `include_titles` is true.
+#[tauri::command]
+async fn export_pdf(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    year: i32,
+    month: u32,
+    path: String,
+    include_titles: Option<bool>,
+    plaintext: Option<bool>,
+) -> Result<String, TrackerError> {
+    check_plaintext_export(plaintext)?;
+    let summary = get_month_summary(state, app_handle, year, month).await?;
+    if summary.total_seconds == 0 {
+        return Err(TrackerError::InvalidInput(format!("Nothing was tracked in {}", summary.month_key)));
+    }
+    let pdf = month_pdf(&summary, include_titles.unwrap_or(false), &chrono::Local)?;
+    fs::write(&path, pdf)?;
+    info!("📤 Exported the {} PDF report to {}", summary.month_key, path);
+    Ok(path)
+}
+
+// When in the day work happens, over the inclusive date range. Needs at least
+// MIN_DAYS_FOR_PRODUCTIVITY_REPORT tracked days in the range to say anything useful.
+#[tauri::command]
+async fn get_productive_hours_report(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    from_date: String,
+    to_date: String,
+) -> Result<ProductivityReport, TrackerError> {
+    let history = history_in_range(&app_handle, &state, &from_date, &to_date)?;
+    let days_with_data = history
+        .values()
+        .filter(|r| r.date >= from_date && r.date <= to_date)
+        .filter(|r| r.laps.iter().any(|lap| lap.worked_duration().unwrap_or(0) > 0))
+        .count();
+    if days_with_data < MIN_DAYS_FOR_PRODUCTIVITY_REPORT {
+        return Err(TrackerError::InvalidInput(format!(
+            "Need at least {} days of data between {} and {} (found {})",
+            MIN_DAYS_FOR_PRODUCTIVITY_REPORT, from_date, to_date, days_with_data
+        )));
+    }
+    let buckets = hourly_totals(&history, &from_date, &to_date, &chrono::Local);
+    Ok(productivity_report(buckets))
+}
+
+// When tracking began and how much of that stretch was actually tracked.
+#[tauri::command]
+async fn get_history_bounds(state: State<'_, AppStateArc>) -> Result<Option<HistoryBounds>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    Ok(history_bounds(&records_guard))
+}
+
+const MAX_METADATA_KEY_LEN: usize = 64;
+const MAX_METADATA_VALUE_LEN: usize = 1024;
+
+fn validate_metadata_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_METADATA_KEY_LEN {
+        return Err(format!("Metadata keys must be 1-{} characters", MAX_METADATA_KEY_LEN));
+    }
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("Metadata key '{}' may only contain letters, digits and underscores", key));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_day_metadata(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    day_key: String,
+    key: String,
+    value: String,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    validate_metadata_key(&key)?;
+    if value.chars().count() > MAX_METADATA_VALUE_LEN {
+        return Err(TrackerError::InvalidInput(format!(
+            "Metadata values are limited to {} characters",
+            MAX_METADATA_VALUE_LEN
+        )));
+    }
+    {
+        let mut records_guard = all_day_records(&state)?;
+        let record = records_guard.get_mut(&day_key).ok_or(TrackerError::DayNotFound(day_key))?;
+        record.metadata.insert(key, value);
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_day_metadata(state: State<'_, AppStateArc>, day_key: String, key: String) -> Result<Option<String>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    let record = records_guard.get(&day_key).ok_or(TrackerError::DayNotFound(day_key))?;
+    Ok(record.metadata.get(&key).cloned())
+}
+
+// Removing a key that isn't set is not an error.
+#[tauri::command]
+async fn delete_day_metadata(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    day_key: String,
+    key: String,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let removed = {
+        let mut records_guard = all_day_records(&state)?;
+        let record = records_guard.get_mut(&day_key).ok_or(TrackerError::DayNotFound(day_key))?;
+        record.metadata.remove(&key).is_some()
+    };
+    if removed {
+        save_state(&app_handle, &state);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_all_day_metadata(state: State<'_, AppStateArc>, day_key: String) -> Result<HashMap<String, String>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    let record = records_guard.get(&day_key).ok_or(TrackerError::DayNotFound(day_key))?;
+    Ok(record.metadata.clone())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineEntryType {
+    Lap,
+    Gap,
+    ActiveLap,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEntry {
+    pub entry_type: TimelineEntryType,
+    pub start_time: u64,
+    pub end_time: Option<u64>,
+    pub duration_seconds: Option<u64>,
+    pub lap_index: Option<usize>, // index into the day's laps; None for an untracked gap
+}
+
+// A day's laps in start order with the breaks between them made explicit. A synthetic
+// break lap (detect_and_fill_gaps) is itself reported as the gap it fills.
+fn session_timeline(record: &DayRecord) -> Vec<TimelineEntry> {
+    let mut order: Vec<usize> = (0..record.laps.len()).collect();
+    order.sort_by_key(|&i| record.laps[i].start_time);
+
+    let mut entries = Vec::new();
+    let mut previous_end: Option<u64> = None;
+    for index in order {
+        let lap = &record.laps[index];
+        if let Some(end) = previous_end.filter(|end| *end < lap.start_time) {
+            entries.push(TimelineEntry {
+                entry_type: TimelineEntryType::Gap,
+                start_time: end,
+                end_time: Some(lap.start_time),
+                duration_seconds: Some(lap.start_time - end),
+                lap_index: None,
+            });
+        }
+        let entry_type = match (lap.end_time, lap.is_synthetic) {
+            (None, _) => TimelineEntryType::ActiveLap,
+            (Some(_), true) => TimelineEntryType::Gap,
+            (Some(_), false) => TimelineEntryType::Lap,
+        };
+        entries.push(TimelineEntry {
+            entry_type,
+            start_time: lap.start_time,
+            end_time: lap.end_time,
+            duration_seconds: lap.duration,
+            lap_index: Some(index),
+        });
+        previous_end = lap.end_time;
+    }
+    entries
+}
+
+#[tauri::command]
+async fn get_session_timeline(state: State<'_, AppStateArc>, day_key: String) -> Result<Vec<TimelineEntry>, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    let record = records_guard.get(&day_key).ok_or(TrackerError::DayNotFound(day_key))?;
+    Ok(session_timeline(record))
+}
+
+#[tauri::command]
+async fn get_all_time_bests(state: State<'_, AppStateArc>) -> Result<AllTimeBests, TrackerError> {
+    let records_guard = all_day_records(&state)?;
+    Ok(all_time_bests(&records_guard))
+}
+
+#[tauri::command]
+async fn add_lap(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    add_lap_direct(&app_handle, &state)
+}
+
+// Also how a paused session resumes: there is no open lap to close, so this just opens one.
+fn add_lap_direct(app_handle: &AppHandle, state: &AppStateArc) -> Result<String, TrackerError> {
+    let snapshot = undo_snapshot(state);
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        let current_time = now_unix();
+        
+        // Close the running lap (if any) and start a new one, resuming the session
+        let was_paused = session.is_paused;
+        let running_start = session.current_lap_start_timestamp;
+        roll_to_next_lap(session, &mut records_guard, current_time);
+        if let Some(note) = session.template.as_mut().and_then(TemplateProgress::take_next_note) {
+            if let Some(lap) = records_guard.get_mut(&session.day_key).and_then(|r| r.laps.last_mut()) {
+                lap.note = Some(note);
+            }
+        }
+
+        let laps = records_guard.get(&session.day_key).map(|r| r.laps.as_slice()).unwrap_or_default();
+        if was_paused {
+            emit_session_event(app_handle, "session-resumed", &session.day_key, laps.last().cloned(), None);
+        } else if let Some(ended) = laps.iter().rev().find(|l| l.start_time == running_start && l.duration.is_some()) {
+            emit_session_event(app_handle, "lap-ended", &session.day_key, Some(ended.clone()), None);
+        }
+        push_undo(state, snapshot, "Start new lap");
+        Ok("New lap added successfully - session resumed".to_string())
+    } else {
+        Err(TrackerError::NoActiveSession)
+    }
+}
+
+#[tauri::command]
+async fn stop_lap(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    stop_lap_direct(&app_handle, &state)
+}
+
+fn stop_lap_direct(app_handle: &AppHandle, state: &AppStateArc) -> Result<String, TrackerError> {
+    let snapshot = undo_snapshot(state);
+    let rules = state.config.lock()?.auto_tag_rules.clone();
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        if session.is_paused {
+            return Err(TrackerError::InvalidInput("Session is already paused".to_string()));
+        }
+        
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        
+        // Get actual lap start time from records
+        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
+            get_active_lap_start_time(day_record)
+                .unwrap_or(session.current_lap_start_timestamp)
+        } else {
+            session.current_lap_start_timestamp
+        };
+        
+        let lap_duration = finalize_lap_duration(lap_start_time);
+        
+        // If lap is very short (< 3 seconds), remove it instead of keeping it
+        if lap_duration < 3 {
+            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+                // Remove the last lap if it's too short
+                if let Some(last_lap) = day_record.laps.last() {
+                    if last_lap.duration.is_none() {
+                        // This is the active lap, remove it
+                        day_record.laps.pop();
+                        
+                        // Mark session as paused by user and reset accumulated time
+                        session.pause(PauseCause::User, current_time);
+                        session.accumulated_seconds = 0;
+                        release_display_assertion_now(state);
+                        
+                        emit_session_event(app_handle, "session-paused", &session.day_key, None, Some("user"));
+                        push_undo(state, snapshot, "Pause");
+                        return Ok("Very short lap removed - session paused".to_string());
+                    }
+                }
+            }
+        }
+        
+        // End current lap normally
+        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+            if let Some(last_lap) = day_record.laps.last_mut() {
+                if last_lap.duration.is_none() {
+                    last_lap.end_time = Some(current_time);
+                    last_lap.duration = Some(lap_duration);
+                    last_lap.end_reason = Some(LapEndReason::UserStopped);
+                    auto_tag_lap(last_lap, &rules);
+                    emit_session_event(app_handle, "lap-ended", &session.day_key, Some(last_lap.clone()), None);
+                }
+            }
+        }
+        
+        // Mark session as paused by user (not ended)
+        session.pause(PauseCause::User, current_time);
+        release_display_assertion_now(state);
+        
+        emit_session_event(app_handle, "session-paused", &session.day_key, None, Some("user"));
+        push_undo(state, snapshot, "Pause");
+        Ok("Lap stopped - session paused".to_string())
+    } else {
+        Err(TrackerError::NoActiveSession)
+    }
+}
+
+// Step back to the state from before the most recent user action (start/end day, new
+// lap, pause). Returns what was undone so the UI can say so.
+#[tauri::command]
+async fn undo_last_action(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let entry = state
+        .undo_stack
+        .lock()?
+        .pop()
+        .ok_or_else(|| TrackerError::InvalidInput("Nothing to undo".to_string()))?;
+
+    {
+        let mut session_guard = state.current_session.lock()?;
+        let mut records_guard = all_day_records(&state)?;
+        *records_guard = entry.state.day_records;
+        *session_guard = entry.state.current_session.as_ref().map(restore_session);
+    }
+
+    save_state(&app_handle, &state);
+    info!("↩️ Undid: {}", entry.description);
+    Ok(format!("Undid: {}", entry.description))
+}
+
+
+
+#[tauri::command]
+async fn check_screen_lock_state() -> Result<bool, TrackerError> {
+    // Use the same method as the monitoring function
+    Ok(check_screen_lock_state_sync()?)
+}
+
+#[tauri::command]
+async fn test_screen_lock_detection() -> Result<String, TrackerError> {
+    // Test all detection methods
+    let mut results = Vec::new();
+    
+    // Method 1: Display sleep check
+    let display_output = Command::new("sh")
+        .arg("-c")
+        .arg("pmset -g ps")
+        .output()?;
+    let display_str = String::from_utf8_lossy(&display_output.stdout);
+    results.push(format!("Power state: {}", display_str.trim()));
+    
+    // Method 2: Screen saver check
+    let screensaver_output = Command::new("sh")
+        .arg("-c")
+        .arg("ps aux | grep -E 'ScreenSaverEngine' | grep -v grep")
+        .output()?;
+    results.push(format!("Screen saver: {}", if screensaver_output.stdout.is_empty() { "Not running" } else { "Running" }));
+    
+    // Method 3: Login window check
+    let login_output = Command::new("sh")
+        .arg("-c")
+        .arg("ps aux | grep -E 'loginwindow' | grep -v grep | wc -l")
+        .output()?;
+    let login_count = String::from_utf8_lossy(&login_output.stdout).trim().parse::<i32>().unwrap_or(0);
+    results.push(format!("Login windows: {}", login_count));
+    
+    // Method 4: Current detection result
+    let current_result = check_screen_lock_state_sync();
+    results.push(format!("Detection result: {}", if current_result.unwrap_or(false) { "LOCKED" } else { "UNLOCKED" }));
+    
+    Ok(results.join("\n"))
+}
+
+// Debug builds only: drive the lock/sleep handlers without locking the screen or sleeping
+// the Mac. Each goes through the same *_direct handler the real event does, so state,
+// events and saves are identical. The monitor keeps seeing an unlocked screen and does
+// not undo a simulated lock; a simulated sleep skips the willSleep marker for the same
+// reason, since the monitor would otherwise resume on its next tick.
+#[cfg(debug_assertions)]
+fn simulated_outcome(state: &AppStateArc, event: &str) -> String {
+    match lock_or_recover(&state.current_session).as_ref() {
+        Some(session) if session.is_paused => format!("Simulated {}: paused", event),
+        Some(_) => format!("Simulated {}: tracking", event),
+        None => format!("Simulated {}: no session", event),
+    }
+}
+
+#[cfg(debug_assertions)]
+#[tauri::command]
+async fn simulate_screen_lock(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    handle_screen_lock_direct(&app_handle, &state);
+    Ok(simulated_outcome(&state, "screen lock"))
+}
+
+#[cfg(debug_assertions)]
+#[tauri::command]
+async fn simulate_screen_unlock(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    handle_screen_unlock_direct(&app_handle, &state);
+    Ok(simulated_outcome(&state, "screen unlock"))
+}
+
+#[cfg(debug_assertions)]
+#[tauri::command]
+async fn simulate_system_sleep(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    handle_system_suspend_direct(&app_handle, &state, now_unix());
+    Ok(simulated_outcome(&state, "system sleep"))
+}
+
+// Waking to an unlocked screen: the monitor resumes through the unlock handler.
+#[cfg(debug_assertions)]
+#[tauri::command]
+async fn simulate_system_wake(state: State<'_, AppStateArc>, app_handle: AppHandle) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    handle_screen_unlock_direct(&app_handle, &state);
+    Ok(simulated_outcome(&state, "system wake"))
+}
+
+
+#[tauri::command]
+async fn handle_system_sleep(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        
+        // Get actual lap start time from records
+        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
+            get_active_lap_start_time(day_record)
+                .unwrap_or(session.current_lap_start_timestamp)
+        } else {
+            session.current_lap_start_timestamp
+        };
+        
+        let lap_duration = finalize_lap_duration(lap_start_time);
+        
+        // End current lap
+        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+            if let Some(last_lap) = day_record.laps.last_mut() {
+                last_lap.end_time = Some(current_time);
+                last_lap.duration = Some(lap_duration);
+                last_lap.end_reason = Some(LapEndReason::SystemSlept);
+                emit_session_event(&app_handle, "lap-ended", &session.day_key, Some(last_lap.clone()), None);
+            }
+        }
+        
+        // Mark session as paused
+        session.pause(PauseCause::Sleep, now_unix());
+        emit_session_event(&app_handle, "session-paused", &session.day_key, None, Some("sleep"));
+        
+        Ok("System sleep detected - lap paused".to_string())
+    } else {
+        Ok("No active session".to_string())
+    }
+}
+
+#[tauri::command]
+async fn handle_system_wake(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    if auto_start_day_if_due(&app_handle, &state) {
+        save_state(&app_handle, &state);
+        return Ok("System wake detected - day started".to_string());
+    }
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        
+        // Start new lap
+        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+            day_record.laps.push(session.open_lap(current_time));
+            let opened = day_record.laps.last().cloned();
+            emit_session_event(&app_handle, "session-resumed", &session.day_key, opened, None);
+        }
+        
+        // Reset lap tracking
+        session.current_lap_start_timestamp = current_time;
+        session.accumulated_seconds = 0;
+        session.resume();
+        
+        Ok("System wake detected - new lap started".to_string())
+    } else {
+        Ok("No active session".to_string())
+    }
+}
+
+#[tauri::command]
+async fn handle_lid_close(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    Ok(if handle_lid_close_direct(&app_handle, &state) {
+        "Lid closed - lap paused".to_string()
+    } else {
+        "Lid closed - still tracking".to_string()
+    })
+}
+
+#[tauri::command]
+async fn handle_lid_open(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    Ok(if handle_lid_open_direct(&app_handle, &state) {
+        "Lid opened - new lap started".to_string()
+    } else {
+        "Lid opened - waiting for unlock".to_string()
+    })
+}
+
+#[tauri::command]
+async fn handle_user_logout(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        
+        // Get actual lap start time from records
+        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
+            get_active_lap_start_time(day_record)
+                .unwrap_or(session.current_lap_start_timestamp)
+        } else {
+            session.current_lap_start_timestamp
+        };
+        
+        let lap_duration = finalize_lap_duration(lap_start_time);
+        
+        // End current lap
+        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+            if let Some(last_lap) = day_record.laps.last_mut() {
+                last_lap.end_time = Some(current_time);
+                last_lap.duration = Some(lap_duration);
+                last_lap.end_reason = Some(LapEndReason::UserLogout);
+                emit_session_event(&app_handle, "lap-ended", &session.day_key, Some(last_lap.clone()), None);
+            }
+        }
+        
+        // Mark session as paused
+        session.pause(PauseCause::Logout, now_unix());
+        emit_session_event(&app_handle, "session-paused", &session.day_key, None, Some("logout"));
+        
+        Ok("User logout detected - lap paused".to_string())
+    } else {
+        Ok("No active session".to_string())
+    }
+}
+
+#[tauri::command]
+async fn handle_user_login(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    let mut session_guard = state.current_session.lock()?;
+    let mut records_guard = state.day_records.lock()?;
+    
+    if let Some(session) = session_guard.as_mut() {
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        
+        // Start new lap
+        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+            day_record.laps.push(session.open_lap(current_time));
+            let opened = day_record.laps.last().cloned();
+            emit_session_event(&app_handle, "session-resumed", &session.day_key, opened, None);
+        }
+        
+        // Reset lap tracking
+        session.current_lap_start_timestamp = current_time;
+        session.accumulated_seconds = 0;
+        session.resume();
+        
+        Ok("User login detected - new lap started".to_string())
+    } else {
+        Ok("No active session".to_string())
+    }
+}
+
+// If two consecutive iterations of the monitoring loop (at most 5s apart, see
+// MAX_ADAPTIVE_POLL_INTERVAL) are separated by more than this many seconds of wall-clock
+// time, the process was suspended in between (system sleep) rather than merely
+// scheduled late.
+const SUSPEND_GAP_THRESHOLD_SECS: u64 = 10;
+
+// How long a lock / unlock reading must persist before it counts, in seconds rather than
+// loop iterations so the poll interval doesn't change detection latency. Both are 0: the
+// first reading acts, as it always has.
+const LOCK_DEBOUNCE_SECS: u64 = 0;
+const UNLOCK_DEBOUNCE_SECS: u64 = 0;
+
+// Turns raw lock-state probes into lock/unlock transitions.
+#[derive(Debug, Default)]
+struct LockDebounce {
+    locked: bool,
+    pending_since: Option<u64>, // when the reading first differed from `locked`
+}
+
+impl LockDebounce {
+    // Some(new state) when a reading has held long enough to flip the state.
+    fn observe(&mut self, is_locked: bool, now: u64) -> Option<bool> {
+        if is_locked == self.locked {
+            self.pending_since = None;
+            return None;
+        }
+        let since = *self.pending_since.get_or_insert(now);
+        let hold = if is_locked { LOCK_DEBOUNCE_SECS } else { UNLOCK_DEBOUNCE_SECS };
+        if now.saturating_sub(since) < hold {
+            return None;
+        }
+        self.force(is_locked);
+        Some(is_locked)
+    }
+
+    fn force(&mut self, locked: bool) {
+        self.locked = locked;
+        self.pending_since = None;
+    }
+}
+
+// How often to re-check the power source; pmset is a subprocess.
+const POWER_CHECK_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct PowerStatus {
+    on_battery: bool,
+    battery_percent: Option<u8>, // None without a battery
+}
+
+// Parse `pmset -g ps`, e.g.
+//   Now drawing from 'Battery Power'
+//    -InternalBattery-0 (id=4653155)	76%; discharging; 3:12 remaining present: true
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_power_status(pmset: &str) -> PowerStatus {
+    let battery_percent = pmset
+        .lines()
+        .find(|line| line.contains("InternalBattery"))
+        .and_then(|line| {
+            let before_percent = &line[..line.find('%')?];
+            let digits = before_percent.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+            digits.parse().ok()
+        });
+    PowerStatus { on_battery: pmset.contains("'Battery Power'"), battery_percent }
+}
+
+#[cfg(target_os = "macos")]
+fn power_status() -> PowerStatus {
+    Command::new("pmset")
+        .args(["-g", "ps"])
+        .output()
+        .map(|out| parse_power_status(&String::from_utf8_lossy(&out.stdout)))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn power_status() -> PowerStatus {
+    PowerStatus::default()
+}
+
+// What the pause_on_battery_percent check should do: Some(true) to pause, Some(false)
+// to resume a session it paused earlier, None to leave it alone. It only pauses once
+// per unplugged stretch, so resuming by hand on a low battery sticks.
+fn battery_pause_action(power: PowerStatus, threshold: Option<u8>, battery_paused: bool) -> Option<bool> {
+    if battery_paused {
+        return (!power.on_battery).then_some(false);
+    }
+    let low = power.on_battery && threshold.zip(power.battery_percent).is_some_and(|(limit, percent)| percent < limit);
+    low.then_some(true)
+}
+
+// Wall-clock timestamp of the last NSWorkspace willSleep notification, 0 once
+// consumed. Lets the monitoring loop resync its lock-state machine even after a
+// sleep too short to trip the gap check above (otherwise a <10s sleep that wakes
+// to an unlocked screen would leave the session paused with nothing to resume it).
+static SLEEP_NOTIFIED_AT: AtomicU64 = AtomicU64::new(0);
+
+// How far wall-clock and monotonic time may disagree over one monitoring tick before
+// the wall clock counts as having been stepped. Both are read in whole seconds, so a
+// second or two of difference is rounding.
+const CLOCK_JUMP_TOLERANCE_SECS: i64 = 5;
+
+// Seconds the wall clock moved beyond (positive) or behind (negative) the monotonic
+// clock over one tick, when that is more than CLOCK_JUMP_TOLERANCE_SECS.
+fn clock_skew(wall_elapsed: i64, monotonic_elapsed: u64) -> Option<i64> {
+    let skew = wall_elapsed - monotonic_elapsed as i64;
+    (skew.abs() > CLOCK_JUMP_TOLERANCE_SECS).then_some(skew)
+}
+
+fn local_utc_offset_secs() -> i32 {
+    chrono::Local::now().offset().local_minus_utc()
+}
+
+// Register for NSWorkspace's willSleep notification so the open lap is closed at
+// the exact moment the machine goes to sleep. The polling thread can't do this:
+// it is frozen during sleep and only learns about it after wake (see the gap
+// detector in start_system_monitoring, which remains as a safety net).
+// No didWake observer is needed — on wake, the monitoring loop's gap detector and
+// lock-state check decide whether to resume immediately (woke unlocked) or wait
+// for the real unlock (woke locked). Resuming blindly on didWake would create a
+// phantom 1-2s lap whenever the Mac wakes to a locked screen.
+#[cfg(target_os = "macos")]
+fn register_sleep_observer(app_handle: AppHandle, state: AppStateArc) {
+    use block::ConcreteBlock;
+    use cocoa::foundation::NSString;
+
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let center: id = msg_send![workspace, notificationCenter];
+        let name = NSString::alloc(nil).init_str("NSWorkspaceWillSleepNotification");
+
+        let block = ConcreteBlock::new(move |_notification: id| {
+            info!("💤 NSWorkspaceWillSleepNotification - closing open lap before suspend");
+            let now = now_unix();
+            handle_system_suspend_direct(&app_handle, &state, now);
+            SLEEP_NOTIFIED_AT.store(now, Ordering::Relaxed);
+        });
+        // The notification center copies the block, but keep our copy alive too:
+        // the observer is registered once and never removed.
+        let block = block.copy();
+        let block_ptr = &*block as *const _ as *const std::ffi::c_void;
+        let _observer: id = msg_send![center, addObserverForName: name
+                                                          object: nil
+                                                           queue: nil
+                                                      usingBlock: block_ptr];
+        std::mem::forget(block);
+    }
+}
+
+// The display sleeping is not the machine sleeping: the polling thread keeps running,
+// so only the transition is observed here. Whether it pauses is up to
+// handle_display_sleep_direct.
+#[cfg(target_os = "macos")]
+fn register_display_sleep_observers(app_handle: AppHandle, state: AppStateArc) {
+    use block::ConcreteBlock;
+    use cocoa::foundation::NSString;
+
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let center: id = msg_send![workspace, notificationCenter];
+        for (name, woke) in [("NSWorkspaceScreensDidSleepNotification", false), ("NSWorkspaceScreensDidWakeNotification", true)] {
+            let name = NSString::alloc(nil).init_str(name);
+            let app_handle = app_handle.clone();
+            let state = state.clone();
+            let block = ConcreteBlock::new(move |_notification: id| {
+                if woke {
+                    handle_display_wake_direct(&app_handle, &state);
+                } else {
+                    info!("🖥️ Display slept");
+                    handle_display_sleep_direct(&app_handle, &state);
+                }
+            });
+            // Registered once and never removed; see register_sleep_observer.
+            let block = block.copy();
+            let block_ptr = &*block as *const _ as *const std::ffi::c_void;
+            let _observer: id = msg_send![center, addObserverForName: name
+                                                              object: nil
+                                                               queue: nil
+                                                          usingBlock: block_ptr];
+            std::mem::forget(block);
+        }
+    }
+}
+
+// kIOPMMessageClamshellStateChange, sent by IOPMrootDomain whenever the lid opens or
+// closes. Bit 0 of its argument (kClamshellStateBit) is set while the lid is closed;
+// bit 1 (kClamshellSleepBit) when closing it puts the Mac to sleep, i.e. it is not
+// running in clamshell mode on an external display.
+#[cfg(target_os = "macos")]
+const CLAMSHELL_STATE_CHANGE_MESSAGE: u32 = 0xE003_4100;
+const CLAMSHELL_CLOSED_BIT: usize = 1 << 0;
+const CLAMSHELL_SLEEP_BIT: usize = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LidEvent {
+    ClosedToSleep,
+    ClosedAwake, // clamshell mode: the user is still working on an external display
+    Opened,
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn lid_event(message_argument: usize) -> LidEvent {
+    if message_argument & CLAMSHELL_CLOSED_BIT == 0 {
+        LidEvent::Opened
+    } else if message_argument & CLAMSHELL_SLEEP_BIT != 0 {
+        LidEvent::ClosedToSleep
+    } else {
+        LidEvent::ClosedAwake
+    }
+}
+
+// Lid changes come from IOPMrootDomain's general-interest messages, not from NSWorkspace.
+// Only a close that sleeps the Mac pauses; register_sleep_observer sees that sleep too,
+// this just closes the lap as the lid shuts rather than when the sleep lands.
+#[cfg(target_os = "macos")]
+fn register_lid_observer(app_handle: AppHandle, state: AppStateArc) {
+    extern "C" fn on_root_domain_message(refcon: *mut std::ffi::c_void, _service: u32, message_type: u32, argument: *mut std::ffi::c_void) {
+        if message_type != CLAMSHELL_STATE_CHANGE_MESSAGE {
+            return;
+        }
+        let (app_handle, state) = unsafe { &*(refcon as *const (AppHandle, AppStateArc)) };
+        match lid_event(argument as usize) {
+            LidEvent::ClosedToSleep => {
+                handle_lid_close_direct(app_handle, state);
+            }
+            LidEvent::ClosedAwake => debug!("💻 Lid closed in clamshell mode - still tracking"),
+            LidEvent::Opened => {
+                handle_lid_open_direct(app_handle, state);
+            }
+        }
+    }
+
+    unsafe {
+        let root_domain = IOServiceGetMatchingService(0, IOServiceMatching(c"IOPMrootDomain".as_ptr()));
+        if root_domain == 0 {
+            warn!("💻 IOPMrootDomain not found - lid close will not be detected");
+            return;
+        }
+        let port = IONotificationPortCreate(0);
+        // Registered once and never removed, so the context is leaked on purpose.
+        let context = Box::into_raw(Box::new((app_handle, state))) as *mut std::ffi::c_void;
+        let mut notification = 0;
+        let result = IOServiceAddInterestNotification(
+            port,
+            root_domain,
+            c"IOGeneralInterest".as_ptr(),
+            on_root_domain_message,
+            context,
+            &mut notification,
+        );
+        if result != 0 {
+            warn!("💻 Could not watch the lid (IOKit error {})", result);
+            return;
+        }
+        CFRunLoopAddSource(CFRunLoopGetMain(), IONotificationPortGetRunLoopSource(port), kCFRunLoopCommonModes);
+    }
+}
+
+// Fast user switching: another user's session takes over the console while ours keeps
+// running in the background. Treated exactly like a lock — the lap closes, and switching
+// back only resumes if the user hadn't paused by hand before switching away.
+#[cfg(target_os = "macos")]
+fn register_fast_user_switch_observers(app_handle: AppHandle, state: AppStateArc) {
+    use block::ConcreteBlock;
+    use cocoa::foundation::NSString;
+
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let workspace_center: id = msg_send![workspace, notificationCenter];
+        let distributed_center: id = msg_send![class!(NSDistributedNotificationCenter), defaultCenter];
+
+        // The NSWorkspace pair is the documented API; the distributed com.apple.session
+        // names are also observed in case one side is missed. The handlers ignore a
+        // repeat (already paused / already active), so hearing both is harmless.
+        let observers = [
+            (distributed_center, "com.apple.session.became-inactive", false),
+            (distributed_center, "com.apple.session.became-active", true),
+            (workspace_center, "NSWorkspaceSessionDidResignActiveNotification", false),
+            (workspace_center, "NSWorkspaceSessionDidBecomeActiveNotification", true),
+        ];
+        for (center, name, switched_in) in observers {
+            let name = NSString::alloc(nil).init_str(name);
+            let app_handle = app_handle.clone();
+            let state = state.clone();
+            let block = ConcreteBlock::new(move |_notification: id| {
+                if switched_in {
+                    handle_fast_user_switch_in_direct(&app_handle, &state);
+                } else {
+                    handle_fast_user_switch_out_direct(&app_handle, &state);
+                }
+            });
+            // Registered once and never removed; see register_sleep_observer.
+            let block = block.copy();
+            let block_ptr = &*block as *const _ as *const std::ffi::c_void;
+            let _observer: id = msg_send![center, addObserverForName: name
+                                                              object: nil
+                                                               queue: nil
+                                                          usingBlock: block_ptr];
+            std::mem::forget(block);
+        }
+    }
+}
+
+// What the monitor loop asks of the OS. The loop only talks to the platform through
+// this, so tests can run it against a scripted one (see tests::MockPlatform).
+trait Platform: Send + Sync {
+    fn screen_locked(&self) -> Result<bool, String>;
+    fn frontmost_app(&self) -> Option<String>;
+    fn frontmost_window_title(&self) -> Option<String>;
+    fn power(&self) -> PowerStatus;
+    fn create_display_assertion(&self) -> Option<usize>;
+    fn release_display_assertion(&self, assertion_id: usize);
+}
+
+struct NativePlatform;
+
+impl Platform for NativePlatform {
+    fn screen_locked(&self) -> Result<bool, String> {
+        check_screen_lock_state_sync()
+    }
+    fn frontmost_app(&self) -> Option<String> {
+        frontmost_app_name()
+    }
+    fn frontmost_window_title(&self) -> Option<String> {
+        frontmost_window_title()
+    }
+    fn power(&self) -> PowerStatus {
+        power_status()
+    }
+    fn create_display_assertion(&self) -> Option<usize> {
+        create_display_assertion()
+    }
+    fn release_display_assertion(&self, assertion_id: usize) {
+        release_display_assertion(assertion_id)
+    }
+}
+
+// What the monitor loop saw on one tick that needs the app to act on it, in the order
+// it saw it. Everything else the loop does is plain bookkeeping on AppState.
+#[derive(Debug, Clone, PartialEq)]
+enum MonitorEvent {
+    SuspendGap { pre_gap_ts: u64 },
+    ClockJump { skew: i64, pre_jump_ts: u64, monotonic_elapsed: u64, now: u64 },
+    TimezoneChanged { previous_offset_secs: i32, offset_secs: i32 },
+    ScreenLocked,
+    ScreenUnlocked,
+    // Time for the continuous-work, goal, overtime and break-reminder checks.
+    Alerts,
+    AutoEndDay { was_running: bool },
+    PomodoroTick { since_last_tick: u64 },
+    BatteryLow,
+    PowerRestored,
+}
+
+// System monitoring functions
+fn start_system_monitoring(app_handle: AppHandle, state: AppStateArc) {
+    let react_state = state.clone();
+    spawn_monitor(state, Arc::new(NativePlatform), move |event| match event {
+        MonitorEvent::SuspendGap { pre_gap_ts } => handle_system_suspend_direct(&app_handle, &react_state, pre_gap_ts),
+        MonitorEvent::ClockJump { skew, pre_jump_ts, monotonic_elapsed, now } => {
+            handle_clock_jump_direct(&app_handle, &react_state, skew, pre_jump_ts, monotonic_elapsed, now)
+        }
+        MonitorEvent::TimezoneChanged { previous_offset_secs, offset_secs } => {
+            handle_timezone_change_direct(&app_handle, &react_state, previous_offset_secs, offset_secs)
+        }
+        MonitorEvent::ScreenLocked | MonitorEvent::BatteryLow => handle_screen_lock_direct(&app_handle, &react_state),
+        MonitorEvent::ScreenUnlocked | MonitorEvent::PowerRestored => handle_screen_unlock_direct(&app_handle, &react_state),
+        MonitorEvent::Alerts => {
+            check_continuous_work(&app_handle, &react_state);
+            check_daily_goal(&app_handle, &react_state);
+            check_overtime(&app_handle, &react_state);
+            check_break_reminder(&app_handle, &react_state);
+        }
+        MonitorEvent::AutoEndDay { was_running } => {
+            handle_screen_lock_direct(&app_handle, &react_state);
+            if let Err(e) = auto_end_day_direct(&app_handle, &react_state, was_running) {
+                error!("Failed to auto-end the day: {}", e);
+            }
+        }
+        MonitorEvent::PomodoroTick { since_last_tick } => check_pomodoro(&app_handle, &react_state, since_last_tick),
+    });
+}
+
+// The monitor loop, on a thread tracked by state.shutdown: polls `platform` every tick,
+// keeps the running lap's time and per-app credit, and hands what needs the app to
+// `react`.
+fn spawn_monitor(state: AppStateArc, platform: Arc<dyn Platform>, mut react: impl FnMut(MonitorEvent) + Send + 'static) {
+    let state_clone = state.clone();
+
+    state.shutdown.spawn("monitor", move || {
+        let mut lock_state = LockDebounce::default();
+        let mut last_iteration_ts = now_unix();
+        let mut on_battery = false;
+        let mut power_checked_at = 0;
+        let mut battery_paused = false; // this loop paused the session for low battery
+        let mut last_hour = None; // local hour of the previous tick, for auto_end_day_at_hour
+        let mut last_tick = Instant::now();
+        let mut utc_offset = local_utc_offset_secs();
+        let create_assertion = || platform.create_display_assertion();
+        let release_assertion = |id| platform.release_display_assertion(id);
+        loop {
+            // Suspend (system sleep) detection. Polling can never observe the sleep
+            // transition itself: macOS freezes this thread along with the process, so
+            // the first thing we see is the wake. What we CAN observe is the aftermath —
+            // wall-clock time jumping far beyond the 1s we slept between iterations.
+            // Treat a large jump as "the process was suspended" and retroactively close
+            // the open lap at the last pre-gap timestamp, so none of the asleep interval
+            // is counted as active time. The NSWorkspaceWillSleepNotification observer
+            // normally closes the lap first (at the exact sleep moment); this is the
+            // safety net for a missed notification.
+            let iteration_ts = now_unix();
+            state_clone.health.monitor_heartbeat.store(iteration_ts, Ordering::Relaxed);
+            let since_last_tick = iteration_ts.saturating_sub(last_iteration_ts);
+            let gap_threshold = lock_or_recover(&state_clone.current_session)
+                .as_ref()
+                .map_or(SUSPEND_GAP_THRESHOLD_SECS, |session| session.overrides.gap_threshold());
+            let gap_detected = since_last_tick > gap_threshold;
+            if gap_detected {
+                info!("💤 Suspend gap detected ({}s) - closing lap at pre-gap timestamp",
+                         iteration_ts - last_iteration_ts);
+                react(MonitorEvent::SuspendGap { pre_gap_ts: last_iteration_ts });
+            }
+
+            // Wall-clock steps (NTP, a manual change). Instant stops during sleep on
+            // macOS, so a forward jump is indistinguishable from a suspend and is left to
+            // the gap handling above; only a jump with no sleep involved is treated here.
+            let monotonic_elapsed = last_tick.elapsed().as_secs();
+            last_tick = Instant::now();
+            let wall_elapsed = iteration_ts as i64 - last_iteration_ts as i64;
+            let sleep_pending = SLEEP_NOTIFIED_AT.load(Ordering::Relaxed) != 0;
+            if let Some(skew) = clock_skew(wall_elapsed, monotonic_elapsed) {
+                if skew < 0 || (!gap_detected && !sleep_pending) {
+                    react(MonitorEvent::ClockJump { skew, pre_jump_ts: last_iteration_ts, monotonic_elapsed, now: iteration_ts });
+                }
+            }
+            last_iteration_ts = iteration_ts;
+
+            let offset = local_utc_offset_secs();
+            if offset != utc_offset {
+                react(MonitorEvent::TimezoneChanged { previous_offset_secs: utc_offset, offset_secs: offset });
+                utc_offset = offset;
+            }
+
+            // Sleeps shorter than the gap threshold don't produce a detectable gap, but
+            // the willSleep observer still closed the lap. Consume its marker once enough
+            // wall clock has passed that the suspend really happened, so short sleeps go
+            // through the same resync below.
+            let sleep_notified_ts = SLEEP_NOTIFIED_AT.load(Ordering::Relaxed);
+            let slept_via_notification =
+                sleep_notified_ts != 0 && iteration_ts >= sleep_notified_ts + 2;
+            if slept_via_notification {
+                SLEEP_NOTIFIED_AT.store(0, Ordering::Relaxed);
+            }
+
+            if gap_detected || slept_via_notification {
+                // Force the lock-state machine to "locked" so the normal unlock path
+                // below starts a fresh lap — whether the Mac woke to a locked screen
+                // (lap resumes at the real unlock) or an already-unlocked one (the
+                // very next check sees "unlocked" and resumes immediately).
+                lock_state.force(true);
+            }
+
+            // Check screen lock state
+            match platform.screen_locked() {
+                Ok(is_locked) => match lock_state.observe(is_locked, iteration_ts) {
+                    Some(true) => {
+                        info!("🔒 Screen lock detected!");
+                        react(MonitorEvent::ScreenLocked);
+                    }
+                    Some(false) => {
+                        info!("🔓 Screen unlock detected!");
+                        react(MonitorEvent::ScreenUnlocked);
+                    }
+                    None => {}
+                },
+                Err(e) => error!("Error checking screen lock state: {}", e),
+            }
+
+            react(MonitorEvent::Alerts);
+            let hour = chrono::Timelike::hour(&chrono::Local::now());
+            let end_at = lock_or_recover(&state_clone.config).auto_end_day_at_hour;
+            let running = lock_or_recover(&state_clone.current_session).as_ref().map(|s| !s.is_paused);
+            if auto_end_due(last_hour, hour, end_at) && running.is_some() {
+                info!("🌙 Reached {}:00 with the day still running - ending it", hour);
+                react(MonitorEvent::AutoEndDay { was_running: running == Some(true) });
+            }
+            last_hour = Some(hour);
+            if !gap_detected {
+                react(MonitorEvent::PomodoroTick { since_last_tick });
+            }
+            if let Some(session) = lock_or_recover(&state_clone.current_session).as_mut() {
+                advance_active_time(session, since_last_tick, gap_detected);
+            }
+
+            // Credit the time since the last tick to whichever app is in front. Never
+            // across a suspend gap (the machine was asleep), and never while paused
+            // (credit_app_time only writes to a running lap).
+            if !gap_detected {
+                if let Some(app) = platform.frontmost_app() {
+                    credit_frontmost_app(&state_clone, &app, since_last_tick);
+                }
+                let capture_titles = lock_or_recover(&state_clone.config).capture_window_titles;
+                if capture_titles {
+                    if let Some(title) = platform.frontmost_window_title() {
+                        record_frontmost_window_title(&state_clone, &title);
+                    }
+                }
+            }
+
+            if iteration_ts.saturating_sub(power_checked_at) >= POWER_CHECK_INTERVAL_SECS {
+                let power = platform.power();
+                on_battery = power.on_battery;
+                power_checked_at = iteration_ts;
+                let threshold = lock_or_recover(&state_clone.config).pause_on_battery_percent;
+                match battery_pause_action(power, threshold, battery_paused) {
+                    Some(true) => {
+                        let tracking = lock_or_recover(&state_clone.current_session)
+                            .as_ref()
+                            .is_some_and(|session| !session.is_paused);
+                        if tracking {
+                            info!("🪫 Battery at {:?}% on battery power - pausing", power.battery_percent);
+                            react(MonitorEvent::BatteryLow);
+                            battery_paused = true;
+                        }
+                    }
+                    Some(false) => {
+                        info!("🔌 Power connected - resuming after the low-battery pause");
+                        battery_paused = false;
+                        react(MonitorEvent::PowerRestored);
+                    }
+                    None => {}
+                }
+            }
+            let tracking = lock_or_recover(&state_clone.current_session)
+                .as_ref()
+                .is_some_and(|session| !session.is_paused);
+            let keep_awake = tracking && lock_or_recover(&state_clone.config).keep_display_awake;
+            sync_display_assertion(&mut lock_or_recover(&state_clone.display_assertion), keep_awake, create_assertion, release_assertion);
+            // Re-read every tick so set_monitoring_poll_interval applies without a restart.
+            let poll_interval = lock_or_recover(&state_clone.config).effective_poll_interval(tracking, on_battery);
+            if !state_clone.shutdown.sleep(poll_interval) {
+                info!("🛑 Monitoring loop stopped");
+                break;
+            }
+        }
+        sync_display_assertion(&mut lock_or_recover(&state_clone.display_assertion), false, create_assertion, release_assertion);
+    });
+}
+
+// Take or drop the keep-display-awake assertion so it is held exactly while `wanted`.
+// Every create is paired with one release; a failed create leaves nothing held and is
+// retried on the next call. The monitor takes it each tick; pausing drops it at once
+// (release_display_assertion_now) rather than on the next tick.
+fn sync_display_assertion(
+    held: &mut Option<usize>,
+    wanted: bool,
+    create: impl FnOnce() -> Option<usize>,
+    release: impl FnOnce(usize),
+) {
+    match (held.take(), wanted) {
+        (None, true) => *held = create(),
+        (Some(id), false) => release(id),
+        (current, _) => *held = current,
+    }
+}
+
+// Let the display sleep as soon as tracking pauses. Safe with any lock held.
+fn release_display_assertion_now(state: &AppStateArc) {
+    sync_display_assertion(&mut lock_or_recover(&state.display_assertion), false, || None, release_display_assertion);
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOPMAssertionCreateWithName(assertion_type: id, level: u32, name: id, assertion_id: *mut u32) -> i32;
+    fn IOPMAssertionRelease(assertion_id: u32) -> i32;
+    fn IONotificationPortCreate(main_port: u32) -> *mut std::ffi::c_void;
+    fn IONotificationPortGetRunLoopSource(port: *mut std::ffi::c_void) -> *mut std::ffi::c_void;
+    fn IOServiceMatching(name: *const std::ffi::c_char) -> *mut std::ffi::c_void;
+    fn IOServiceGetMatchingService(main_port: u32, matching: *mut std::ffi::c_void) -> u32;
+    fn IOServiceAddInterestNotification(
+        port: *mut std::ffi::c_void,
+        service: u32,
+        interest_type: *const std::ffi::c_char,
+        callback: extern "C" fn(*mut std::ffi::c_void, u32, u32, *mut std::ffi::c_void),
+        refcon: *mut std::ffi::c_void,
+        notification: *mut u32,
+    ) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRunLoopGetMain() -> *mut std::ffi::c_void;
+    fn CFRunLoopAddSource(run_loop: *mut std::ffi::c_void, source: *mut std::ffi::c_void, mode: *const std::ffi::c_void);
+    static kCFRunLoopCommonModes: *const std::ffi::c_void;
+}
+
+// A PreventUserIdleDisplaySleep assertion, named so `pmset -g assertions` shows who holds it.
+#[cfg(target_os = "macos")]
+fn create_display_assertion() -> Option<usize> {
+    use cocoa::foundation::NSString;
+    const K_IOPM_ASSERTION_LEVEL_ON: u32 = 255;
+    let mut assertion_id = 0;
+    let result = unsafe {
+        let assertion_type = NSString::alloc(nil).init_str("PreventUserIdleDisplaySleep");
+        let name = NSString::alloc(nil).init_str("Screen Time Tracker: tracking");
+        let result = IOPMAssertionCreateWithName(assertion_type, K_IOPM_ASSERTION_LEVEL_ON, name, &mut assertion_id);
+        let _: () = msg_send![assertion_type, release];
+        let _: () = msg_send![name, release];
+        result
+    };
+    if result != 0 {
+        warn!("Could not keep the display awake (IOKit error {})", result);
+        return None;
+    }
+    info!("☀️ Keeping the display awake while tracking");
+    Some(assertion_id as usize)
+}
+
+#[cfg(target_os = "macos")]
+fn release_display_assertion(assertion_id: usize) {
+    unsafe { IOPMAssertionRelease(assertion_id as u32) };
+    info!("🌙 Display may sleep again");
+}
+
+// A display-required power request. Unlike SetThreadExecutionState it is not tied to
+// the thread that made it, so any thread can clear it.
+#[cfg(target_os = "windows")]
+fn create_display_assertion() -> Option<usize> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Power::{PowerCreateRequest, PowerRequestDisplayRequired, PowerSetRequest};
+    use windows_sys::Win32::System::Threading::{POWER_REQUEST_CONTEXT_SIMPLE_STRING, REASON_CONTEXT, REASON_CONTEXT_0};
+    let mut reason: Vec<u16> = "Screen Time Tracker: tracking".encode_utf16().chain(std::iter::once(0)).collect();
+    let context = REASON_CONTEXT {
+        Version: 0, // POWER_REQUEST_CONTEXT_VERSION
+        Flags: POWER_REQUEST_CONTEXT_SIMPLE_STRING,
+        Reason: REASON_CONTEXT_0 { SimpleReasonString: reason.as_mut_ptr() },
+    };
+    let request = unsafe { PowerCreateRequest(&context) };
+    if request.is_null() || request as isize == -1 {
+        warn!("Could not keep the display awake");
+        return None;
+    }
+    if unsafe { PowerSetRequest(request, PowerRequestDisplayRequired) } == 0 {
+        unsafe { CloseHandle(request) };
+        warn!("Could not keep the display awake");
+        return None;
+    }
+    info!("☀️ Keeping the display awake while tracking");
+    Some(request as usize)
+}
+
+#[cfg(target_os = "windows")]
+fn release_display_assertion(request: usize) {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Power::{PowerClearRequest, PowerRequestDisplayRequired};
+    let request = request as windows_sys::Win32::Foundation::HANDLE;
+    unsafe {
+        PowerClearRequest(request, PowerRequestDisplayRequired);
+        CloseHandle(request);
+    }
+    info!("🌙 Display may sleep again");
+}
+
+// Linux has no desktop-independent equivalent; the setting does nothing there.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn create_display_assertion() -> Option<usize> {
+    None
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn release_display_assertion(_: usize) {}
+
+// The monitor tick is the only writer of accumulated_seconds, so the running total
+// never depends on how often anything reads the status. Time across a suspend gap is
+// never credited: by the time this runs, the gap handler has already closed the lap.
+fn advance_active_time(session: &mut CurrentSession, since_last_tick: u64, gap_detected: bool) {
+    if session.is_paused || gap_detected {
+        return;
+    }
+    session.accumulated_seconds += since_last_tick;
+}
+
+// Whether a stretch of `elapsed` seconds has earned the break alert. Fires once per
+// stretch: `already_alerted` is only cleared when the stretch ends.
+fn continuous_work_alert_due(elapsed: u64, limit: Option<u64>, already_alerted: bool) -> bool {
+    match limit {
+        Some(limit) => !already_alerted && elapsed >= limit,
+        None => false,
+    }
+}
+
+// A loop counts as dead once it misses this many seconds of beats. Generous enough for
+// the slowest poll interval (5s) and the 30s autosave period, plus scheduling slack.
+const MONITOR_STALE_AFTER_SECS: u64 = 30;
+const AUTOSAVE_STALE_AFTER_SECS: u64 = 90;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub monitoring_alive: bool,
+    pub autosave_alive: bool,
+    pub poisoned_lock_recoveries: u64,
+}
+
+// A loop that has not beaten yet (heartbeat 0) is still starting up, not dead. A beat
+// from the future (clock moved back) is treated as fresh.
+fn heartbeat_alive(heartbeat: u64, now: u64, stale_after: u64, started_at: u64) -> bool {
+    let since = if heartbeat == 0 { started_at } else { heartbeat };
+    now.saturating_sub(since) <= stale_after
+}
+
+// Seconds since state was last saved, counted from launch before the first save.
+fn save_age(last_saved_at: u64, now: u64, started_at: u64) -> u64 {
+    let since = if last_saved_at == 0 { started_at } else { last_saved_at };
+    now.saturating_sub(since)
+}
+
+fn seconds_since_last_save(state: &AppStateArc) -> u64 {
+    save_age(
+        state.persistence.last_saved_at.load(Ordering::Relaxed),
+        now_unix(),
+        APP_STARTED_AT.load(Ordering::Relaxed),
+    )
+}
+
+// Whether the background loops are still running, so the UI can warn that tracking has
+// stopped even though the window still responds.
+#[tauri::command]
+async fn get_health(state: State<'_, AppStateArc>) -> Result<HealthReport, TrackerError> {
+    let now = now_unix();
+    let started_at = APP_STARTED_AT.load(Ordering::Relaxed);
+    Ok(HealthReport {
+        monitoring_alive: heartbeat_alive(
+            state.health.monitor_heartbeat.load(Ordering::Relaxed),
+            now,
+            MONITOR_STALE_AFTER_SECS,
+            started_at,
+        ),
+        autosave_alive: heartbeat_alive(
+            state.health.autosave_heartbeat.load(Ordering::Relaxed),
+            now,
+            AUTOSAVE_STALE_AFTER_SECS,
+            started_at,
+        ),
+        poisoned_lock_recoveries: POISON_RECOVERIES.load(Ordering::Relaxed),
+    })
+}
+
+// Where the session is, for SessionContext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    Active,
+    UserPaused,
+    SystemPaused, // lock, sleep, logout or a restart
+}
+
+// A snapshot of the session to paste into a bug report.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionContext {
+    pub session_state: Option<SessionState>, // None before start_day
+    pub day_key: Option<String>,
+    pub accumulated_seconds: Option<u64>,
+    pub current_lap_start_timestamp: Option<u64>,
+    pub paused_cause: Option<PauseCause>,
+    pub total_laps_today: usize,
+    pub total_completed_laps_today: usize,
+    pub day_total_duration: u64,
+    pub is_past_midnight_rollover_risk: bool,
+    pub monitoring_threads_running: bool,
+}
+
+// How close to local midnight counts as a rollover risk in SessionContext.
+const MIDNIGHT_RISK_WINDOW_SECS: u32 = 10 * 60;
+
+// Whether `seconds_into_day` (local) is within MIDNIGHT_RISK_WINDOW_SECS of midnight,
+// on either side of it.
+fn near_midnight(seconds_into_day: u32) -> bool {
+    seconds_into_day.min(86_400u32.saturating_sub(seconds_into_day)) <= MIDNIGHT_RISK_WINDOW_SECS
+}
+
+// Lap counts and totals are for the session's day, or today without a session.
+fn session_context(
+    session: Option<&CurrentSession>,
+    records: &HashMap<String, DayRecord>,
+    today: &str,
+    seconds_into_day: u32,
+    threads_running: bool,
+) -> SessionContext {
+    let day = session.map_or(today, |s| s.day_key.as_str());
+    let laps = records.get(day).map(|r| r.laps.as_slice()).unwrap_or_default();
+    SessionContext {
+        session_state: session.map(|s| match (s.is_paused, s.user_paused) {
+            (false, _) => SessionState::Active,
+            (true, true) => SessionState::UserPaused,
+            (true, false) => SessionState::SystemPaused,
+        }),
+        day_key: session.map(|s| s.day_key.clone()),
+        accumulated_seconds: session.map(|s| s.accumulated_seconds),
+        current_lap_start_timestamp: session.map(|s| s.current_lap_start_timestamp),
+        paused_cause: session.and_then(|s| s.paused_cause),
+        total_laps_today: laps.len(),
+        total_completed_laps_today: laps.iter().filter(|lap| lap.duration.is_some()).count(),
+        day_total_duration: laps.iter().filter_map(Lap::worked_duration).sum(),
+        is_past_midnight_rollover_risk: near_midnight(seconds_into_day),
+        monitoring_threads_running: threads_running,
+    }
+}
+
+#[tauri::command]
+async fn get_current_session_context(state: State<'_, AppStateArc>) -> Result<SessionContext, TrackerError> {
+    use chrono::Timelike;
+    let now = now_unix();
+    let started_at = APP_STARTED_AT.load(Ordering::Relaxed);
+    let threads_running = heartbeat_alive(state.health.monitor_heartbeat.load(Ordering::Relaxed), now, MONITOR_STALE_AFTER_SECS, started_at)
+        && heartbeat_alive(state.health.autosave_heartbeat.load(Ordering::Relaxed), now, AUTOSAVE_STALE_AFTER_SECS, started_at);
+    let seconds_into_day = chrono::Local::now().num_seconds_from_midnight();
+    let session_guard = state.current_session.lock()?;
+    let records_guard = state.day_records.lock()?;
+    Ok(session_context(session_guard.as_ref(), &records_guard, &today_local_key(), seconds_into_day, threads_running))
+}
+
+// Track the current stretch of unbroken work and send the break alert when it crosses
+// the configured limit. Runs every monitoring tick; a pause seen on any tick resets it.
+fn check_continuous_work(app_handle: &AppHandle, state: &AppStateArc) {
+    let config = lock_or_recover(&state.config).clone();
+
+    let alert_after = {
+        let mut session_guard = lock_or_recover(&state.current_session);
+        let Some(session) = session_guard.as_mut() else { return };
+        let limit = session.overrides.max_continuous_work(&config);
+        if session.is_paused {
+            session.continuous_work_start = None;
+            session.continuous_work_alerted = false;
+            return;
+        }
+        let now = now_unix();
+        let elapsed = now.saturating_sub(*session.continuous_work_start.get_or_insert(now));
+        if !continuous_work_alert_due(elapsed, limit, session.continuous_work_alerted) {
+            return;
+        }
+        session.continuous_work_alerted = true;
+        elapsed
+    };
+
+    info!("⏰ {}s of continuous work - suggesting a break", alert_after);
+    let body = format!(
+        "You've been working for {}h {}m without a break.",
+        alert_after / 3600,
+        (alert_after % 3600) / 60
+    );
+    send_alert(app_handle, state, "Time for a break", &body);
+}
+
+// How many break reminders a lap running for `lap_elapsed` seconds has earned in total,
+// when the next one is owed (`None` if reminders are off or none is owed). Catching up
+// after the app was closed mid-lap sends one reminder, not one per missed interval.
+fn break_reminders_due(lap_elapsed: u64, interval_secs: u64, sent: u64) -> Option<u64> {
+    if interval_secs == 0 {
+        return None;
+    }
+    let earned = lap_elapsed / interval_secs;
+    (earned > sent).then_some(earned)
+}
+
+// Remind the user to take a break every break_reminder_minutes of the running lap. A
+// lock, sleep or stop ends the lap, and the next lap starts counting from zero.
+fn check_break_reminder(app_handle: &AppHandle, state: &AppStateArc) {
+    let interval_secs = lock_or_recover(&state.config).break_reminder_minutes.saturating_mul(60);
+
+    let lap_elapsed = {
+        let mut session_guard = lock_or_recover(&state.current_session);
+        let Some(session) = session_guard.as_mut() else { return };
+        if session.is_paused {
+            return;
+        }
+        let lap_elapsed = session.compute_accurate_elapsed();
+        let Some(earned) = break_reminders_due(lap_elapsed, interval_secs, session.break_reminders_sent) else {
+            return;
+        };
+        session.break_reminders_sent = earned;
+        lap_elapsed
+    };
+
+    info!("☕ Break reminder after {}s in the current lap", lap_elapsed);
+    let body = format!(
+        "This lap has been running for {}h {:02}m. Stand up and stretch?",
+        lap_elapsed / 3600,
+        (lap_elapsed % 3600) / 60
+    );
+    send_alert(app_handle, state, "Break reminder", &body);
+}
+
+// Label the running lap with the pomodoro interval: its tag, and for breaks a note (unless
+// the user already wrote one). Laps opened by the lock/unlock handlers know nothing about
+// pomodoros, so this also labels the lap resumed after an unlock.
+fn label_pomodoro_lap(session: &CurrentSession, records: &mut HashMap<String, DayRecord>, pomodoro: &PomodoroState) {
+    let Some(lap) = records.get_mut(&session.day_key).and_then(|r| r.laps.last_mut()) else { return };
+    if lap.duration.is_some() {
+        return;
+    }
+    if !lap.tags.iter().any(|t| t == pomodoro.tag()) {
+        let _ = add_tag(lap, pomodoro.tag());
+    }
+    if pomodoro.is_break() && lap.note.is_none() {
+        lap.note = Some(POMODORO_BREAK_NOTE.to_string());
+    }
+}
+
+// Drive the pomodoro from the monitoring loop. `elapsed` is the time since the last tick;
+// it only counts while a lap is running. Each interval change is an add_lap.
+fn check_pomodoro(app_handle: &AppHandle, state: &AppStateArc, elapsed: u64) {
+    let mut pomodoro_guard = lock_or_recover(&state.pomodoro);
+    let Some(pomodoro) = pomodoro_guard.as_mut() else { return };
+
+    let mut session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+    let Some(session) = session_guard.as_mut() else {
+        // The day ended under us; nothing left to drive.
+        *pomodoro_guard = None;
+        return;
+    };
+    if session.is_paused {
+        return;
+    }
+    if !pomodoro.advance(elapsed) {
+        label_pomodoro_lap(session, &mut records_guard, pomodoro);
+        return;
+    }
+
+    roll_to_next_lap(session, &mut records_guard, now_unix());
+    label_pomodoro_lap(session, &mut records_guard, pomodoro);
+    let changed = pomodoro.clone();
+    drop(records_guard);
+    drop(session_guard);
+    drop(pomodoro_guard);
+
+    info!("🍅 Pomodoro interval changed to {:?}", changed.phase);
+    save_state(app_handle, state);
+    let _ = app_handle.emit("pomodoro-interval-changed", &changed);
+    let minutes = changed.phase_remaining_seconds / 60;
+    let (title, body) = match changed.phase {
+        PomodoroPhase::Work => ("Back to work", format!("Break's over. Next focus block: {} minutes.", minutes)),
+        PomodoroPhase::ShortBreak => ("Pomodoro break", format!("Nice work. Take {} minutes off.", minutes)),
+        PomodoroPhase::LongBreak => ("Long break", format!("{} pomodoros done. Take {} minutes off.", changed.completed_work_intervals, minutes)),
+    };
+    send_alert(app_handle, state, title, &body);
+}
+
+// The session day's completed laps plus the running lap up to `now`.
+fn worked_today(session: &CurrentSession, records: &HashMap<String, DayRecord>, now: u64) -> u64 {
+    let completed: u64 = records
+        .get(&session.day_key)
+        .map(|r| r.laps.iter().filter_map(Lap::worked_duration).sum())
+        .unwrap_or(0);
+    let running = if session.is_paused {
+        0
+    } else {
+        now.saturating_sub(session.current_lap_start_timestamp)
+    };
+    completed + running
+}
+
+// Warn once per day when the running total passes the overtime cap. Like the goal
+// notification, but only while a lap is running: the total can't grow while paused, and
+// a cap lowered mid-pause shouldn't fire until work actually resumes.
+fn check_overtime(app_handle: &AppHandle, state: &AppStateArc) {
+    let (day_key, worked, is_active) = {
+        let session_guard = lock_or_recover(&state.current_session);
+        let records_guard = lock_or_recover(&state.day_records);
+        let Some(session) = session_guard.as_ref() else { return };
+        (session.day_key.clone(), worked_today(session, &records_guard, now_unix()), !session.is_paused)
+    };
+
+    {
+        let mut config = lock_or_recover(&state.config);
+        if !overtime_alert_due(&config, &day_key, worked, is_active) {
+            return;
+        }
+        config.overtime_notified_day = Some(day_key);
+    }
+
+    info!("🚨 Overtime: {}s tracked today", worked);
+    save_state(app_handle, state);
+    let body = format!(
+        "You've tracked {}h {:02}m today. Time to wrap up?",
+        worked / 3600,
+        (worked % 3600) / 60
+    );
+    send_alert(app_handle, state, "Overtime", &body);
+}
+
+// Notify once per day when the running total reaches the daily goal. Runs every
+// monitoring tick, so it fires even with no window open.
+fn check_daily_goal(app_handle: &AppHandle, state: &AppStateArc) {
+    let (day_key, worked, overrides) = {
+        let session_guard = lock_or_recover(&state.current_session);
+        let records_guard = lock_or_recover(&state.day_records);
+        let Some(session) = session_guard.as_ref() else { return };
+        (session.day_key.clone(), worked_today(session, &records_guard, now_unix()), session.overrides.clone())
+    };
+
+    let goal = {
+        let mut config = lock_or_recover(&state.config);
+        let goal = overrides.daily_goal(&config);
+        if !goal_notification_due(&config, goal, &day_key, worked) {
+            return;
+        }
+        config.goal_notified_day = Some(day_key);
+        goal.unwrap_or(0)
+    };
+
+    info!("🎉 Daily goal of {}s reached", goal);
+    save_state(app_handle, state);
+    let body = format!("You've hit {}h {:02}m today 🎉", goal / 3600, (goal % 3600) / 60);
+    send_alert(app_handle, state, "Daily goal reached", &body);
+}
+
+// Payload of the discrete session events (session-started, lap-ended, session-paused,
+// session-resumed, day-ended), so the UI can react without polling.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub day_key: String,
+    pub timestamp: u64,
+    // The lap that just ended (lap-ended) or just opened (session-started / -resumed).
+    pub lap: Option<Lap>,
+    // What paused the session (session-paused only): user, lock, sleep or logout.
+    pub cause: Option<String>,
+}
+
+// Safe to call with the session/records locks held: emitting only serialises the payload
+// and hands it to the webviews.
+fn emit_session_event(app_handle: &AppHandle, event: &str, day_key: &str, lap: Option<Lap>, cause: Option<&str>) {
+    let payload = SessionEvent {
+        day_key: day_key.to_string(),
+        timestamp: now_unix(),
+        lap,
+        cause: cause.map(str::to_string),
+    };
+    if let Some(state) = app_handle.try_state::<AppStateArc>() {
+        lock_or_recover(&state.events).record(&payload, event);
+        append_journal(app_handle, &state, event, &payload);
+    }
+    let _ = app_handle.emit(event, payload);
+}
+
+// One line of journal.jsonl: a session event as emitted, numbered so replay can tell
+// which ones the snapshot in state.json already reflects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    seq: u64,
+    kind: String,
+    #[serde(flatten)]
+    event: SessionEvent,
+}
+
+// Lap starts, lap ends, pauses and day ends are appended (and synced) as they happen, so
+// a crash loses at most the line being written instead of everything since the last
+// 30s autosave. Edits made through commands save straight away and are not journaled.
+fn append_journal(app_handle: &AppHandle, state: &AppStateArc, kind: &str, event: &SessionEvent) {
+    use std::io::Write;
+    let Ok(path) = get_journal_file_path(app_handle) else { return };
+    let _journal = lock_or_recover(&state.persistence.journal_lock);
+    let entry = JournalEntry {
+        seq: state.persistence.journal_seq.load(Ordering::Relaxed) + 1,
+        kind: kind.to_string(),
+        event: event.clone(),
+    };
+    let Ok(line) = journal_line(&entry) else { return };
+    let appended = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line).and_then(|_| file.sync_data()));
+    match appended {
+        Ok(()) => state.persistence.journal_seq.store(entry.seq, Ordering::Relaxed),
+        Err(e) => warn!("⚠️ Could not append to the journal: {}", e),
+    }
+}
+
+// Drop the entries a saved snapshot already includes.
+fn compact_journal(app_handle: &AppHandle, state: &AppStateArc, snapshot_seq: u64) -> Result<(), TrackerError> {
+    let path = get_journal_file_path(app_handle)?;
+    let _journal = lock_or_recover(&state.persistence.journal_lock);
+    let Ok(contents) = fs::read_to_string(&path) else { return Ok(()) };
+    let (entries, valid_len) = parse_journal(&contents);
+    let kept: Vec<&JournalEntry> = entries.iter().filter(|entry| entry.seq > snapshot_seq).collect();
+    if kept.len() == entries.len() && valid_len == contents.len() {
+        return Ok(());
+    }
+    let mut text = String::new();
+    for entry in kept {
+        text.push_str(&journal_line(entry)?);
+        text.push('\n');
+    }
+    write_atomically(&path, &text)?;
+    Ok(())
+}
+
+// An entry as one line of journal.jsonl, sealed on its own while encryption is on.
+fn journal_line(entry: &JournalEntry) -> Result<String, TrackerError> {
+    Ok(encryption::seal_text(serde_json::to_string(entry)?)?)
+}
+
+// The entries up to the first line that is incomplete or won't parse, and the byte
+// length of that valid prefix. A torn final write is expected after a crash.
+fn parse_journal(contents: &str) -> (Vec<JournalEntry>, usize) {
+    let mut entries = Vec::new();
+    let mut valid_len = 0;
+    for line in contents.split_inclusive('\n') {
+        if !line.ends_with('\n') {
+            break;
+        }
+        let entry = encryption::open_line(line.trim_end())
+            .ok()
+            .and_then(|text| serde_json::from_str::<JournalEntry>(&text).ok());
+        match entry {
+            Some(entry) => entries.push(entry),
+            None => break,
+        }
+        valid_len += line.len();
+    }
+    (entries, valid_len)
+}
+
+// Re-apply the journal entries newer than the snapshot. Returns how many were applied.
+fn replay_journal(persisted: &mut PersistedState, entries: &[JournalEntry]) -> usize {
+    let mut applied = 0;
+    let snapshot_seq = persisted.journal_seq;
+    for entry in entries.iter().filter(|entry| entry.seq > snapshot_seq) {
+        let day = &entry.event.day_key;
+        let timestamp = entry.event.timestamp;
+        match entry.kind.as_str() {
+            "session-started" | "session-resumed" => {
+                let record = persisted.day_records.entry(day.clone()).or_insert_with(|| DayRecord {
+                    date: day.clone(),
+                    total_duration: 0,
+                    laps: Vec::new(),
+                    is_active: true,
+                    metadata: HashMap::new(),
+                    ended_explicitly: false,
+                });
+                record.is_active = true;
+                if let Some(lap) = &entry.event.lap {
+                    if !record.laps.iter().any(|l| l.start_time == lap.start_time) {
+                        record.laps.push(lap.clone());
+                    }
+                }
+                let session = persisted.current_session.get_or_insert_with(|| PersistedSessionState {
+                    day_key: day.clone(),
+                    current_lap_start_timestamp: timestamp,
+                    accumulated_seconds: 0,
+                    is_paused: false,
+                    user_paused: false,
+                    project: None,
+                    paused_cause: None,
+                    paused_since: None,
+                    overrides: DaySessionOverrides::default(),
+                    template: None,
+                });
+                session.day_key = day.clone();
+                session.is_paused = false;
+                session.user_paused = false;
+                session.paused_cause = None;
+                session.paused_since = None;
+                session.accumulated_seconds = 0;
+                if let Some(lap) = &entry.event.lap {
+                    session.current_lap_start_timestamp = lap.start_time;
+                    session.project = lap.project.clone();
+                }
+            }
+            "lap-ended" => {
+                if let (Some(lap), Some(record)) = (&entry.event.lap, persisted.day_records.get_mut(day)) {
+                    match record.laps.iter_mut().find(|l| l.start_time == lap.start_time) {
+                        Some(existing) => *existing = lap.clone(),
+                        None => record.laps.push(lap.clone()),
+                    }
+                }
+            }
+            "session-paused" => {
+                // A lap still open here was discarded rather than ended (a lap-ended
+                // entry would have closed it), as stop_lap does with very short laps.
+                if let Some(record) = persisted.day_records.get_mut(day) {
+                    record.laps.retain(|lap| lap.end_time.is_some());
+                }
+                if let Some(session) = persisted.current_session.as_mut() {
+                    let cause = entry
+                        .event
+                        .cause
+                        .as_deref()
+                        .and_then(|cause| serde_json::from_value(serde_json::Value::from(cause)).ok());
+                    session.is_paused = true;
+                    session.user_paused = cause == Some(PauseCause::User);
+                    session.paused_cause = cause;
+                    session.paused_since = Some(timestamp);
+                }
+            }
+            "day-ended" => {
+                if let Some(record) = persisted.day_records.get_mut(day) {
+                    record.is_active = false;
+                    record.total_duration = record.laps.iter().filter_map(Lap::worked_duration).sum();
+                }
+                persisted.current_session = None;
+            }
+            _ => {}
+        }
+        persisted.journal_seq = entry.seq;
+        persisted.last_heartbeat = persisted.last_heartbeat.max(timestamp);
+        applied += 1;
+    }
+    applied
+}
+
+// Bound on the in-memory feed behind get_recent_events. The per-day history is kept in
+// full and persisted.
+const RECENT_EVENTS_CAPACITY: usize = 500;
+
+// One line of the activity feed: "paused 12:03 (lock)", "resumed 12:21 after 18m".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackerEvent {
+    pub timestamp: u64,
+    pub day_key: String,
+    pub kind: String, // the session event name, e.g. session-paused
+    #[serde(default)]
+    pub cause: Option<String>,
+    // lap-ended: the lap's worked seconds. session-resumed: how long the pause lasted.
+    #[serde(default)]
+    pub duration: Option<u64>,
+}
+
+// Audit trail of session events. Always taken last: other locks may be held, but no other
+// lock is ever taken while holding this one.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    recent: std::collections::VecDeque<TrackerEvent>,
+    by_day: HashMap<String, Vec<TrackerEvent>>,
+}
+
+impl EventLog {
+    fn from_days(by_day: HashMap<String, Vec<TrackerEvent>>) -> Self {
+        let mut all: Vec<&TrackerEvent> = by_day.values().flatten().collect();
+        all.sort_by_key(|e| e.timestamp);
+        let skip = all.len().saturating_sub(RECENT_EVENTS_CAPACITY);
+        let recent = all.into_iter().skip(skip).cloned().collect();
+        Self { recent, by_day }
+    }
+
+    fn record(&mut self, payload: &SessionEvent, kind: &str) {
+        let duration = match kind {
+            "lap-ended" => payload.lap.as_ref().and_then(Lap::worked_duration),
+            "session-resumed" => self
+                .recent
+                .iter()
+                .rev()
+                .find(|e| e.day_key == payload.day_key && e.kind != "lap-ended")
+                .filter(|e| e.kind == "session-paused")
+                .map(|e| payload.timestamp.saturating_sub(e.timestamp)),
+            _ => None,
+        };
+        let event = TrackerEvent {
+            timestamp: payload.timestamp,
+            day_key: payload.day_key.clone(),
+            kind: kind.to_string(),
+            cause: payload.cause.clone(),
+            duration,
+        };
+        if self.recent.len() == RECENT_EVENTS_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(event.clone());
+        self.by_day.entry(event.day_key.clone()).or_default().push(event);
+    }
+
+    // Newest first.
+    fn latest(&self, limit: usize) -> Vec<TrackerEvent> {
+        self.recent.iter().rev().take(limit).cloned().collect()
+    }
+}
+
+// The latest tracker events across days, newest first, for the activity feed.
+#[tauri::command]
+async fn get_recent_events(state: State<'_, AppStateArc>, limit: usize) -> Result<Vec<TrackerEvent>, TrackerError> {
+    Ok(state.events.lock()?.latest(limit))
+}
+
+// Every tracker event recorded on one day, oldest first.
+#[tauri::command]
+async fn get_events_for_date(state: State<'_, AppStateArc>, date: String) -> Result<Vec<TrackerEvent>, TrackerError> {
+    chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|e| TrackerError::InvalidInput(e.to_string()))?;
+    Ok(state.events.lock()?.by_day.get(&date).cloned().unwrap_or_default())
+}
+
+// Push the live status to the frontend once a second while a session exists, so windows
+// listen for status-updated instead of polling get_current_status. Runs on its own
+// thread: the monitoring loop's interval is configurable and may be much slower.
+fn start_status_ticker(app_handle: AppHandle, state: AppStateArc) {
+    state.shutdown.clone().spawn("status-ticker", move || loop {
+        if let Some(status) = live_status(&state) {
+            let _ = app_handle.emit("status-updated", status);
+        }
+        if !state.shutdown.sleep(Duration::from_secs(1)) {
+            break;
+        }
+    });
+}
+
+const MIN_STATE_TICK_MS: u64 = 50;
+const MAX_STATE_TICK_MS: u64 = 2_000;
+
+// A ticker emitting state-tick at a rate the frontend chose. Unlike the status ticker it
+// also emits while no session exists (status None), and every event carries the id
+// subscribe_to_state_changes returned.
+pub struct StateSubscription {
+    id: String,
+    stop: Arc<ShutdownToken>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateTick {
+    pub subscription_id: String,
+    pub status: Option<CurrentStatus>,
+}
+
+// Start emitting state-tick every `interval_ms` (clamped to 50-2000), replacing any
+// earlier subscription. Returns the new subscription's id.
+#[tauri::command]
+async fn subscribe_to_state_changes(
+    state: State<'_, AppStateArc>,
+    app_handle: AppHandle,
+    interval_ms: u64,
+) -> Result<String, TrackerError> {
+    stop_state_subscription(&state);
+    let interval = Duration::from_millis(interval_ms.clamp(MIN_STATE_TICK_MS, MAX_STATE_TICK_MS));
+    let id = uuid::Uuid::new_v4().to_string();
+    let stop = Arc::new(ShutdownToken::default());
+    let (thread_state, thread_stop, subscription_id) = (state.inner().clone(), stop.clone(), id.clone());
+    stop.spawn("state-tick", move || {
+        while !thread_state.shutdown.is_stopped() {
+            let tick = StateTick { subscription_id: subscription_id.clone(), status: live_status(&thread_state) };
+            let _ = app_handle.emit("state-tick", tick);
+            if !thread_stop.sleep(interval) {
+                break;
+            }
+        }
+    });
+    *state.state_subscription.lock()? = Some(StateSubscription { id: id.clone(), stop });
+    info!("📡 State subscription {} started ({}ms)", id, interval.as_millis());
+    Ok(id)
+}
+
+#[tauri::command]
+async fn unsubscribe_from_state_changes(state: State<'_, AppStateArc>) -> Result<(), TrackerError> {
+    stop_state_subscription(&state);
+    Ok(())
+}
+
+// Stop the ticker and wait for its thread; a tick in flight is short.
+fn stop_state_subscription(state: &AppStateArc) {
+    let Some(subscription) = lock_or_recover(&state.state_subscription).take() else { return };
+    if !subscription.stop.shutdown(Duration::from_secs(1)).is_empty() {
+        warn!("State subscription {} did not stop in time", subscription.id);
+    }
+}
+
+// The status as get_current_status reports it, for the ticker and the status server.
+// Read-only: it never advances accumulated time.
+fn live_status(state: &AppStateArc) -> Option<CurrentStatus> {
+    let config = lock_or_recover(&state.config).clone();
+    let pomodoro = lock_or_recover(&state.pomodoro).clone();
+    let session_guard = lock_or_recover(&state.current_session);
+    let records_guard = lock_or_recover(&state.day_records);
+    session_guard
+        .as_ref()
+        .map(|session| current_status(session, &records_guard, &config, pomodoro, seconds_since_last_save(state)))
+}
+
+const DEFAULT_STATUS_SERVER_PORT: u16 = 47_615;
+// How often the accept loop looks for a stop request between connections.
+const STATUS_SERVER_POLL: Duration = Duration::from_millis(200);
+
+pub struct StatusServer {
+    port: u16,
+    stop: Arc<std::sync::atomic::AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum StatusRoute {
+    Status,
+    Today,
+}
+
+// Route an HTTP request line ("GET /status HTTP/1.1"). Err carries the status code
+// to answer with. A query string is ignored, so overlays can add cache-busters.
+fn route_status_request(request_line: &str) -> Result<StatusRoute, u16> {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else { return Err(400) };
+    if method != "GET" {
+        return Err(405);
+    }
+    match target.split('?').next() {
+        Some("/status") => Ok(StatusRoute::Status),
+        Some("/today") => Ok(StatusRoute::Today),
+        _ => Err(404),
+    }
+}
+
+// Only requests addressed to the loopback names. Anything else is a browser that was
+// pointed here by a rebound DNS name, not an overlay on this machine.
+fn status_host_allowed(host: Option<&str>) -> bool {
+    let Some(host) = host.map(str::trim) else { return false };
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name == "127.0.0.1" || name.eq_ignore_ascii_case("localhost")
+}
+
+// What /today serves: times and projects only. Notes, tags, app breakdowns and window
+// titles never leave through the server.
+#[derive(Debug, Serialize)]
+struct StatusServerLap {
+    start_time: u64,
+    end_time: Option<u64>,
+    duration: Option<u64>,
+    project: Option<String>,
+    is_synthetic: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusServerDay {
+    date: String,
+    total_duration: u64,
+    is_active: bool,
+    laps: Vec<StatusServerLap>,
+}
+
+impl From<&DayRecord> for StatusServerDay {
+    fn from(record: &DayRecord) -> Self {
+        StatusServerDay {
+            date: record.date.clone(),
+            total_duration: record.total_duration,
+            is_active: record.is_active,
+            laps: record
+                .laps
+                .iter()
+                .map(|lap| StatusServerLap {
+                    start_time: lap.start_time,
+                    end_time: lap.end_time,
+                    duration: lap.duration,
+                    project: lap.project.clone(),
+                    is_synthetic: lap.is_synthetic,
+                })
+                .collect(),
+        }
+    }
+}
+
+// Answer one connection. /status is null while no day is running; /today is 404
+// until today has a record. Nothing is served while the store is encrypted.
+fn serve_status_request(stream: std::net::TcpStream, state: &AppStateArc) -> std::io::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut host = None;
+    for _ in 0..64 {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_string());
+            }
+        }
+    }
+    let route = if !status_host_allowed(host.as_deref()) || encryption::status().encrypted {
+        Err(403)
+    } else {
+        route_status_request(&request_line)
+    };
+    let (code, body) = match route {
+        Ok(StatusRoute::Status) => {
+            let status = live_status(state).map(|status| CurrentStatus { current_lap_note: None, ..status });
+            (200, serde_json::to_string(&status)?)
+        }
+        Ok(StatusRoute::Today) => match lock_or_recover(&state.day_records).get(&today_local_key()) {
+            Some(record) => (200, serde_json::to_string(&StatusServerDay::from(record))?),
+            None => (404, "null".to_string()),
+        },
+        Err(code) => (code, "null".to_string()),
+    };
+    let reason = match code {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        _ => "Method Not Allowed",
+    };
+    let cors = if lock_or_recover(&state.config).status_server_allow_cors {
+        "Access-Control-Allow-Origin: *\r\n"
+    } else {
+        ""
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        cors,
+        body.len(),
+        body
+    )
+}
+
+// Stop the running server (waiting for its port to be released), then start one if the
+// config asks for it. Called at startup, after unlock_store and after every settings
+// change; a locked store has no config loaded yet, so nothing starts until it is unlocked.
+fn apply_status_server(state: &AppStateArc) -> Result<(), TrackerError> {
+    let (enabled, port) = {
+        let config = lock_or_recover(&state.config);
+        (config.status_server_enabled, config.status_server_port)
+    };
+    let mut server_guard = lock_or_recover(&state.status_server);
+    if let Some(server) = server_guard.take() {
+        server.stop.store(true, Ordering::Relaxed);
+        let _ = server.thread.join();
+        info!("🌐 Status server on port {} stopped", server.port);
+    }
+    if !enabled || encryption::is_locked() {
+        return Ok(());
+    }
+
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+    listener.set_nonblocking(true)?;
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (thread_stop, thread_state) = (stop.clone(), state.clone());
+    let thread = thread::Builder::new().name("status-server".to_string()).spawn(move || {
+        while !thread_stop.load(Ordering::Relaxed) && !thread_state.shutdown.is_stopped() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = serve_status_request(stream, &thread_state) {
+                        debug!("Status request failed: {}", e);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(STATUS_SERVER_POLL),
+                Err(e) => {
+                    warn!("Status server stopped: {}", e);
+                    break;
+                }
+            }
+        }
+    })?;
+    info!("🌐 Status server listening on http://127.0.0.1:{}", port);
+    *server_guard = Some(StatusServer { port, stop, thread });
+    Ok(())
+}
+
+// Turn the local status server on or off, optionally on another port or with CORS
+// (see TrackerConfig::status_server_allow_cors). Applies at once.
+#[tauri::command]
+async fn set_status_server(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    enabled: bool,
+    port: Option<u16>,
+    allow_cors: Option<bool>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    if port == Some(0) {
+        return Err(TrackerError::InvalidInput("port must be between 1 and 65535".to_string()));
+    }
+    {
+        let mut config = state.config.lock()?;
+        config.status_server_enabled = enabled;
+        if let Some(port) = port {
+            config.status_server_port = port;
+        }
+        if let Some(allow_cors) = allow_cors {
+            config.status_server_allow_cors = allow_cors;
+        }
+    }
+    save_state(&app_handle, &state);
+    apply_status_server(&state)
+}
+
+// screentime:// links, for launchers and shell scripts (`open screentime://stop-lap`).
+#[derive(Debug, PartialEq, Eq)]
+enum DeepLinkAction {
+    StartDay,
+    StopLap,
+    AddLap,
+    Status,
+}
+
+// The action a URL asks for; None for another scheme or an unknown path. The action is
+// the host, so a trailing slash or a query string makes no difference.
+fn parse_deep_link(url: &str) -> Option<DeepLinkAction> {
+    let rest = url.strip_prefix("screentime://")?;
+    match rest.split(['/', '?']).next()? {
+        "start-day" => Some(DeepLinkAction::StartDay),
+        "stop-lap" => Some(DeepLinkAction::StopLap),
+        "add-lap" => Some(DeepLinkAction::AddLap),
+        "status" => Some(DeepLinkAction::Status),
+        _ => None,
+    }
+}
+
+// Run a deep link through the same functions as the commands and confirm it with a
+// notification. `status` also writes the live status to deeplink-status.json in the data
+// directory (`null` with no day running), except while the store is encrypted.
+fn handle_deep_link(app_handle: &AppHandle, state: &AppStateArc, url: &str) {
+    {
+        let mut pending = lock_or_recover(&state.pending_deep_links);
+        if encryption::is_locked() {
+            info!("🔗 Holding {} until the store is unlocked", url);
+            pending.push(url.to_string());
+            return;
+        }
+    }
+    let Some(action) = parse_deep_link(url) else {
+        warn!("🔗 Ignoring unknown deep link {}", url);
+        return;
+    };
+    info!("🔗 Deep link: {:?}", action);
+    let outcome = match action {
+        DeepLinkAction::StartDay => start_day_direct(app_handle, state, None, DaySessionOverrides::default()),
+        DeepLinkAction::StopLap => stop_lap_direct(app_handle, state),
+        DeepLinkAction::AddLap => add_lap_direct(app_handle, state),
+        DeepLinkAction::Status => Ok(write_deep_link_status(app_handle, state)),
+    };
+    match outcome {
+        Ok(message) => send_alert(app_handle, state, "Screen Time", &message),
+        Err(e) => {
+            warn!("🔗 Deep link {:?} failed: {}", action, e);
+            send_alert(app_handle, state, "Screen Time", &e.to_string());
+        }
+    }
+}
+
+// The status deep link's notification text, after writing deeplink-status.json.
+fn write_deep_link_status(app_handle: &AppHandle, state: &AppStateArc) -> String {
+    let status = live_status(state);
+    if encryption::status().encrypted {
+        info!("🔗 Not writing deeplink-status.json: the store is encrypted");
+    } else {
+        let written = serde_json::to_string_pretty(&status)
+            .map_err(TrackerError::from)
+            .and_then(|json| Ok(write_atomically(&get_app_data_dir(app_handle)?.join("deeplink-status.json"), &json)?));
+        if let Err(e) = written {
+            warn!("Could not write deeplink-status.json: {}", e);
+        }
+    }
+    status_line(status.as_ref())
+}
+
+// "Paused — 3h 12m so far", for notifications confirming an action taken without the window.
+fn status_line(status: Option<&CurrentStatus>) -> String {
+    match status {
+        None => "No day is being tracked".to_string(),
+        Some(status) => {
+            let total = status.total_session_duration + status.current_lap_duration;
+            let label = if status.is_active { "Tracking" } else { "Paused" };
+            format!("{} — {}h {}m so far", label, total / 3600, (total % 3600) / 60)
+        }
+    }
+}
+
+// Handle links that arrive while running, and the one the app was launched with, if any.
+// Windows and Linux start a second instance for a link; the single-instance plugin hands
+// it to this one instead.
+fn install_deep_links(app_handle: &AppHandle, state: &AppStateArc) {
+    use tauri_plugin_deep_link::DeepLinkExt;
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    if let Err(e) = app_handle.deep_link().register_all() {
+        warn!("Could not register the screentime:// scheme: {}", e);
+    }
+    let (handle, link_state) = (app_handle.clone(), state.clone());
+    app_handle.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle_deep_link(&handle, &link_state, url.as_str());
+        }
+    });
+    if let Ok(Some(urls)) = app_handle.deep_link().get_current() {
+        for url in urls {
+            handle_deep_link(app_handle, state, url.as_str());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShortcutAction {
+    ToggleTimer,
+    AddLap,
+}
+
+// Presses closer together than this are one press (a held or bouncing key).
+const SHORTCUT_DEBOUNCE_MS: u64 = 500;
+
+// When each action (indexed by ShortcutAction) last ran from a shortcut, in unix
+// milliseconds. Kept per action, so a lap right after a toggle is not swallowed.
+static SHORTCUT_PRESSED_AT: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+
+fn shortcut_debounced(last_ms: u64, now_ms: u64) -> bool {
+    now_ms.saturating_sub(last_ms) < SHORTCUT_DEBOUNCE_MS
+}
+
+// A binding as the shortcut plugin understands it; None for a blank one.
+fn parse_shortcut(binding: Option<&str>) -> Result<Option<Shortcut>, TrackerError> {
+    binding
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(|b| b.parse().map_err(|e| TrackerError::InvalidInput(format!("Bad shortcut '{}': {}", b, e))))
+        .transpose()
+}
+
+// Which configured binding `pressed` is, if any.
+fn shortcut_action(config: &TrackerConfig, pressed: &Shortcut) -> Option<ShortcutAction> {
+    let matches = |binding: &Option<String>| parse_shortcut(binding.as_deref()).ok().flatten().as_ref() == Some(pressed);
+    if matches(&config.toggle_shortcut) {
+        Some(ShortcutAction::ToggleTimer)
+    } else if matches(&config.lap_shortcut) {
+        Some(ShortcutAction::AddLap)
+    } else {
+        None
+    }
+}
+
+// The global-shortcut plugin's handler. Toggling stops the running lap, or resumes a
+// paused session by opening a new one; either way a notification reports where the day
+// stands, since the window is usually hidden.
+fn handle_shortcut(app_handle: &AppHandle, pressed: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let state = app_handle.state::<AppStateArc>().inner().clone();
+    let Some(action) = shortcut_action(&lock_or_recover(&state.config), pressed) else { return };
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+    if shortcut_debounced(SHORTCUT_PRESSED_AT[action as usize].swap(now_ms, Ordering::Relaxed), now_ms) {
+        debug!("Ignoring a repeated shortcut press");
+        return;
+    }
+    info!("⌨️ Shortcut: {:?}", action);
+    let paused = lock_or_recover(&state.current_session).as_ref().map(|session| session.is_paused);
+    let outcome = match (action, paused) {
+        (_, None) => Err(TrackerError::NoActiveSession),
+        (ShortcutAction::ToggleTimer, Some(false)) => stop_lap_direct(app_handle, &state),
+        (ShortcutAction::ToggleTimer, Some(true)) | (ShortcutAction::AddLap, Some(_)) => add_lap_direct(app_handle, &state),
+    };
+    let body = match outcome {
+        Ok(_) => status_line(live_status(&state).as_ref()),
+        Err(e) => {
+            warn!("⌨️ Shortcut {:?} failed: {}", action, e);
+            e.to_string()
+        }
+    };
+    send_alert(app_handle, &state, "Screen Time", &body);
+}
+
+// Register the configured shortcuts in place of whatever was registered before. Called
+// at startup, and by set_shortcuts to put the saved bindings back when new ones fail.
+fn apply_shortcuts(app_handle: &AppHandle, state: &AppStateArc) -> Result<(), TrackerError> {
+    let (toggle, lap) = {
+        let config = lock_or_recover(&state.config);
+        (parse_shortcut(config.toggle_shortcut.as_deref())?, parse_shortcut(config.lap_shortcut.as_deref())?)
+    };
+    register_shortcuts(app_handle, toggle, lap)
+}
+
+fn register_shortcuts(app_handle: &AppHandle, toggle: Option<Shortcut>, lap: Option<Shortcut>) -> Result<(), TrackerError> {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+    let shortcuts = app_handle.global_shortcut();
+    shortcuts
+        .unregister_all()
+        .map_err(|e| TrackerError::InvalidInput(format!("Could not clear the shortcuts: {}", e)))?;
+    for shortcut in [toggle, lap].into_iter().flatten() {
+        shortcuts
+            .register(shortcut)
+            .map_err(|e| TrackerError::InvalidInput(format!("Could not register {}: {}", shortcut, e)))?;
+    }
+    info!("⌨️ Global shortcuts registered");
+    Ok(())
+}
+
+// Change the global shortcuts; None or blank turns one off. Applies at once, and is only
+// saved once the new bindings are registered; if one cannot be (another app owns it),
+// the previous bindings are put back and nothing changes.
+#[tauri::command]
+async fn set_shortcuts(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    toggle: Option<String>,
+    lap: Option<String>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let toggle_shortcut = parse_shortcut(toggle.as_deref())?;
+    let lap_shortcut = parse_shortcut(lap.as_deref())?;
+    if toggle_shortcut.is_some() && toggle_shortcut == lap_shortcut {
+        return Err(TrackerError::InvalidInput("The two shortcuts must differ".to_string()));
+    }
+    if let Err(e) = register_shortcuts(&app_handle, toggle_shortcut, lap_shortcut) {
+        if let Err(restore) = apply_shortcuts(&app_handle, &state) {
+            error!("❌ Could not restore the previous shortcuts: {}", restore);
+        }
+        return Err(e);
+    }
+    {
+        let mut config = state.config.lock()?;
+        config.toggle_shortcut = toggle_shortcut.map(|_| toggle.unwrap_or_default().trim().to_string());
+        config.lap_shortcut = lap_shortcut.map(|_| lap.unwrap_or_default().trim().to_string());
+    }
+    save_state(&app_handle, &state);
+    Ok(())
+}
+
+// Commands that change tracking data or settings refuse to run until the store is
+// unlocked: nothing is loaded yet, so their result could not be saved.
+fn ensure_unlocked() -> Result<(), TrackerError> {
+    if encryption::is_locked() {
+        return Err(TrackerError::StoreLocked);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_store_status() -> Result<encryption::StoreStatus, TrackerError> {
+    Ok(encryption::status())
+}
+
+// Unlock an encrypted store with its passphrase and load the history. A wrong passphrase
+// is an error and leaves every file as it is.
+#[tauri::command]
+async fn unlock_store(app_handle: AppHandle, state: State<'_, AppStateArc>, passphrase: String) -> Result<(), TrackerError> {
+    if !encryption::is_locked() {
+        return Ok(());
+    }
+    // Held until the history is loaded, so no deep link runs against an empty state.
+    let queued_links = {
+        let mut pending = lock_or_recover(&state.pending_deep_links);
+        encryption::unlock(&get_app_data_dir(&app_handle)?, &passphrase)?;
+        info!("🔓 Store unlocked");
+        load_and_initialize(&app_handle, &state);
+        std::mem::take(&mut *pending)
+    };
+    for url in queued_links {
+        handle_deep_link(&app_handle, &state, &url);
+    }
+    // Both stayed off while locked; the loaded config decides now.
+    if let Err(e) = apply_status_server(&state) {
+        error!("❌ Could not start the status server: {}", e);
+    }
+    state.sync.request();
+    let _ = app_handle.emit("store-unlocked", ());
+    Ok(())
+}
+
+// Encrypt state.json, the day files, archives, backups and the journal with a key derived
+// from `passphrase`. Every file is staged before any is replaced; both kinds are read
+// until all are.
+#[tauri::command]
+async fn enable_encryption(app_handle: AppHandle, state: State<'_, AppStateArc>, passphrase: String) -> Result<(), TrackerError> {
+    let data_dir = get_app_data_dir(&app_handle)?;
+    rewrite_store_files(&app_handle, &state, || encryption::enable(&data_dir, &passphrase))?;
+    info!("🔐 Encryption enabled");
+    Ok(())
+}
+
+// Decrypt every file in place again. encryption.json goes last, so sealed files stay
+// readable if this is cut short.
+#[tauri::command]
+async fn disable_encryption(app_handle: AppHandle, state: State<'_, AppStateArc>, passphrase: String) -> Result<(), TrackerError> {
+    let data_dir = get_app_data_dir(&app_handle)?;
+    let mut began = false;
+    let rewritten = rewrite_store_files(&app_handle, &state, || {
+        encryption::begin_disable(&data_dir, &passphrase)?;
+        began = true;
+        Ok(())
+    });
+    if let Err(e) = rewritten {
+        if began {
+            encryption::cancel_disable();
+        }
+        return Err(e);
+    }
+    encryption::finish_disable(&data_dir)?;
+    info!("🔓 Encryption disabled");
+    Ok(())
+}
+
+// Switch the store between sealed and plain with `switch_mode` and rewrite every stored
+// file in the new format. A save first brings the files up to date; then, with saves and
+// journal appends held off, each file is written to a .staged sibling and only once all
+// are staged are they renamed over the originals. A failure while staging leaves every
+// original as it was.
+fn rewrite_store_files(
+    app_handle: &AppHandle,
+    state: &AppStateArc,
+    switch_mode: impl FnOnce() -> Result<(), TrackerError>,
+) -> Result<(), TrackerError> {
+    load_settled_days(state);
+    write_state(app_handle, state, false)?;
+    let _written = lock_or_recover(&state.persistence.written_days);
+    let _journal = lock_or_recover(&state.persistence.journal_lock);
+    switch_mode()?;
+
+    let state_file = get_state_file_path(app_handle)?;
+    let mut files = vec![state_file.clone()];
+    for path in day_file_paths(&get_days_dir(app_handle)?) {
+        let backup = day_backup_path(&path);
+        files.push(path);
+        files.push(backup);
+    }
+    for year_month in archived_months(app_handle)? {
+        files.push(get_archive_file_path(app_handle, &year_month)?);
+    }
+    files.extend(recovery_candidates(&state_file, &get_backup_file_path(app_handle)?));
+    // The sync token is only a file off macOS; there it is in the keychain.
+    #[cfg(not(target_os = "macos"))]
+    files.push(sync::token_file_path(app_handle)?);
+    files.retain(|path| path.exists());
+    let journal_file = get_journal_file_path(app_handle)?;
+
+    let mut staged = Vec::new();
+    let mut stage = |path: &Path, contents: String| -> Result<(), TrackerError> {
+        let staged_path = path.with_file_name(format!(
+            "{}.staged",
+            path.file_name().and_then(|name| name.to_str()).unwrap_or_default()
+        ));
+        staged.push((staged_path.clone(), path.to_path_buf()));
+        fs::write(&staged_path, contents)?;
+        // Renaming keeps the staged file's permissions; the sync token's are 0600.
+        fs::set_permissions(&staged_path, fs::metadata(path)?.permissions())?;
+        Ok(())
+    };
+    let staged_all = (|| {
+        for path in &files {
+            stage(path, encryption::seal_text(encryption::read_text(path)?)?)?;
+        }
+        if let Ok(contents) = fs::read_to_string(&journal_file) {
+            let mut text = String::new();
+            for entry in parse_journal(&contents).0 {
+                text.push_str(&journal_line(&entry)?);
+                text.push('\n');
+            }
+            stage(&journal_file, text)?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = staged_all {
+        for (staged_path, _) in &staged {
+            fs::remove_file(staged_path).ok();
+        }
+        return Err(e);
+    }
+    let mut renamed = Ok(());
+    for (staged_path, path) in &staged {
+        if renamed.is_ok() {
+            renamed = fs::rename(staged_path, path);
+        }
+        if renamed.is_err() {
+            fs::remove_file(staged_path).ok();
+        }
+    }
+    Ok(renamed?)
+}
+
+// Point sync at an https backend, or pass no base_url to turn it off. Pending days are
+// kept either way and go up once sync is configured again. The token is kept in the
+// token store (see sync::store_token); omitted, the stored one is kept. Window titles are
+// sent only with include_titles; with encryption on, plaintext must be true.
+#[tauri::command]
+async fn set_sync_config(
+    app_handle: AppHandle,
+    state: State<'_, AppStateArc>,
+    base_url: Option<String>,
+    token: Option<String>,
+    include_titles: Option<bool>,
+    plaintext: Option<bool>,
+) -> Result<(), TrackerError> {
+    ensure_unlocked()?;
+    let sync = match base_url.map(|url| url.trim().to_string()).filter(|url| !url.is_empty()) {
+        Some(base_url) if !base_url.starts_with("https://") => {
+            return Err(TrackerError::InvalidInput("base_url must start with https://".to_string()));
+        }
+        Some(base_url) => {
+            check_plaintext_export(plaintext)?;
+            Some(sync::SyncConfig {
+                base_url,
+                legacy_token: String::new(),
+                include_titles: include_titles.unwrap_or(false),
+                plaintext: plaintext.unwrap_or(false),
+            })
+        }
+        None => None,
+    };
+    let enabled = sync.is_some();
+    match (enabled, token) {
+        (true, Some(token)) => sync::store_token(&app_handle, token.trim())?,
+        (false, _) => sync::store_token(&app_handle, "")?,
+        (true, None) => {}
+    }
+    state.config.lock()?.sync = sync;
+    save_state(&app_handle, &state);
+    if enabled {
+        state.sync.request();
+    }
+    Ok(())
+}
+
+// Run a sync round now instead of waiting for the next end_day.
+#[tauri::command]
+async fn sync_now(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<sync::SyncStatus, TrackerError> {
+    ensure_unlocked()?;
+    sync::run_round(&app_handle, &state).await?;
+    Ok(sync::status(&state))
+}
+
+#[tauri::command]
+async fn get_sync_status(state: State<'_, AppStateArc>) -> Result<sync::SyncStatus, TrackerError> {
+    Ok(sync::status(&state))
+}
+
+// Direct handlers that don't need State wrapper
+fn handle_screen_lock_direct(app_handle: &AppHandle, state: &AppStateArc) {
+    close_lap_and_pause(app_handle, state, PauseCause::Lock, LapEndReason::ScreenLocked);
+}
+
+// The display went to sleep with the machine still running. Unless the user turned
+// pause_on_display_sleep off, this pauses like a lock but records why.
+#[cfg(target_os = "macos")]
+fn handle_display_sleep_direct(app_handle: &AppHandle, state: &AppStateArc) {
+    if !lock_or_recover(&state.config).pause_on_display_sleep {
+        debug!("🖥️ Display slept - still tracking (pause_on_display_sleep is off)");
+        return;
+    }
+    close_lap_and_pause(app_handle, state, PauseCause::DisplaySleep, LapEndReason::DisplaySlept);
+}
+
+// Resume on a display wake or lid open only when the matching sleep (`by`) is what
+// paused us and the screen is not locked; otherwise the unlock path decides.
+fn wake_resumes(paused_cause: Option<PauseCause>, by: PauseCause, locked: bool) -> bool {
+    paused_cause == Some(by) && !locked
+}
+
+#[cfg(target_os = "macos")]
+fn handle_display_wake_direct(app_handle: &AppHandle, state: &AppStateArc) {
+    let paused_cause = lock_or_recover(&state.current_session).as_ref().and_then(|s| s.paused_cause);
+    let locked = check_screen_lock_state_sync().unwrap_or(true);
+    if wake_resumes(paused_cause, PauseCause::DisplaySleep, locked) {
+        info!("🖥️ Display woke unlocked - resuming");
+        handle_screen_unlock_direct(app_handle, state);
+    }
+}
+
+// The lid closed and the Mac is going to sleep: paused as a sleep, unless the user turned
+// pause_on_lid_close off. Returns whether it paused.
+fn handle_lid_close_direct(app_handle: &AppHandle, state: &AppStateArc) -> bool {
+    if !lock_or_recover(&state.config).pause_on_lid_close {
+        debug!("💻 Lid closed - still tracking (pause_on_lid_close is off)");
+        return false;
+    }
+    info!("💻 Lid closed - pausing as for sleep");
+    handle_system_suspend_direct(app_handle, state, now_unix());
+    true
+}
+
+// The lid opened: resume as on wake, if the lid (or a sleep) paused us and the screen
+// is unlocked. Returns whether a new lap started.
+fn handle_lid_open_direct(app_handle: &AppHandle, state: &AppStateArc) -> bool {
+    let paused_cause = lock_or_recover(&state.current_session).as_ref().and_then(|s| s.paused_cause);
+    let locked = check_screen_lock_state_sync().unwrap_or(true);
+    if !wake_resumes(paused_cause, PauseCause::Sleep, locked) {
+        debug!("💻 Lid opened - leaving it to the unlock path");
+        return false;
+    }
+    info!("💻 Lid opened unlocked - resuming");
+    handle_screen_unlock_direct(app_handle, state);
+    true
+}
+
+// Close the running lap with `reason` and pause with `cause`: shared by lock and
+// display sleep, which differ only in what they record.
+fn close_lap_and_pause(app_handle: &AppHandle, state: &AppStateArc, cause: PauseCause, reason: LapEndReason) {
+    let rules = lock_or_recover(&state.config).auto_tag_rules.clone();
+    let mut session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+    
+    if let Some(session) = session_guard.as_mut() {
+        // Skip if already paused (prevent duplicate events)
+        if session.is_paused {
+            debug!(?cause, "🔒 Pause ignored: session already paused");
+            return;
+        }
+        
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        
+        // Get actual lap start time from records
+        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
+            get_active_lap_start_time(day_record)
+                .unwrap_or(session.current_lap_start_timestamp)
+        } else {
+            session.current_lap_start_timestamp
+        };
+        
+        let lap_duration = finalize_lap_duration(lap_start_time);
+        
+        // End current lap
+        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+            if let Some(last_lap) = day_record.laps.last_mut() {
+                last_lap.end_time = Some(current_time);
+                last_lap.duration = Some(lap_duration);
+                last_lap.end_reason = Some(reason);
+                auto_tag_lap(last_lap, &rules);
+                emit_session_event(app_handle, "lap-ended", &session.day_key, Some(last_lap.clone()), None);
+            }
+        }
+        
+        // Mark as paused by system (not user)
+        session.pause(cause, now_unix());
+        release_display_assertion_now(state);
+        info!(day = %session.day_key, lap_seconds = lap_duration, ?cause, "🔒 Lap closed, session paused");
+        let label = if cause == PauseCause::DisplaySleep { "display_sleep" } else { "lock" };
+        emit_session_event(app_handle, "session-paused", &session.day_key, None, Some(label));
+    }
+    
+    // Release locks before saving
+    drop(session_guard);
+    drop(records_guard);
+    
+    // Save state
+    save_state(app_handle, state);
+}
+
+fn handle_screen_unlock_direct(app_handle: &AppHandle, state: &AppStateArc) {
+    // The user is back. If they were away long enough (or it is past the cutoff) and the
+    // date has changed, close out the previous day first — otherwise the lap we are about
+    // to open would be filed under the day they started, which is how Saturday's work
+    // ended up counted as Friday's. This must run before the lap is pushed below.
+    roll_over_day_if_due(app_handle, state);
+    if auto_start_day_if_due(app_handle, state) {
+        save_state(app_handle, state);
+        return;
+    }
+
+    let mut session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+    let mut idle_gap = None;
+
+    if let Some(session) = session_guard.as_mut() {
+        // Skip if already active (prevent duplicate events)
+        if !session.is_paused {
+            debug!("🔓 Unlock ignored: session already active");
+            return;
+        }
+        
+        // Only auto-start a new lap if user didn't manually pause
+        // If user manually paused, respect their choice and don't auto-resume
+        if !session.user_paused {
+            let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            
+            // Start new lap
+            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+                let previous_end = day_record.laps.last().and_then(|lap| lap.end_time);
+                let threshold = lock_or_recover(&state.config).idle_gap_notification_secs;
+                idle_gap = idle_gap_to_report(previous_end, current_time, threshold);
+                day_record.laps.push(session.open_lap(current_time));
+            }
+            
+            // Reset lap tracking and resume
+            session.current_lap_start_timestamp = current_time;
+            session.accumulated_seconds = 0;
+            session.resume();
+            let opened = records_guard.get(&session.day_key).and_then(|r| r.laps.last()).cloned();
+            emit_session_event(app_handle, "session-resumed", &session.day_key, opened, None);
+            info!(day = %session.day_key, "🔓 Screen unlocked - new lap started");
+        } else {
+            info!(day = %session.day_key, "🔓 Screen unlocked - staying paused (user paused)");
+        }
+    }
+    
+    // Release locks before saving
+    drop(session_guard);
+    drop(records_guard);
+
+    if let Some(gap) = idle_gap {
+        notify_idle_gap(app_handle, state, gap);
+    }
+    
+    // Save state
+    save_state(app_handle, state);
+}
+
+#[cfg(target_os = "macos")]
+fn handle_fast_user_switch_out_direct(app_handle: &AppHandle, state: &AppStateArc) {
+    info!("👥 Switched to another user - pausing");
+    handle_screen_lock_direct(app_handle, state);
+    let _ = app_handle.emit("fast-user-switch", serde_json::json!({ "active": false }));
+}
+
+#[cfg(target_os = "macos")]
+fn handle_fast_user_switch_in_direct(app_handle: &AppHandle, state: &AppStateArc) {
+    info!("👤 Switched back to this user");
+    handle_screen_unlock_direct(app_handle, state);
+    let _ = app_handle.emit("fast-user-switch", serde_json::json!({ "active": true }));
+}
+
+// Close the currently-open lap because the machine is going (or went) to sleep.
+// `end_ts` is when the lap should end: "now" when called from the NSWorkspace
+// willSleep observer (delivered just before the process suspends), or the last
+// pre-gap poll timestamp when called from the monitoring loop's gap detector
+// after wake. It must never be the wake time — ending the lap at wake is exactly
+// what counted a whole night's sleep as one giant active lap.
+fn handle_system_suspend_direct(app_handle: &AppHandle, state: &AppStateArc, end_ts: u64) {
+    let mut session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+
+    let mut changed = false;
+    if let Some(session) = session_guard.as_mut() {
+        // Skip if already paused (locked before sleep, user pause, or the willSleep
+        // observer already closed the lap and this is the gap detector re-firing).
+        if !session.is_paused {
+            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
+                if let Some(last_lap) = day_record.laps.last_mut() {
+                    if last_lap.duration.is_none() {
+                        // Guard against an end_ts that is somehow before the lap start.
+                        let end = end_ts.max(last_lap.start_time);
+                        last_lap.end_time = Some(end);
+                        last_lap.duration = Some(end - last_lap.start_time);
+                        last_lap.end_reason = Some(LapEndReason::SystemSlept);
+                        last_lap.note_excluded(now_unix());
+                        emit_session_event(app_handle, "lap-ended", &session.day_key, Some(last_lap.clone()), None);
+                        info!(day = %session.day_key, lap_seconds = end - last_lap.start_time, "💤 Suspend - lap closed at pre-sleep timestamp");
+                    }
+                }
+                day_record.total_duration = day_record.laps.iter()
+                    .filter_map(Lap::worked_duration)
+                    .sum();
+            }
+
+            // Mark session as paused by system (not user)
+            session.pause(PauseCause::Sleep, end_ts);
+            release_display_assertion_now(state);
+            changed = true;
+            emit_session_event(app_handle, "session-paused", &session.day_key, None, Some("sleep"));
+        }
+    }
+
+    // Release locks before saving
+    drop(session_guard);
+    drop(records_guard);
+
+    if changed {
+        save_state(app_handle, state);
+    }
+}
+
+// The wall clock was stepped by `skew` seconds between two ticks. Laps are stamped in
+// wall time, so the running lap is closed where the monotonic clock says it got to
+// (`pre_jump_ts` plus `monotonic_elapsed`, in the old clock's terms) and a new lap opens
+// at the corrected time `now`, or where the closed lap ended if the clock went back, so
+// laps never overlap. The step itself is not time left out of the lap, so nothing is
+// added to excluded_seconds. A paused session has no lap to split.
+fn handle_clock_jump_direct(app_handle: &AppHandle, state: &AppStateArc, skew: i64, pre_jump_ts: u64, monotonic_elapsed: u64, now: u64) {
+    warn!(skew_seconds = skew, "⏰ Wall clock jumped {}s against the monotonic clock", skew);
+    let mut session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+
+    let mut split = false;
+    if let Some(session) = session_guard.as_mut().filter(|session| !session.is_paused) {
+        if let Some(last_lap) = records_guard.get_mut(&session.day_key).and_then(|r| r.laps.last_mut()) {
+            if last_lap.duration.is_none() {
+                let end = (pre_jump_ts + monotonic_elapsed).max(last_lap.start_time);
+                last_lap.end_time = Some(end);
+                last_lap.duration = Some(end - last_lap.start_time);
+                last_lap.end_reason = Some(LapEndReason::ClockChanged);
+                emit_session_event(app_handle, "lap-ended", &session.day_key, Some(last_lap.clone()), None);
+            }
+        }
+        let previous_end = records_guard.get(&session.day_key).and_then(|r| r.laps.last()).and_then(|lap| lap.end_time);
+        roll_to_next_lap(session, &mut records_guard, previous_end.map_or(now, |end| now.max(end)));
+        let opened = records_guard.get(&session.day_key).and_then(|r| r.laps.last()).cloned();
+        emit_session_event(app_handle, "session-resumed", &session.day_key, opened, None);
+        split = true;
+    }
+
+    drop(session_guard);
+    drop(records_guard);
+
+    let _ = app_handle.emit("clock-changed", serde_json::json!({ "skew_seconds": skew, "lap_split": split }));
+    if split {
+        save_state(app_handle, state);
+    }
+}
+
+// The local timezone changed (travel, or the user changed it). The date may now be
+// different, so the day is re-evaluated — forward only, maybe_roll_over_day never moves
+// a session back to an earlier date — and the UI is told why laps may look shifted.
+fn handle_timezone_change_direct(app_handle: &AppHandle, state: &AppStateArc, previous_offset_secs: i32, offset_secs: i32) {
+    warn!(previous_offset_secs, offset_secs, "🌍 Local timezone changed");
+    roll_over_day_if_due(app_handle, state);
+    let day_key = lock_or_recover(&state.current_session).as_ref().map(|s| s.day_key.clone());
+    let _ = app_handle.emit(
+        "timezone-changed",
+        serde_json::json!({
+            "previous_offset_secs": previous_offset_secs,
+            "offset_secs": offset_secs,
+            "day_key": day_key,
+        }),
+    );
+}
+
+fn check_screen_lock_state_sync() -> Result<bool, String> {
+    // Use the macOS-specific detection method
+    #[cfg(target_os = "macos")]
+    {
+        return check_macos_screen_lock_state();
+    }
+
+    // Windows pushes lock/unlock to platform::windows; report what it last saw
+    #[cfg(target_os = "windows")]
+    {
+        Ok(platform::windows::is_session_locked())
+    }
+
+    // Linux reports going idle as a lock; see platform::linux
+    #[cfg(target_os = "linux")]
+    {
+        Ok(platform::linux::is_idle())
+    }
+
+    // For other systems, return false
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    Ok(false)
+}
+
+#[cfg(target_os = "macos")]
+fn check_macos_screen_lock_state() -> Result<bool, String> {
+    // Method 1: Use native Cocoa/Objective-C to check session state
+    unsafe {
+        let ws_class = class!(NSWorkspace);
+        let shared_workspace: id = msg_send![ws_class, sharedWorkspace];
+        
+        // Check if screen is locked by looking at the active space
+        // When locked, we won't be able to get active application
+        let active_app: id = msg_send![shared_workspace, frontmostApplication];
+        if active_app == nil {
+            return Ok(true); // No frontmost app usually means locked
+        }
+        
+        // Get the localized name of the frontmost application
+        let app_name: id = msg_send![active_app, localizedName];
+        if app_name != nil {
+            let name_str: *const i8 = msg_send![app_name, UTF8String];
+            if !name_str.is_null() {
+                let name = std::ffi::CStr::from_ptr(name_str).to_string_lossy();
+                // If loginwindow or ScreenSaverEngine is frontmost, screen is locked
+                if name == "loginwindow" || name == "ScreenSaverEngine" {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    
+    // Method 2: Check for screen saver process. A subprocess, so it runs at most every
+    // SCREENSAVER_CHECK_INTERVAL_SECS and polls in between reuse its answer; the native
+    // check above already catches a frontmost ScreenSaverEngine on every poll.
+    let now = now_unix();
+    if now.saturating_sub(SCREENSAVER_CHECKED_AT.load(Ordering::Relaxed)) >= SCREENSAVER_CHECK_INTERVAL_SECS {
+        let running = match Command::new("pgrep").arg("-x").arg("ScreenSaverEngine").output() {
+            Ok(output) => !output.stdout.is_empty(),
+            Err(e) => {
+                debug!("pgrep failed: {}", e);
+                false
+            }
+        };
+        SCREENSAVER_RUNNING.store(running, Ordering::Relaxed);
+        SCREENSAVER_CHECKED_AT.store(now, Ordering::Relaxed);
+    }
+    if SCREENSAVER_RUNNING.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+
+    // Methods 1 (native NSWorkspace) and 2 (pgrep) above are sufficient to detect a
+    // locked screen. We intentionally do NOT shell out to `osascript` here: this runs
+    // on every poll while UNLOCKED (the common case), and an AppleScript spawn each
+    // cycle was the single biggest source of idle CPU. It also needed the Automation
+    // permission, which is why get_permissions_status never reports it as missing.
+    Ok(false)
+}
+
+#[cfg(target_os = "macos")]
+const SCREENSAVER_CHECK_INTERVAL_SECS: u64 = 5;
+#[cfg(target_os = "macos")]
+static SCREENSAVER_CHECKED_AT: AtomicU64 = AtomicU64::new(0);
+#[cfg(target_os = "macos")]
+static SCREENSAVER_RUNNING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// Localized name of the frontmost application. None when there is no frontmost app, or
+// when it is the lock screen / screen saver, which is not time spent in an app.
+#[cfg(target_os = "macos")]
+fn frontmost_app_name() -> Option<String> {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let active_app: id = msg_send![workspace, frontmostApplication];
+        if active_app == nil {
+            return None;
+        }
+        let app_name: id = msg_send![active_app, localizedName];
+        if app_name == nil {
+            return None;
+        }
+        let name_str: *const i8 = msg_send![app_name, UTF8String];
+        if name_str.is_null() {
+            return None;
+        }
+        let name = std::ffi::CStr::from_ptr(name_str).to_string_lossy().into_owned();
+        if name == "loginwindow" || name == "ScreenSaverEngine" {
+            return None;
+        }
+        Some(name)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn frontmost_app_name() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> id;
+    fn CGEventSourceSecondsSinceLastEventType(source_state: i32, event_type: u32) -> f64;
+    fn CGPreflightScreenCaptureAccess() -> bool;
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+}
+
+// Which macOS privacy permissions the app holds. None where the platform has no such
+// permission. Neither check prompts the user.
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionsStatus {
+    // Always false: lock detection no longer runs AppleScript against System Events.
+    pub automation_required: bool,
+    pub accessibility_granted: Option<bool>,
+    // Needed for window titles (CGWindowList hides other apps' titles without it).
+    pub screen_recording_granted: Option<bool>,
+    // Only macOS asks; elsewhere the notification plugin posts without a permission.
+    pub notifications_authorized: Option<bool>,
+    // Permissions an enabled feature needs but lacks, for the frontend to point the user
+    // at System Settings: "screen_recording", "notifications".
+    pub missing: Vec<String>,
+}
+
+#[tauri::command]
+async fn get_permissions_status(state: State<'_, AppStateArc>) -> Result<PermissionsStatus, TrackerError> {
+    let config = state.config.lock()?.clone();
+    #[cfg(target_os = "macos")]
+    let (accessibility_granted, screen_recording_granted) =
+        unsafe { (Some(AXIsProcessTrusted()), Some(CGPreflightScreenCaptureAccess())) };
+    #[cfg(not(target_os = "macos"))]
+    let (accessibility_granted, screen_recording_granted) = (None, None);
+    let notifications_authorized = cfg!(target_os = "macos").then_some(config.notifications_authorized);
+
+    let mut missing = Vec::new();
+    if config.capture_window_titles && screen_recording_granted == Some(false) {
+        missing.push("screen_recording".to_string());
+    }
+    if notifications_authorized == Some(false) {
+        missing.push("notifications".to_string());
+    }
+    Ok(PermissionsStatus {
+        automation_required: false,
+        accessibility_granted,
+        screen_recording_granted,
+        notifications_authorized,
+        missing,
+    })
+}
+
+// Seconds since the last keyboard / mouse / trackpad input anywhere in the session.
+#[cfg(target_os = "macos")]
+fn system_idle_seconds() -> f64 {
+    // kCGEventSourceStateCombinedSessionState, kCGAnyInputEventType
+    unsafe { CGEventSourceSecondsSinceLastEventType(0, !0) }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn system_idle_seconds() -> f64 {
+    0.0
+}
+
+// Title of the frontmost app's front window, via the window server's window list. macOS
+// only exposes other apps' window titles once Screen Recording permission is granted;
+// without it the title is simply absent and this returns None.
+#[cfg(target_os = "macos")]
+fn frontmost_window_title() -> Option<String> {
+    use cocoa::foundation::NSString;
+
+    // kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements
+    const ON_SCREEN_ONLY: u32 = (1 << 0) | (1 << 4);
+
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let active_app: id = msg_send![workspace, frontmostApplication];
+        if active_app == nil {
+            return None;
+        }
+        let pid: i32 = msg_send![active_app, processIdentifier];
+
+        // A CFArray of CFDictionary, toll-free bridged to NSArray / NSDictionary. The list
+        // is ordered front to back, so the first normal-layer window of the app is its
+        // front window.
+        let windows = CGWindowListCopyWindowInfo(ON_SCREEN_ONLY, 0);
+        if windows == nil {
+            return None;
+        }
+        let owner_key = NSString::alloc(nil).init_str("kCGWindowOwnerPID");
+        let layer_key = NSString::alloc(nil).init_str("kCGWindowLayer");
+        let name_key = NSString::alloc(nil).init_str("kCGWindowName");
+
+        let mut title = None;
+        let count: usize = msg_send![windows, count];
+        for i in 0..count {
+            let window: id = msg_send![windows, objectAtIndex: i];
+            let owner: id = msg_send![window, objectForKey: owner_key];
+            let layer: id = msg_send![window, objectForKey: layer_key];
+            if owner == nil || layer == nil {
+                continue;
+            }
+            let owner_pid: i32 = msg_send![owner, intValue];
+            let layer: i32 = msg_send![layer, intValue];
+            if owner_pid != pid || layer != 0 {
+                continue;
+            }
+            let name: id = msg_send![window, objectForKey: name_key];
+            if name != nil {
+                let utf8: *const i8 = msg_send![name, UTF8String];
+                if !utf8.is_null() {
+                    let text = std::ffi::CStr::from_ptr(utf8).to_string_lossy().trim().to_string();
+                    if !text.is_empty() {
+                        title = Some(text);
+                    }
+                }
+            }
+            break;
+        }
+
+        for obj in [owner_key, layer_key, name_key, windows] {
+            let _: () = msg_send![obj, release];
+        }
+        title
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn frontmost_window_title() -> Option<String> {
+    None
+}
+
+// Cap on distinct titles kept per lap, so a lap spent flicking through browser tabs
+// can't grow without bound.
+const MAX_WINDOW_TITLES_PER_LAP: usize = 50;
+
+// Note `title` on the session's running lap, once, up to the per-lap cap.
+fn note_window_title(session: &CurrentSession, records: &mut HashMap<String, DayRecord>, title: &str) {
+    if session.is_paused {
+        return;
+    }
+    let Some(lap) = records.get_mut(&session.day_key).and_then(|r| r.laps.last_mut()) else { return };
+    if lap.duration.is_some()
+        || lap.window_titles.len() >= MAX_WINDOW_TITLES_PER_LAP
+        || lap.window_titles.iter().any(|t| t == title)
+    {
+        return;
+    }
+    lap.window_titles.push(title.to_string());
+}
+
+fn record_frontmost_window_title(state: &AppStateArc, title: &str) {
+    let session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+    if let Some(session) = session_guard.as_ref() {
+        note_window_title(session, &mut records_guard, title);
+    }
+}
+
+// Add `seconds` of `app` to the session's running lap. A paused session, or a day whose
+// last lap is already closed, has no running lap and gets nothing.
+fn credit_app_time(
+    session: &CurrentSession,
+    records: &mut HashMap<String, DayRecord>,
+    app: &str,
+    seconds: u64,
+) {
+    if session.is_paused || seconds == 0 {
+        return;
+    }
+    let Some(lap) = records.get_mut(&session.day_key).and_then(|r| r.laps.last_mut()) else { return };
+    if lap.duration.is_none() {
+        *lap.app_breakdown.entry(app.to_string()).or_insert(0) += seconds;
+    }
+}
+
+fn credit_frontmost_app(state: &AppStateArc, app: &str, seconds: u64) {
+    let session_guard = lock_or_recover(&state.current_session);
+    let mut records_guard = lock_or_recover(&state.day_records);
+    if let Some(session) = session_guard.as_ref() {
+        credit_app_time(session, &mut records_guard, app, seconds);
+    }
+}
+
+// Get system uptime in seconds (macOS)
+#[cfg(target_os = "macos")]
+fn get_system_uptime() -> Result<u64, String> {
+    let output = Command::new("sysctl")
+        .arg("-n")
+        .arg("kern.boottime")
+        .output()
+        .map_err(|e| e.to_string())?;
+    
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    // Parse boot time from output like: { sec = 1234567890, usec = 0 }
+    if let Some(sec_str) = output_str.split("sec = ").nth(1) {
+        if let Some(boot_time_str) = sec_str.split(',').next() {
+            if let Ok(boot_time) = boot_time_str.trim().parse::<u64>() {
+                let current_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                return Ok(current_time - boot_time);
+            }
+        }
+    }
+    
+    Err("Failed to parse system uptime".to_string())
+}
+
+// Check if we should show startup notification
+fn should_show_startup_notification(state: &AppStateArc) -> Result<bool, String> {
+    // Check if system was recently started (within last 10 minutes)
+    #[cfg(target_os = "macos")]
+    {
+        let uptime = get_system_uptime()?;
+        if uptime > 600 {
+            // System has been running for more than 10 minutes, not a fresh startup
+            return Ok(false);
+        }
+    }
+    
+    let session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
+
+    // We now auto-start/continue a day on boot, so notify whenever a session exists
+    // to let the user know tracking is running (and that they can pause if idle).
+    Ok(session_guard.is_some())
+}
+
+// Show startup notification to user
+fn show_startup_notification(app_handle: &AppHandle, state: &AppStateArc) {
+    use tauri_plugin_notification::NotificationExt;
+
+    info!("🔔 Showing startup notification to user");
+
+    // Reflect whether we auto-started active tracking or restored a paused session.
+    let is_paused = state
+        .current_session
+        .lock()
+        .ok()
+        .and_then(|g| g.as_ref().map(|s| s.is_paused))
+        .unwrap_or(false);
+
+    let body = if is_paused {
+        "Your day is restored but paused. Open the app to resume when you start working."
+    } else {
+        "Tracking started for today. Open the app to pause if you're not working yet."
+    };
+
+    let notification = app_handle
+        .notification()
+        .builder()
+        .title("Screen Time Tracker")
+        .body(body)
+        .show();
     
-    if let Some(session) = session_guard.as_mut() {
-        // Skip if already paused (prevent duplicate events)
-        if session.is_paused {
+    match notification {
+        Ok(_) => info!("✅ Startup notification shown successfully"),
+        Err(e) => error!("❌ Failed to show notification: {}", e),
+    }
+}
+
+// Check and notify on startup
+fn check_and_notify_on_startup(app_handle: &AppHandle, state: &AppStateArc) {
+    // Wait a bit for the system to settle after startup
+    if !state.shutdown.sleep(Duration::from_secs(3)) {
+        return;
+    }
+    
+    match should_show_startup_notification(state) {
+        Ok(should_notify) => {
+            if should_notify {
+                show_startup_notification(app_handle, state);
+            } else {
+                info!("ℹ️ No need to show startup notification");
+            }
+        }
+        Err(e) => {
+            error!("❌ Error checking startup notification: {}", e);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Menu-bar (tray) app: the app lives in the macOS menu bar. Left-clicking the
+// tray icon toggles a small popover (quick glance + basic controls); the popover
+// can expand to the full window. Keeping the full webview closed by default is
+// what keeps idle memory/CPU low — no webview process runs until you open one.
+// ----------------------------------------------------------------------------
+
+// Convert a tray icon rectangle into physical (x, y, width, height).
+fn rect_to_physical(rect: &tauri::Rect) -> (f64, f64, f64, f64) {
+    let (x, y) = match rect.position {
+        tauri::Position::Physical(p) => (p.x as f64, p.y as f64),
+        tauri::Position::Logical(p) => (p.x, p.y),
+    };
+    let (w, h) = match rect.size {
+        tauri::Size::Physical(s) => (s.width as f64, s.height as f64),
+        tauri::Size::Logical(s) => (s.width, s.height),
+    };
+    (x, y, w, h)
+}
+
+// Toggle the popover: close it if open, otherwise build a small borderless window
+// anchored under the tray icon and show it. It closes itself when it loses focus.
+fn toggle_popover(app: &AppHandle, rect: tauri::Rect) {
+    if let Some(existing) = app.get_webview_window("popover") {
+        let _ = existing.close();
+        return;
+    }
+
+    let width = 288.0;
+    let height = 214.0;
+
+    let win = match WebviewWindowBuilder::new(app, "popover", WebviewUrl::App("popover.html".into()))
+        .inner_size(width, height)
+        .decorations(false)
+        .always_on_top(true)
+        .resizable(false)
+        .skip_taskbar(true)
+        .visible(false)
+        .build()
+    {
+        Ok(w) => w,
+        Err(e) => {
+            error!("❌ Failed to build popover window: {}", e);
             return;
         }
-        
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        
-        // Get actual lap start time from records
-        let lap_start_time = if let Some(day_record) = records_guard.get(&session.day_key) {
-            get_active_lap_start_time(day_record)
-                .unwrap_or(session.current_lap_start_timestamp)
-        } else {
-            session.current_lap_start_timestamp
-        };
-        
-        let lap_duration = finalize_lap_duration(lap_start_time);
-        
-        // End current lap
-        if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-            if let Some(last_lap) = day_record.laps.last_mut() {
-                last_lap.end_time = Some(current_time);
-                last_lap.duration = Some(lap_duration);
-            }
+    };
+
+    // Anchor the popover horizontally centered under the tray icon.
+    let scale = win.scale_factor().unwrap_or(1.0);
+    let (ix, iy, iw, ih) = rect_to_physical(&rect);
+    let px = (ix + iw / 2.0 - (width * scale) / 2.0).max(0.0);
+    let py = iy + ih + 2.0;
+    let _ = win.set_position(tauri::PhysicalPosition::new(px, py));
+    let _ = win.show();
+    let _ = win.set_focus();
+
+    // Dismiss when the user clicks away (popover loses focus).
+    let win_for_event = win.clone();
+    win.on_window_event(move |event| {
+        if let WindowEvent::Focused(false) = event {
+            let _ = win_for_event.close();
+        }
+    });
+}
+
+// Show (or create) the full UI window and close the popover. The full window is
+// created on demand so no webview exists while the app sits in the menu bar.
+fn open_main_window(app: &AppHandle) {
+    if let Some(w) = app.get_webview_window("main") {
+        let _ = w.show();
+        let _ = w.unminimize();
+        let _ = w.set_focus();
+    } else {
+        match WebviewWindowBuilder::new(app, "main", WebviewUrl::App("index.html".into()))
+            .title("Screen Time Tracker")
+            .inner_size(1000.0, 800.0)
+            .min_inner_size(800.0, 600.0)
+            .resizable(true)
+            .build()
+        {
+            Ok(_) => info!("✅ Opened full window"),
+            Err(e) => error!("❌ Failed to build main window: {}", e),
+        }
+    }
+
+    if let Some(p) = app.get_webview_window("popover") {
+        let _ = p.close();
+    }
+}
+
+// What the tray icon reflects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrayState {
+    NotStarted,
+    Active,
+    Paused,
+}
+
+fn format_hours_minutes(seconds: u64) -> String {
+    format!("{}h {:02}m", seconds / 3600, (seconds % 3600) / 60)
+}
+
+// Tray state, menu-bar title and tooltip for a status as get_current_status reports it.
+fn tray_display(status: Option<&CurrentStatus>) -> (TrayState, String, String) {
+    let Some(status) = status else {
+        return (TrayState::NotStarted, String::new(), "Screen Time Tracker: day not started".to_string());
+    };
+    let total = format_hours_minutes(status.total_session_duration + status.current_lap_duration);
+    let (tray_state, suffix) = if status.is_active {
+        (TrayState::Active, "")
+    } else {
+        (TrayState::Paused, " (paused)")
+    };
+    (tray_state, total.clone(), format!("Screen Time Tracker: {} today{}", total, suffix))
+}
+
+// The tray icon for a state, derived from the app icon so no extra artwork is needed:
+// full colour while tracking, faded while paused, greyscale before the day starts.
+fn tray_icon_for(tray_state: TrayState) -> tauri::image::Image<'static> {
+    let base = tauri::image::Image::from_bytes(include_bytes!("../icons/32x32.png"))
+        .expect("failed to load tray icon");
+    let mut rgba = base.rgba().to_vec();
+    for px in rgba.chunks_exact_mut(4) {
+        match tray_state {
+            TrayState::Active => {}
+            TrayState::Paused => px[3] /= 2,
+            TrayState::NotStarted => {
+                let luma = ((px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000) as u8;
+                px[0] = luma;
+                px[1] = luma;
+                px[2] = luma;
+            }
+        }
+    }
+    tauri::image::Image::new_owned(rgba, base.width(), base.height())
+}
+
+// Tracking actions in the tray's right-click menu, kept (as managed state) so
+// refresh_tray can enable only the ones that make sense right now.
+struct TrayMenuItems {
+    start_day: MenuItem<tauri::Wry>,
+    pause: MenuItem<tauri::Wry>,
+    resume: MenuItem<tauri::Wry>,
+    add_lap: MenuItem<tauri::Wry>,
+    end_day: MenuItem<tauri::Wry>,
+}
+
+// Which of (start day, pause, resume, add lap, end day) apply in a given state.
+fn tray_actions_enabled(tray_state: TrayState) -> [bool; 5] {
+    match tray_state {
+        TrayState::NotStarted => [true, false, false, false, false],
+        TrayState::Active => [false, true, false, true, true],
+        TrayState::Paused => [false, false, true, false, true],
+    }
+}
+
+// Run a tray menu action through the same logic as the matching command, then persist
+// and redraw the tray straight away.
+fn handle_tray_action(app_handle: &AppHandle, action: &str) {
+    let state = app_handle.state::<AppStateArc>();
+    let state = state.inner();
+    let result = match action {
+        "start_day" => start_day_direct(app_handle, state, None, DaySessionOverrides::default()),
+        "pause" => stop_lap_direct(app_handle, state),
+        "resume" | "add_lap" => add_lap_direct(app_handle, state),
+        "end_day" => end_day_direct(app_handle, state).map(|record| format!("Ended {}", record.date)),
+        _ => return,
+    };
+    match result {
+        Ok(message) => info!("🖱️ Tray {}: {}", action, message),
+        Err(e) => error!("❌ Tray {} failed: {}", action, e),
+    }
+    save_state(app_handle, state);
+    refresh_tray(app_handle, state);
+}
+
+// Redraw the tray from the live session. Called on a Rust-side timer (and after
+// actions that change the session), so it stays current with every window closed.
+fn refresh_tray(app_handle: &AppHandle, state: &AppStateArc) {
+    let Some(tray) = app_handle.tray_by_id("tray") else { return };
+    let status = {
+        let config = lock_or_recover(&state.config).clone();
+        let pomodoro = lock_or_recover(&state.pomodoro).clone();
+        let session_guard = lock_or_recover(&state.current_session);
+        let records_guard = lock_or_recover(&state.day_records);
+        session_guard
+            .as_ref()
+            .map(|session| current_status(session, &records_guard, &config, pomodoro, seconds_since_last_save(state)))
+    };
+    let (tray_state, title, tooltip) = tray_display(status.as_ref());
+    if let Some(items) = app_handle.try_state::<TrayMenuItems>() {
+        let enabled = tray_actions_enabled(tray_state);
+        let all = [&items.start_day, &items.pause, &items.resume, &items.add_lap, &items.end_day];
+        for (item, on) in all.into_iter().zip(enabled) {
+            let _ = item.set_enabled(on);
+        }
+    }
+    let _ = tray.set_icon(Some(tray_icon_for(tray_state)));
+    let _ = tray.set_title(Some(title));
+    let _ = tray.set_tooltip(Some(tooltip));
+}
+
+// Expand from the popover to the full window.
+#[tauri::command]
+async fn show_main_window(app: AppHandle) -> Result<(), TrackerError> {
+    open_main_window(&app);
+    Ok(())
+}
+
+// Tauri command to start day from notification
+#[tauri::command]
+async fn start_day_from_notification(app_handle: AppHandle, state: State<'_, AppStateArc>) -> Result<String, TrackerError> {
+    ensure_unlocked()?;
+    // Check if already has an active session
+    let should_add_lap = {
+        let session_guard = state.current_session.lock()?;
+        
+        if let Some(session) = session_guard.as_ref() {
+            if session.is_paused {
+                // We have a paused session, just add a new lap to resume
+                true
+            } else {
+                // Already actively tracking
+                return Ok("Already tracking".to_string());
+            }
+        } else {
+            // No session, start a new day
+            false
+        }
+    }; // Drop the lock here before awaiting
+    
+    if should_add_lap {
+        add_lap_direct(&app_handle, &state)
+    } else {
+        start_day_direct(&app_handle, &state, None, DaySessionOverrides::default())
+    }
+}
+
+
+// The autostart plugin writes a LaunchAgent containing only RunAtLoad, which fires solely
+// at login. If the tracker then dies mid-session — crashed, force-quit, or killed because
+// its bundle was replaced by a rebuild — launchd will not bring it back until the *next*
+// login, and a Mac that is only ever slept can go days without one. That is how three days
+// of screen time went unrecorded: the app exited on Jul 11 and nothing restarted it while
+// the machine stayed up for 3+ days.
+//
+// KeepAlive/SuccessfulExit=false makes launchd relaunch the job whenever it exits
+// abnormally (non-zero status or killed by a signal), while still honouring a clean exit:
+// the tray's Quit calls process::exit(0), so quitting on purpose still quits for good.
+//
+// The plist is patched rather than rewritten so the plugin stays the single source of
+// truth for the executable path (which differs between a dev run and the installed .app).
+#[cfg(target_os = "macos")]
+fn ensure_autostart_keepalive() {
+    const KEEP_ALIVE: &str = "\t<key>KeepAlive</key>\n\t<dict>\n\t\t<key>SuccessfulExit</key>\n\t\t<false/>\n\t</dict>\n";
+
+    let Ok(home) = std::env::var("HOME") else { return };
+    let plist = PathBuf::from(home).join("Library/LaunchAgents/screen-time-tracker.plist");
+
+    let Ok(contents) = fs::read_to_string(&plist) else { return };
+    if contents.contains("KeepAlive") {
+        return;
+    }
+    // Insert into the job dict, i.e. just before the last closing </dict>.
+    let Some(insert_at) = contents.rfind("</dict>") else { return };
+
+    let mut patched = String::with_capacity(contents.len() + KEEP_ALIVE.len());
+    patched.push_str(&contents[..insert_at]);
+    patched.push_str(KEEP_ALIVE);
+    patched.push_str(&contents[insert_at..]);
+
+    match fs::write(&plist, patched) {
+        Ok(_) => info!("✅ Autostart KeepAlive set (relaunch if the app dies)"),
+        Err(e) => error!("❌ Failed to patch autostart plist: {}", e),
+    }
+}
+
+// Where the login item lives, for error messages the user can act on.
+#[cfg(target_os = "macos")]
+const LOGIN_ITEM_LOCATION: &str = "~/Library/LaunchAgents/screen-time-tracker.plist (System Settings > General > Login Items)";
+#[cfg(target_os = "windows")]
+const LOGIN_ITEM_LOCATION: &str = "the Run key under HKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const LOGIN_ITEM_LOCATION: &str = "~/.config/autostart";
+
+// Register (or remove) the login item through the autostart plugin: a LaunchAgent on
+// macOS, the Run key on Windows, an autostart .desktop entry on Linux. On macOS the
+// KeepAlive patch goes on after every enable(), which rewrites the plist from scratch.
+fn apply_launch_at_login(app_handle: &AppHandle, enabled: bool) -> Result<(), TrackerError> {
+    use tauri_plugin_autostart::ManagerExt;
+    let autolaunch = app_handle.autolaunch();
+    let result = if enabled { autolaunch.enable() } else { autolaunch.disable() };
+    result.map_err(|e| {
+        TrackerError::InvalidInput(format!(
+            "Could not {} launch at login ({}). Check that {} can be changed.",
+            if enabled { "turn on" } else { "turn off" },
+            e,
+            LOGIN_ITEM_LOCATION
+        ))
+    })?;
+    #[cfg(target_os = "macos")]
+    if enabled {
+        ensure_autostart_keepalive();
+    }
+    Ok(())
+}
+
+// Launch at login is on by default, so the first run registers it. After that startup
+// only refreshes a login item that still exists (enable() rewrites the executable path,
+// which differs between a dev run and the installed app), so one removed by hand or
+// through set_launch_at_login stays removed.
+fn refresh_launch_at_login(app_handle: &AppHandle) {
+    use tauri_plugin_autostart::ManagerExt;
+    let first_run = get_state_file_path(app_handle).is_ok_and(|path| !path.exists());
+    if !first_run && !app_handle.autolaunch().is_enabled().unwrap_or(false) {
+        info!("Launch at login is off");
+        return;
+    }
+    match apply_launch_at_login(app_handle, true) {
+        Ok(()) => info!("✅ Autostart enabled (launch on login)"),
+        Err(e) => error!("❌ {}", e),
+    }
+}
+
+// Whether the app is registered to launch at login, read from the system each time.
+#[tauri::command]
+async fn get_launch_at_login(app_handle: AppHandle) -> Result<bool, TrackerError> {
+    use tauri_plugin_autostart::ManagerExt;
+    app_handle
+        .autolaunch()
+        .is_enabled()
+        .map_err(|e| TrackerError::InvalidInput(format!("Could not read {} ({})", LOGIN_ITEM_LOCATION, e)))
+}
+
+// Add or remove the login item. Returns the state the system reports afterwards.
+#[tauri::command]
+async fn set_launch_at_login(app_handle: AppHandle, enabled: bool) -> Result<bool, TrackerError> {
+    apply_launch_at_login(&app_handle, enabled)?;
+    info!("🚀 Launch at login {}", if enabled { "enabled" } else { "disabled" });
+    get_launch_at_login(app_handle).await
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    APP_STARTED_AT.store(now_unix(), Ordering::Relaxed);
+    let app_state = Arc::new(AppState::new());
+    // Every command goes through here, so each invocation is logged once.
+    let commands: fn(tauri::ipc::Invoke<tauri::Wry>) -> bool = tauri::generate_handler![
+        start_day,
+        start_day_with_config,
+        end_day,
+        handle_screen_lock,
+        handle_screen_unlock,
+        get_current_status,
+        get_current_day_laps,
+        get_today_summary,
+        is_tracking_today,
+        get_all_day_records,
+        merge_day_into_previous,
+        archive_old_records,
+        archive_month,
+        unarchive_month,
+        get_records_between,
+        get_statistics,
+        get_heatmap,
+        set_archive_completed_months,
+        purge_records_before,
+        set_retention_days,
+        load_archive,
+        get_storage_info,
+        check_state_file_integrity,
+        get_day_auto_closed,
+        acknowledge_day_auto_closed,
+        recompute_all_totals,
+        get_recent_events,
+        set_auto_tag_rules,
+        get_auto_tag_rules,
+        save_template,
+        get_templates,
+        delete_template,
+        start_day_from_template,
+        set_adaptive_polling,
+        set_pause_on_battery_below,
+        set_auto_end_day_at,
+        set_status_server,
+        get_session_timeline,
+        set_tracking_boundary,
+        get_out_of_hours_laps,
+        get_permissions_status,
+        set_day_metadata,
+        get_day_metadata,
+        delete_day_metadata,
+        get_all_day_metadata,
+        get_events_for_date,
+        tag_lap,
+        untag_lap,
+        get_time_by_tag,
+        set_lap_color,
+        get_laps_by_color,
+        detect_and_fill_gaps,
+        set_active_project,
+        get_project_totals,
+        set_hourly_rate,
+        get_invoice_data,
+        set_project_for_lap,
+        get_history,
+        get_all_time_bests,
+        get_history_bounds,
+        get_health,
+        get_current_session_context,
+        get_week_summary,
+        set_week_start_day,
+        get_focus_score,
+        get_tracking_consistency_report,
+        set_work_days,
+        set_sync_config,
+        import_days,
+        get_session_recovery_info,
+        trim_laps_below_threshold,
+        get_sessions_for_project,
+        set_keep_display_awake,
+        set_pause_on_display_sleep,
+        set_pause_on_lid_close,
+        set_lap_project_bulk,
+        get_launch_at_login,
+        set_launch_at_login,
+        export_day_to_json,
+        set_auto_start_day,
+        detect_overlapping_laps,
+        set_shortcuts,
+        get_app_uptime_vs_tracked_ratio,
+        annotate_current_lap,
+        export_to_json,
+        get_store_status,
+        unlock_store,
+        enable_encryption,
+        disable_encryption,
+        subscribe_to_state_changes,
+        unsubscribe_from_state_changes,
+        sync_now,
+        get_sync_status,
+        get_comparison,
+        set_max_expected_fragmentation,
+        get_sessions_by_duration_percentile,
+        export_to_toggl_json,
+        export_to_markdown,
+        get_month_summary,
+        export_pdf,
+        copy_day_summary,
+        set_idle_gap_notification,
+        set_idle_threshold,
+        get_productive_hours_report,
+        set_daily_goal,
+        set_goal_notification_enabled,
+        set_overtime_alert,
+        set_break_reminder_minutes,
+        start_pomodoro,
+        set_pomodoro_config,
+        set_monitoring_poll_interval,
+        get_state_file_size,
+        compact_state_file,
+        set_pretty_json,
+        cancel_pomodoro,
+        get_app_breakdown,
+        get_top_apps,
+        request_notification_permission,
+        set_max_continuous_work_alert,
+        set_capture_window_titles,
+        purge_window_titles,
+        add_lap,
+        stop_lap,
+        undo_last_action,
+        check_screen_lock_state,
+        test_screen_lock_detection,
+        #[cfg(debug_assertions)]
+        simulate_screen_lock,
+        #[cfg(debug_assertions)]
+        simulate_screen_unlock,
+        #[cfg(debug_assertions)]
+        simulate_system_sleep,
+        #[cfg(debug_assertions)]
+        simulate_system_wake,
+        handle_system_sleep,
+        handle_system_wake,
+        handle_lid_close,
+        handle_lid_open,
+        handle_user_logout,
+        handle_user_login,
+        start_day_from_notification,
+        show_main_window,
+        get_log_path,
+    ];
+
+    tauri::Builder::default()
+        // Registered first, so a second launch (e.g. for a deep link) exits straight away.
+        .plugin(tauri_plugin_single_instance::init(|app, _, _| open_main_window(app)))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().with_handler(handle_shortcut).build())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            None,
+        ))
+        .manage(app_state.clone())
+        .invoke_handler(move |invoke| {
+            debug!(command = invoke.message.command(), "command invoked");
+            commands(invoke)
+        })
+        .setup(move |app| {
+            let app_handle = app.handle().clone();
+            if let Some(guard) = init_logging(&app_handle) {
+                app.manage(LogGuard(guard));
+            }
+
+            // Run as a menu-bar (accessory) app: no Dock icon, lives in the menu bar.
+            #[cfg(target_os = "macos")]
+            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+
+            // Build the menu-bar tray icon. Left click toggles the popover; a right-click
+            // menu provides the tracking actions, Open (full window) and Quit.
+            {
+                let items = TrayMenuItems {
+                    start_day: MenuItemBuilder::with_id("start_day", "Start Day").build(app)?,
+                    pause: MenuItemBuilder::with_id("pause", "Pause").build(app)?,
+                    resume: MenuItemBuilder::with_id("resume", "Resume").build(app)?,
+                    add_lap: MenuItemBuilder::with_id("add_lap", "Add Lap").build(app)?,
+                    end_day: MenuItemBuilder::with_id("end_day", "End Day").build(app)?,
+                };
+                let open_item = MenuItemBuilder::with_id("open", "Open Screen Time Tracker").build(app)?;
+                let quit_item = MenuItemBuilder::with_id("quit", "Quit").build(app)?;
+                let tray_menu = MenuBuilder::new(app)
+                    .items(&[&items.start_day, &items.pause, &items.resume, &items.add_lap, &items.end_day])
+                    .item(&PredefinedMenuItem::separator(app)?)
+                    .items(&[&open_item, &quit_item])
+                    .build()?;
+                app.manage(items);
+
+                let tray_icon = tauri::image::Image::from_bytes(include_bytes!("../icons/32x32.png"))
+                    .expect("failed to load tray icon");
+
+                TrayIconBuilder::with_id("tray")
+                    .icon(tray_icon)
+                    .tooltip("Screen Time Tracker")
+                    .menu(&tray_menu)
+                    .show_menu_on_left_click(false)
+                    .on_menu_event(|app, event| match event.id().as_ref() {
+                        "open" => open_main_window(app),
+                        action @ ("start_day" | "pause" | "resume" | "add_lap" | "end_day") => {
+                            handle_tray_action(app, action)
+                        }
+                        "quit" => {
+                            // The `ExitRequested` handler in `run()` calls `prevent_exit()`
+                            // to keep the app alive when its windows close — but that also
+                            // swallows a normal `app.exit()`, so Quit never actually quit.
+                            // Persist state, then force the process down directly.
+                            // Stop the background loops first so none of them writes
+                            // behind the final save.
+                            let state = app.state::<AppStateArc>();
+                            let stragglers = state.shutdown.shutdown(Duration::from_secs(2));
+                            if !stragglers.is_empty() {
+                                warn!(?stragglers, "Background threads still running at quit");
+                            }
+                            save_state(app, state.inner());
+                            std::process::exit(0);
+                        }
+                        _ => {}
+                    })
+                    .on_tray_icon_event(|tray, event| {
+                        if let TrayIconEvent::Click { button, button_state, rect, .. } = event {
+                            if button == MouseButton::Left && button_state == MouseButtonState::Up {
+                                toggle_popover(tray.app_handle(), rect);
+                            }
+                        }
+                    })
+                    .build(app)?;
+            }
+
+            refresh_launch_at_login(&app_handle);
+
+            // Load saved state from disk and decide today's session (auto-start / continue / end).
+            // An encrypted store waits for unlock_store instead.
+            if get_app_data_dir(&app_handle).is_ok_and(|dir| encryption::detect(&dir)) {
+                info!("🔐 Data is encrypted; waiting for the passphrase");
+            } else {
+                load_and_initialize(&app_handle, &app_state);
+            }
+
+            #[cfg(target_os = "macos")]
+            install_notification_actions(&app_handle);
+            
+            // Check if we should show startup notification (after system restart)
+            let state_for_notification = app_state.clone();
+            let handle_for_notification = app_handle.clone();
+            app_state.shutdown.spawn("startup-notification", move || {
+                check_and_notify_on_startup(&handle_for_notification, &state_for_notification);
+            });
+            
+            // Start system monitoring when app starts. This single thread handles
+            // screen lock/unlock polling plus suspend-gap detection (previously a second,
+            // redundant macOS-only lock-detection thread ran in parallel — removed to cut
+            // idle CPU, as was a per-second `pmset` sleep check that could never fire).
+            start_system_monitoring(app_handle.clone(), app_state.clone());
+            start_status_ticker(app_handle.clone(), app_state.clone());
+            if let Err(e) = apply_status_server(&app_state) {
+                error!("❌ Could not start the status server: {}", e);
+            }
+            sync::start(app_handle.clone(), app_state.clone());
+            install_deep_links(&app_handle, &app_state);
+            if let Err(e) = apply_shortcuts(&app_handle, &app_state) {
+                error!("❌ Could not register the global shortcuts: {}", e);
+            }
+
+            // Close the open lap at the exact moment the machine sleeps; the gap
+            // detector inside the monitoring loop is the fallback if this is missed.
+            #[cfg(target_os = "macos")]
+            register_sleep_observer(app_handle.clone(), app_state.clone());
+            #[cfg(target_os = "macos")]
+            register_fast_user_switch_observers(app_handle.clone(), app_state.clone());
+            #[cfg(target_os = "macos")]
+            register_display_sleep_observers(app_handle.clone(), app_state.clone());
+            #[cfg(target_os = "macos")]
+            register_lid_observer(app_handle.clone(), app_state.clone());
+
+            // Windows can't be polled for lock state; listen for session change messages.
+            #[cfg(target_os = "windows")]
+            platform::windows::start_session_monitoring(app_handle.clone(), app_state.clone());
+            // Nor can Linux, portably; idle past idle_threshold_secs stands in for a lock.
+            #[cfg(target_os = "linux")]
+            platform::linux::start_idle_monitoring(app_handle.clone(), app_state.clone());
+
+            refresh_tray(&app_handle, &app_state);
+
+            // Start periodic state saving (every 30 seconds)
+            let state_for_autosave = app_state.clone();
+            let handle_for_autosave = app_handle.clone();
+            app_state.shutdown.spawn("autosave", move || {
+                while state_for_autosave.shutdown.sleep(Duration::from_secs(30)) {
+                    state_for_autosave.health.autosave_heartbeat.store(now_unix(), Ordering::Relaxed);
+                    // Catches the day change for a session that never pauses — nobody
+                    // locks the screen or sleeps the Mac, so no unlock event ever fires
+                    // and the cutoff backstop has to be evaluated on a timer. Rolls over
+                    // and saves; otherwise this is just the periodic save.
+                    roll_over_day_if_due(&handle_for_autosave, &state_for_autosave);
+                    enforce_retention(&handle_for_autosave, &state_for_autosave);
+                    save_state(&handle_for_autosave, &state_for_autosave);
+                    // The tray's today total only shows minutes, so every 30s is plenty.
+                    refresh_tray(&handle_for_autosave, &state_for_autosave);
+                }
+            });
+            
+            Ok(())
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app_handle, event| {
+            // The app is a menu-bar app: keep it running even when all windows are
+            // closed. It only quits via the tray's Quit item (app.exit).
+            if let RunEvent::ExitRequested { api, .. } = event {
+                api.prevent_exit();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3600;
+
+    fn lap(start: u64, end: Option<u64>) -> Lap {
+        Lap { duration: end.map(|e| e - start), end_time: end, ..Lap::open(start) }
+    }
+
+    fn day(date: &str, laps: Vec<Lap>) -> DayRecord {
+        let total = laps.iter().filter_map(|l| l.duration).sum();
+        DayRecord { date: date.to_string(), total_duration: total, laps, is_active: false, metadata: HashMap::new(), ended_explicitly: false }
+    }
+
+    // --- rollover decision -------------------------------------------------
+    // `gap` is 0 whenever a lap is open, so "working through" is expressed as gap == 0.
+
+    #[test]
+    fn night_owl_past_midnight_stays_on_the_same_day() {
+        // Friday 21:00 -> Saturday 02:00, still typing. The date changed, but there is no
+        // break and it is nowhere near the cutoff. This is the case that must NOT roll.
+        assert!(!should_roll_over(true, 0, false));
+    }
+
+    #[test]
+    fn short_break_after_midnight_stays_on_the_same_day() {
+        // Stepped away for 30 minutes at 01:00. Still Friday's session.
+        assert!(!should_roll_over(true, 30 * 60, false));
+    }
+
+    #[test]
+    fn slept_overnight_starts_a_new_day() {
+        // Machine asleep from Friday 02:00, back at Saturday 10:00: an 8h gap.
+        assert!(should_roll_over(true, 8 * HOUR, true));
+        // ...and it would roll on the gap alone, even before the cutoff hour.
+        assert!(should_roll_over(true, 8 * HOUR, false));
+    }
+
+    #[test]
+    fn six_hour_gap_is_the_boundary() {
+        assert!(!should_roll_over(true, 6 * HOUR - 1, false));
+        assert!(should_roll_over(true, 6 * HOUR, false));
+    }
+
+    #[test]
+    fn cutoff_rolls_over_a_session_that_never_paused() {
+        // Worked straight through the night with no break at all: gap is 0 forever, so
+        // only the cutoff can end the day. Without this the day_key never rolls, which is
+        // how a Saturday afternoon lap got filed under Friday.
+        assert!(should_roll_over(true, 0, true));
+    }
+
+    #[test]
+    fn same_date_never_rolls_over() {
+        // A long idle stretch within one day (a 7h meeting-free afternoon away from the
+        // desk) must not start a second record for the same date.
+        assert!(!should_roll_over(false, 9 * HOUR, false));
+        assert!(!should_roll_over(false, 0, true));
+    }
+
+    // --- where the day is cut ------------------------------------------------
+
+    #[test]
+    fn worked_through_the_night_is_cut_at_the_cutoff_not_at_wake() {
+        // Open lap running since last night; it is now 09:00 and the cutoff was 06:00.
+        // The old day must end at 06:00, so the pre-dawn hours stay with it and only
+        // 06:00->09:00 counts as today. Cutting at `now` would hand today three hours of
+        // last night's work.
+        let cutoff = 6 * HOUR;
+        let now = 9 * HOUR;
+        assert_eq!(rollover_boundary(true, cutoff, now), cutoff);
+    }
+
+    #[test]
+    fn returning_from_a_break_cuts_at_now() {
+        // No open lap: the user stopped last night and just came back. The old day ended
+        // when they stopped, and today starts now.
+        let cutoff = 6 * HOUR;
+        let now = 10 * HOUR;
+        assert_eq!(rollover_boundary(false, cutoff, now), now);
+    }
+
+    #[test]
+    fn boundary_never_runs_ahead_of_the_clock() {
+        // Guards the case where the cutoff has not been reached yet: the boundary must
+        // never be a future timestamp, or a lap would be given a negative duration.
+        let cutoff = 6 * HOUR;
+        let now = 2 * HOUR;
+        assert_eq!(rollover_boundary(true, cutoff, now), now);
+    }
+
+    // --- backfill of history written before the day rolled over -------------
+
+    // Local wall-clock -> unix, so these tests read as times rather than epoch numbers.
+    fn at(day: u32, hh: u32, mm: u32) -> u64 {
+        use chrono::TimeZone;
+        chrono::Local
+            .with_ymd_and_hms(2026, 7, day, hh, mm, 0)
+            .single()
+            .unwrap()
+            .timestamp() as u64
+    }
+
+    #[test]
+    fn backfill_moves_a_lap_recorded_on_the_wrong_day() {
+        // The real defect: a lap that started at 12:52 the NEXT afternoon, still filed
+        // under the previous day because the running app never re-checked the date.
+        let mut records = HashMap::new();
+        records.insert(
+            "2026-07-10".into(),
+            day("2026-07-10", vec![
+                lap(at(10, 21, 0), Some(at(10, 23, 0))),
+                lap(at(11, 12, 52), Some(at(11, 13, 30))),
+            ]),
+        );
+
+        let moved = backfill_misattributed_laps(&mut records, None);
+
+        assert_eq!(moved, 1);
+        assert_eq!(records["2026-07-10"].laps.len(), 1);
+        assert_eq!(records["2026-07-11"].laps.len(), 1);
+        assert_eq!(records["2026-07-11"].laps[0].start_time, at(11, 12, 52));
+        // Totals must be recomputed on both sides, not just the lap lists.
+        assert_eq!(records["2026-07-10"].total_duration, 2 * 3600);
+        assert_eq!(records["2026-07-11"].total_duration, 38 * 60);
+    }
+
+    #[test]
+    fn backfill_leaves_genuine_late_night_work_alone() {
+        // Worked Friday 21:00 into Saturday 02:23. Those small-hours laps started before
+        // the cutoff, so they belong to Friday — the day they started on. Moving them to
+        // Saturday would be a second bug dressed up as a fix.
+        let mut records = HashMap::new();
+        records.insert(
+            "2026-07-05".into(),
+            day("2026-07-05", vec![
+                lap(at(5, 21, 0), Some(at(5, 23, 30))),
+                lap(at(6, 0, 26), Some(at(6, 2, 23))),
+            ]),
+        );
+
+        let moved = backfill_misattributed_laps(&mut records, None);
+
+        assert_eq!(moved, 0);
+        assert_eq!(records["2026-07-05"].laps.len(), 2);
+        assert!(!records.contains_key("2026-07-06"));
+    }
+
+    #[test]
+    fn backfill_never_touches_the_open_lap() {
+        // The open lap belongs to the live session; re-filing it would orphan the session.
+        let mut records = HashMap::new();
+        let mut today = day("2026-07-14", vec![lap(at(14, 9, 0), None)]);
+        today.is_active = true;
+        records.insert("2026-07-14".into(), today);
+
+        let moved = backfill_misattributed_laps(&mut records, Some("2026-07-14"));
+
+        assert_eq!(moved, 0);
+        assert!(records["2026-07-14"].laps.iter().any(|l| l.duration.is_none()));
+    }
+
+    #[test]
+    fn backfill_drops_a_day_left_empty_but_keeps_the_live_one() {
+        // A record whose only lap was misfiled has no reason to exist afterwards — but the
+        // session's own day must survive even if it is momentarily empty.
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(at(11, 14, 0), Some(at(11, 15, 0)))]));
+        records.insert("2026-07-14".into(), day("2026-07-14", vec![]));
+
+        let moved = backfill_misattributed_laps(&mut records, Some("2026-07-14"));
+
+        assert_eq!(moved, 1);
+        assert!(!records.contains_key("2026-07-10"), "emptied day should be dropped");
+        assert!(records.contains_key("2026-07-11"), "lap should land on the day it happened");
+        assert!(records.contains_key("2026-07-14"), "the live day must survive");
+    }
+
+    #[test]
+    fn backfill_is_idempotent() {
+        // It runs behind a schema_version gate, but a second pass must still be a no-op:
+        // once every lap sits on the day it started, there is nothing left to move.
+        let mut records = HashMap::new();
+        records.insert(
+            "2026-07-10".into(),
+            day("2026-07-10", vec![lap(at(11, 12, 52), Some(at(11, 13, 30)))]),
+        );
+
+        assert_eq!(backfill_misattributed_laps(&mut records, None), 1);
+        assert_eq!(backfill_misattributed_laps(&mut records, None), 0);
+    }
+
+    // --- merge (undo a rollover) -------------------------------------------
+
+    #[test]
+    fn merge_folds_a_day_into_the_previous_one() {
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, Some(400))]));
+        records.insert("2026-07-11".into(), day("2026-07-11", vec![lap(500, Some(700))]));
+
+        let target = merge_records_into_previous(&mut records, "2026-07-11").unwrap();
+
+        assert_eq!(target, "2026-07-10");
+        assert!(!records.contains_key("2026-07-11"), "merged day should stop existing");
+        let merged = &records["2026-07-10"];
+        assert_eq!(merged.laps.len(), 2);
+        assert_eq!(merged.total_duration, 300 + 200);
+        // Laps must come back in chronological order, not append order.
+        assert!(merged.laps[0].start_time < merged.laps[1].start_time);
+    }
+
+    #[test]
+    fn merge_skips_over_days_with_no_record() {
+        // The machine was off on the 12th, so the 13th merges into the 11th.
+        let mut records = HashMap::new();
+        records.insert("2026-07-11".into(), day("2026-07-11", vec![lap(100, Some(200))]));
+        records.insert("2026-07-13".into(), day("2026-07-13", vec![lap(900, Some(950))]));
+
+        let target = merge_records_into_previous(&mut records, "2026-07-13").unwrap();
+        assert_eq!(target, "2026-07-11");
+        assert_eq!(records["2026-07-11"].laps.len(), 2);
+    }
+
+    #[test]
+    fn merge_carries_the_open_lap_and_active_flag() {
+        // Merging today (still being tracked) back into yesterday must keep the day alive
+        // and leave the open lap open, or the running session would be orphaned.
+        let mut records = HashMap::new();
+        records.insert("2026-07-13".into(), day("2026-07-13", vec![lap(100, Some(400))]));
+        let mut today = day("2026-07-14", vec![lap(500, None)]);
+        today.is_active = true;
+        records.insert("2026-07-14".into(), today);
+
+        let target = merge_records_into_previous(&mut records, "2026-07-14").unwrap();
+
+        let merged = &records[&target];
+        assert!(merged.is_active, "absorbing day must stay active");
+        assert!(merged.laps.iter().any(|l| l.duration.is_none()), "open lap must survive");
+        // The open lap contributes nothing to the total until it closes.
+        assert_eq!(merged.total_duration, 300);
+    }
+
+    #[test]
+    fn merge_refuses_when_there_is_no_earlier_day() {
+        let mut records = HashMap::new();
+        records.insert("2026-07-04".into(), day("2026-07-04", vec![lap(100, Some(200))]));
+        assert!(merge_records_into_previous(&mut records, "2026-07-04").is_err());
+        // The record must survive a refused merge.
+        assert!(records.contains_key("2026-07-04"));
+    }
+
+    // --- lap tags ------------------------------------------------------------
+
+    #[test]
+    fn tags_are_normalized_and_deduplicated() {
+        let mut l = lap(100, Some(200));
+        add_tag(&mut l, "  Work ").unwrap();
+        add_tag(&mut l, "WORK").unwrap();
+        assert_eq!(l.tags, vec!["work".to_string()]);
+        assert!(add_tag(&mut l, "   ").is_err());
+    }
+
+    #[test]
+    fn tag_count_per_lap_is_capped() {
+        let mut l = lap(100, Some(200));
+        for i in 0..MAX_TAGS_PER_LAP {
+            add_tag(&mut l, &format!("t{}", i)).unwrap();
+        }
+        assert!(add_tag(&mut l, "one-too-many").is_err());
+        assert!(remove_tag(&mut l, "T0").is_ok());
+        assert!(remove_tag(&mut l, "t0").is_err(), "removing a missing tag should fail");
+    }
+
+    #[test]
+    fn time_by_tag_sums_each_tag_and_buckets_untagged() {
+        let mut tagged = lap(100, Some(400));
+        add_tag(&mut tagged, "work").unwrap();
+        add_tag(&mut tagged, "meeting").unwrap();
+        let mut records = HashMap::new();
+        records.insert(
+            "2026-07-10".into(),
+            day("2026-07-10", vec![tagged, lap(500, Some(600)), lap(700, None)]),
+        );
+        records.insert("2026-07-20".into(), day("2026-07-20", vec![lap(900, Some(999))]));
+
+        let totals = time_by_tag(&records, "2026-07-01", "2026-07-15");
+
+        assert_eq!(totals["work"], 300);
+        assert_eq!(totals["meeting"], 300);
+        // The open lap contributes nothing, and the day outside the range is ignored.
+        assert_eq!(totals[UNTAGGED], 100);
+    }
+
+    // --- lap colours -----------------------------------------------------------
+
+    #[test]
+    fn only_six_digit_hex_colours_are_accepted() {
+        assert_eq!(normalize_color("#ff5733").unwrap(), "#FF5733");
+        assert!(normalize_color("ff5733").is_err());
+        assert!(normalize_color("#fff").is_err());
+        assert!(normalize_color("#GG5733").is_err());
+        assert!(normalize_color("#FF57331").is_err());
+    }
+
+    #[test]
+    fn laps_with_color_reports_day_and_index() {
+        let mut blue = lap(300, Some(400));
+        blue.color = Some("#0000FF".into());
+        let mut records = HashMap::new();
+        records.insert("2026-07-11".into(), day("2026-07-11", vec![lap(100, Some(200)), blue.clone()]));
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![blue]));
+
+        let found = laps_with_color(&records, "#0000FF");
+
+        let keys: Vec<(&str, usize)> = found.iter().map(|(d, i, _)| (d.as_str(), *i)).collect();
+        assert_eq!(keys, vec![("2026-07-10", 0), ("2026-07-11", 1)]);
+    }
+
+    // --- projects --------------------------------------------------------------
+
+    #[test]
+    fn blank_project_names_mean_no_project() {
+        assert_eq!(normalize_project(Some("  Acme  ".into())), Ok(Some("Acme".to_string())));
+        assert_eq!(normalize_project(Some("   ".into())), Ok(None));
+        assert_eq!(normalize_project(None), Ok(None));
+    }
+
+    #[test]
+    fn invoice_groups_laps_by_day_and_project_and_rounds_to_cents() {
+        let mut client = lap(0, Some(1000));
+        client.project = Some("Acme".to_string());
+        let mut synthetic = lap(2000, Some(5600));
+        synthetic.is_synthetic = true;
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".to_string(), day("2026-07-10", vec![client, lap(1000, Some(1200)), synthetic]));
+        records.insert("2026-07-11".to_string(), day("2026-07-11", vec![lap(0, Some(3600)), lap(4000, None)]));
+        records.insert("2026-07-12".to_string(), day("2026-07-12", vec![lap(0, Some(3600))]));
+
+        let invoice = invoice_data(&records, "2026-07-10", "2026-07-11", 50.0, "EUR");
+        assert_eq!(invoice.currency, "EUR");
+        assert_eq!(invoice.total_billable_seconds, 1000 + 200 + 3600);
+        assert_eq!(invoice.total_amount, 66.67);
+        let first = &invoice.line_items[0];
+        assert_eq!((first.date.as_str(), first.billable_seconds, first.amount), ("2026-07-10", 1200, 16.67));
+        let projects: Vec<(&str, u64, f64)> =
+            first.projects.iter().map(|p| (p.project.as_str(), p.billable_seconds, p.amount)).collect();
+        assert_eq!(projects, [("Acme", 1000, 13.89), ("General", 200, 2.78)]);
+        assert_eq!(invoice.line_items.len(), 2);
+    }
+
+    #[test]
+    fn invoice_keeps_a_project_named_general_apart_from_unassigned_time() {
+        let mut named = lap(0, Some(600));
+        named.project = Some("General".to_string());
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".to_string(), day("2026-07-10", vec![named, lap(600, Some(900))]));
+
+        let invoice = invoice_data(&records, "2026-07-10", "2026-07-10", 60.0, "USD");
+        let seconds: Vec<u64> = invoice.line_items[0].projects.iter().map(|p| p.billable_seconds).collect();
+        assert_eq!(seconds, [600, 300]);
+    }
+
+    #[test]
+    fn project_names_reject_csv_breaking_characters() {
+        for bad in ["Acme, Inc", "The \"Big\" One", "two\nlines"] {
+            assert!(normalize_project(Some(bad.into())).is_err(), "{:?}", bad);
         }
-        
-        // Mark as paused by system (not user)
+        assert!(normalize_project(Some("Acme Inc.".into())).is_ok());
+    }
+
+    #[test]
+    fn project_totals_bucket_laps_without_a_project() {
+        let mut acme = lap(100, Some(400));
+        acme.project = Some("Acme".into());
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![acme, lap(500, Some(550))]));
+
+        let totals = project_totals(&records, "2026-07-10", "2026-07-10");
+
+        assert_eq!(totals["Acme"], 300);
+        assert_eq!(totals[NO_PROJECT], 50);
+    }
+
+    fn session_on(day_key: &str, project: Option<&str>) -> CurrentSession {
+        CurrentSession {
+            day_key: day_key.to_string(),
+            current_lap_start_timestamp: 0,
+            accumulated_seconds: 0,
+            is_paused: false,
+            user_paused: false,
+            project: project.map(str::to_string),
+            continuous_work_start: None,
+            continuous_work_alerted: false,
+            break_reminders_sent: 0,
+            paused_cause: None,
+            paused_since: None,
+            overrides: DaySessionOverrides::default(),
+            template: None,
+        }
+    }
+
+    #[test]
+    fn switching_project_splits_the_running_lap() {
+        let mut session = session_on("2026-07-10", Some("Acme"));
+        let mut records = HashMap::new();
+        let mut running = lap(100, None);
+        running.project = Some("Acme".into());
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![running]));
+
+        session.project = Some("Globex".into());
+        roll_to_next_lap(&mut session, &mut records, 400);
+
+        let laps = &records["2026-07-10"].laps;
+        assert_eq!(laps.len(), 2);
+        assert_eq!(laps[0].duration, Some(300));
+        assert_eq!(laps[0].project.as_deref(), Some("Acme"), "closed lap keeps the old project");
+        assert_eq!(laps[1].start_time, 400, "new lap starts exactly where the old one ended");
+        assert_eq!(laps[1].project.as_deref(), Some("Globex"));
+        assert_eq!(session.current_lap_start_timestamp, 400);
+    }
+
+    #[test]
+    fn next_lap_drops_a_running_lap_of_a_second_or_less() {
+        let mut session = session_on("2026-07-10", None);
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, Some(200)), lap(300, None)]));
+
+        roll_to_next_lap(&mut session, &mut records, 301);
+
+        let laps = &records["2026-07-10"].laps;
+        assert_eq!(laps.len(), 2);
+        assert_eq!(laps.iter().filter(|l| l.duration.is_none()).count(), 1, "only one open lap");
+        assert_eq!(laps[1].start_time, 301);
+    }
+
+    // --- per-app breakdown -----------------------------------------------------
+
+    #[test]
+    fn app_time_is_credited_only_to_a_running_lap() {
+        let mut session = session_on("2026-07-10", None);
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, None)]));
+
+        credit_app_time(&session, &mut records, "Xcode", 1);
+        credit_app_time(&session, &mut records, "Xcode", 2);
+        session.is_paused = true;
+        credit_app_time(&session, &mut records, "Slack", 5);
+
+        let breakdown = &records["2026-07-10"].laps[0].app_breakdown;
+        assert_eq!(breakdown["Xcode"], 3);
+        assert!(!breakdown.contains_key("Slack"), "nothing is sampled while paused");
+    }
+
+    #[test]
+    fn utc_offsets_parse_in_common_spellings() {
+        assert_eq!(parse_utc_offset("Z").unwrap().local_minus_utc(), 0);
+        assert_eq!(parse_utc_offset("utc").unwrap().local_minus_utc(), 0);
+        assert_eq!(parse_utc_offset("+02:00").unwrap().local_minus_utc(), 7200);
+        assert_eq!(parse_utc_offset("-0530").unwrap().local_minus_utc(), -(5 * 3600 + 30 * 60));
+        assert!(parse_utc_offset("02:00").is_err());
+        assert!(parse_utc_offset("+2").is_err());
+        assert!(parse_utc_offset("+01:75").is_err());
+    }
+
+    #[test]
+    fn toggl_export_skips_open_laps_and_orders_by_start() {
+        let mut records = HashMap::new();
+        let mut tagged = lap(1_783_000_000, Some(1_783_003_600));
+        tagged.tags = vec!["work".into()];
+        tagged.note = Some("Planning".into());
+        tagged.window_titles = vec!["secret.txt".into()];
+        records.insert("2026-07-02".into(), day("2026-07-02", vec![tagged, lap(1_783_010_000, None)]));
+        records.insert("2026-07-01".into(), day("2026-07-01", vec![lap(1_782_900_000, Some(1_782_900_600))]));
+
+        let entries = toggl_entries(&records, 42, Some(7), parse_utc_offset("+02:00").unwrap());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["duration"], 600);
+        assert_eq!(entries[1]["duration"], 3600);
+        assert_eq!(entries[1]["description"], "Planning");
+        assert_eq!(entries[1]["workspace_id"], 42);
+        assert_eq!(entries[1]["project_id"], 7);
+        assert_eq!(entries[1]["tags"][0], "work");
+        assert!(entries[1]["start"].as_str().unwrap().ends_with("+02:00"));
+        assert!(!entries[1].to_string().contains("secret.txt"));
+    }
+
+    #[test]
+    fn goal_progress_is_none_without_a_goal() {
+        assert_eq!(goal_progress(None, 3600), None);
+        assert_eq!(goal_progress(Some(0), 3600), None);
+        assert_eq!(goal_progress(Some(7200), 3600), Some((50.0, 3600)));
+        assert_eq!(goal_progress(Some(3600), 5400), Some((150.0, 0)));
+    }
+
+    #[test]
+    fn hourly_totals_split_laps_at_hour_boundaries() {
+        // 2026-07-02 14:40:00 UTC.
+        let start = 1_783_003_200;
+        let mut records = HashMap::new();
+        records.insert("2026-07-02".into(), day("2026-07-02", vec![
+            lap(start, Some(start + 90 * 60)),
+            lap(start + 100 * 60, None),
+        ]));
+
+        let buckets = hourly_totals(&records, "2026-07-01", "2026-07-03", &chrono::Utc);
+        assert_eq!(buckets[14], 20 * 60);
+        assert_eq!(buckets[15], 60 * 60);
+        assert_eq!(buckets[16], 10 * 60);
+        assert_eq!(buckets.iter().sum::<u64>(), 90 * 60);
+
+        assert_eq!(hourly_totals(&records, "2026-07-03", "2026-07-04", &chrono::Utc).iter().sum::<u64>(), 0);
+    }
+
+    #[test]
+    fn laps_are_split_at_the_work_window_edges() {
+        // 2026-07-02 14:40:00 UTC, running to 18:10.
+        let start = 1_783_003_200;
+        let end = start + 210 * 60;
+        let segments = out_of_hours_segments(start, end, 9, 17, &chrono::Utc);
+        assert_eq!(segments, [(start + 140 * 60, end)]);
+
+        let config = TrackerConfig { work_start_hour: 9, work_end_hour: 17, ..TrackerConfig::default() };
+        let record = day("2026-07-02", vec![lap(start, Some(end)), lap(end, None)]);
+        assert_eq!(work_window_split(&record, &config, &chrono::Utc), (140 * 60, 70 * 60));
+
+        // Entirely inside the window.
+        assert!(out_of_hours_segments(start, start + 600, 9, 17, &chrono::Utc).is_empty());
+    }
+
+    #[test]
+    fn productivity_report_picks_peak_hour_and_best_block() {
+        let mut buckets = [0u64; 24];
+        buckets[9] = 3000;
+        buckets[10] = 3600;
+        buckets[11] = 3000;
+        buckets[12] = 500;
+        buckets[14] = 3600;
+        buckets[15] = 3500;
+
+        let report = productivity_report(buckets);
+        assert_eq!(report.peak_hour, 10);
+        assert_eq!(report.peak_hour_total_seconds, 3600);
+        assert_eq!(report.hourly_buckets.len(), 24);
+        assert_eq!(report.recommended_focus_window, (9, 13));
+    }
+
+    #[test]
+    fn poll_interval_defaults_and_is_clamped() {
+        let config: TrackerConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.poll_interval(), Duration::from_millis(DEFAULT_POLL_INTERVAL_MS));
+
+        let config = TrackerConfig { monitoring_poll_interval_ms: 20, ..Default::default() };
+        assert_eq!(config.poll_interval(), Duration::from_millis(100));
+        let config = TrackerConfig { monitoring_poll_interval_ms: 60_000, ..Default::default() };
+        assert_eq!(config.poll_interval(), Duration::from_millis(5000));
+    }
+
+    #[test]
+    fn tray_shows_todays_total_and_state() {
+        let mut session = session_on("2026-07-10", None);
+        session.current_lap_start_timestamp = now_unix();
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(0, Some(3900))]));
+        let config = TrackerConfig::default();
+
+        let (tray_state, title, tooltip) = tray_display(None);
+        assert_eq!(tray_state, TrayState::NotStarted);
+        assert!(title.is_empty());
+        assert!(tooltip.contains("not started"));
+
+        let active = current_status(&session, &records, &config, None, 0);
+        let (tray_state, title, _) = tray_display(Some(&active));
+        assert_eq!(tray_state, TrayState::Active);
+        assert_eq!(title, "1h 05m");
+
+        session.is_paused = true;
+        let paused = current_status(&session, &records, &config, None, 0);
+        let (tray_state, _, tooltip) = tray_display(Some(&paused));
+        assert_eq!(tray_state, TrayState::Paused);
+        assert_eq!(tooltip, "Screen Time Tracker: 1h 05m today (paused)");
+    }
+
+    #[test]
+    fn session_overrides_take_precedence_over_config() {
+        let config = TrackerConfig {
+            daily_goal_secs: Some(8 * HOUR),
+            max_continuous_work_secs: Some(HOUR),
+            ..TrackerConfig::default()
+        };
+        let mut session = session_on("2026-07-10", None);
+        let status = current_status(&session, &HashMap::new(), &config, None, 0);
+        assert_eq!(status.goal_seconds, Some(8 * HOUR));
+        assert_eq!(status.effective_gap_threshold_secs, SUSPEND_GAP_THRESHOLD_SECS);
+
+        session.overrides = DaySessionOverrides {
+            daily_goal_seconds: Some(6 * HOUR),
+            gap_threshold_secs: Some(60),
+            max_continuous_work_secs: Some(0), // no break alerts today
+        };
+        let status = current_status(&session, &HashMap::new(), &config, None, 0);
+        assert_eq!(status.goal_seconds, Some(6 * HOUR));
+        assert_eq!(status.effective_max_continuous_work_secs, None);
+        assert_eq!(status.effective_gap_threshold_secs, 60);
+    }
+
+    #[test]
+    fn status_reports_why_and_since_when_the_session_is_paused() {
+        let mut session = session_on("2026-07-10", None);
+        let records = HashMap::new();
+        let config = TrackerConfig::default();
+
+        session.pause(PauseCause::Lock, 1_000);
+        let status = current_status(&session, &records, &config, None, 0);
+        assert_eq!(status.paused_cause, Some(PauseCause::Lock));
+        assert_eq!(status.paused_since, Some(1_000));
+        assert!(!status.user_paused);
+        assert_eq!(serde_json::to_value(status.paused_cause).unwrap(), "lock");
+
+        // Resuming by any route clears all three, including a user pause.
+        session.pause(PauseCause::User, 2_000);
+        assert!(session.user_paused);
+        roll_to_next_lap(&mut session, &mut HashMap::new(), 2_500);
+        let status = current_status(&session, &records, &config, None, 0);
+        assert_eq!((status.paused_cause, status.paused_since, status.user_paused), (None, None, false));
+    }
+
+    #[test]
+    fn tray_menu_offers_only_applicable_actions() {
+        // (start day, pause, resume, add lap, end day)
+        assert_eq!(tray_actions_enabled(TrayState::NotStarted), [true, false, false, false, false]);
+        assert_eq!(tray_actions_enabled(TrayState::Active), [false, true, false, true, true]);
+        assert_eq!(tray_actions_enabled(TrayState::Paused), [false, false, true, false, true]);
+    }
+
+    #[test]
+    fn pomodoro_cycles_through_short_and_long_breaks() {
+        let config = PomodoroConfig {
+            work_seconds: 25 * 60,
+            short_break_seconds: 5 * 60,
+            long_break_seconds: 15 * 60,
+            long_break_every: 2,
+        };
+        let mut pomodoro = PomodoroState::new(config);
+        assert_eq!(pomodoro.tag(), POMODORO_WORK_TAG);
+        assert!(!pomodoro.advance(25 * 60 - 1));
+        assert!(pomodoro.advance(1));
+        assert_eq!(pomodoro.phase, PomodoroPhase::ShortBreak);
+        assert_eq!(pomodoro.phase_remaining_seconds, 5 * 60);
+        assert_eq!(pomodoro.tag(), POMODORO_BREAK_TAG);
+
+        assert!(pomodoro.advance(5 * 60));
+        assert_eq!(pomodoro.phase, PomodoroPhase::Work);
+        assert!(pomodoro.advance(25 * 60));
+        assert_eq!(pomodoro.phase, PomodoroPhase::LongBreak);
+        assert_eq!(pomodoro.phase_remaining_seconds, 15 * 60);
+        assert_eq!(pomodoro.completed_work_intervals, 2);
+    }
+
+    #[test]
+    fn pomodoro_labels_only_the_running_lap() {
+        let session = session_on("2026-07-10", None);
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, None)]));
+        let mut pomodoro = PomodoroState::new(PomodoroConfig::default());
+
+        label_pomodoro_lap(&session, &mut records, &pomodoro);
+        label_pomodoro_lap(&session, &mut records, &pomodoro);
+        let running = &records["2026-07-10"].laps[0];
+        assert_eq!(running.tags, vec![POMODORO_WORK_TAG.to_string()]);
+        assert_eq!(running.note, None);
+
+        pomodoro.advance(pomodoro.phase_remaining_seconds);
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, None)]));
+        label_pomodoro_lap(&session, &mut records, &pomodoro);
+        assert_eq!(records["2026-07-10"].laps[0].note.as_deref(), Some(POMODORO_BREAK_NOTE));
+
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, Some(200))]));
+        label_pomodoro_lap(&session, &mut records, &pomodoro);
+        assert!(records["2026-07-10"].laps[0].tags.is_empty());
+    }
+
+    #[test]
+    fn break_reminders_repeat_every_interval() {
+        let interval = 50 * 60;
+        assert_eq!(break_reminders_due(interval - 1, interval, 0), None);
+        assert_eq!(break_reminders_due(interval, interval, 0), Some(1));
+        assert_eq!(break_reminders_due(interval + 600, interval, 1), None);
+        assert_eq!(break_reminders_due(2 * interval, interval, 1), Some(2));
+        // Reopening the app three intervals into a lap sends one catch-up reminder.
+        assert_eq!(break_reminders_due(3 * interval + 5, interval, 0), Some(3));
+        assert_eq!(break_reminders_due(10 * interval, 0, 0), None);
+    }
+
+    #[test]
+    fn opening_a_lap_resets_break_reminders() {
+        let mut session = session_on("2026-07-10", None);
+        session.break_reminders_sent = 2;
+        session.open_lap(1000);
+        assert_eq!(session.break_reminders_sent, 0);
+    }
+
+    #[test]
+    fn overtime_alert_fires_once_and_only_while_active() {
+        let config = TrackerConfig { overtime_alert_seconds: Some(9 * 3600), ..Default::default() };
+        assert!(!overtime_alert_due(&config, "2026-07-10", 9 * 3600 - 1, true));
+        assert!(overtime_alert_due(&config, "2026-07-10", 9 * 3600, true));
+        // Past the cap while paused (e.g. the cap was lowered during a break): wait for work.
+        assert!(!overtime_alert_due(&config, "2026-07-10", 10 * 3600, false));
+
+        let fired = TrackerConfig { overtime_notified_day: Some("2026-07-10".into()), ..config.clone() };
+        assert!(!overtime_alert_due(&fired, "2026-07-10", 10 * 3600, true));
+        assert!(overtime_alert_due(&fired, "2026-07-11", 9 * 3600, true));
+        assert!(!overtime_alert_due(&TrackerConfig::default(), "2026-07-11", 99_999, true));
+    }
+
+    #[test]
+    fn worked_today_counts_the_running_lap_only_while_active() {
+        let mut session = session_on("2026-07-10", None);
+        session.current_lap_start_timestamp = 1000;
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(0, Some(600)), lap(1000, None)]));
+
+        assert_eq!(worked_today(&session, &records, 1300), 900);
         session.is_paused = true;
-        session.user_paused = false; // System paused, not user
+        assert_eq!(worked_today(&session, &records, 1300), 600);
+    }
+
+    #[test]
+    fn goal_notification_fires_once_per_day() {
+        let mut config = TrackerConfig { daily_goal_secs: Some(3600), ..Default::default() };
+        assert!(!goal_notification_due(&config, config.daily_goal_secs, "2026-07-10", 3599));
+        assert!(goal_notification_due(&config, config.daily_goal_secs, "2026-07-10", 3600));
+
+        config.goal_notified_day = Some("2026-07-10".into());
+        assert!(!goal_notification_due(&config, config.daily_goal_secs, "2026-07-10", 7200));
+        // A new day key means the previous day's notification no longer counts.
+        assert!(goal_notification_due(&config, config.daily_goal_secs, "2026-07-11", 3600));
+
+        config.goal_notification_muted = true;
+        assert!(!goal_notification_due(&config, config.daily_goal_secs, "2026-07-11", 3600));
+        assert!(!goal_notification_due(&TrackerConfig::default(), None, "2026-07-11", 99_999));
     }
-    
-    // Release locks before saving
-    drop(session_guard);
-    drop(records_guard);
-    
-    // Save state
-    save_state(app_handle, state);
-}
 
-fn handle_screen_unlock_direct(app_handle: &AppHandle, state: &AppStateArc) {
-    // The user is back. If they were away long enough (or it is past the cutoff) and the
-    // date has changed, close out the previous day first — otherwise the lap we are about
-    // to open would be filed under the day they started, which is how Saturday's work
-    // ended up counted as Friday's. This must run before the lap is pushed below.
-    roll_over_day_if_due(app_handle, state);
+    #[test]
+    fn week_summary_counts_days_meeting_the_goal() {
+        let mut records = HashMap::new();
+        // 2026-07-06 is a Monday.
+        records.insert("2026-07-06".into(), day("2026-07-06", vec![lap(0, Some(7200))]));
+        records.insert("2026-07-08".into(), day("2026-07-08", vec![lap(0, Some(3600))]));
+        records.insert("2026-07-12".into(), day("2026-07-12", vec![lap(0, Some(1800)), lap(2000, Some(7400))]));
+        records.insert("2026-07-13".into(), day("2026-07-13", vec![lap(0, Some(9000))]));
+
+        let wednesday = chrono::NaiveDate::from_ymd_opt(2026, 7, 8).unwrap();
+        let summary = week_summary(&records, wednesday, 0, Some(7200));
+        assert_eq!(summary.week_key, "2026-W28");
+        assert_eq!(summary.week_start, "2026-07-06");
+        assert_eq!(summary.week_end, "2026-07-12");
+        assert_eq!(summary.daily_totals.len(), 7);
+        assert_eq!(summary.total_seconds, 7200 + 3600 + 7200);
+        assert_eq!(summary.days_tracked, 3);
+        assert_eq!(summary.days_goal_met, Some(2));
+
+        assert_eq!(week_summary(&records, wednesday, 0, None).days_goal_met, None);
+    }
 
-    let mut session_guard = state.current_session.lock().unwrap();
-    let mut records_guard = state.day_records.lock().unwrap();
+    #[test]
+    fn markdown_report_lists_every_day_and_totals() {
+        let mut noted = lap(9 * HOUR, Some(9 * HOUR + 3725));
+        noted.note = Some("Design | review".to_string());
+        noted.tags = vec!["client".to_string(), "ui".to_string()];
+        noted.window_titles = vec!["Figma".to_string()];
+        let mut records = HashMap::new();
+        records.insert("1970-01-01".to_string(), day("1970-01-01", vec![noted, lap(11 * HOUR, Some(11 * HOUR + 60))]));
+        records.insert("1970-01-03".to_string(), day("1970-01-03", vec![lap(49 * HOUR, Some(50 * HOUR))]));
+        let from = chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        let to = chrono::NaiveDate::from_ymd_opt(1970, 1, 3).unwrap();
+
+        let md = markdown_report(&records, from, to, false, &chrono::Utc);
+        assert!(md.starts_with("# Time report: 1970-01-01 to 1970-01-03\n"));
+        assert!(md.contains("| 09:00 | 1:02:05 | Design \\| review | client;ui |\n"));
+        assert!(md.contains("| 11:00 | 0:01:00 | — |  |\n"));
+        assert!(md.contains("**Day total:** 1:03:05"));
+        assert!(md.contains("## Friday, 1970-01-02\n\nNo activity"));
+        assert!(md.ends_with("**Grand total:** 2:03:05\n"));
+        assert!(!md.contains("Figma"));
+
+        assert!(markdown_report(&records, from, to, true, &chrono::Utc).contains("| client;ui | Figma |"));
+    }
 
-    if let Some(session) = session_guard.as_mut() {
-        // Skip if already active (prevent duplicate events)
-        if !session.is_paused {
-            return;
+    #[test]
+    fn idle_gap_is_reported_only_past_the_threshold() {
+        assert_eq!(idle_gap_to_report(Some(1000), 2200, Some(1200)), Some(1200));
+        assert_eq!(idle_gap_to_report(Some(1000), 2199, Some(1200)), None);
+        assert_eq!(idle_gap_to_report(Some(1000), 9000, None), None, "off by default");
+        assert_eq!(idle_gap_to_report(None, 9000, Some(60)), None, "no earlier lap to measure from");
+    }
+
+    #[test]
+    fn sunday_first_weeks_use_a_date_range_key() {
+        let mut records = HashMap::new();
+        records.insert("2026-07-12".into(), day("2026-07-12", vec![lap(0, Some(1800))]));
+        records.insert("2026-07-13".into(), day("2026-07-13", vec![lap(0, Some(900))]));
+
+        // Sunday 2026-07-12 opens its own week rather than closing the previous one.
+        for date in [(2026, 7, 12), (2026, 7, 15), (2026, 7, 18)] {
+            let date = chrono::NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap();
+            let summary = week_summary(&records, date, 6, None);
+            assert_eq!(summary.week_key, "2026-07-12/2026-07-18");
+            assert_eq!(summary.daily_totals[0], ("2026-07-12".to_string(), 1800));
+            assert_eq!(summary.total_seconds, 2700);
         }
-        
-        // Only auto-start a new lap if user didn't manually pause
-        // If user manually paused, respect their choice and don't auto-resume
-        if !session.user_paused {
-            let now = Instant::now();
-            let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-            
-            // Start new lap
-            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-                day_record.laps.push(Lap {
-                    start_time: current_time,
-                    end_time: None,
-                    duration: None,
-                });
-                
-            }
-            
-            // Reset lap tracking and resume
-            session.current_lap_start = now;
-            session.current_lap_start_timestamp = current_time;
-            session.accumulated_seconds = 0;
-            session.last_activity_time = now;
-            session.is_paused = false; // Resume active tracking
+    }
+
+    #[test]
+    fn duration_percentile_keeps_days_between_the_bounds_newest_first() {
+        let mut records = HashMap::new();
+        for (date, secs) in [("2026-07-01", 1000), ("2026-07-02", 2000), ("2026-07-03", 3000), ("2026-07-04", 4000), ("2026-07-05", 5000)] {
+            records.insert(date.to_string(), day(date, vec![lap(0, Some(secs))]));
         }
+        // p25 = 2000, p75 = 4000 over five evenly spaced days.
+        let dates: Vec<String> = records_in_duration_percentile(&records, 25.0, 75.0)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.date)
+            .collect();
+        assert_eq!(dates, ["2026-07-04", "2026-07-03", "2026-07-02"]);
+
+        assert!(records_in_duration_percentile(&records, 75.0, 25.0).is_err());
+        assert!(records_in_duration_percentile(&records, -1.0, 50.0).is_err());
+        records.remove("2026-07-05");
+        records.remove("2026-07-04");
+        assert!(records_in_duration_percentile(&records, 0.0, 100.0).is_err());
     }
-    
-    // Release locks before saving
-    drop(session_guard);
-    drop(records_guard);
-    
-    // Save state
-    save_state(app_handle, state);
-}
 
-// Close the currently-open lap because the machine is going (or went) to sleep.
-// `end_ts` is when the lap should end: "now" when called from the NSWorkspace
-// willSleep observer (delivered just before the process suspends), or the last
-// pre-gap poll timestamp when called from the monitoring loop's gap detector
-// after wake. It must never be the wake time — ending the lap at wake is exactly
-// what counted a whole night's sleep as one giant active lap.
-fn handle_system_suspend_direct(app_handle: &AppHandle, state: &AppStateArc, end_ts: u64) {
-    let mut session_guard = state.current_session.lock().unwrap();
-    let mut records_guard = state.day_records.lock().unwrap();
+    #[test]
+    fn gaps_above_the_threshold_become_synthetic_laps_outside_the_total() {
+        let mut record = day("2026-07-10", vec![lap(0, Some(600)), lap(700, Some(1300)), lap(3100, Some(3700)), lap(3700, None)]);
+        assert_eq!(fill_gaps(&mut record, 300, "Break"), 1);
+
+        assert_eq!(record.laps.len(), 5);
+        let gap = &record.laps[2];
+        assert!(gap.is_synthetic);
+        assert_eq!((gap.start_time, gap.end_time, gap.duration), (1300, Some(3100), Some(1800)));
+        assert_eq!(gap.note.as_deref(), Some("Break"));
+        assert_eq!(record.total_duration, 1800);
+        assert_eq!(time_by_tag(&HashMap::from([("2026-07-10".to_string(), record.clone())]), "2026-07-10", "2026-07-10")[UNTAGGED], 1800);
+
+        // The gap is closed now, so a second pass is a no-op.
+        assert_eq!(fill_gaps(&mut record, 300, "Break"), 0);
+    }
 
-    let mut changed = false;
-    if let Some(session) = session_guard.as_mut() {
-        // Skip if already paused (locked before sleep, user pause, or the willSleep
-        // observer already closed the lap and this is the gap detector re-firing).
-        if !session.is_paused {
-            if let Some(day_record) = records_guard.get_mut(&session.day_key) {
-                if let Some(last_lap) = day_record.laps.last_mut() {
-                    if last_lap.duration.is_none() {
-                        // Guard against an end_ts that is somehow before the lap start.
-                        let end = end_ts.max(last_lap.start_time);
-                        last_lap.end_time = Some(end);
-                        last_lap.duration = Some(end - last_lap.start_time);
-                    }
-                }
-                day_record.total_duration = day_record.laps.iter()
-                    .filter_map(|lap| lap.duration)
-                    .sum();
-            }
+    #[test]
+    fn monitor_tick_accumulates_only_while_active_and_never_across_a_gap() {
+        let mut session = session_on("2026-07-10", None);
+        advance_active_time(&mut session, 1, false);
+        advance_active_time(&mut session, 2, false);
+        assert_eq!(session.accumulated_seconds, 3);
 
-            // Mark session as paused by system (not user)
-            session.is_paused = true;
-            session.user_paused = false;
-            changed = true;
-        }
+        session.is_paused = true;
+        advance_active_time(&mut session, 1, false);
+        assert_eq!(session.accumulated_seconds, 3);
     }
 
-    // Release locks before saving
-    drop(session_guard);
-    drop(records_guard);
+    #[test]
+    fn lock_right_after_an_idle_gap_keeps_only_pre_gap_time() {
+        // Lap opened at 1000, last monitor tick at 1300, then nothing until 5000 and the
+        // screen locks on the first tick after waking.
+        let mut session = session_on("2026-07-10", None);
+        session.current_lap_start_timestamp = 1000;
+        advance_active_time(&mut session, 300, false);
+
+        // The gap handler closes the lap at the pre-gap tick and pauses the session...
+        let mut record = day("2026-07-10", vec![lap(1000, None)]);
+        finalize_dangling_lap(&mut record, 1300, None);
+        session.is_paused = true;
+        advance_active_time(&mut session, 3700, true);
+
+        // ...so neither the lap nor the accumulator picks up the 3700s gap, and the lock
+        // handler finds nothing left to close.
+        assert_eq!(record.laps[0].duration, Some(300));
+        assert_eq!(record.total_duration, 300);
+        assert_eq!(session.accumulated_seconds, 300);
+        let status = current_status(&session, &HashMap::from([("2026-07-10".to_string(), record)]), &TrackerConfig::default(), None, 0);
+        assert_eq!((status.current_lap_duration, status.total_session_duration), (0, 300));
+    }
 
-    if changed {
-        save_state(app_handle, state);
+    #[test]
+    fn archiving_groups_old_days_by_month_and_spares_the_live_day() {
+        let mut records = HashMap::new();
+        for date in ["2026-05-30", "2026-05-02", "2026-06-14", "2026-06-20", "2026-07-01"] {
+            records.insert(date.to_string(), day(date, vec![lap(0, Some(60))]));
+        }
+        // A day left marked active (a session that was never ended) is not archived either.
+        let mut stale = day("2026-05-15", vec![lap(0, None)]);
+        stale.is_active = true;
+        records.insert("2026-05-15".to_string(), stale);
+        let by_month = archivable_records(&records, "2026-06-20", Some("2026-06-14"));
+        let summary: Vec<(&str, Vec<&str>)> = by_month
+            .iter()
+            .map(|(month, days)| (month.as_str(), days.iter().map(|r| r.date.as_str()).collect()))
+            .collect();
+        assert_eq!(summary, [("2026-05", vec!["2026-05-02", "2026-05-30"])]);
     }
-}
 
-fn check_screen_lock_state_sync() -> Result<bool, String> {
-    // Use the macOS-specific detection method
-    #[cfg(target_os = "macos")]
-    {
-        return check_macos_screen_lock_state();
+    #[test]
+    fn autosave_archives_up_to_the_later_of_the_two_cutoffs() {
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 7, 20).unwrap();
+        assert_eq!(retention_cutoff(today, None, false), None);
+        assert_eq!(retention_cutoff(today, Some(90), false).as_deref(), Some("2026-04-21"));
+        assert_eq!(retention_cutoff(today, None, true).as_deref(), Some("2026-07-01"));
+        assert_eq!(retention_cutoff(today, Some(90), true).as_deref(), Some("2026-07-01"));
+        assert_eq!(retention_cutoff(today, Some(5), true).as_deref(), Some("2026-07-15"));
     }
-    
-    // For non-macOS systems, return false
-    #[cfg(not(target_os = "macos"))]
-    Ok(false)
-}
 
-#[cfg(target_os = "macos")]
-fn check_macos_screen_lock_state() -> Result<bool, String> {
-    // Method 1: Use native Cocoa/Objective-C to check session state
-    unsafe {
-        let ws_class = class!(NSWorkspace);
-        let shared_workspace: id = msg_send![ws_class, sharedWorkspace];
-        
-        // Check if screen is locked by looking at the active space
-        // When locked, we won't be able to get active application
-        let active_app: id = msg_send![shared_workspace, frontmostApplication];
-        if active_app == nil {
-            return Ok(true); // No frontmost app usually means locked
-        }
-        
-        // Get the localized name of the frontmost application
-        let app_name: id = msg_send![active_app, localizedName];
-        if app_name != nil {
-            let name_str: *const i8 = msg_send![app_name, UTF8String];
-            if !name_str.is_null() {
-                let name = std::ffi::CStr::from_ptr(name_str).to_string_lossy();
-                // If loginwindow or ScreenSaverEngine is frontmost, screen is locked
-                if name == "loginwindow" || name == "ScreenSaverEngine" {
-                    return Ok(true);
-                }
-            }
-        }
+    #[test]
+    fn merging_into_an_archive_keeps_the_newer_copy_of_a_day() {
+        let existing = vec![day("2026-05-02", vec![lap(0, Some(60))]), day("2026-05-09", vec![])];
+        let incoming = vec![day("2026-05-02", vec![lap(0, Some(90))]), day("2026-05-01", vec![])];
+        let merged = merge_archive(existing, incoming);
+        let dates: Vec<&str> = merged.iter().map(|r| r.date.as_str()).collect();
+        assert_eq!(dates, ["2026-05-01", "2026-05-02", "2026-05-09"]);
+        assert_eq!(merged[1].total_duration, 90);
     }
-    
-    // Method 2: Check for screen saver process
-    let screensaver_output = Command::new("pgrep")
-        .arg("-x")
-        .arg("ScreenSaverEngine")
-        .output()
-        .map_err(|e| e.to_string())?;
-    
-    if !screensaver_output.stdout.is_empty() {
-        return Ok(true);
+
+    #[test]
+    fn errors_serialise_with_a_stable_code_and_readable_message() {
+        let json = serde_json::to_value(TrackerError::AlreadyTracking).unwrap();
+        assert_eq!(json["code"], "already_tracking");
+        assert_eq!(json["message"], "Already tracking today's session");
+
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".to_string(), day("2026-07-10", vec![lap(0, Some(60))]));
+        let err = lap_at_mut(&mut records, "2026-07-10", 3).unwrap_err();
+        assert_eq!(err.code(), "lap_index_out_of_range");
+        assert_eq!(err.to_string(), "No lap 3 on 2026-07-10");
+        assert_eq!(lap_at_mut(&mut records, "2026-07-11", 0).unwrap_err().code(), "day_not_found");
     }
 
-    // Methods 1 (native NSWorkspace) and 2 (pgrep) above are sufficient to detect a
-    // locked screen. We intentionally do NOT shell out to `osascript` here: this runs
-    // on every poll while UNLOCKED (the common case), and an AppleScript spawn each
-    // cycle was the single biggest source of idle CPU.
-    Ok(false)
-}
+    #[test]
+    fn window_titles_are_deduplicated_and_capped_per_lap() {
+        let session = session_on("2026-07-10", None);
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, None)]));
 
-// Get system uptime in seconds (macOS)
-#[cfg(target_os = "macos")]
-fn get_system_uptime() -> Result<u64, String> {
-    let output = Command::new("sysctl")
-        .arg("-n")
-        .arg("kern.boottime")
-        .output()
-        .map_err(|e| e.to_string())?;
-    
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    // Parse boot time from output like: { sec = 1234567890, usec = 0 }
-    if let Some(sec_str) = output_str.split("sec = ").nth(1) {
-        if let Some(boot_time_str) = sec_str.split(',').next() {
-            if let Ok(boot_time) = boot_time_str.trim().parse::<u64>() {
-                let current_time = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                return Ok(current_time - boot_time);
-            }
+        note_window_title(&session, &mut records, "main.rs — crate");
+        note_window_title(&session, &mut records, "main.rs — crate");
+        for i in 0..MAX_WINDOW_TITLES_PER_LAP + 5 {
+            note_window_title(&session, &mut records, &format!("tab {}", i));
         }
+
+        let titles = &records["2026-07-10"].laps[0].window_titles;
+        assert_eq!(titles.len(), MAX_WINDOW_TITLES_PER_LAP);
+        assert_eq!(titles[0], "main.rs — crate");
+        assert_eq!(titles.iter().filter(|t| *t == "main.rs — crate").count(), 1);
     }
-    
-    Err("Failed to parse system uptime".to_string())
-}
 
-// Check if we should show startup notification
-fn should_show_startup_notification(state: &AppStateArc) -> Result<bool, String> {
-    // Check if system was recently started (within last 10 minutes)
-    #[cfg(target_os = "macos")]
-    {
-        let uptime = get_system_uptime()?;
-        if uptime > 600 {
-            // System has been running for more than 10 minutes, not a fresh startup
-            return Ok(false);
-        }
+    #[test]
+    fn day_breakdown_merges_laps_and_ranks_by_time() {
+        let mut a = lap(0, Some(100));
+        a.app_breakdown.insert("Slack".into(), 40);
+        a.app_breakdown.insert("Xcode".into(), 60);
+        let mut b = lap(200, Some(300));
+        b.app_breakdown.insert("Xcode".into(), 100);
+        let record = day("2026-07-10", vec![a, b, lap(400, Some(500))]);
+
+        assert_eq!(
+            day_app_breakdown(&record),
+            vec![("Xcode".to_string(), 160), ("Slack".to_string(), 40)]
+        );
     }
-    
-    let session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
 
-    // We now auto-start/continue a day on boot, so notify whenever a session exists
-    // to let the user know tracking is running (and that they can pause if idle).
-    Ok(session_guard.is_some())
-}
+    #[test]
+    fn top_apps_merge_case_variants_and_report_share() {
+        let mut a = lap(0, Some(100));
+        a.app_breakdown.insert("Xcode".into(), 60);
+        a.app_breakdown.insert("Slack".into(), 20);
+        let mut b = lap(200, Some(300));
+        b.app_breakdown.insert("xcode".into(), 10);
+        b.app_breakdown.insert("Safari".into(), 10);
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![a, b]));
+        // An older record with no app data at all.
+        records.insert("2026-07-09".into(), day("2026-07-09", vec![lap(0, Some(500))]));
 
-// Show startup notification to user
-fn show_startup_notification(app_handle: &AppHandle, state: &AppStateArc) {
-    use tauri_plugin_notification::NotificationExt;
+        let top = top_apps(&records, "2026-07-01", "2026-07-31", "2026-08-01", 2);
 
-    println!("🔔 Showing startup notification to user");
+        assert_eq!(top.len(), 2);
+        assert_eq!((top[0].0.as_str(), top[0].1), ("Xcode", 70));
+        assert!((top[0].2 - 70.0).abs() < 1e-9, "percent is of all app time, not of the top N");
+        assert_eq!((top[1].0.as_str(), top[1].1), ("Slack", 20));
+    }
 
-    // Reflect whether we auto-started active tracking or restored a paused session.
-    let is_paused = state
-        .current_session
-        .lock()
-        .ok()
-        .and_then(|g| g.as_ref().map(|s| s.is_paused))
-        .unwrap_or(false);
+    #[test]
+    fn top_apps_count_the_open_lap_only_when_the_range_includes_today() {
+        let mut running = lap(0, None);
+        running.app_breakdown.insert("Xcode".into(), 30);
+        let mut records = HashMap::new();
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![running]));
 
-    let body = if is_paused {
-        "Your day is restored but paused. Open the app to resume when you start working."
-    } else {
-        "Tracking started for today. Open the app to pause if you're not working yet."
-    };
+        assert!(top_apps(&records, "2026-07-10", "2026-07-10", "2026-07-11", 5).is_empty());
+        assert_eq!(top_apps(&records, "2026-07-10", "2026-07-10", "2026-07-10", 5)[0].1, 30);
+    }
 
-    let notification = app_handle
-        .notification()
-        .builder()
-        .title("Screen Time Tracker")
-        .body(body)
-        .show();
-    
-    match notification {
-        Ok(_) => println!("✅ Startup notification shown successfully"),
-        Err(e) => eprintln!("❌ Failed to show notification: {}", e),
+    // --- all-time bests ----------------------------------------------------------
+
+    #[test]
+    fn statistics_cover_streaks_and_period_comparisons() {
+        // 2026-07-15 is a Wednesday.
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 7, 15).unwrap();
+        let daily: BTreeMap<String, u64> = [
+            ("2026-06-10", 3600),
+            ("2026-06-11", 7200),
+            ("2026-06-12", 0),
+            ("2026-07-06", 1800),
+            ("2026-07-13", 600),
+            ("2026-07-14", 1200),
+        ]
+        .into_iter()
+        .map(|(date, secs)| (date.to_string(), secs))
+        .collect();
+
+        let stats = statistics(&daily, today, 0);
+        assert_eq!(stats.total_tracked_seconds, 14_400);
+        assert_eq!(stats.tracked_days, 5);
+        assert_eq!(stats.average_seconds_per_tracked_day, 2880);
+        assert_eq!((stats.best_day_key.as_deref(), stats.best_day_seconds), (Some("2026-06-11"), 7200));
+        assert_eq!(stats.current_streak_days, 2, "today is still empty, so the run to yesterday counts");
+        assert_eq!(stats.longest_streak_days, 2);
+        assert_eq!((stats.this_week_seconds, stats.last_week_seconds), (1800, 1800));
+        assert_eq!((stats.this_month_seconds, stats.last_month_seconds), (3600, 10_800));
+
+        // Weeks starting on Sunday put 2026-07-13 and -14 in the week that began on the 12th.
+        let sunday_first = statistics(&daily, today, 6);
+        assert_eq!((sunday_first.this_week_seconds, sunday_first.last_week_seconds), (1800, 1800));
+        assert_eq!(statistics(&daily, chrono::NaiveDate::from_ymd_opt(2026, 7, 17).unwrap(), 0).current_streak_days, 0);
     }
-}
 
-// Check and notify on startup
-fn check_and_notify_on_startup(app_handle: &AppHandle, state: &AppStateArc) {
-    // Wait a bit for the system to settle after startup
-    thread::sleep(Duration::from_secs(3));
-    
-    match should_show_startup_notification(state) {
-        Ok(should_notify) => {
-            if should_notify {
-                show_startup_notification(app_handle, state);
-            } else {
-                println!("ℹ️ No need to show startup notification");
-            }
+    #[test]
+    fn heatmap_fills_every_date_and_ignores_empty_days_for_levels() {
+        let mut records = HashMap::new();
+        for (date, secs) in [("2024-01-02", 600), ("2024-01-03", 1200), ("2024-03-01", 1800), ("2024-12-31", 12 * HOUR)] {
+            records.insert(date.to_string(), day(date, vec![lap(0, Some(secs))]));
         }
-        Err(e) => {
-            eprintln!("❌ Error checking startup notification: {}", e);
+        // 2024 is a leap year and starts on a Monday.
+        let map = heatmap(&records, 2024, 0).unwrap();
+        assert_eq!((map.seconds.len(), map.levels.len()), (366, 366));
+        assert_eq!(map.leading_blank_days, 0);
+        assert_eq!((map.seconds[0], map.levels[0]), (0, 0));
+        assert_eq!(map.seconds[365], 12 * HOUR);
+        // The 12-hour day gets the top level without pushing the others down to 1.
+        assert_eq!([map.levels[1], map.levels[2], map.levels[60], map.levels[365]], [1, 2, 3, 4]);
+
+        assert_eq!(heatmap(&records, 2024, 6).unwrap().leading_blank_days, 1, "Sunday-first weeks");
+        assert!(heatmap(&HashMap::new(), 2023, 0).unwrap().levels.iter().all(|l| *l == 0));
+    }
+
+    #[test]
+    fn all_time_bests_are_empty_for_no_history() {
+        let bests = all_time_bests(&HashMap::new());
+        assert_eq!(bests.longest_streak_days, 0);
+        assert_eq!(bests.longest_single_day_key, "");
+        assert_eq!(bests.most_laps_in_a_day, 0);
+    }
+
+    #[test]
+    fn all_time_bests_find_records_and_the_longest_consecutive_run() {
+        let mut records = HashMap::new();
+        // 10th-12th is a three-day run; the 13th is missing; 14th-15th is a two-day run.
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(0, Some(100))]));
+        records.insert("2026-07-11".into(), day("2026-07-11", vec![lap(0, Some(50)), lap(60, Some(70))]));
+        records.insert("2026-07-12".into(), day("2026-07-12", vec![lap(0, Some(900))]));
+        records.insert("2026-07-14".into(), day("2026-07-14", vec![lap(0, Some(400)), lap(500, Some(900))]));
+        records.insert("2026-07-15".into(), day("2026-07-15", vec![lap(0, Some(10))]));
+
+        let bests = all_time_bests(&records);
+
+        assert_eq!(bests.longest_streak_days, 3);
+        assert_eq!(bests.longest_streak_start, "2026-07-10");
+        assert_eq!(bests.longest_streak_end, "2026-07-12");
+        assert_eq!(bests.longest_single_lap_seconds, 900);
+        assert_eq!(bests.longest_single_lap_date, "2026-07-12");
+        assert_eq!(bests.longest_single_day_seconds, 900);
+        assert_eq!(bests.longest_single_day_key, "2026-07-12");
+        // Tied on two laps: likewise the earlier day.
+        assert_eq!(bests.most_laps_in_a_day, 2);
+        assert_eq!(bests.most_laps_date, "2026-07-11");
+    }
+
+    #[test]
+    fn history_bounds_span_the_calendar_and_count_active_days() {
+        assert!(history_bounds(&HashMap::new()).is_none());
+
+        let mut records = HashMap::new();
+        records.insert("2026-06-28".into(), day("2026-06-28", vec![lap(0, Some(100))]));
+        records.insert("2026-07-02".into(), day("2026-07-02", vec![]));
+        records.insert("2026-07-03".into(), day("2026-07-03", vec![lap(0, Some(10))]));
+
+        let bounds = history_bounds(&records).unwrap();
+        assert_eq!((bounds.first_day.as_str(), bounds.last_day.as_str()), ("2026-06-28", "2026-07-03"));
+        assert_eq!(bounds.total_days_span, 6);
+        assert_eq!(bounds.active_days_count, 2);
+    }
+
+    #[test]
+    fn a_poisoned_lock_is_recovered_and_tracking_continues() {
+        let state = AppState::new();
+        *state.current_session.lock().unwrap() = Some(session_on("2026-07-10", None));
+
+        // A thread panics while holding the session lock.
+        let session = state.current_session.clone();
+        let _ = thread::spawn(move || {
+            let _guard = session.lock().unwrap();
+            panic!("boom");
+        })
+        .join();
+        assert!(state.current_session.is_poisoned());
+
+        // The monitor tick still gets the session and keeps accumulating...
+        if let Some(session) = lock_or_recover(&state.current_session).as_mut() {
+            advance_active_time(session, 1, false);
         }
+        assert_eq!(lock_or_recover(&state.current_session).as_ref().unwrap().accumulated_seconds, 1);
+        // ...and the poison is cleared, so commands' `lock()?` works again.
+        assert!(state.current_session.lock().is_ok());
     }
-}
 
-// ----------------------------------------------------------------------------
-// Menu-bar (tray) app: the app lives in the macOS menu bar. Left-clicking the
-// tray icon toggles a small popover (quick glance + basic controls); the popover
-// can expand to the full window. Keeping the full webview closed by default is
-// what keeps idle memory/CPU low — no webview process runs until you open one.
-// ----------------------------------------------------------------------------
+    #[test]
+    fn heartbeats_go_stale_only_after_the_grace_period() {
+        assert!(heartbeat_alive(1000, 1020, 30, 900));
+        assert!(!heartbeat_alive(1000, 1031, 30, 900));
+        // Never beaten yet: measured from startup instead.
+        assert!(heartbeat_alive(0, 920, 30, 900));
+        assert!(!heartbeat_alive(0, 1000, 30, 900));
+        // Clock stepped backwards.
+        assert!(heartbeat_alive(1000, 990, 30, 900));
+    }
 
-// Convert a tray icon rectangle into physical (x, y, width, height).
-fn rect_to_physical(rect: &tauri::Rect) -> (f64, f64, f64, f64) {
-    let (x, y) = match rect.position {
-        tauri::Position::Physical(p) => (p.x as f64, p.y as f64),
-        tauri::Position::Logical(p) => (p.x, p.y),
-    };
-    let (w, h) = match rect.size {
-        tauri::Size::Physical(s) => (s.width as f64, s.height as f64),
-        tauri::Size::Logical(s) => (s.width, s.height),
-    };
-    (x, y, w, h)
-}
+    #[test]
+    fn save_failures_alert_once_then_at_most_hourly() {
+        assert!(persistence_alert_due(0, 1_000));
+        assert!(!persistence_alert_due(1_000, 1_030));
+        assert!(!persistence_alert_due(1_000, 1_000 + PERSISTENCE_ALERT_INTERVAL_SECS - 1));
+        assert!(persistence_alert_due(1_000, 1_000 + PERSISTENCE_ALERT_INTERVAL_SECS));
+
+        // A successful save re-arms the alert for the next failure.
+        let health = PersistenceHealth::default();
+        health.last_alerted_at.store(1_000, Ordering::Relaxed);
+        *health.last_error.lock().unwrap() = Some("disk full".into());
+        health.note_success(1_100);
+        assert!(persistence_alert_due(health.last_alerted_at.load(Ordering::Relaxed), 1_101));
+        assert_eq!(health.last_saved_at.load(Ordering::Relaxed), 1_100);
+        assert!(health.last_error.lock().unwrap().is_none());
+    }
 
-// Toggle the popover: close it if open, otherwise build a small borderless window
-// anchored under the tray icon and show it. It closes itself when it loses focus.
-fn toggle_popover(app: &AppHandle, rect: tauri::Rect) {
-    if let Some(existing) = app.get_webview_window("popover") {
-        let _ = existing.close();
-        return;
+    #[test]
+    fn compact_state_json_round_trips_to_the_same_state() {
+        // Older files have no use_pretty_json: they keep the readable format.
+        assert!(serde_json::from_str::<TrackerConfig>("{}").unwrap().use_pretty_json);
+
+        let state = AppState::new();
+        state.day_records.lock().unwrap().insert("2026-07-10".into(), day("2026-07-10", vec![lap(0, Some(60)), lap(90, None)]));
+        let persisted = snapshot_state(&Arc::new(state));
+        let pretty = serde_json::to_string_pretty(&persisted).unwrap();
+        let compact = serde_json::to_string(&persisted).unwrap();
+        assert!(compact.len() < pretty.len());
+
+        let reread: PersistedState = serde_json::from_str(&compact).unwrap();
+        assert_eq!(serde_json::to_string(&reread).unwrap(), compact);
     }
 
-    let width = 288.0;
-    let height = 214.0;
+    #[test]
+    fn log_pruning_keeps_the_newest_files_within_budget() {
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+        let files = vec![
+            (PathBuf::from("tracker.2026-07-08.log"), at(100), 400),
+            (PathBuf::from("tracker.2026-07-10.log"), at(300), 400),
+            (PathBuf::from("tracker.2026-07-09.log"), at(200), 400),
+        ];
+        assert_eq!(logs_over_budget(files.clone(), 800), [PathBuf::from("tracker.2026-07-08.log")]);
+        assert!(logs_over_budget(files.clone(), 1200).is_empty());
+        // Even an oversized newest file survives.
+        assert_eq!(logs_over_budget(files, 10).len(), 2);
+    }
 
-    let win = match WebviewWindowBuilder::new(app, "popover", WebviewUrl::App("popover.html".into()))
-        .inner_size(width, height)
-        .decorations(false)
-        .always_on_top(true)
-        .resizable(false)
-        .skip_taskbar(true)
-        .visible(false)
-        .build()
-    {
-        Ok(w) => w,
-        Err(e) => {
-            eprintln!("❌ Failed to build popover window: {}", e);
-            return;
+    #[test]
+    fn saving_a_year_of_history_rewrites_only_the_hot_day() {
+        let start = chrono::NaiveDate::from_ymd_opt(2025, 3, 4).unwrap();
+        let mut records = HashMap::new();
+        for offset in 0..=365 {
+            let date = (start + chrono::Duration::days(offset)).format("%Y-%m-%d").to_string();
+            let laps = (0..8).map(|i| lap(i * HOUR, Some(i * HOUR + 1800))).collect();
+            records.insert(date.clone(), day(&date, laps));
         }
-    };
+        let today = "2026-03-04";
+        records.get_mut(today).unwrap().is_active = true;
+        let is_hot = |record: &DayRecord| is_hot_day(record, today, Some(today));
+
+        // First save after an upgrade: every settled day gets its file once.
+        let first = plan_day_writes(&records, is_hot, &HashMap::new());
+        assert_eq!(first.write.len(), 365);
+        let mut written: HashMap<String, u64> = first.write.into_iter().collect();
+
+        // After that, a save touches only what state.json holds: the hot day.
+        records.get_mut(today).unwrap().laps.push(lap(20 * HOUR, None));
+        assert_eq!(plan_day_writes(&records, is_hot, &written), DayWritePlan::default());
+        let hot: HashMap<_, _> = records.iter().filter(|(_, r)| is_hot(r)).collect();
+        let full_bytes = serde_json::to_vec(&records).unwrap().len();
+        let hot_bytes = serde_json::to_vec(&hot).unwrap().len();
+        assert!(hot_bytes * 100 < full_bytes, "{} vs {}", hot_bytes, full_bytes);
+
+        // An edit to a settled day rewrites just that day; a removed day loses its file.
+        records.get_mut("2025-06-01").unwrap().metadata.insert("mood".into(), "ok".into());
+        records.remove("2025-06-02");
+        let plan = plan_day_writes(&records, is_hot, &written);
+        assert_eq!(plan.write.iter().map(|(d, _)| d.as_str()).collect::<Vec<_>>(), ["2025-06-01"]);
+        assert_eq!(plan.remove, ["2025-06-02"]);
+
+        // A settled day that becomes hot again moves back into state.json.
+        written.retain(|date, _| date != "2025-06-02");
+        records.get_mut("2025-06-03").unwrap().is_active = true;
+        assert!(plan_day_writes(&records, is_hot, &written).remove.contains(&"2025-06-03".to_string()));
+    }
 
-    // Anchor the popover horizontally centered under the tray icon.
-    let scale = win.scale_factor().unwrap_or(1.0);
-    let (ix, iy, iw, ih) = rect_to_physical(&rect);
-    let px = (ix + iw / 2.0 - (width * scale) / 2.0).max(0.0);
-    let py = iy + ih + 2.0;
-    let _ = win.set_position(tauri::PhysicalPosition::new(px, py));
-    let _ = win.show();
-    let _ = win.set_focus();
+    #[test]
+    fn recovery_falls_back_through_backup_generations() {
+        let dir = std::env::temp_dir().join(format!("stt-recovery-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let state_file = dir.join("state.json");
+        let backup_file = dir.join("state.backup.json");
+
+        let candidates = recovery_candidates(&state_file, &backup_file);
+        let names: Vec<String> = candidates.iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["state.backup.json", "state.json.1", "state.json.2", "state.json.3"]);
+
+        let valid = serde_json::to_string(&snapshot_state(&Arc::new(AppState::new()))).unwrap();
+        fs::write(&backup_file, "{ truncated").unwrap();
+        fs::write(backup_generation_path(&state_file, 2), &valid).unwrap();
+        let recovered = candidates.iter().find(|p| try_load_persisted_state(p).is_some());
+        assert_eq!(recovered, Some(&backup_generation_path(&state_file, 2)));
+
+        // Rotation moves each snapshot down a generation.
+        rotate_backups(&state_file, &backup_file);
+        assert!(!backup_file.exists());
+        assert_eq!(fs::read_to_string(backup_generation_path(&state_file, 1)).unwrap(), "{ truncated");
+        assert!(try_load_persisted_state(&backup_generation_path(&state_file, 3)).is_some());
+
+        fs::remove_dir_all(&dir).ok();
+    }
 
-    // Dismiss when the user clicks away (popover loses focus).
-    let win_for_event = win.clone();
-    win.on_window_event(move |event| {
-        if let WindowEvent::Focused(false) = event {
-            let _ = win_for_event.close();
-        }
-    });
-}
+    #[test]
+    fn settled_days_load_on_first_history_read_and_fall_back_to_their_backup() {
+        let dir = std::env::temp_dir().join(format!("stt-days-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write_day = |path: PathBuf, record: &DayRecord| fs::write(path, serde_json::to_string(record).unwrap()).unwrap();
+        write_day(dir.join("2026-03-01.json"), &day("2026-03-01", vec![lap(0, Some(HOUR))]));
+        fs::write(dir.join("2026-03-02.json"), "{ torn").unwrap();
+        write_day(dir.join("2026-03-02.json.bak"), &day("2026-03-02", vec![lap(0, Some(2 * HOUR))]));
+        write_day(dir.join("2026-03-03.json"), &day("2026-03-03", vec![lap(0, Some(HOUR))]));
+
+        let state: AppStateArc = Arc::new(AppState::new());
+        let hot = day("2026-03-03", vec![lap(0, Some(HOUR)), lap(2 * HOUR, None)]);
+        lock_or_recover(&state.day_records).insert(hot.date.clone(), hot);
+        *lock_or_recover(&state.persistence.cold_days) = Some(dir.clone());
+        assert_eq!(lock_or_recover(&state.day_records).len(), 1, "nothing is read up front");
+        assert!(!state.persistence.settled_loaded.load(Ordering::Relaxed));
+
+        let records = all_day_records(&state).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records["2026-03-02"].laps[0].end_time, Some(2 * HOUR), "read from the backup");
+        assert_eq!(records["2026-03-03"].laps.len(), 2, "the copy in memory wins");
+        drop(records);
+        let mut loaded: Vec<String> = lock_or_recover(&state.persistence.loaded_days).keys().cloned().collect();
+        loaded.sort();
+        assert_eq!(loaded, ["2026-03-01", "2026-03-02"]);
+        assert!(state.persistence.settled_loaded.load(Ordering::Relaxed));
+
+        fs::remove_dir_all(&dir).ok();
+    }
 
-// Show (or create) the full UI window and close the popover. The full window is
-// created on demand so no webview exists while the app sits in the menu bar.
-fn open_main_window(app: &AppHandle) {
-    if let Some(w) = app.get_webview_window("main") {
-        let _ = w.show();
-        let _ = w.unminimize();
-        let _ = w.set_focus();
-    } else {
-        match WebviewWindowBuilder::new(app, "main", WebviewUrl::App("index.html".into()))
-            .title("Screen Time Tracker")
-            .inner_size(1000.0, 800.0)
-            .min_inner_size(800.0, 600.0)
-            .resizable(true)
-            .build()
-        {
-            Ok(_) => println!("✅ Opened full window"),
-            Err(e) => eprintln!("❌ Failed to build main window: {}", e),
+    #[test]
+    fn event_log_times_pauses_and_stays_bounded() {
+        let event = |timestamp: u64, cause: Option<&str>| SessionEvent {
+            day_key: "2024-03-04".to_string(),
+            timestamp,
+            lap: None,
+            cause: cause.map(str::to_string),
+        };
+        let mut log = EventLog::default();
+        log.record(&event(1_000, Some("lock")), "session-paused");
+        log.record(&event(2_080, None), "session-resumed");
+
+        let latest = log.latest(10);
+        assert_eq!(latest[0].kind, "session-resumed");
+        assert_eq!(latest[0].duration, Some(1_080));
+        assert_eq!(latest[1].cause.as_deref(), Some("lock"));
+
+        for i in 0..RECENT_EVENTS_CAPACITY as u64 {
+            log.record(&event(3_000 + i, Some("user")), "session-paused");
         }
+        assert_eq!(log.recent.len(), RECENT_EVENTS_CAPACITY);
+        assert_eq!(log.by_day["2024-03-04"].len(), RECENT_EVENTS_CAPACITY + 2);
+        // A reload rebuilds the same bounded feed from the persisted days.
+        assert_eq!(EventLog::from_days(log.by_day.clone()).recent, log.recent);
     }
 
-    if let Some(p) = app.get_webview_window("popover") {
-        let _ = p.close();
+    #[test]
+    fn date_keys_follow_the_local_offset_not_utc() {
+        use chrono::TimeZone;
+        let kiribati = chrono::FixedOffset::east_opt(14 * 3600).unwrap();
+        // 10:30 UTC is already half past midnight the next day at UTC+14.
+        let instant = chrono::Utc.with_ymd_and_hms(2024, 3, 4, 10, 30, 0).unwrap();
+        assert_eq!(date_key_in(instant, &kiribati), "2024-03-05");
+        assert_eq!(date_key_in(instant - chrono::Duration::hours(1), &kiribati), "2024-03-04");
+        assert_eq!(date_key_in(instant, &chrono::Utc), "2024-03-04");
     }
-}
 
-// Expand from the popover to the full window.
-#[tauri::command]
-async fn show_main_window(app: AppHandle) -> Result<(), String> {
-    open_main_window(&app);
-    Ok(())
-}
+    #[test]
+    fn tagging_rules_validate_and_match_by_start_hour() {
+        let rule = |name: &str, start_hour, end_hour| TaggingRule { name: name.to_string(), start_hour, end_hour };
+        let rules = vec![rule("Email", 8, 9), rule("Deep work", 9, 12), rule("Admin", 17, 23)];
+        assert!(validate_tagging_rules(&rules).is_ok());
+        assert!(validate_tagging_rules(&[rule("Late", 22, 24)]).is_err());
+        assert!(validate_tagging_rules(&[rule("Backwards", 12, 9)]).is_err());
+        assert!(validate_tagging_rules(&[rule("Email", 8, 9), rule("email", 10, 11)]).is_err());
+
+        assert_eq!(matching_rule(&rules, 8).map(|r| r.name.as_str()), Some("Email"));
+        assert_eq!(matching_rule(&rules, 9).map(|r| r.name.as_str()), Some("Deep work"));
+        assert_eq!(matching_rule(&rules, 12), None);
+
+        // An existing note always wins.
+        let mut noted = lap(0, Some(60));
+        noted.note = Some("Standup".into());
+        auto_tag_lap(&mut noted, &[rule("All day", 0, 23)]);
+        assert_eq!(noted.note.as_deref(), Some("Standup"));
+    }
 
-// Tauri command to start day from notification
-#[tauri::command]
-async fn start_day_from_notification(state: State<'_, AppStateArc>) -> Result<String, String> {
-    // Check if already has an active session
-    let should_add_lap = {
-        let session_guard = state.current_session.lock().map_err(|e| e.to_string())?;
-        
-        if let Some(session) = session_guard.as_ref() {
-            if session.is_paused {
-                // We have a paused session, just add a new lap to resume
-                true
-            } else {
-                // Already actively tracking
-                return Ok("Already tracking".to_string());
-            }
-        } else {
-            // No session, start a new day
-            false
+    #[test]
+    fn shutdown_token_stops_and_joins_its_loops() {
+        use std::sync::atomic::AtomicUsize;
+        let token = Arc::new(ShutdownToken::default());
+        let ticks = Arc::new(AtomicUsize::new(0));
+        for name in ["fast", "slow"] {
+            let (token_in, ticks) = (token.clone(), ticks.clone());
+            let interval = if name == "fast" { Duration::from_millis(5) } else { Duration::from_secs(3600) };
+            token.spawn(name, move || {
+                while token_in.sleep(interval) {
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            });
         }
-    }; // Drop the lock here before awaiting
-    
-    if should_add_lap {
-        add_lap(state).await
-    } else {
-        start_day(state).await
+        let nudged = Arc::new(AtomicUsize::new(0));
+        let nudged_in = nudged.clone();
+        token.on_shutdown(move || {
+            nudged_in.fetch_add(1, Ordering::Relaxed);
+        });
+        while ticks.load(Ordering::Relaxed) == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // The hour-long sleep is cut short rather than waited out.
+        let started = Instant::now();
+        assert!(token.shutdown(Duration::from_secs(5)).is_empty());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(nudged.load(Ordering::Relaxed), 1);
+        assert!(token.is_stopped());
+        assert!(!token.sleep(Duration::from_secs(3600)));
     }
-}
 
+    // A platform whose lock state and frontmost app the test sets by hand.
+    #[derive(Default)]
+    struct MockPlatform {
+        locked: AtomicBool,
+        frontmost_app: Mutex<Option<String>>,
+    }
 
-// The autostart plugin writes a LaunchAgent containing only RunAtLoad, which fires solely
-// at login. If the tracker then dies mid-session — crashed, force-quit, or killed because
-// its bundle was replaced by a rebuild — launchd will not bring it back until the *next*
-// login, and a Mac that is only ever slept can go days without one. That is how three days
-// of screen time went unrecorded: the app exited on Jul 11 and nothing restarted it while
-// the machine stayed up for 3+ days.
-//
-// KeepAlive/SuccessfulExit=false makes launchd relaunch the job whenever it exits
-// abnormally (non-zero status or killed by a signal), while still honouring a clean exit:
-// the tray's Quit calls process::exit(0), so quitting on purpose still quits for good.
-//
-// The plist is patched rather than rewritten so the plugin stays the single source of
-// truth for the executable path (which differs between a dev run and the installed .app).
-#[cfg(target_os = "macos")]
-fn ensure_autostart_keepalive() {
-    const KEEP_ALIVE: &str = "\t<key>KeepAlive</key>\n\t<dict>\n\t\t<key>SuccessfulExit</key>\n\t\t<false/>\n\t</dict>\n";
+    impl Platform for MockPlatform {
+        fn screen_locked(&self) -> Result<bool, String> {
+            Ok(self.locked.load(Ordering::Relaxed))
+        }
+        fn frontmost_app(&self) -> Option<String> {
+            self.frontmost_app.lock().unwrap().clone()
+        }
+        fn frontmost_window_title(&self) -> Option<String> {
+            None
+        }
+        fn power(&self) -> PowerStatus {
+            PowerStatus::default()
+        }
+        fn create_display_assertion(&self) -> Option<usize> {
+            None
+        }
+        fn release_display_assertion(&self, _: usize) {}
+    }
 
-    let Ok(home) = std::env::var("HOME") else { return };
-    let plist = PathBuf::from(home).join("Library/LaunchAgents/screen-time-tracker.plist");
+    // The monitor loop against `platform` on a fresh AppState, polling as fast as the
+    // config allows. What it reports arrives on the receiver; stop it through
+    // state.shutdown.
+    fn start_mock_monitor(platform: Arc<MockPlatform>) -> (AppStateArc, std::sync::mpsc::Receiver<MonitorEvent>) {
+        let state = Arc::new(AppState::new());
+        state.config.lock().unwrap().monitoring_poll_interval_ms = *POLL_INTERVAL_RANGE_MS.start();
+        let (tx, rx) = std::sync::mpsc::channel();
+        spawn_monitor(state.clone(), platform, move |event| {
+            let _ = tx.send(event);
+        });
+        (state, rx)
+    }
 
-    let Ok(contents) = fs::read_to_string(&plist) else { return };
-    if contents.contains("KeepAlive") {
-        return;
+    #[test]
+    fn monitor_reports_lock_changes_from_its_platform_and_stops_on_shutdown() {
+        let platform = Arc::new(MockPlatform::default());
+        let (state, events) = start_mock_monitor(platform.clone());
+        let next = |wanted: MonitorEvent| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while let Ok(event) = events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                if event == wanted {
+                    return true;
+                }
+            }
+            false
+        };
+        assert!(next(MonitorEvent::Alerts), "the loop ticks");
+        platform.locked.store(true, Ordering::Relaxed);
+        assert!(next(MonitorEvent::ScreenLocked));
+        platform.locked.store(false, Ordering::Relaxed);
+        assert!(next(MonitorEvent::ScreenUnlocked));
+
+        assert!(state.shutdown.shutdown(Duration::from_secs(5)).is_empty());
+        while events.try_recv().is_ok() {}
+        assert!(matches!(events.try_recv(), Err(std::sync::mpsc::TryRecvError::Disconnected)));
     }
-    // Insert into the job dict, i.e. just before the last closing </dict>.
-    let Some(insert_at) = contents.rfind("</dict>") else { return };
 
-    let mut patched = String::with_capacity(contents.len() + KEEP_ALIVE.len());
-    patched.push_str(&contents[..insert_at]);
-    patched.push_str(KEEP_ALIVE);
-    patched.push_str(&contents[insert_at..]);
+    #[test]
+    fn polling_slows_down_when_idle_or_on_battery() {
+        let config = TrackerConfig::default(); // 1s, adaptive
+        assert_eq!(config.effective_poll_interval(true, false), Duration::from_secs(1));
+        assert_eq!(config.effective_poll_interval(false, false), IDLE_POLL_INTERVAL);
+        assert_eq!(config.effective_poll_interval(true, true), Duration::from_secs(2));
+        assert_eq!(config.effective_poll_interval(false, true), MAX_ADAPTIVE_POLL_INTERVAL);
+
+        // A slower configured interval is never sped up.
+        let slow = TrackerConfig { monitoring_poll_interval_ms: 5000, ..TrackerConfig::default() };
+        assert_eq!(slow.effective_poll_interval(false, true), Duration::from_secs(5));
+        let fixed = TrackerConfig { adaptive_polling: false, ..TrackerConfig::default() };
+        assert_eq!(fixed.effective_poll_interval(false, true), Duration::from_secs(1));
+    }
 
-    match fs::write(&plist, patched) {
-        Ok(_) => println!("✅ Autostart KeepAlive set (relaunch if the app dies)"),
-        Err(e) => eprintln!("❌ Failed to patch autostart plist: {}", e),
+    #[test]
+    fn lock_debounce_flips_once_per_transition() {
+        let mut lock_state = LockDebounce::default();
+        assert_eq!(lock_state.observe(false, 100), None);
+        assert_eq!(lock_state.observe(true, 101), Some(true));
+        assert_eq!(lock_state.observe(true, 102), None);
+        // After a suspend gap the loop forces "locked" so the next unlock resumes.
+        lock_state.force(true);
+        assert_eq!(lock_state.observe(false, 200), Some(false));
     }
-}
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    let app_state = Arc::new(AppState::new());
-    
-    tauri::Builder::default()
-        .plugin(tauri_plugin_notification::init())
-        .plugin(tauri_plugin_autostart::init(
-            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
-            None,
-        ))
-        .manage(app_state.clone())
-        .invoke_handler(tauri::generate_handler![
-            start_day,
-            end_day,
-            handle_screen_lock,
-            handle_screen_unlock,
-            get_current_status,
-            get_current_day_laps,
-            get_all_day_records,
-            merge_day_into_previous,
-            add_lap,
-            stop_lap,
-            check_screen_lock_state,
-            test_screen_lock_detection,
-            handle_system_sleep,
-            handle_system_wake,
-            handle_user_logout,
-            handle_user_login,
-            start_day_from_notification,
-            show_main_window
-        ])
-        .setup(move |app| {
-            let app_handle = app.handle().clone();
+    #[test]
+    fn day_metadata_validates_keys_and_round_trips() {
+        assert!(validate_metadata_key("energy_level").is_ok());
+        assert!(validate_metadata_key("").is_err());
+        assert!(validate_metadata_key("git-commits").is_err());
+        assert!(validate_metadata_key(&"k".repeat(MAX_METADATA_KEY_LEN + 1)).is_err());
+
+        let mut record = day("2024-03-04", vec![lap(0, Some(60))]);
+        record.metadata.insert("mood".into(), "focused, \"good\" day".into());
+        let json = serde_json::to_string(&record).unwrap();
+        let back: DayRecord = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.metadata, record.metadata);
+
+        // Records written before metadata existed load with none.
+        let old: DayRecord = serde_json::from_str(r#"{"date":"2024-03-04","total_duration":0,"laps":[],"is_active":false}"#).unwrap();
+        assert!(old.metadata.is_empty());
+    }
 
-            // Run as a menu-bar (accessory) app: no Dock icon, lives in the menu bar.
-            #[cfg(target_os = "macos")]
-            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+    #[test]
+    fn timeline_interleaves_gaps_between_laps() {
+        let mut filled = lap(400, Some(500));
+        filled.is_synthetic = true;
+        // Stored out of order on purpose; the timeline sorts by start.
+        let record = day("2024-03-04", vec![lap(600, None), lap(100, Some(300)), filled, lap(500, Some(600))]);
+
+        let timeline = session_timeline(&record);
+        let kinds: Vec<_> = timeline.iter().map(|e| (e.entry_type, e.start_time, e.lap_index)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (TimelineEntryType::Lap, 100, Some(1)),
+                (TimelineEntryType::Gap, 300, None),
+                (TimelineEntryType::Gap, 400, Some(2)),
+                (TimelineEntryType::Lap, 500, Some(3)),
+                (TimelineEntryType::ActiveLap, 600, Some(0)),
+            ]
+        );
+        assert_eq!(timeline[1].duration_seconds, Some(100));
+        assert_eq!(timeline[4].end_time, None);
+    }
 
-            // Build the menu-bar tray icon. Left click toggles the popover; a right-click
-            // menu provides Open (full window) and Quit.
-            {
-                let open_item = MenuItemBuilder::with_id("open", "Open Screen Time Tracker").build(app)?;
-                let quit_item = MenuItemBuilder::with_id("quit", "Quit").build(app)?;
-                let tray_menu = MenuBuilder::new(app).items(&[&open_item, &quit_item]).build()?;
+    // --- continuous work ---------------------------------------------------------
 
-                let tray_icon = tauri::image::Image::from_bytes(include_bytes!("../icons/32x32.png"))
-                    .expect("failed to load tray icon");
+    #[test]
+    fn continuous_work_alert_fires_once_per_stretch() {
+        assert!(!continuous_work_alert_due(3599, Some(3600), false));
+        assert!(continuous_work_alert_due(3600, Some(3600), false));
+        assert!(!continuous_work_alert_due(7200, Some(3600), true), "already alerted this stretch");
+        assert!(!continuous_work_alert_due(99_999, None, false), "no limit configured");
+    }
 
-                TrayIconBuilder::with_id("tray")
-                    .icon(tray_icon)
-                    .tooltip("Screen Time Tracker")
-                    .menu(&tray_menu)
-                    .show_menu_on_left_click(false)
-                    .on_menu_event(|app, event| match event.id().as_ref() {
-                        "open" => open_main_window(app),
-                        "quit" => {
-                            // The `ExitRequested` handler in `run()` calls `prevent_exit()`
-                            // to keep the app alive when its windows close — but that also
-                            // swallows a normal `app.exit()`, so Quit never actually quit.
-                            // Persist state, then force the process down directly.
-                            let state = app.state::<AppStateArc>();
-                            save_state(app, state.inner());
-                            std::process::exit(0);
-                        }
-                        _ => {}
-                    })
-                    .on_tray_icon_event(|tray, event| {
-                        if let TrayIconEvent::Click { button, button_state, rect, .. } = event {
-                            if button == MouseButton::Left && button_state == MouseButtonState::Up {
-                                toggle_popover(tray.app_handle(), rect);
-                            }
-                        }
-                    })
-                    .build(app)?;
-            }
+    // --- undo ------------------------------------------------------------------
 
-            // Ensure the app launches automatically when the user logs into the machine.
-            {
-                use tauri_plugin_autostart::ManagerExt;
-                let autostart_manager = app.autolaunch();
-                match autostart_manager.enable() {
-                    Ok(_) => println!("✅ Autostart enabled (launch on login)"),
-                    Err(e) => eprintln!("❌ Failed to enable autostart: {}", e),
-                }
-                // enable() rewrites the plist from scratch every launch, so the KeepAlive
-                // patch has to be re-applied after it, not once at install time.
-                #[cfg(target_os = "macos")]
-                ensure_autostart_keepalive();
-            }
+    #[test]
+    fn undo_stack_keeps_only_the_most_recent_actions() {
+        let state: AppStateArc = Arc::new(AppState::new());
+        for i in 0..MAX_UNDO_DEPTH + 3 {
+            push_undo(&state, snapshot_state(&state), &format!("action {}", i));
+        }
+        let stack = state.undo_stack.lock().unwrap();
+        assert_eq!(stack.len(), MAX_UNDO_DEPTH);
+        // The oldest entries are the ones dropped.
+        assert_eq!(stack[0].description, "action 3");
+        assert_eq!(stack.last().unwrap().description, format!("action {}", MAX_UNDO_DEPTH + 2));
+    }
 
-            // Load saved state from disk and decide today's session (auto-start / continue / end).
-            load_and_initialize(&app_handle, &app_state);
-            
-            // Check if we should show startup notification (after system restart)
-            let state_for_notification = app_state.clone();
-            let handle_for_notification = app_handle.clone();
-            thread::spawn(move || {
-                check_and_notify_on_startup(&handle_for_notification, &state_for_notification);
-            });
-            
-            // Start system monitoring when app starts. This single thread handles
-            // screen lock/unlock polling plus suspend-gap detection (previously a second,
-            // redundant macOS-only lock-detection thread ran in parallel — removed to cut
-            // idle CPU, as was a per-second `pmset` sleep check that could never fire).
-            start_system_monitoring(app_handle.clone(), app_state.clone());
+    #[test]
+    fn restore_after_a_crash_keeps_the_time_accumulated_before_it() {
+        let state: AppStateArc = Arc::new(AppState::new());
+        let mut session = session_on("2026-07-10", None);
+        session.current_lap_start_timestamp = 1000;
+        advance_active_time(&mut session, 200, false);
+        advance_active_time(&mut session, 250, false);
+        *state.current_session.lock().unwrap() = Some(session);
+        state.day_records.lock().unwrap().insert(
+            "2026-07-10".to_string(),
+            day("2026-07-10", vec![lap(0, Some(600)), lap(1000, None)]),
+        );
+
+        // Save, crash with the lap still open, load. The heartbeat is later than the last
+        // tick, as it is when the machine sat idle before going down.
+        let mut saved = snapshot_state(&state);
+        saved.last_heartbeat = 1900;
+        let loaded: PersistedState = serde_json::from_str(&serde_json::to_string(&saved).unwrap()).unwrap();
+        let mut records = loaded.day_records;
+        close_interrupted_lap(&mut records, loaded.current_session.as_ref().unwrap(), loaded.last_heartbeat);
+
+        let record = &records["2026-07-10"];
+        assert_eq!(record.laps[1].end_time, Some(1450));
+        assert_eq!(record.total_duration, 600 + 450);
+    }
+
+    #[test]
+    fn recompute_totals_reports_only_drifted_days() {
+        let mut records = HashMap::new();
+        let mut drifted = day("2024-03-02", vec![lap(0, Some(600)), lap(1000, Some(1300))]);
+        drifted.total_duration = 42;
+        let mut synthetic = lap(2000, Some(2500));
+        synthetic.is_synthetic = true;
+        let mut with_break = day("2024-03-01", vec![lap(0, Some(100)), synthetic, lap(3000, None)]);
+        with_break.total_duration = 100;
+        records.insert("2024-03-02".to_string(), drifted);
+        records.insert("2024-03-01".to_string(), with_break);
+        records.insert("2024-03-03".to_string(), day("2024-03-03", vec![lap(0, Some(60))]));
+
+        assert_eq!(recompute_totals(&mut records), vec!["2024-03-02".to_string()]);
+        assert_eq!(records["2024-03-02"].total_duration, 900);
+        assert!(recompute_totals(&mut records).is_empty());
+    }
 
-            // Close the open lap at the exact moment the machine sleeps; the gap
-            // detector inside the monitoring loop is the fallback if this is missed.
-            #[cfg(target_os = "macos")]
-            register_sleep_observer(app_handle.clone(), app_state.clone());
+    // --- journal -------------------------------------------------------------------
 
-            // Start periodic state saving (every 30 seconds)
-            let state_for_autosave = app_state.clone();
-            let handle_for_autosave = app_handle.clone();
-            thread::spawn(move || {
-                loop {
-                    thread::sleep(Duration::from_secs(30));
-                    // Catches the day change for a session that never pauses — nobody
-                    // locks the screen or sleeps the Mac, so no unlock event ever fires
-                    // and the cutoff backstop has to be evaluated on a timer. Rolls over
-                    // and saves; otherwise this is just the periodic save.
-                    roll_over_day_if_due(&handle_for_autosave, &state_for_autosave);
-                    save_state(&handle_for_autosave, &state_for_autosave);
-                }
-            });
-            
-            Ok(())
+    #[test]
+    fn journal_replay_ignores_a_torn_tail_and_entries_already_saved() {
+        let line = |seq: u64, kind: &str, lap: Option<Lap>, cause: Option<&str>| {
+            let event = SessionEvent {
+                day_key: "2024-03-01".to_string(),
+                timestamp: 1000 + seq * 100,
+                lap,
+                cause: cause.map(str::to_string),
+            };
+            serde_json::to_string(&JournalEntry { seq, kind: kind.to_string(), event }).unwrap() + "\n"
+        };
+        let complete = [
+            line(1, "session-started", Some(lap(1000, None)), None),
+            line(2, "lap-ended", Some(lap(1000, Some(1500))), None),
+            line(3, "session-paused", None, Some("lock")),
+        ]
+        .concat();
+        let torn = format!("{}{{\"seq\":4,\"kind\":\"session-res", complete);
+
+        let (entries, valid_len) = parse_journal(&torn);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(valid_len, complete.len());
+
+        let state: AppStateArc = Arc::new(AppState::new());
+        let mut persisted = snapshot_state(&state);
+        persisted.journal_seq = 1; // the snapshot already has the session start
+        persisted.last_heartbeat = 1000;
+        persisted.day_records.insert("2024-03-01".to_string(), day("2024-03-01", vec![lap(1000, None)]));
+
+        assert_eq!(replay_journal(&mut persisted, &entries), 2);
+        let record = &persisted.day_records["2024-03-01"];
+        assert_eq!(record.laps.len(), 1, "the ended lap replaces the open one");
+        assert_eq!(record.laps[0].end_time, Some(1500));
+        // The session-started entry was skipped, so no session is invented for the pause.
+        assert!(persisted.current_session.is_none());
+        assert_eq!(persisted.journal_seq, 3);
+        assert_eq!(persisted.last_heartbeat, 1300);
+    }
+
+    #[test]
+    fn template_notes_are_handed_out_in_order_then_run_out() {
+        let template = normalize_template(SessionTemplate {
+            name: "  Standard day ".to_string(),
+            laps: vec![
+                TemplateLap { expected_duration_secs: 900, note: " Standup ".to_string() },
+                TemplateLap { expected_duration_secs: 3 * HOUR, note: String::new() },
+                TemplateLap { expected_duration_secs: 2 * HOUR, note: "Review".to_string() },
+            ],
         })
-        .build(tauri::generate_context!())
-        .expect("error while building tauri application")
-        .run(|_app_handle, event| {
-            // The app is a menu-bar app: keep it running even when all windows are
-            // closed. It only quits via the tray's Quit item (app.exit).
-            if let RunEvent::ExitRequested { api, .. } = event {
-                api.prevent_exit();
-            }
-        });
-}
+        .unwrap();
+        assert_eq!(template.name, "Standard day");
+
+        let mut progress = TemplateProgress { template, next_lap: 0 };
+        assert_eq!(progress.take_next_note().as_deref(), Some("Standup"));
+        assert_eq!(progress.take_next_note(), None, "a blank note leaves the lap without one");
+        assert_eq!(progress.take_next_note().as_deref(), Some("Review"));
+        assert_eq!(progress.take_next_note(), None);
+        assert_eq!(progress.next_lap, 3);
+
+        let empty = SessionTemplate { name: "Empty".to_string(), laps: Vec::new() };
+        assert!(normalize_template(empty).is_err());
+        let blank = SessionTemplate { name: " ".to_string(), laps: progress.template.laps.clone() };
+        assert!(normalize_template(blank).is_err());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn focus_score_falls_as_laps_per_hour_rise() {
+        // Two hours in two laps: one lap per hour against a limit of six.
+        let focused = day("2024-03-01", vec![lap(0, Some(HOUR)), lap(HOUR, Some(2 * HOUR)), lap(2 * HOUR, None)]);
+        let score = focus_score(&focused, DEFAULT_MAX_EXPECTED_FRAGMENTATION);
+        assert_eq!(score.lap_count, 2, "the open lap is not counted");
+        assert_eq!(score.average_lap_duration_seconds, HOUR);
+        assert_eq!(score.longest_lap_seconds, HOUR);
+        assert!((score.fragmentation_ratio - 1.0).abs() < 1e-9);
+        assert!((score.focus_score.unwrap() - 100.0 * 5.0 / 6.0).abs() < 1e-9);
+
+        // Twelve five-minute laps in an hour is past the limit, so it bottoms out at 0.
+        let scattered = day("2024-03-02", (0..12).map(|i| lap(i * 300, Some((i + 1) * 300))).collect());
+        assert_eq!(focus_score(&scattered, DEFAULT_MAX_EXPECTED_FRAGMENTATION).focus_score, Some(0.0));
+
+        let short = focus_score(&day("2024-03-03", vec![lap(0, Some(240))]), DEFAULT_MAX_EXPECTED_FRAGMENTATION);
+        assert_eq!(short.focus_score, None);
+        assert!(short.explanation.is_some());
+    }
 
-    const HOUR: u64 = 3600;
+    #[test]
+    fn comparison_averages_from_the_first_recorded_day() {
+        let c = comparison(3 * HOUR, &[None, None, None, None, Some(2 * HOUR), None, Some(4 * HOUR)]);
+        assert_eq!(c.yesterday_seconds, Some(4 * HOUR));
+        assert_eq!(c.vs_yesterday_seconds, Some(-(HOUR as i64)));
+        assert_eq!(c.vs_yesterday_percent, Some(-25.0));
+        // Three days since the first record, the untracked one counting as zero.
+        assert_eq!(c.recent_average_seconds, Some(2 * HOUR));
+        assert_eq!(c.vs_recent_average_percent, Some(50.0));
+
+        let after_a_day_off = comparison(HOUR, &[Some(HOUR), None]);
+        assert_eq!(after_a_day_off.yesterday_seconds, Some(0));
+        assert_eq!(after_a_day_off.vs_yesterday_seconds, Some(HOUR as i64));
+        assert_eq!(after_a_day_off.vs_yesterday_percent, None);
+
+        let cold_start = comparison(HOUR, &[None; 7]);
+        assert_eq!(cold_start.today_seconds, HOUR);
+        assert_eq!(cold_start.yesterday_seconds, None);
+        assert_eq!(cold_start.vs_yesterday_seconds, None);
+        assert_eq!(cold_start.vs_recent_average_percent, None);
+    }
 
-    fn lap(start: u64, end: Option<u64>) -> Lap {
-        Lap { start_time: start, end_time: end, duration: end.map(|e| e - start) }
+    #[test]
+    fn month_summary_covers_every_day_and_ranks_laps() {
+        let mut records = HashMap::new();
+        // 2024-02-05 00:00 UTC, so each day below starts a multiple of 86400 later.
+        let base = 1_707_091_200;
+        records.insert("2024-02-05".to_string(), day("2024-02-05", vec![lap(base, Some(base + 2 * HOUR)), lap(base + 3 * HOUR, Some(base + 4 * HOUR))]));
+        records.insert("2024-02-06".to_string(), day("2024-02-06", vec![lap(base + 86_400, Some(base + 86_400 + 3 * HOUR)), lap(base + 90_000, None)]));
+        records.insert("2024-03-01".to_string(), day("2024-03-01", vec![lap(base + 25 * 86_400, Some(base + 25 * 86_400 + 9 * HOUR))]));
+
+        let summary = month_summary(&records, 2024, 2, Some(3 * HOUR)).unwrap();
+        assert_eq!(summary.month_key, "2024-02");
+        assert_eq!(summary.daily_totals.len(), 29, "a leap-year February");
+        assert_eq!(summary.total_seconds, 6 * HOUR);
+        assert_eq!(summary.days_tracked, 2);
+        assert_eq!(summary.average_tracked_day_seconds, 3 * HOUR);
+        assert_eq!(summary.best_day, Some(("2024-02-05".to_string(), 3 * HOUR)), "the earlier day wins the tie");
+        assert_eq!(summary.days_goal_met, Some(2));
+        let longest: Vec<u64> = summary.longest_laps.iter().filter_map(|(_, lap)| lap.duration).collect();
+        assert_eq!(longest, vec![3 * HOUR, 2 * HOUR, HOUR], "open laps and other months are left out");
+
+        assert!(month_summary(&records, 2024, 13, None).is_none());
+        assert_eq!(month_summary(&records, 2024, 1, None).unwrap().best_day, None);
     }
 
-    fn day(date: &str, laps: Vec<Lap>) -> DayRecord {
-        let total = laps.iter().filter_map(|l| l.duration).sum();
-        DayRecord { date: date.to_string(), total_duration: total, laps, is_active: false }
+    #[test]
+    fn low_battery_pauses_once_and_power_resumes() {
+        let pmset = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=4653155)\t17%; discharging; 0:48 remaining present: true\n";
+        let low = parse_power_status(pmset);
+        assert_eq!(low, PowerStatus { on_battery: true, battery_percent: Some(17) });
+        let plugged = parse_power_status("Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t17%; charging; present: true\n");
+        assert!(!plugged.on_battery);
+        assert_eq!(parse_power_status("Now drawing from 'AC Power'\n").battery_percent, None, "a desktop Mac");
+
+        assert_eq!(battery_pause_action(low, Some(20), false), Some(true));
+        assert_eq!(battery_pause_action(low, Some(15), false), None);
+        assert_eq!(battery_pause_action(low, None, false), None);
+        // Already paused for the battery: stay put until the charger is back.
+        assert_eq!(battery_pause_action(low, Some(20), true), None);
+        assert_eq!(battery_pause_action(plugged, Some(20), true), Some(false));
+        assert_eq!(battery_pause_action(plugged, Some(20), false), None);
     }
 
-    // --- rollover decision -------------------------------------------------
-    // `gap` is 0 whenever a lap is open, so "working through" is expressed as gap == 0.
+    #[test]
+    fn day_summary_line_reads_like_a_standup_note() {
+        let utc = chrono::FixedOffset::east_opt(0).unwrap();
+        // 2024-06-03 08:41 UTC, then laps until 17:55 with the running one left out.
+        let start = 1_717_404_060;
+        let record = day(
+            "2024-06-03",
+            vec![lap(start, Some(start + 4 * HOUR)), lap(start + 5 * HOUR, Some(start + 33_240)), lap(start + 33_300, None)],
+        );
+        assert_eq!(
+            day_summary_line(&record, SummaryFormat::Text, &utc),
+            "2024-06-03 — 8h 14m across 2 laps, first activity 08:41, last 17:55"
+        );
+        assert!(day_summary_line(&record, SummaryFormat::Slack, &utc).starts_with("*2024-06-03* — "));
+        assert!(day_summary_line(&record, SummaryFormat::Markdown, &utc).starts_with("**2024-06-03** — "));
+        assert_eq!(day_summary_line(&day("2024-06-04", vec![]), SummaryFormat::Text, &utc), "2024-06-04 — nothing tracked");
+    }
 
     #[test]
-    fn night_owl_past_midnight_stays_on_the_same_day() {
-        // Friday 21:00 -> Saturday 02:00, still typing. The date changed, but there is no
-        // break and it is nowhere near the cutoff. This is the case that must NOT roll.
-        assert!(!should_roll_over(true, 0, false));
+    fn session_context_reports_the_session_day() {
+        let mut records = HashMap::new();
+        records.insert("2024-03-01".to_string(), day("2024-03-01", vec![lap(0, Some(HOUR)), lap(2 * HOUR, None)]));
+        let mut session = session_on("2024-03-01", None);
+        session.pause(PauseCause::Lock, 100);
+
+        let context = session_context(Some(&session), &records, "2024-03-02", 12 * 3600, true);
+        assert_eq!(context.session_state, Some(SessionState::SystemPaused));
+        assert_eq!(context.day_key.as_deref(), Some("2024-03-01"));
+        assert_eq!(context.paused_cause, Some(PauseCause::Lock));
+        assert_eq!((context.total_laps_today, context.total_completed_laps_today), (2, 1));
+        assert_eq!(context.day_total_duration, HOUR);
+        assert!(!context.is_past_midnight_rollover_risk);
+
+        let idle = session_context(None, &records, "2024-03-02", 86_400 - 300, false);
+        assert_eq!(idle.session_state, None);
+        assert_eq!(idle.total_laps_today, 0);
+        assert!(idle.is_past_midnight_rollover_risk);
+        assert!(near_midnight(300));
+        assert!(!near_midnight(601));
     }
 
     #[test]
-    fn short_break_after_midnight_stays_on_the_same_day() {
-        // Stepped away for 30 minutes at 01:00. Still Friday's session.
-        assert!(!should_roll_over(true, 30 * 60, false));
+    fn auto_end_fires_on_the_tick_that_enters_the_hour() {
+        assert!(auto_end_due(Some(21), 22, Some(22)));
+        assert!(!auto_end_due(Some(22), 22, Some(22)), "only once per hour");
+        assert!(!auto_end_due(None, 22, Some(22)), "not on the first tick after launch");
+        assert!(!auto_end_due(Some(21), 22, None));
+        assert!(!auto_end_due(Some(21), 22, Some(23)));
     }
 
     #[test]
-    fn slept_overnight_starts_a_new_day() {
-        // Machine asleep from Friday 02:00, back at Saturday 10:00: an 8h gap.
-        assert!(should_roll_over(true, 8 * HOUR, true));
-        // ...and it would roll on the gap alone, even before the cutoff hour.
-        assert!(should_roll_over(true, 8 * HOUR, false));
+    fn status_server_routes_only_read_only_gets() {
+        assert_eq!(route_status_request("GET /status HTTP/1.1\r\n"), Ok(StatusRoute::Status));
+        assert_eq!(route_status_request("GET /today?t=1718000000 HTTP/1.1\r\n"), Ok(StatusRoute::Today));
+        assert_eq!(route_status_request("POST /status HTTP/1.1\r\n"), Err(405));
+        assert_eq!(route_status_request("GET /state.json HTTP/1.1\r\n"), Err(404));
+        assert_eq!(route_status_request(""), Err(400));
     }
 
     #[test]
-    fn six_hour_gap_is_the_boundary() {
-        assert!(!should_roll_over(true, 6 * HOUR - 1, false));
-        assert!(should_roll_over(true, 6 * HOUR, false));
+    fn status_server_answers_only_loopback_hosts_without_private_fields() {
+        assert!(status_host_allowed(Some("127.0.0.1:47615")));
+        assert!(status_host_allowed(Some("LOCALHOST")));
+        assert!(!status_host_allowed(Some("evil.example:47615")));
+        assert!(!status_host_allowed(Some("127.0.0.1.evil.example")));
+        assert!(!status_host_allowed(None));
+
+        let mut worked = lap(0, Some(HOUR));
+        worked.note = Some("merger call".into());
+        worked.window_titles = vec!["Secret.docx".into()];
+        worked.app_breakdown.insert("Slack".into(), 60);
+        worked.tags = vec!["client".into()];
+        let json = serde_json::to_string(&StatusServerDay::from(&day("2026-10-15", vec![worked]))).unwrap();
+        for leaked in ["merger", "Secret", "Slack", "client"] {
+            assert!(!json.contains(leaked), "{} leaked: {}", leaked, json);
+        }
     }
 
     #[test]
-    fn cutoff_rolls_over_a_session_that_never_paused() {
-        // Worked straight through the night with no break at all: gap is 0 forever, so
-        // only the cutoff can end the day. Without this the day_key never rolls, which is
-        // how a Saturday afternoon lap got filed under Friday.
-        assert!(should_roll_over(true, 0, true));
+    fn consistency_counts_workdays_and_runs() {
+        let date = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        // Two Monday-first weeks ending on Wednesday 2024-06-12.
+        let daily: HashMap<String, u64> = [
+            ("2024-06-03", HOUR), // Mon
+            ("2024-06-04", HOUR),
+            ("2024-06-05", 30), // too short to count
+            ("2024-06-06", HOUR),
+            ("2024-06-07", HOUR),
+            ("2024-06-08", HOUR), // Saturday: not a workday, doesn't add anything
+            ("2024-06-10", HOUR), // Mon
+            ("2024-06-11", HOUR),
+        ]
+        .into_iter()
+        .map(|(d, secs)| (d.to_string(), secs))
+        .collect();
+        let report = consistency_report(&daily, date("2024-06-12"), 2, 0, &[0, 1, 2, 3, 4]);
+        assert_eq!(report.total_workdays_in_period, 7, "today is left out until it is tracked");
+        assert_eq!(report.tracked_workdays, 6);
+        assert_eq!(report.missed_days, vec!["2024-06-05".to_string()]);
+        assert_eq!(report.weeks_with_full_coverage, 1, "only the current week so far");
+        assert_eq!(report.longest_tracked_run, 4, "Thursday to the next Tuesday, over the weekend");
+        assert_eq!(report.longest_missed_run, 1);
+        assert!((report.consistency_percent - 600.0 / 7.0).abs() < 1e-9);
     }
 
     #[test]
-    fn same_date_never_rolls_over() {
-        // A long idle stretch within one day (a 7h meeting-free afternoon away from the
-        // desk) must not start a second record for the same date.
-        assert!(!should_roll_over(false, 9 * HOUR, false));
-        assert!(!should_roll_over(false, 0, true));
+    fn sync_merge_keeps_newer_copy_and_laps_only_the_older_has() {
+        let local = day("2026-03-02", vec![lap(1_000, Some(1_000 + HOUR)), lap(10_000, Some(10_000 + HOUR))]);
+        let same_lap = Lap { id: local.laps[0].id.clone(), ..lap(1_000, Some(1_000 + 2 * HOUR)) };
+        let mut remote = day("2026-03-02", vec![same_lap, lap(20_000, Some(20_000 + HOUR))]);
+        remote.metadata.insert("from".to_string(), "laptop".to_string());
+
+        let merged = sync::merge_day(local.clone(), 100, remote.clone(), 200);
+        let starts: Vec<u64> = merged.laps.iter().map(|l| l.start_time).collect();
+        assert_eq!(starts, vec![1_000, 10_000, 20_000]);
+        assert_eq!(merged.laps[0].end_time, Some(1_000 + 2 * HOUR)); // the newer copy's lap wins
+        assert_eq!(merged.total_duration, 4 * HOUR);
+        assert_eq!(merged.metadata.get("from").map(String::as_str), Some("laptop"));
+
+        // Older remote: the local copy leads instead.
+        let merged = sync::merge_day(local, 300, remote, 200);
+        assert_eq!(merged.laps[0].end_time, Some(1_000 + HOUR));
+        assert!(!merged.metadata.contains_key("from"));
+
+        // The session's day: the local copy leads whatever the remote's age, keeping its
+        // open lap and is_active; of the remote's laps only the closed one comes in.
+        let mut live = day("2026-03-03", vec![lap(1_000, Some(2_000)), lap(3_000, None)]);
+        live.is_active = true;
+        let other = day("2026-03-03", vec![lap(5_000, Some(6_000)), lap(7_000, None)]);
+        let merged = sync::merge_day(live, u64::MAX, other.clone(), 200);
+        assert!(merged.is_active);
+        let ends: Vec<Option<u64>> = merged.laps.iter().map(|l| l.end_time).collect();
+        assert_eq!(ends, vec![Some(2_000), None, Some(6_000)]);
+
+        // Any other day is settled here, keeping titles the server was never sent.
+        let mut titled = other.clone();
+        titled.laps[0].window_titles = vec!["Notes".to_string()];
+        let settled = sync::settle_remote(DayRecord { is_active: true, ..other }, Some(&titled));
+        assert!(!settled.is_active);
+        assert_eq!(settled.laps.len(), 1);
+        assert_eq!(settled.laps[0].window_titles, vec!["Notes".to_string()]);
+
+        assert_eq!(sync::retry_delay(1), 30);
+        assert_eq!(sync::retry_delay(2), 60);
+        assert_eq!(sync::retry_delay(30), 3600);
     }
 
-    // --- where the day is cut ------------------------------------------------
+    #[test]
+    fn lap_filter_drops_only_short_laps_with_a_listed_end_reason() {
+        let ended = |start: u64, secs: u64, reason: Option<LapEndReason>| Lap { end_reason: reason, ..lap(start, Some(start + secs)) };
+        let record = day("2026-03-02", vec![
+            ended(0, 30, Some(LapEndReason::ScreenLocked)),
+            ended(100, HOUR, Some(LapEndReason::ScreenLocked)),
+            ended(5_000, 30, Some(LapEndReason::UserStopped)),
+            ended(6_000, 30, None),
+        ]);
+        let filter = LapFilter { end_reasons: vec![LapEndReason::ScreenLocked], shorter_than_secs: Some(120) };
+        let filtered = filter.apply(&record);
+        let starts: Vec<u64> = filtered.laps.iter().map(|l| l.start_time).collect();
+        assert_eq!(starts, vec![100, 5_000, 6_000]);
+        assert_eq!(filtered.total_duration, HOUR + 60);
+
+        let all_locks = LapFilter { shorter_than_secs: None, ..filter };
+        assert_eq!(all_locks.apply(&record).laps.len(), 2);
+        assert_eq!(LapFilter::default().apply(&record).laps.len(), 4);
+    }
 
     #[test]
-    fn worked_through_the_night_is_cut_at_the_cutoff_not_at_wake() {
-        // Open lap running since last night; it is now 09:00 and the cutoff was 06:00.
-        // The old day must end at 06:00, so the pre-dawn hours stay with it and only
-        // 06:00->09:00 counts as today. Cutting at `now` would hand today three hours of
-        // last night's work.
-        let cutoff = 6 * HOUR;
-        let now = 9 * HOUR;
-        assert_eq!(rollover_boundary(true, cutoff, now), cutoff);
+    fn import_merges_by_lap_id_and_flags_overlaps_from_the_other_machine() {
+        let shared = lap(0, Some(HOUR));
+        let legacy = Lap { id: String::new(), ..lap(2 * HOUR, Some(3 * HOUR)) };
+        let mut local = day("2026-03-02", vec![shared.clone(), legacy.clone(), lap(5 * HOUR, Some(6 * HOUR))]);
+        let incoming = day("2026-03-02", vec![
+            Lap { end_time: Some(HOUR + 600), duration: Some(HOUR + 600), ..shared.clone() }, // ended later there
+            legacy,                                     // same lap, no id on either side
+            lap(5 * HOUR + 1_800, Some(7 * HOUR)),      // other machine, overlapping the last local lap
+            lap(8 * HOUR, Some(9 * HOUR)),
+        ]);
+
+        let mut report = ImportReport::default();
+        merge_imported_day(&mut local, incoming, &mut report);
+        assert_eq!(local.laps.len(), 5);
+        assert_eq!(local.laps[0].end_time, Some(HOUR + 600));
+        assert_eq!((report.laps_added, report.laps_replaced), (2, 1));
+        assert_eq!(report.conflicts.len(), 1);
+        let conflict = &report.conflicts[0];
+        assert_eq!((conflict.local_index, conflict.imported_index, conflict.overlap_seconds), (2, 3, 1_800));
+
+        // Importing the same copy again changes nothing.
+        let mut again = ImportReport::default();
+        let before = local.clone();
+        merge_imported_day(&mut local, before.clone(), &mut again);
+        assert_eq!(local.laps.len(), before.laps.len());
+        assert_eq!((again.laps_added, again.laps_replaced, again.conflicts.len()), (0, 0, 0));
+
+        // A lap still running on the other machine is never imported.
+        let mut mid_lap = day("2026-03-03", vec![lap(0, Some(HOUR)), lap(2 * HOUR, None)]);
+        assert_eq!(drop_open_laps(&mut mid_lap), 1);
+        assert!(mid_lap.laps.iter().all(|lap| lap.end_time.is_some()));
     }
 
     #[test]
-    fn returning_from_a_break_cuts_at_now() {
-        // No open lap: the user stopped last night and just came back. The old day ended
-        // when they stopped, and today starts now.
-        let cutoff = 6 * HOUR;
-        let now = 10 * HOUR;
-        assert_eq!(rollover_boundary(false, cutoff, now), now);
+    fn sealed_text_opens_only_with_its_key() {
+        let key = [7u8; 32];
+        let sealed = encryption::seal(&key, b"{\"date\":\"2026-03-02\"}");
+        assert!(!sealed.contains("2026"));
+        assert_ne!(sealed, encryption::seal(&key, b"{\"date\":\"2026-03-02\"}"), "a fresh nonce every time");
+        assert_eq!(encryption::open(&key, &sealed).as_deref(), Some(&b"{\"date\":\"2026-03-02\"}"[..]));
+        assert_eq!(encryption::open(&[8u8; 32], &sealed), None);
+
+        let mut tampered = sealed.into_bytes();
+        let last = tampered.len() - 5;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert_eq!(encryption::open(&key, &String::from_utf8(tampered).unwrap()), None);
     }
 
     #[test]
-    fn boundary_never_runs_ahead_of_the_clock() {
-        // Guards the case where the cutoff has not been reached yet: the boundary must
-        // never be a future timestamp, or a lap would be given a negative duration.
-        let cutoff = 6 * HOUR;
-        let now = 2 * HOUR;
-        assert_eq!(rollover_boundary(true, cutoff, now), now);
+    fn anonymized_records_keep_only_rounded_relative_times() {
+        let utc = chrono::Utc;
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let midnight = 1_772_409_600; // 2026-03-02T00:00:00Z
+        let mut worked = lap(midnight + 86_400 + 9 * HOUR + 7 * 60, Some(midnight + 86_400 + 10 * HOUR + 53 * 60));
+        worked.note = Some("Call with Acme about the merger".to_string());
+        worked.tags = vec!["acme".to_string()];
+        worked.app_breakdown.insert("Slack".to_string(), 600);
+        worked.window_titles = vec!["Acme merger - Google Docs".to_string()];
+        worked.project = Some("client".to_string());
+        let mut record = day("2026-03-03", vec![worked, lap(midnight + 86_400 + 11 * HOUR, None)]);
+        record.metadata.insert("location".to_string(), "Acme HQ".to_string());
+
+        let out = anonymize_records(&[record], start, &utc);
+        assert_eq!(out.len(), 1);
+        let day = &out[0];
+        assert_eq!(day.date, "day-1");
+        assert!(day.metadata.is_empty());
+        assert_eq!(day.laps.len(), 1, "the open lap is left out");
+        let lap = &day.laps[0];
+        assert_eq!((lap.start_time, lap.end_time), (86_400 + 9 * HOUR, Some(86_400 + 11 * HOUR)));
+        assert_eq!(day.total_duration, 2 * HOUR);
+        assert_eq!(lap.project.as_deref(), Some("client"));
+
+        let json = serde_json::to_string(&out).unwrap();
+        let midnight = midnight.to_string();
+        for leaked in ["Acme", "acme", "Slack", "2026", "merger", midnight.as_str()] {
+            assert!(!json.contains(leaked), "{} survived anonymization", leaked);
+        }
     }
 
-    // --- backfill of history written before the day rolled over -------------
+    #[test]
+    fn accurate_elapsed_follows_the_wall_clock() {
+        let mut session = session_on("2026-07-10", None);
+        session.current_lap_start_timestamp = now_unix() - 90;
+        assert!((90..=91).contains(&session.compute_accurate_elapsed()));
+
+        // A clock set back past the lap start must not underflow.
+        session.current_lap_start_timestamp = now_unix() + 600;
+        assert_eq!(session.compute_accurate_elapsed(), 0);
+    }
 
-    // Local wall-clock -> unix, so these tests read as times rather than epoch numbers.
-    fn at(day: u32, hh: u32, mm: u32) -> u64 {
-        use chrono::TimeZone;
-        chrono::Local
-            .with_ymd_and_hms(2026, 7, day, hh, mm, 0)
-            .single()
-            .unwrap()
-            .timestamp() as u64
+    #[test]
+    fn running_lap_is_only_an_open_last_lap() {
+        let mut record = day("2026-07-10", vec![lap(100, Some(200)), lap(300, None)]);
+        assert_eq!(running_lap(&record).map(|l| l.start_time), Some(300));
+
+        record.laps[1].end_time = Some(400);
+        assert!(running_lap(&record).is_none());
+        assert!(running_lap(&day("2026-07-10", vec![])).is_none());
     }
 
     #[test]
-    fn backfill_moves_a_lap_recorded_on_the_wrong_day() {
-        // The real defect: a lap that started at 12:52 the NEXT afternoon, still filed
-        // under the previous day because the running app never re-checked the date.
-        let mut records = HashMap::new();
-        records.insert(
-            "2026-07-10".into(),
-            day("2026-07-10", vec![
-                lap(at(10, 21, 0), Some(at(10, 23, 0))),
-                lap(at(11, 12, 52), Some(at(11, 13, 30))),
-            ]),
-        );
+    fn deep_links_route_by_host() {
+        assert_eq!(parse_deep_link("screentime://start-day"), Some(DeepLinkAction::StartDay));
+        assert_eq!(parse_deep_link("screentime://stop-lap/"), Some(DeepLinkAction::StopLap));
+        assert_eq!(parse_deep_link("screentime://add-lap?from=raycast"), Some(DeepLinkAction::AddLap));
+        assert_eq!(parse_deep_link("screentime://status"), Some(DeepLinkAction::Status));
+        assert_eq!(parse_deep_link("screentime://end-day"), None);
+        assert_eq!(parse_deep_link("https://status"), None);
+    }
 
-        let moved = backfill_misattributed_laps(&mut records, None);
+    #[test]
+    fn tracked_since_counts_only_time_after_launch() {
+        let mut gap = lap(1_000, Some(1_200));
+        gap.is_synthetic = true;
+        let laps = [
+            lap(100, Some(400)),   // before launch
+            lap(900, Some(1_100)), // straddles it: 100s after
+            gap,
+            lap(1_500, None), // running: 500s up to now
+        ];
+        assert_eq!(tracked_since(&laps, 1_000, 2_000), 600);
+    }
 
-        assert_eq!(moved, 1);
-        assert_eq!(records["2026-07-10"].laps.len(), 1);
-        assert_eq!(records["2026-07-11"].laps.len(), 1);
-        assert_eq!(records["2026-07-11"].laps[0].start_time, at(11, 12, 52));
-        // Totals must be recomputed on both sides, not just the lap lists.
-        assert_eq!(records["2026-07-10"].total_duration, 2 * 3600);
-        assert_eq!(records["2026-07-11"].total_duration, 38 * 60);
+    #[test]
+    fn shortcuts_match_their_configured_binding() {
+        let config = TrackerConfig::default();
+        let toggle: Shortcut = "CmdOrCtrl+Shift+T".parse().unwrap();
+        let lap: Shortcut = "CmdOrCtrl+Shift+L".parse().unwrap();
+        assert_eq!(shortcut_action(&config, &toggle), Some(ShortcutAction::ToggleTimer));
+        assert_eq!(shortcut_action(&config, &lap), Some(ShortcutAction::AddLap));
+        assert_eq!(shortcut_action(&config, &"Alt+T".parse().unwrap()), None);
+
+        assert!(parse_shortcut(Some("  ")).unwrap().is_none());
+        assert!(parse_shortcut(Some("Shift+Nonsense")).is_err());
+        assert!(shortcut_debounced(10_000, 10_200));
+        assert!(!shortcut_debounced(10_000, 10_600));
     }
 
     #[test]
-    fn backfill_leaves_genuine_late_night_work_alone() {
-        // Worked Friday 21:00 into Saturday 02:23. Those small-hours laps started before
-        // the cutoff, so they belong to Friday — the day they started on. Moving them to
-        // Saturday would be a second bug dressed up as a fix.
-        let mut records = HashMap::new();
-        records.insert(
-            "2026-07-05".into(),
-            day("2026-07-05", vec![
-                lap(at(5, 21, 0), Some(at(5, 23, 30))),
-                lap(at(6, 0, 26), Some(at(6, 2, 23))),
-            ]),
-        );
+    fn overlapping_laps_reports_overlaps_and_duplicates() {
+        let mut gap = lap(150, Some(250));
+        gap.is_synthetic = true;
+        let record = day("2026-07-10", vec![
+            lap(100, Some(400)),
+            lap(100, Some(200)), // duplicate start
+            lap(300, Some(500)), // overlaps the first by 100s
+            gap,
+            lap(600, Some(700)),
+            lap(700, None), // open: not checked
+        ]);
+        let found: Vec<_> = overlapping_laps(&record)
+            .into_iter()
+            .map(|r| (r.lap_a_index, r.lap_b_index, r.overlap_seconds, r.kind))
+            .collect();
+        assert_eq!(found, vec![(1, 0, 100, OverlapKind::Duplicate), (0, 2, 100, OverlapKind::Overlap)]);
+
+        // Equal starts nested inside an earlier lap are still duplicates of each other.
+        let nested = day("2026-07-11", vec![lap(100, Some(400)), lap(200, Some(250)), lap(200, Some(300))]);
+        let found: Vec<_> = overlapping_laps(&nested)
+            .into_iter()
+            .map(|r| (r.lap_a_index, r.lap_b_index, r.overlap_seconds, r.kind))
+            .collect();
+        assert_eq!(found, vec![(0, 1, 50, OverlapKind::Overlap), (1, 2, 50, OverlapKind::Duplicate)]);
+    }
 
-        let moved = backfill_misattributed_laps(&mut records, None);
+    #[test]
+    fn auto_start_waits_for_the_setting_and_skips_days_ended_by_hand() {
+        let mut config = TrackerConfig::default();
+        let mut ended = day("2026-07-10", vec![lap(100, Some(200))]);
+        assert!(!auto_start_due(&config, false, None, 0));
+
+        config.auto_start_day = true;
+        assert!(auto_start_due(&config, false, None, 0));
+        assert!(!auto_start_due(&config, true, None, 0));
+        assert!(auto_start_due(&config, false, Some(&ended), 0));
+        ended.ended_explicitly = true;
+        assert!(!auto_start_due(&config, false, Some(&ended), 0));
+
+        assert!(auto_start_due(&config, false, None, 5));
+        config.auto_start_weekdays_only = true;
+        assert!(!auto_start_due(&config, false, None, 5));
+        assert!(auto_start_due(&config, false, None, 4));
+    }
 
-        assert_eq!(moved, 0);
-        assert_eq!(records["2026-07-05"].laps.len(), 2);
-        assert!(!records.contains_key("2026-07-06"));
+    #[test]
+    fn day_export_json_round_trips_and_can_drop_metadata() {
+        let mut record = day("2026-07-10", vec![lap(100, Some(200))]);
+        record.metadata.insert("mood".into(), "good".into());
+
+        let full: DayRecord = serde_json::from_str(&day_export_json(&record, true).unwrap()).unwrap();
+        assert_eq!(full.metadata.get("mood").map(String::as_str), Some("good"));
+
+        let json = day_export_json(&record, false).unwrap();
+        assert!(!json.contains("metadata"));
+        let bare: DayRecord = serde_json::from_str(&json).unwrap();
+        assert!(bare.metadata.is_empty());
+        assert_eq!(bare.laps.len(), 1);
     }
 
     #[test]
-    fn backfill_never_touches_the_open_lap() {
-        // The open lap belongs to the live session; re-filing it would orphan the session.
+    fn bulk_project_assignment_respects_existing_projects_and_the_running_lap() {
+        let mut owned = lap(300, Some(400));
+        owned.project = Some("Beta".into());
         let mut records = HashMap::new();
-        let mut today = day("2026-07-14", vec![lap(at(14, 9, 0), None)]);
-        today.is_active = true;
-        records.insert("2026-07-14".into(), today);
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, Some(200)), owned, lap(500, None)]));
+        records.insert("2026-07-11".into(), day("2026-07-11", vec![lap(100, Some(200))]));
+        records.insert("2026-07-20".into(), day("2026-07-20", vec![lap(100, Some(200))]));
+        let acme = Some("Acme".to_string());
+
+        let report = assign_project_in_range(&mut records, "2026-07-10", "2026-07-11", &acme, false);
+        assert_eq!(report, BulkUpdateReport { days_updated: 2, laps_updated: 2, laps_skipped: 2, archived_days_skipped: 0 });
+        assert_eq!(records["2026-07-10"].laps[1].project.as_deref(), Some("Beta"));
+        assert_eq!(records["2026-07-10"].laps[2].project, None);
+        assert_eq!(records["2026-07-20"].laps[0].project, None);
+
+        let report = assign_project_in_range(&mut records, "2026-07-10", "2026-07-11", &acme, true);
+        assert_eq!(report, BulkUpdateReport { days_updated: 1, laps_updated: 1, laps_skipped: 3, archived_days_skipped: 0 });
+        assert_eq!(records["2026-07-10"].laps[1].project, acme);
+    }
 
-        let moved = backfill_misattributed_laps(&mut records, Some("2026-07-14"));
+    #[test]
+    fn display_assertion_is_released_once_for_every_create() {
+        use std::cell::Cell;
+        let (created, released) = (Cell::new(0), Cell::new(0));
+        let mut held = None;
+        let mut drive = |wanted: bool| {
+            sync_display_assertion(
+                &mut held,
+                wanted,
+                || {
+                    created.set(created.get() + 1);
+                    Some(created.get())
+                },
+                |id| {
+                    assert_eq!(id, created.get(), "released an assertion that is not the one held");
+                    released.set(released.get() + 1);
+                },
+            );
+            held
+        };
 
-        assert_eq!(moved, 0);
-        assert!(records["2026-07-14"].laps.iter().any(|l| l.duration.is_none()));
+        // active -> active -> paused -> paused -> active -> stopped
+        assert_eq!(drive(true), Some(1));
+        assert_eq!(drive(true), Some(1));
+        assert_eq!(drive(false), None);
+        assert_eq!(drive(false), None);
+        assert_eq!(drive(true), Some(2));
+        assert_eq!(drive(false), None);
+        assert_eq!((created.get(), released.get()), (2, 2));
+
+        // A failed create holds nothing, so there is nothing to release.
+        let mut held = None;
+        sync_display_assertion(&mut held, true, || None, |_| panic!("nothing was held"));
+        sync_display_assertion(&mut held, false, || None, |_| panic!("nothing was held"));
     }
 
     #[test]
-    fn backfill_drops_a_day_left_empty_but_keeps_the_live_one() {
-        // A record whose only lap was misfiled has no reason to exist afterwards — but the
-        // session's own day must survive even if it is momentarily empty.
+    fn project_sessions_group_matching_laps_newest_day_first() {
+        let acme = |start, end| Lap { project: Some("Acme".into()), ..lap(start, Some(end)) };
         let mut records = HashMap::new();
-        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(at(11, 14, 0), Some(at(11, 15, 0)))]));
-        records.insert("2026-07-14".into(), day("2026-07-14", vec![]));
+        records.insert("2026-07-10".into(), day("2026-07-10", vec![acme(100, 400), lap(500, Some(550))]));
+        records.insert("2026-07-11".into(), day("2026-07-11", vec![acme(100, 200), acme(300, 350), lap(400, None)]));
+        records.insert("2026-07-12".into(), day("2026-07-12", vec![lap(100, Some(200))]));
 
-        let moved = backfill_misattributed_laps(&mut records, Some("2026-07-14"));
+        let sessions = project_sessions(&records, "Acme", "", "9999-12-31");
+        assert_eq!((sessions.total_seconds, sessions.lap_count), (450, 3));
+        let days: Vec<_> = sessions.days.iter().map(|d| (d.date.as_str(), d.day_total_seconds)).collect();
+        assert_eq!(days, vec![("2026-07-11", 150), ("2026-07-10", 300)]);
 
-        assert_eq!(moved, 1);
-        assert!(!records.contains_key("2026-07-10"), "emptied day should be dropped");
-        assert!(records.contains_key("2026-07-11"), "lap should land on the day it happened");
-        assert!(records.contains_key("2026-07-14"), "the live day must survive");
+        let unassigned = project_sessions(&records, NO_PROJECT, "", "9999-12-31");
+        assert_eq!((unassigned.total_seconds, unassigned.lap_count), (150, 2));
     }
 
     #[test]
-    fn backfill_is_idempotent() {
-        // It runs behind a schema_version gate, but a second pass must still be a no-op:
-        // once every lap sits on the day it started, there is nothing left to move.
+    fn display_wake_resumes_only_a_display_sleep_pause_when_unlocked() {
+        assert!(wake_resumes(Some(PauseCause::DisplaySleep), PauseCause::DisplaySleep, false));
+        assert!(!wake_resumes(Some(PauseCause::DisplaySleep), PauseCause::DisplaySleep, true));
+        assert!(!wake_resumes(Some(PauseCause::Lock), PauseCause::DisplaySleep, false));
+        assert!(!wake_resumes(Some(PauseCause::User), PauseCause::DisplaySleep, false));
+        assert!(!wake_resumes(None, PauseCause::DisplaySleep, false));
+        assert_eq!(serde_json::to_value(PauseCause::DisplaySleep).unwrap(), "display_sleep");
+    }
+
+    #[test]
+    fn trimming_short_laps_spares_running_synthetic_and_long_laps() {
+        let mut pause = lap(200, Some(201));
+        pause.is_synthetic = true;
         let mut records = HashMap::new();
-        records.insert(
-            "2026-07-10".into(),
-            day("2026-07-10", vec![lap(at(11, 12, 52), Some(at(11, 13, 30)))]),
-        );
+        records.insert("2026-08-02".into(), day("2026-08-02", vec![lap(0, Some(2)), lap(10, Some(HOUR)), pause, lap(HOUR, None)]));
+        records.insert("2026-08-01".into(), day("2026-08-01", vec![lap(0, Some(59)), lap(100, Some(400))]));
+
+        let dry = trim_short_laps(&mut records, 3_600, true);
+        assert_eq!(dry, TrimReport {
+            laps_found: 2,
+            laps_removed: 0,
+            days_affected: vec!["2026-08-01".into(), "2026-08-02".into()],
+            seconds_removed: 61,
+            archived_days_skipped: 0,
+        });
+        assert_eq!(records["2026-08-01"].laps.len(), 2);
 
-        assert_eq!(backfill_misattributed_laps(&mut records, None), 1);
-        assert_eq!(backfill_misattributed_laps(&mut records, None), 0);
+        let done = trim_short_laps(&mut records, 3_600, false);
+        assert_eq!(done.laps_removed, 2);
+        assert_eq!(records["2026-08-02"].laps.len(), 3);
+        assert_eq!(records["2026-08-02"].total_duration, HOUR - 10);
+        assert_eq!(records["2026-08-01"].total_duration, 300);
     }
 
-    // --- merge (undo a rollover) -------------------------------------------
+    #[test]
+    fn clock_skew_ignores_rounding_and_reports_steps_both_ways() {
+        assert_eq!(clock_skew(1, 1), None);
+        assert_eq!(clock_skew(6, 1), None);
+        assert_eq!(clock_skew(-2, 1), None);
+        assert_eq!(clock_skew(3_601, 1), Some(3_600));
+        assert_eq!(clock_skew(-599, 1), Some(-600));
+    }
 
     #[test]
-    fn merge_folds_a_day_into_the_previous_one() {
-        let mut records = HashMap::new();
-        records.insert("2026-07-10".into(), day("2026-07-10", vec![lap(100, Some(400))]));
-        records.insert("2026-07-11".into(), day("2026-07-11", vec![lap(500, Some(700))]));
+    fn recovery_preview_reports_the_persisted_session_and_days() {
+        let state: AppStateArc = Arc::new(AppState::new());
+        let mut persisted = snapshot_state(&state);
+        persisted.schema_version = CURRENT_SCHEMA_VERSION;
+        persisted.day_records.insert("2026-09-01".to_string(), day("2026-09-01", vec![lap(0, Some(HOUR))]));
+        let preview = recovery_preview(&persisted, &[], 2_048, 1_700_000_000);
+        assert!(!preview.has_session);
+        assert_eq!((preview.session_day_key, preview.session_accumulated_seconds), (None, None));
+        assert_eq!((preview.day_records_count, preview.file_size_bytes, preview.last_modified), (1, 2_048, 1_700_000_000));
+
+        // Settled days live in days/; one also still in state.json counts once.
+        let day_files = ["2026-08-30".to_string(), "2026-08-31".to_string(), "2026-09-01".to_string()];
+        assert_eq!(recovery_preview(&persisted, &day_files, 4_096, 0).day_records_count, 3);
+        assert_eq!(preview.schema_version, CURRENT_SCHEMA_VERSION);
+
+        persisted.current_session = Some(PersistedSessionState {
+            day_key: "2026-09-01".to_string(),
+            current_lap_start_timestamp: 0,
+            accumulated_seconds: 120,
+            is_paused: false,
+            user_paused: false,
+            project: None,
+            paused_cause: None,
+            paused_since: None,
+            overrides: DaySessionOverrides::default(),
+            template: None,
+        });
+        let preview = recovery_preview(&persisted, &[], 2_048, 1_700_000_000);
+        assert!(preview.has_session);
+        assert_eq!(preview.session_day_key.as_deref(), Some("2026-09-01"));
+        assert_eq!(preview.session_accumulated_seconds, Some(120));
+    }
 
-        let target = merge_records_into_previous(&mut records, "2026-07-11").unwrap();
+    // Regression cases for the 2026 DST switches: US (America/New_York, 2am) on Mar 8 and
+    // Nov 1, EU (Europe/Berlin, 1am UTC) on Mar 29 and Oct 25.
+    fn date(key: &str) -> chrono::NaiveDate {
+        chrono::NaiveDate::parse_from_str(key, "%Y-%m-%d").unwrap()
+    }
 
-        assert_eq!(target, "2026-07-10");
-        assert!(!records.contains_key("2026-07-11"), "merged day should stop existing");
-        let merged = &records["2026-07-10"];
-        assert_eq!(merged.laps.len(), 2);
-        assert_eq!(merged.total_duration, 300 + 200);
-        // Laps must come back in chronological order, not append order.
-        assert!(merged.laps[0].start_time < merged.laps[1].start_time);
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> u64 {
+        use chrono::TimeZone;
+        chrono::Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap().timestamp() as u64
     }
 
     #[test]
-    fn merge_skips_over_days_with_no_record() {
-        // The machine was off on the 12th, so the 13th merges into the 11th.
-        let mut records = HashMap::new();
-        records.insert("2026-07-11".into(), day("2026-07-11", vec![lap(100, Some(200))]));
-        records.insert("2026-07-13".into(), day("2026-07-13", vec![lap(900, Some(950))]));
+    fn local_days_are_23_or_25_hours_across_dst_switches() {
+        let day_len = |key: &str, tz: &chrono_tz::Tz| {
+            let start = date(key);
+            local_hour_timestamp(start.succ_opt().unwrap(), 0, tz) - local_hour_timestamp(start, 0, tz)
+        };
+        let (ny, berlin) = (chrono_tz::America::New_York, chrono_tz::Europe::Berlin);
+        assert_eq!(day_len("2026-03-08", &ny), 23 * HOUR);
+        assert_eq!(day_len("2026-11-01", &ny), 25 * HOUR);
+        assert_eq!(day_len("2026-03-29", &berlin), 23 * HOUR);
+        assert_eq!(day_len("2026-10-25", &berlin), 25 * HOUR);
+        assert_eq!(day_len("2026-07-01", &berlin), 24 * HOUR);
+    }
 
-        let target = merge_records_into_previous(&mut records, "2026-07-13").unwrap();
-        assert_eq!(target, "2026-07-11");
-        assert_eq!(records["2026-07-11"].laps.len(), 2);
+    #[test]
+    fn skipped_and_repeated_hours_resolve_to_a_real_instant() {
+        let (ny, berlin) = (chrono_tz::America::New_York, chrono_tz::Europe::Berlin);
+        // Spring forward: 02:00 never happens, so it means the jump to 03:00.
+        assert_eq!(local_hour_timestamp(date("2026-03-08"), 2, &ny), utc(2026, 3, 8, 7, 0));
+        assert_eq!(local_hour_timestamp(date("2026-03-29"), 2, &berlin), utc(2026, 3, 29, 1, 0));
+        // Fall back: the hour happens twice; the first one counts.
+        assert_eq!(local_hour_timestamp(date("2026-11-01"), 1, &ny), utc(2026, 11, 1, 5, 0));
+        assert_eq!(local_hour_timestamp(date("2026-10-25"), 2, &berlin), utc(2026, 10, 25, 0, 0));
     }
 
     #[test]
-    fn merge_carries_the_open_lap_and_active_flag() {
-        // Merging today (still being tracked) back into yesterday must keep the day alive
-        // and leave the open lap open, or the running session would be orphaned.
-        let mut records = HashMap::new();
-        records.insert("2026-07-13".into(), day("2026-07-13", vec![lap(100, Some(400))]));
-        let mut today = day("2026-07-14", vec![lap(500, None)]);
-        today.is_active = true;
-        records.insert("2026-07-14".into(), today);
+    fn the_rollover_cutoff_follows_the_offset_in_force_that_morning() {
+        let (ny, berlin) = (chrono_tz::America::New_York, chrono_tz::Europe::Berlin);
+        let noon = |ts: u64| chrono::DateTime::from_timestamp(ts as i64, 0).unwrap();
+        assert_eq!(cutoff_timestamp_in(noon(utc(2026, 3, 8, 16, 0)), &ny), utc(2026, 3, 8, 10, 0));
+        assert_eq!(cutoff_timestamp_in(noon(utc(2026, 11, 1, 17, 0)), &ny), utc(2026, 11, 1, 11, 0));
+        assert_eq!(cutoff_timestamp_in(noon(utc(2026, 3, 29, 10, 0)), &berlin), utc(2026, 3, 29, 4, 0));
+        assert_eq!(cutoff_timestamp_in(noon(utc(2026, 10, 25, 11, 0)), &berlin), utc(2026, 10, 25, 5, 0));
+    }
 
-        let target = merge_records_into_previous(&mut records, "2026-07-14").unwrap();
+    #[test]
+    fn a_lap_worked_through_a_dst_switch_is_cut_at_the_real_cutoff() {
+        let ny = chrono_tz::America::New_York;
+        // 22:00 EST on Mar 7 to the 06:00 EDT cutoff is 7 hours, not 8.
+        let start = utc(2026, 3, 8, 3, 0);
+        let cutoff = cutoff_timestamp_in(chrono::DateTime::from_timestamp(utc(2026, 3, 8, 12, 0) as i64, 0).unwrap(), &ny);
+        let mut record = day("2026-03-07", vec![lap(start, None)]);
+        finalize_dangling_lap(&mut record, rollover_boundary(true, cutoff, cutoff + HOUR), Some(LapEndReason::DayEnded));
+        assert_eq!(record.total_duration, 7 * HOUR);
+
+        // 22:00 EDT on Oct 31 to 06:00 EST is 9 hours, and the repeated 1 o'clock hour
+        // lands in the hourly totals twice rather than being lost or spilling into 2.
+        let start = utc(2026, 11, 1, 2, 0);
+        let cutoff = cutoff_timestamp_in(chrono::DateTime::from_timestamp(utc(2026, 11, 1, 12, 0) as i64, 0).unwrap(), &ny);
+        let mut record = day("2026-10-31", vec![lap(start, None)]);
+        finalize_dangling_lap(&mut record, rollover_boundary(true, cutoff, cutoff + HOUR), Some(LapEndReason::DayEnded));
+        assert_eq!(record.total_duration, 9 * HOUR);
+        let records = HashMap::from([(record.date.clone(), record)]);
+        let buckets = hourly_totals(&records, "2026-10-31", "2026-10-31", &ny);
+        assert_eq!((buckets[0], buckets[1], buckets[2], buckets[22]), (HOUR, 2 * HOUR, HOUR, HOUR));
+        assert_eq!(buckets.iter().sum::<u64>(), 9 * HOUR);
+    }
 
-        let merged = &records[&target];
-        assert!(merged.is_active, "absorbing day must stay active");
-        assert!(merged.laps.iter().any(|l| l.duration.is_none()), "open lap must survive");
-        // The open lap contributes nothing to the total until it closes.
-        assert_eq!(merged.total_duration, 300);
+    #[test]
+    fn save_age_counts_from_launch_until_the_first_save() {
+        assert_eq!(save_age(0, 1_030, 1_000), 30);
+        assert_eq!(save_age(1_020, 1_030, 1_000), 10);
+        assert_eq!(save_age(1_040, 1_030, 1_000), 0, "a save from the future is fresh");
     }
 
     #[test]
-    fn merge_refuses_when_there_is_no_earlier_day() {
-        let mut records = HashMap::new();
-        records.insert("2026-07-04".into(), day("2026-07-04", vec![lap(100, Some(200))]));
-        assert!(merge_records_into_previous(&mut records, "2026-07-04").is_err());
-        // The record must survive a refused merge.
-        assert!(records.contains_key("2026-07-04"));
+    fn excluded_time_is_kept_per_lap_and_summed_per_day() {
+        let mut slept = lap(100, Some(400));
+        slept.note_excluded(1_000);
+        slept.note_excluded(400); // closed at "now": nothing left out
+        assert_eq!(slept.excluded_seconds, 600);
+        let record = day("2026-10-01", vec![slept, lap(1_000, Some(1_500)), lap(1_500, None)]);
+        assert_eq!(excluded_seconds(&record), 600);
+        assert_eq!(worked_seconds(&record), 800, "excluded time is never work");
+
+        let old: Lap = serde_json::from_str(r#"{"start_time":1,"end_time":2,"duration":1}"#).unwrap();
+        assert_eq!(old.excluded_seconds, 0);
+    }
+
+    #[test]
+    fn lid_messages_decode_and_only_a_sleep_pause_resumes_on_open() {
+        assert_eq!(lid_event(0b11), LidEvent::ClosedToSleep);
+        assert_eq!(lid_event(0b01), LidEvent::ClosedAwake, "closed but docked: keeps tracking");
+        assert_eq!(lid_event(0b10), LidEvent::Opened);
+        assert_eq!(lid_event(0), LidEvent::Opened);
+        assert!(wake_resumes(Some(PauseCause::Sleep), PauseCause::Sleep, false));
+        assert!(!wake_resumes(Some(PauseCause::User), PauseCause::Sleep, false));
+        assert!(!wake_resumes(Some(PauseCause::Sleep), PauseCause::Sleep, true));
+    }
+
+    #[test]
+    fn purging_titles_reaches_journal_entries_and_counts_only_titled_laps() {
+        let mut titled = lap(100, Some(200));
+        titled.window_titles = vec!["Budget.xlsx".to_string()];
+        let mut laps = vec![titled.clone(), lap(200, Some(300))];
+        assert_eq!(clear_window_titles(&mut laps), 1);
+        assert!(laps.iter().all(|l| l.window_titles.is_empty()));
+
+        let mut entries = [JournalEntry {
+            seq: 1,
+            kind: "lap-ended".to_string(),
+            event: SessionEvent { day_key: "2026-10-01".to_string(), timestamp: 200, lap: Some(titled), cause: None },
+        }];
+        assert_eq!(clear_window_titles(entries.iter_mut().filter_map(|e| e.event.lap.as_mut())), 1);
+        assert!(!journal_line(&entries[0]).unwrap().contains("Budget"));
     }
 }
@@ -0,0 +1,216 @@
+// Linux idle detection.
+//
+// There is no desktop-independent lock or sleep signal to listen for on Linux, so
+// "away" means the session has had no input for TrackerConfig::idle_threshold_secs.
+// Under Wayland (built with the `wayland` feature) the compositor reports that through
+// ext-idle-notify-v1; without a Wayland session the X11 screensaver extension's idle
+// counter is polled instead. A Wayland session never falls back to X11: XWayland's
+// counter only sees input sent to X clients, so it would report the user idle while
+// they type into native windows. Going idle
+// is handled as a screen lock and the next input as an unlock. IDLE mirrors the state
+// so the polling loop in start_system_monitoring sees the same answer from
+// check_screen_lock_state_sync.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+use crate::{handle_screen_lock_direct, handle_screen_unlock_direct, lock_or_recover, AppStateArc};
+
+static IDLE: AtomicBool = AtomicBool::new(false);
+
+// Whether the session is currently idle past the threshold.
+pub fn is_idle() -> bool {
+    IDLE.load(Ordering::Relaxed)
+}
+
+// Spawn the idle watcher thread. Call once, from setup.
+pub fn start_idle_monitoring(app_handle: AppHandle, state: AppStateArc) {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty()) {
+        #[cfg(feature = "wayland")]
+        {
+            let threshold_secs = lock_or_recover(&state.config).idle_threshold_secs;
+            match wayland::connect(threshold_secs) {
+                Ok(watcher) => {
+                    info!("🐧 Listening for Wayland idle notifications ({}s threshold)", threshold_secs);
+                    let thread_state = state.clone();
+                    state.shutdown.spawn("wayland-idle", move || watcher.run(app_handle, thread_state));
+                }
+                Err(e) => warn!("Wayland idle notification unavailable ({}) - idle detection unavailable", e),
+            }
+        }
+        #[cfg(not(feature = "wayland"))]
+        {
+            let _ = (app_handle, state);
+            warn!("Wayland session but built without the `wayland` feature - idle detection unavailable");
+        }
+        return;
+    }
+
+    let thread_state = state.clone();
+    state.shutdown.spawn("x11-idle", move || x11::run(app_handle, thread_state));
+}
+
+// Record a transition and forward it; repeats of the current state are ignored.
+fn set_idle(app_handle: &AppHandle, state: &AppStateArc, idle: bool) {
+    if IDLE.swap(idle, Ordering::Relaxed) == idle {
+        return;
+    }
+    if idle {
+        info!("💤 Session idle");
+        handle_screen_lock_direct(app_handle, state);
+    } else {
+        info!("👋 Session active again");
+        handle_screen_unlock_direct(app_handle, state);
+    }
+}
+
+#[cfg(feature = "wayland")]
+mod wayland {
+    use super::*;
+    use std::os::fd::{AsFd, AsRawFd};
+    use wayland_client::globals::{registry_queue_init, GlobalListContents};
+    use wayland_client::protocol::{wl_registry, wl_seat};
+    use wayland_client::{Connection, Dispatch, EventQueue, QueueHandle};
+    use wayland_protocols::ext::idle_notify::v1::client::{ext_idle_notification_v1, ext_idle_notifier_v1};
+
+    // How long one wait on the socket may block before the shutdown token is checked.
+    const POLL_TIMEOUT_MS: i32 = 1000;
+
+    pub struct Watcher {
+        queue: EventQueue<Handler>,
+        // Kept so the notification outlives connect(); it is only ever read from.
+        _notification: ext_idle_notification_v1::ExtIdleNotificationV1,
+    }
+
+    struct Handler {
+        app_handle: AppHandle,
+        state: AppStateArc,
+    }
+
+    // The compositor fixes the timeout when the notification is created, so a changed
+    // idle_threshold_secs applies from the next launch.
+    pub fn connect(threshold_secs: u64) -> Result<Watcher, String> {
+        let conn = Connection::connect_to_env().map_err(|e| e.to_string())?;
+        let (globals, queue) = registry_queue_init::<Handler>(&conn).map_err(|e| e.to_string())?;
+        let qh = queue.handle();
+        let seat: wl_seat::WlSeat = globals.bind(&qh, 1..=1, ()).map_err(|e| format!("no seat: {}", e))?;
+        let notifier: ext_idle_notifier_v1::ExtIdleNotifierV1 =
+            globals.bind(&qh, 1..=1, ()).map_err(|e| format!("no ext_idle_notifier_v1: {}", e))?;
+        let timeout_ms = u32::try_from(threshold_secs.saturating_mul(1000)).unwrap_or(u32::MAX);
+        let notification = notifier.get_idle_notification(timeout_ms, &seat, &qh, ());
+        Ok(Watcher { queue, _notification: notification })
+    }
+
+    impl Watcher {
+        // Dispatch until shutdown. Reads go through prepare_read plus a timed poll rather
+        // than blocking_dispatch, which nothing could wake when the app quits.
+        pub fn run(mut self, app_handle: AppHandle, state: AppStateArc) {
+            let mut handler = Handler { app_handle, state: state.clone() };
+            while !state.shutdown.is_stopped() {
+                if let Err(e) = self.queue.dispatch_pending(&mut handler) {
+                    warn!("Wayland dispatch failed, idle detection stopped: {}", e);
+                    return;
+                }
+                if let Err(e) = self.queue.flush() {
+                    warn!("Wayland connection lost, idle detection stopped: {}", e);
+                    return;
+                }
+                let Some(guard) = self.queue.prepare_read() else { continue };
+                let mut fds = libc::pollfd {
+                    fd: guard.connection_fd().as_fd().as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                if unsafe { libc::poll(&mut fds, 1, POLL_TIMEOUT_MS) } > 0 {
+                    if let Err(e) = guard.read() {
+                        warn!("Wayland read failed, idle detection stopped: {}", e);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    impl Dispatch<ext_idle_notification_v1::ExtIdleNotificationV1, ()> for Handler {
+        fn event(
+            handler: &mut Self,
+            _: &ext_idle_notification_v1::ExtIdleNotificationV1,
+            event: ext_idle_notification_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                ext_idle_notification_v1::Event::Idled => set_idle(&handler.app_handle, &handler.state, true),
+                ext_idle_notification_v1::Event::Resumed => set_idle(&handler.app_handle, &handler.state, false),
+                _ => {}
+            }
+        }
+    }
+
+    // The remaining objects send nothing the watcher needs.
+    impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Handler {
+        fn event(_: &mut Self, _: &wl_registry::WlRegistry, _: wl_registry::Event, _: &GlobalListContents, _: &Connection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<wl_seat::WlSeat, ()> for Handler {
+        fn event(_: &mut Self, _: &wl_seat::WlSeat, _: wl_seat::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<ext_idle_notifier_v1::ExtIdleNotifierV1, ()> for Handler {
+        fn event(
+            _: &mut Self,
+            _: &ext_idle_notifier_v1::ExtIdleNotifierV1,
+            _: ext_idle_notifier_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+}
+
+mod x11 {
+    use super::*;
+    use x11_dl::{xlib, xss};
+
+    // How often the X server's idle counter is read.
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    // libX11 and libXss are loaded at runtime, so a machine without them (or without an
+    // X display) just runs without idle detection. The threshold is re-read every poll.
+    pub fn run(app_handle: AppHandle, state: AppStateArc) {
+        let (Ok(xlib), Ok(xss)) = (xlib::Xlib::open(), xss::Xss::open()) else {
+            warn!("libX11/libXss not found - idle detection unavailable");
+            return;
+        };
+        unsafe {
+            let display = (xlib.XOpenDisplay)(std::ptr::null());
+            if display.is_null() {
+                warn!("No X display - idle detection unavailable");
+                return;
+            }
+            let root = (xlib.XDefaultRootWindow)(display);
+            let info = (xss.XScreenSaverAllocInfo)();
+            if info.is_null() {
+                (xlib.XCloseDisplay)(display);
+                return;
+            }
+            info!("🐧 Polling the X11 screensaver idle counter");
+
+            while state.shutdown.sleep(POLL_INTERVAL) {
+                if (xss.XScreenSaverQueryInfo)(display, root, info) == 0 {
+                    continue;
+                }
+                let threshold_ms = lock_or_recover(&state.config).idle_threshold_secs.saturating_mul(1000);
+                set_idle(&app_handle, &state, (*info).idle >= threshold_ms);
+            }
+
+            (xlib.XFree)(info.cast());
+            (xlib.XCloseDisplay)(display);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+// Per-OS integrations that are too large to live inline in lib.rs.
+
+#[cfg(target_os = "windows")]
+pub mod windows;
+
+#[cfg(target_os = "linux")]
+pub mod linux;
@@ -0,0 +1,140 @@
+// Windows screen lock detection.
+//
+// Windows has no cheap "is the session locked?" query to poll, but it will post
+// WM_WTSSESSION_CHANGE to any window registered with WTSRegisterSessionNotification. This
+// thread owns a hidden window for exactly that, pumps its messages, and forwards lock /
+// unlock straight to the shared handlers. It also mirrors the state into SESSION_LOCKED so
+// the polling loop in start_system_monitoring sees the same answer from
+// check_screen_lock_state_sync (it relies on that after a suspend gap).
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+use tauri::AppHandle;
+use tracing::{error, info, warn};
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::System::RemoteDesktop::{
+    WTSRegisterSessionNotification, WTSUnRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION,
+};
+use windows_sys::Win32::System::Threading::GetCurrentThreadId;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, PostThreadMessageW,
+    RegisterClassW, TranslateMessage, MSG, WM_QUIT, WM_WTSSESSION_CHANGE, WNDCLASSW,
+    WTS_SESSION_LOCK, WTS_SESSION_UNLOCK,
+};
+
+use crate::{handle_screen_lock_direct, handle_screen_unlock_direct, AppStateArc};
+
+static SESSION_LOCKED: AtomicBool = AtomicBool::new(false);
+
+// The pump thread's id, so shutdown can post it WM_QUIT (0 until it starts).
+static WATCHER_THREAD_ID: AtomicU32 = AtomicU32::new(0);
+
+// The window procedure is a bare extern fn, so it reaches the app through this.
+static TARGET: OnceLock<(AppHandle, AppStateArc)> = OnceLock::new();
+
+// Whether the last session change we were told about was a lock.
+pub fn is_session_locked() -> bool {
+    SESSION_LOCKED.load(Ordering::Relaxed)
+}
+
+// Spawn the session-notification thread. Call once, from setup.
+pub fn start_session_monitoring(app_handle: AppHandle, state: AppStateArc) {
+    if TARGET.set((app_handle, state.clone())).is_err() {
+        return;
+    }
+
+    // GetMessageW blocks, so the shutdown token can't wake this loop; WM_QUIT does.
+    state.shutdown.on_shutdown(|| {
+        let thread_id = WATCHER_THREAD_ID.load(Ordering::Relaxed);
+        if thread_id != 0 {
+            unsafe { PostThreadMessageW(thread_id, WM_QUIT, 0, 0) };
+        }
+    });
+
+    state.shutdown.spawn("windows-session", || unsafe {
+        WATCHER_THREAD_ID.store(GetCurrentThreadId(), Ordering::Relaxed);
+        let class_name = wide("ScreenTimeTrackerSessionWatcher");
+        let instance = GetModuleHandleW(std::ptr::null());
+
+        let class = WNDCLASSW {
+            style: 0,
+            lpfnWndProc: Some(window_proc),
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: instance,
+            hIcon: std::ptr::null_mut(),
+            hCursor: std::ptr::null_mut(),
+            hbrBackground: std::ptr::null_mut(),
+            lpszMenuName: std::ptr::null(),
+            lpszClassName: class_name.as_ptr(),
+        };
+        if RegisterClassW(&class) == 0 {
+            error!("Failed to register session watcher window class");
+            return;
+        }
+
+        // A plain top-level window that is never shown. Message-only windows
+        // (HWND_MESSAGE) don't reliably receive session change notifications.
+        let hwnd = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            instance,
+            std::ptr::null(),
+        );
+        if hwnd.is_null() {
+            error!("Failed to create session watcher window");
+            return;
+        }
+
+        if WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION) == 0 {
+            warn!("WTSRegisterSessionNotification failed - lock detection unavailable");
+            return;
+        }
+        info!("🪟 Listening for Windows session lock/unlock");
+
+        // GetMessageW returns 0 on WM_QUIT and -1 on error; stop on either.
+        let mut msg: MSG = std::mem::zeroed();
+        while GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+
+        WTSUnRegisterSessionNotification(hwnd);
+    });
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if msg == WM_WTSSESSION_CHANGE {
+        if let Some((app_handle, state)) = TARGET.get() {
+            match wparam as u32 {
+                WTS_SESSION_LOCK => {
+                    info!("🔒 Windows session locked");
+                    SESSION_LOCKED.store(true, Ordering::Relaxed);
+                    handle_screen_lock_direct(app_handle, state);
+                }
+                WTS_SESSION_UNLOCK => {
+                    info!("🔓 Windows session unlocked");
+                    SESSION_LOCKED.store(false, Ordering::Relaxed);
+                    handle_screen_unlock_direct(app_handle, state);
+                }
+                _ => {}
+            }
+        }
+        return 0;
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
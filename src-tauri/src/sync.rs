@@ -0,0 +1,538 @@
+// Mirror day records to a self-hosted REST backend.
+//
+// The server speaks a small protocol; every request carries `Authorization: Bearer <token>`:
+//   GET {base_url}/days         -> [{"date": "YYYY-MM-DD", "updated_at": unix_secs}, ...]
+//   GET {base_url}/days/{date}  -> {"updated_at": unix_secs, "record": DayRecord}
+//   PUT {base_url}/days/{date}  <- {"updated_at": unix_secs, "record": DayRecord}
+//
+// `updated_at` is when the day last changed on the device that wrote it. A local change
+// is noticed by comparing the day's fingerprint with the one last synced. A day changed
+// on one side only is copied to the other (last write wins); changed on both, the newer
+// copy wins and any lap only the older one has is added to it, so no tracked time is
+// lost. A round runs after each end_day and on sync_now. Tracking never waits on it: a
+// failed round leaves its days pending and is retried with backoff.
+//
+// The running session's day is only ever written here: a remote copy of it contributes
+// closed laps this machine lacks, never its own open lap, is_active or totals. Window
+// titles go up only with include_titles, and nothing goes up unsealed while encryption
+// is on unless the user passed plaintext. The token lives in the macOS keychain (a
+// sync-token file, sealed like the rest of the store, elsewhere), never in state.json.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+use crate::{
+    archived_months, check_plaintext_export, day_fingerprint, get_app_data_dir, load_settled_days, lock_or_recover, now_unix,
+    save_state, write_atomically, AppStateArc, DayRecord, Lap, TrackerError,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub base_url: String,
+    // Only read from state files written before the token moved out of them; see
+    // adopt_legacy_token. Never written back.
+    #[serde(default, rename = "token", skip_serializing)]
+    pub legacy_token: String,
+    #[serde(default)]
+    pub include_titles: bool,
+    // The user's go-ahead to send days unsealed while encryption is on.
+    #[serde(default)]
+    pub plaintext: bool,
+}
+
+// The version of a day both sides last agreed on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SyncedDay {
+    fingerprint: u64,
+    updated_at: u64,
+}
+
+// Kept in sync.json beside state.json, so pending days survive a restart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SyncState {
+    synced: HashMap<String, SyncedDay>,
+    pending: BTreeMap<String, u64>, // date -> when it changed locally
+    last_success_at: Option<u64>,
+    last_error: Option<String>,
+    consecutive_failures: u32,
+    next_attempt_at: u64, // no automatic round before this (unix seconds)
+}
+
+#[derive(Default)]
+pub struct SyncEngine {
+    state: std::sync::Mutex<SyncState>,
+    wake: tokio::sync::Notify,
+    round: tokio::sync::Mutex<()>, // one round at a time
+}
+
+impl SyncEngine {
+    // Ask the background task for a round, e.g. after end_day.
+    pub fn request(&self) {
+        self.wake.notify_one();
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncStatus {
+    pub enabled: bool,
+    pub last_success_at: Option<u64>,
+    pub pending_days: Vec<String>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub next_attempt_at: Option<u64>, // set while retrying after a failure
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteDay {
+    date: String,
+    updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DayEnvelope {
+    updated_at: u64,
+    record: DayRecord,
+}
+
+const FIRST_RETRY_SECS: u64 = 30;
+const MAX_RETRY_SECS: u64 = 60 * 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Seconds to wait after the `failures`-th failure in a row: 30s, doubling, capped at an hour.
+pub(crate) fn retry_delay(failures: u32) -> u64 {
+    FIRST_RETRY_SECS
+        .saturating_mul(1u64 << failures.saturating_sub(1).min(20))
+        .min(MAX_RETRY_SECS)
+}
+
+// The newer copy, plus any closed lap (by Lap::key) only the older one has. A lap still
+// open in the older copy is left out: it only runs on the machine that wrote it.
+pub(crate) fn merge_day(local: DayRecord, local_at: u64, remote: DayRecord, remote_at: u64) -> DayRecord {
+    let (mut newer, older) = if remote_at > local_at { (remote, local) } else { (local, remote) };
+    for lap in older.laps.into_iter().filter(|lap| lap.end_time.is_some()) {
+        let key = lap.key(&newer.date);
+        if !newer.laps.iter().any(|l| l.key(&newer.date) == key) {
+            newer.laps.push(lap);
+        }
+    }
+    newer.laps.sort_by_key(|lap| lap.start_time);
+    newer.total_duration = newer.laps.iter().filter_map(Lap::worked_duration).sum();
+    newer
+}
+
+// A remote copy as it may be kept here: another machine's live day is settled on this
+// one, so its open lap and is_active stay behind. Titles the server was never sent are
+// kept from `local`.
+pub(crate) fn settle_remote(mut remote: DayRecord, local: Option<&DayRecord>) -> DayRecord {
+    remote.is_active = false;
+    remote.laps.retain(|lap| lap.end_time.is_some());
+    if let Some(local) = local {
+        for lap in remote.laps.iter_mut().filter(|lap| lap.window_titles.is_empty()) {
+            let key = lap.key(&local.date);
+            if let Some(mine) = local.laps.iter().find(|l| l.key(&local.date) == key) {
+                lap.window_titles = mine.window_titles.clone();
+            }
+        }
+    }
+    remote.total_duration = remote.laps.iter().filter_map(Lap::worked_duration).sum();
+    remote
+}
+
+fn sync_file_path(app_handle: &AppHandle) -> Result<std::path::PathBuf, TrackerError> {
+    Ok(get_app_data_dir(app_handle)?.join("sync.json"))
+}
+
+#[cfg(target_os = "macos")]
+const KEYCHAIN_SERVICE: &str = "screen-time.sync";
+#[cfg(target_os = "macos")]
+const KEYCHAIN_ACCOUNT: &str = "token";
+#[cfg(target_os = "macos")]
+const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+
+#[cfg(target_os = "macos")]
+#[link(name = "Security", kind = "framework")]
+extern "C" {
+    fn SecKeychainFindGenericPassword(
+        keychain: *const std::ffi::c_void,
+        service_length: u32,
+        service: *const std::ffi::c_char,
+        account_length: u32,
+        account: *const std::ffi::c_char,
+        password_length: *mut u32,
+        password: *mut *mut std::ffi::c_void,
+        item: *mut *mut std::ffi::c_void,
+    ) -> i32;
+    fn SecKeychainAddGenericPassword(
+        keychain: *const std::ffi::c_void,
+        service_length: u32,
+        service: *const std::ffi::c_char,
+        account_length: u32,
+        account: *const std::ffi::c_char,
+        password_length: u32,
+        password: *const std::ffi::c_void,
+        item: *mut *mut std::ffi::c_void,
+    ) -> i32;
+    fn SecKeychainItemModifyAttributesAndData(
+        item: *mut std::ffi::c_void,
+        attributes: *const std::ffi::c_void,
+        length: u32,
+        data: *const std::ffi::c_void,
+    ) -> i32;
+    fn SecKeychainItemDelete(item: *mut std::ffi::c_void) -> i32;
+    fn SecKeychainItemFreeContent(attributes: *mut std::ffi::c_void, data: *mut std::ffi::c_void) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(object: *const std::ffi::c_void);
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_error(status: i32) -> TrackerError {
+    TrackerError::PersistenceError(std::io::Error::other(format!("Keychain error {}", status)))
+}
+
+// The stored sync token, if any.
+#[cfg(target_os = "macos")]
+pub fn read_token(_app_handle: &AppHandle) -> Result<Option<String>, TrackerError> {
+    let (mut length, mut data) = (0u32, std::ptr::null_mut());
+    let status = unsafe {
+        SecKeychainFindGenericPassword(
+            std::ptr::null(),
+            KEYCHAIN_SERVICE.len() as u32,
+            KEYCHAIN_SERVICE.as_ptr().cast(),
+            KEYCHAIN_ACCOUNT.len() as u32,
+            KEYCHAIN_ACCOUNT.as_ptr().cast(),
+            &mut length,
+            &mut data,
+            std::ptr::null_mut(),
+        )
+    };
+    match status {
+        0 => {
+            let token = unsafe { std::slice::from_raw_parts(data as *const u8, length as usize) };
+            let token = String::from_utf8_lossy(token).into_owned();
+            unsafe { SecKeychainItemFreeContent(std::ptr::null_mut(), data) };
+            Ok(Some(token))
+        }
+        ERR_SEC_ITEM_NOT_FOUND => Ok(None),
+        status => Err(keychain_error(status)),
+    }
+}
+
+// Replace the stored sync token; an empty one removes it.
+#[cfg(target_os = "macos")]
+pub fn store_token(_app_handle: &AppHandle, token: &str) -> Result<(), TrackerError> {
+    let mut item = std::ptr::null_mut();
+    let found = unsafe {
+        SecKeychainFindGenericPassword(
+            std::ptr::null(),
+            KEYCHAIN_SERVICE.len() as u32,
+            KEYCHAIN_SERVICE.as_ptr().cast(),
+            KEYCHAIN_ACCOUNT.len() as u32,
+            KEYCHAIN_ACCOUNT.as_ptr().cast(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut item,
+        )
+    };
+    let status = match found {
+        ERR_SEC_ITEM_NOT_FOUND if token.is_empty() => 0,
+        ERR_SEC_ITEM_NOT_FOUND => unsafe {
+            SecKeychainAddGenericPassword(
+                std::ptr::null(),
+                KEYCHAIN_SERVICE.len() as u32,
+                KEYCHAIN_SERVICE.as_ptr().cast(),
+                KEYCHAIN_ACCOUNT.len() as u32,
+                KEYCHAIN_ACCOUNT.as_ptr().cast(),
+                token.len() as u32,
+                token.as_ptr().cast(),
+                std::ptr::null_mut(),
+            )
+        },
+        0 => unsafe {
+            let status = if token.is_empty() {
+                SecKeychainItemDelete(item)
+            } else {
+                SecKeychainItemModifyAttributesAndData(item, std::ptr::null(), token.len() as u32, token.as_ptr().cast())
+            };
+            CFRelease(item);
+            status
+        },
+        status => status,
+    };
+    match status {
+        0 => Ok(()),
+        status => Err(keychain_error(status)),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn token_file_path(app_handle: &AppHandle) -> Result<std::path::PathBuf, TrackerError> {
+    Ok(get_app_data_dir(app_handle)?.join("sync-token"))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn read_token(app_handle: &AppHandle) -> Result<Option<String>, TrackerError> {
+    let path = token_file_path(app_handle)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(crate::encryption::read_text(&path)?))
+}
+
+// Replace the stored sync token; an empty one removes it. Sealed while encryption is on,
+// and readable only by the user on Unix.
+#[cfg(not(target_os = "macos"))]
+pub fn store_token(app_handle: &AppHandle, token: &str) -> Result<(), TrackerError> {
+    let path = token_file_path(app_handle)?;
+    if token.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    write_atomically(&path, &crate::encryption::seal_text(token.to_string())?)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+// Older state files kept the token in the sync config; move it to the token store. Call
+// once the config is loaded.
+pub fn adopt_legacy_token(app_handle: &AppHandle, state: &AppStateArc) {
+    let mut config = lock_or_recover(&state.config);
+    let Some(sync) = config.sync.as_mut().filter(|sync| !sync.legacy_token.is_empty()) else {
+        return;
+    };
+    match store_token(app_handle, &sync.legacy_token) {
+        Ok(()) => {
+            sync.legacy_token.clear();
+            info!("🔑 Moved the sync token out of state.json");
+        }
+        Err(e) => warn!("Could not move the sync token out of state.json: {}", e),
+    }
+}
+
+fn save_sync_state(app_handle: &AppHandle, sync: &SyncState) -> Result<(), TrackerError> {
+    write_atomically(&sync_file_path(app_handle)?, &serde_json::to_string_pretty(sync)?)?;
+    Ok(())
+}
+
+pub fn status(state: &AppStateArc) -> SyncStatus {
+    let enabled = lock_or_recover(&state.config).sync.is_some();
+    let sync = lock_or_recover(&state.sync.state);
+    SyncStatus {
+        enabled,
+        last_success_at: sync.last_success_at,
+        pending_days: sync.pending.keys().cloned().collect(),
+        last_error: sync.last_error.clone(),
+        consecutive_failures: sync.consecutive_failures,
+        next_attempt_at: (sync.consecutive_failures > 0).then_some(sync.next_attempt_at),
+    }
+}
+
+// Load sync.json and start the background task. Call once, from setup.
+pub fn start(app_handle: AppHandle, state: AppStateArc) {
+    if let Ok(json) = sync_file_path(&app_handle).and_then(|path| Ok(std::fs::read_to_string(path)?)) {
+        match serde_json::from_str(&json) {
+            Ok(loaded) => *lock_or_recover(&state.sync.state) = loaded,
+            Err(e) => warn!("Ignoring unreadable sync.json: {}", e),
+        }
+    }
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // Sleep until asked, or until a pending retry is due.
+            let retry_in = {
+                let sync = lock_or_recover(&state.sync.state);
+                (sync.consecutive_failures > 0).then(|| sync.next_attempt_at.saturating_sub(now_unix()))
+            };
+            match retry_in {
+                Some(secs) => {
+                    let _ = tokio::time::timeout(Duration::from_secs(secs), state.sync.wake.notified()).await;
+                }
+                None => state.sync.wake.notified().await,
+            }
+            if state.shutdown.is_stopped() {
+                break;
+            }
+            // unlock_store requests a round once the config is loaded.
+            if crate::encryption::is_locked() || lock_or_recover(&state.config).sync.is_none() {
+                continue;
+            }
+            if let Err(e) = run_round(&app_handle, &state).await {
+                warn!("🔄 Sync failed: {}", e);
+            }
+        }
+    });
+}
+
+// One full round; on failure the error and next retry are recorded before returning it.
+pub async fn run_round(app_handle: &AppHandle, state: &AppStateArc) -> Result<(), TrackerError> {
+    if crate::encryption::is_locked() {
+        return Err(TrackerError::StoreLocked);
+    }
+    let _round = state.sync.round.lock().await;
+    let config = lock_or_recover(&state.config)
+        .sync
+        .clone()
+        .ok_or_else(|| TrackerError::InvalidInput("Sync is not configured".to_string()))?;
+    let result = match check_plaintext_export(config.plaintext.then_some(true)) {
+        Ok(()) => round(app_handle, state, &config).await,
+        Err(e) => Err(e),
+    };
+
+    let now = now_unix();
+    let mut sync = lock_or_recover(&state.sync.state);
+    match &result {
+        Ok(()) => {
+            sync.last_success_at = Some(now);
+            sync.last_error = None;
+            sync.consecutive_failures = 0;
+            sync.next_attempt_at = 0;
+        }
+        Err(e) => {
+            sync.last_error = Some(e.to_string());
+            sync.consecutive_failures += 1;
+            sync.next_attempt_at = now + retry_delay(sync.consecutive_failures);
+        }
+    }
+    if let Err(e) = save_sync_state(app_handle, &sync) {
+        warn!("Could not write sync.json: {}", e);
+    }
+    result
+}
+
+async fn round(app_handle: &AppHandle, state: &AppStateArc, config: &SyncConfig) -> Result<(), TrackerError> {
+    let network = |e: reqwest::Error| TrackerError::InvalidInput(format!("Sync server: {}", e));
+    let base = config.base_url.trim_end_matches('/');
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().map_err(network)?;
+    let token = match read_token(app_handle)? {
+        Some(token) => token,
+        None => config.legacy_token.clone(),
+    };
+
+    // A day pending since before this run may not have been loaded yet.
+    load_settled_days(state);
+    note_local_changes(state);
+
+    // Pull every day the server has a newer copy of.
+    let index: Vec<RemoteDay> = client
+        .get(format!("{}/days", base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(network)?
+        .json()
+        .await
+        .map_err(network)?;
+    let archived = archived_months(app_handle)?;
+    let mut pulled = 0;
+    for remote in index {
+        let known_at = lock_or_recover(&state.sync.state).synced.get(&remote.date).map(|d| d.updated_at);
+        if known_at.is_some_and(|at| remote.updated_at <= at) {
+            continue;
+        }
+        let envelope: DayEnvelope = client
+            .get(format!("{}/days/{}", base, remote.date))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(network)?
+            .json()
+            .await
+            .map_err(network)?;
+        if envelope.record.date != remote.date {
+            continue;
+        }
+        pulled += usize::from(apply_remote(state, &archived, envelope));
+    }
+    if pulled > 0 {
+        info!("🔄 Pulled {} day(s) from the sync server", pulled);
+        save_state(app_handle, state);
+    }
+
+    // Push everything changed here, including merges made above.
+    let pending: Vec<(String, u64)> = lock_or_recover(&state.sync.state).pending.iter().map(|(d, at)| (d.clone(), *at)).collect();
+    for (date, updated_at) in pending {
+        let Some(record) = lock_or_recover(&state.day_records).get(&date).cloned() else {
+            // Archived or purged since; the server keeps its copy.
+            lock_or_recover(&state.sync.state).pending.remove(&date);
+            continue;
+        };
+        let mut sent = record.clone();
+        if !config.include_titles {
+            sent.laps.iter_mut().for_each(|lap| lap.window_titles.clear());
+        }
+        client
+            .put(format!("{}/days/{}", base, date))
+            .bearer_auth(&token)
+            .json(&DayEnvelope { updated_at, record: sent })
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(network)?;
+        let mut sync = lock_or_recover(&state.sync.state);
+        sync.synced.insert(date.clone(), SyncedDay { fingerprint: day_fingerprint(&record), updated_at });
+        sync.pending.remove(&date);
+    }
+    Ok(())
+}
+
+// Queue every live day whose content differs from the version last synced.
+fn note_local_changes(state: &AppStateArc) {
+    let now = now_unix();
+    let records_guard = lock_or_recover(&state.day_records);
+    let mut sync = lock_or_recover(&state.sync.state);
+    for (date, record) in records_guard.iter() {
+        let fingerprint = day_fingerprint(record);
+        if sync.synced.get(date).map(|d| d.fingerprint) != Some(fingerprint) {
+            sync.pending.entry(date.clone()).or_insert(now);
+        }
+    }
+}
+
+// Take a remote copy into the live records. Days in archived months stay archived.
+// Returns whether anything changed.
+fn apply_remote(state: &AppStateArc, archived: &[String], envelope: DayEnvelope) -> bool {
+    let DayEnvelope { updated_at, record: remote } = envelope;
+    let date = remote.date.clone();
+    let session_day = lock_or_recover(&state.current_session).as_ref().map(|s| s.day_key.clone());
+    let mut records_guard = lock_or_recover(&state.day_records);
+    let mut sync = lock_or_recover(&state.sync.state);
+    let merged = match (records_guard.remove(&date), sync.pending.get(&date).copied()) {
+        (None, _) if archived.iter().any(|month| date.starts_with(month.as_str())) => return false,
+        // The session's day always keeps the local copy, which counts as the newer one.
+        (Some(local), _) if session_day.as_deref() == Some(date.as_str()) => {
+            let merged = merge_day(local, u64::MAX, remote, updated_at);
+            sync.pending.insert(date.clone(), now_unix());
+            merged
+        }
+        // Changed on both sides: the merge goes back up on this round's push.
+        (Some(local), Some(local_at)) => {
+            let remote = settle_remote(remote, Some(&local));
+            let merged = merge_day(local, local_at, remote, updated_at);
+            sync.pending.insert(date.clone(), now_unix());
+            merged
+        }
+        (local, _) => {
+            let taken = settle_remote(remote, local.as_ref());
+            sync.synced.insert(date.clone(), SyncedDay { fingerprint: day_fingerprint(&taken), updated_at });
+            taken
+        }
+    };
+    records_guard.insert(date, merged);
+    true
+}
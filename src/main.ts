@@ -1,5 +1,19 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { ReportsView } from "./reports";
+import type { PauseCause } from "./pause";
+
+interface PomodoroState {
+  phase: 'work' | 'short_break' | 'long_break';
+  phase_remaining_seconds: number;
+  completed_work_intervals: number;
+  config: {
+    work_seconds: number;
+    short_break_seconds: number;
+    long_break_seconds: number;
+    long_break_every: number;
+  };
+}
 
 interface CurrentStatus {
   day_key: string;
@@ -7,19 +21,93 @@ interface CurrentStatus {
   current_lap_start_timestamp: number;
   total_session_duration: number;
   is_active: boolean;
+  active_project: string | null;
+  current_continuous_work_seconds: number;
+  goal_seconds: number | null;
+  goal_progress_percent: number | null;
+  goal_remaining_seconds: number | null;
+  overtime: boolean;
+  pomodoro_state: PomodoroState | null;
+  paused_cause: PauseCause | null;
+  paused_since: number | null;
+  user_paused: boolean;
+  effective_max_continuous_work_secs: number | null;
+  effective_gap_threshold_secs: number;
+  current_lap_note: string | null;
+  seconds_since_last_save: number;
+  excluded_seconds_today: number;
+}
+
+interface Comparison {
+  today_seconds: number;
+  yesterday_seconds: number | null;
+  recent_average_seconds: number | null;
+  vs_yesterday_seconds: number | null;
+  vs_yesterday_percent: number | null;
+  vs_recent_average_seconds: number | null;
+  vs_recent_average_percent: number | null;
 }
 
 interface Lap {
   start_time: number;
   end_time?: number;
   duration?: number;
+  tags: string[];
+  project: string | null;
+  color: string | null;
+  app_breakdown: Record<string, number>;
+  window_titles: string[];
+  note: string | null;
+  is_synthetic: boolean;
+  end_reason: LapEndReason | null;
+  id: string;
+  excluded_seconds: number;
 }
 
+type LapEndReason = 'UserStopped' | 'ScreenLocked' | 'SystemSlept' | 'ClockChanged' | 'DisplaySlept' | 'UserLogout' | 'DayEnded' | 'AutoEnded' | 'LapAdded';
+
 interface DayRecord {
   date: string;
   total_duration: number;
   laps: Lap[];
   is_active: boolean;
+  metadata: Record<string, string>;
+  ended_explicitly: boolean;
+}
+
+// Emitted at startup when the previous day's session was over and got ended.
+interface DayAutoClosed {
+  day_key: string;
+  closed_at: number | null;
+}
+
+interface HealthReport {
+  monitoring_alive: boolean;
+  autosave_alive: boolean;
+  poisoned_lock_recoveries: number;
+}
+
+// What every command rejects with (TrackerError in lib.rs).
+interface TrackerError {
+  code:
+    | 'no_active_session'
+    | 'already_tracking'
+    | 'day_not_found'
+    | 'lap_index_out_of_range'
+    | 'persistence_error'
+    | 'state_poisoned'
+    | 'invalid_input'
+    | 'store_locked'
+    | 'wrong_passphrase';
+  message: string;
+}
+
+function isTrackerError(error: unknown): error is TrackerError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error;
+}
+
+function errorMessage(error: unknown): string {
+  return isTrackerError(error) ? error.message : String(error);
 }
 
 class ScreenTimeTracker {
@@ -33,6 +121,36 @@ class ScreenTimeTracker {
     this.setupEventListeners();
     this.startStatusUpdates();
     this.startScreenLockMonitoring();
+    this.startHealthChecks();
+    this.unlockStoreIfNeeded();
+    this.showDayAutoClosed();
+  }
+
+  // A day closed at startup is announced before this window listens, so ask for it too.
+  private async showDayAutoClosed(): Promise<void> {
+    const closed = await invoke<DayAutoClosed | null>('get_day_auto_closed');
+    if (!closed) return;
+    const at = closed.closed_at ? ` at ${new Date(closed.closed_at * 1000).toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' })}` : '';
+    this.showNotification(`The session for ${closed.day_key} was closed automatically${at}`, 'success');
+    await invoke('acknowledge_day_auto_closed');
+  }
+
+  // An encrypted store loads nothing until it is given the passphrase.
+  private async unlockStoreIfNeeded(): Promise<void> {
+    const status = await invoke<{ encrypted: boolean; locked: boolean }>('get_store_status');
+    if (!status.locked) return;
+    for (;;) {
+      const passphrase = window.prompt('Your tracking data is encrypted. Enter your passphrase to unlock it:');
+      if (passphrase === null) return;
+      try {
+        await invoke('unlock_store', { passphrase });
+        this.loadCurrentStatus();
+        this.showDayAutoClosed();
+        return;
+      } catch (error) {
+        this.showNotification(isTrackerError(error) ? error.message : String(error), 'error');
+      }
+    }
   }
 
   private initializeUI(): void {
@@ -65,6 +183,7 @@ class ScreenTimeTracker {
                 <h2>Today's Total</h2>
                 <div class="timer" id="total-timer">00:00:00</div>
                 <div class="session-info" id="total-info">0 laps completed</div>
+                <div class="session-info" id="total-comparison"></div>
               </div>
             </div>
 
@@ -146,7 +265,12 @@ class ScreenTimeTracker {
       this.showNotification('Day started successfully!', 'success');
       await this.loadCurrentStatus();
     } catch (error) {
-      this.showNotification(`Failed to start day: ${error}`, 'error');
+      if (isTrackerError(error) && error.code === 'already_tracking') {
+        // Started elsewhere (tray, popover, notification) - just catch up.
+        await this.loadCurrentStatus();
+        return;
+      }
+      this.showNotification(`Failed to start day: ${errorMessage(error)}`, 'error');
     }
   }
 
@@ -159,7 +283,7 @@ class ScreenTimeTracker {
       this.hideLapsSection();
       // Reports (which now owns the day-by-day breakdown) refreshes itself on open.
     } catch (error) {
-      this.showNotification(`Failed to end day: ${error}`, 'error');
+      this.showNotification(`Failed to end day: ${errorMessage(error)}`, 'error');
     }
   }
 
@@ -169,7 +293,7 @@ class ScreenTimeTracker {
       this.showNotification('New lap started!', 'success');
       await this.loadCurrentStatus();
     } catch (error) {
-      this.showNotification(`Failed to add lap: ${error}`, 'error');
+      this.showNotification(`Failed to add lap: ${errorMessage(error)}`, 'error');
     }
   }
 
@@ -178,7 +302,7 @@ class ScreenTimeTracker {
       const result = await invoke<string>('test_screen_lock_detection');
       this.showNotification(`Lock Detection Test:\n${result}`, 'success');
     } catch (error) {
-      this.showNotification(`Failed to test lock detection: ${error}`, 'error');
+      this.showNotification(`Failed to test lock detection: ${errorMessage(error)}`, 'error');
     }
   }
 
@@ -188,7 +312,7 @@ class ScreenTimeTracker {
       this.showNotification(`Simulated Lock: ${result}`, 'success');
       await this.loadCurrentStatus();
     } catch (error) {
-      this.showNotification(`Failed to simulate lock: ${error}`, 'error');
+      this.showNotification(`Failed to simulate lock: ${errorMessage(error)}`, 'error');
     }
   }
 
@@ -198,7 +322,7 @@ class ScreenTimeTracker {
       this.showNotification(`Simulated Unlock: ${result}`, 'success');
       await this.loadCurrentStatus();
     } catch (error) {
-      this.showNotification(`Failed to simulate unlock: ${error}`, 'error');
+      this.showNotification(`Failed to simulate unlock: ${errorMessage(error)}`, 'error');
     }
   }
 
@@ -216,7 +340,7 @@ class ScreenTimeTracker {
       // Session is paused, not ended - just update button states
       this.updateButtonStates();
     } catch (error) {
-      this.showNotification(`Failed to stop lap: ${error}`, 'error');
+      this.showNotification(`Failed to stop lap: ${errorMessage(error)}`, 'error');
     }
   }
 
@@ -227,15 +351,7 @@ class ScreenTimeTracker {
 
   private async loadCurrentStatus(): Promise<void> {
     try {
-      this.currentStatus = await invoke<CurrentStatus | null>('get_current_status');
-
-      // Update isTracking state based on backend response
-      this.isTracking = this.currentStatus?.is_active ?? false;
-
-
-      // Update button states when session state changes
-      this.updateButtonStates();
-      this.updateTimerDisplay();
+      this.applyStatus(await invoke<CurrentStatus | null>('get_current_status'));
 
       // Load laps for both active and paused sessions
       if (this.currentStatus) {
@@ -243,11 +359,43 @@ class ScreenTimeTracker {
       } else {
         this.hideLapsSection();
       }
+      await this.loadComparison();
     } catch (error) {
       console.error('Failed to load current status:', error);
     }
   }
 
+  // "▲ 38 min vs yesterday" under the total; blank until there is a yesterday to compare.
+  private async loadComparison(): Promise<void> {
+    const line = document.getElementById('total-comparison');
+    if (!line) return;
+    try {
+      const delta = (await invoke<Comparison>('get_comparison')).vs_yesterday_seconds;
+      line.textContent = delta === null
+        ? ''
+        : `${delta >= 0 ? '▲' : '▼'} ${Math.round(Math.abs(delta) / 60)} min vs yesterday`;
+    } catch (error) {
+      console.error('Failed to load comparison:', error);
+    }
+  }
+
+  private applyStatus(status: CurrentStatus | null): void {
+    this.currentStatus = status;
+
+    // Update isTracking state based on backend response
+    this.isTracking = this.currentStatus?.is_active ?? false;
+
+    // Update button states when session state changes
+    this.updateButtonStates();
+    this.updateTimerDisplay();
+  }
+
+  // " · Project" when a project is active, so the session line shows what is being tracked.
+  private projectSuffix(): string {
+    const project = this.currentStatus?.active_project;
+    return project ? ` · ${project}` : '';
+  }
+
   private updateTimerDisplay(): void {
     const currentTimer = document.getElementById('current-timer');
     const totalTimer = document.getElementById('total-timer');
@@ -272,7 +420,7 @@ class ScreenTimeTracker {
         totalTimer.textContent = this.formatTime(Math.floor(smoothTotal));
       }
       if (sessionInfo) {
-        sessionInfo.textContent = `Active session for ${this.currentStatus.day_key}`;
+        sessionInfo.textContent = `Active session for ${this.currentStatus.day_key}${this.projectSuffix()}`;
       }
       if (totalInfo) {
         totalInfo.textContent = `Total from all laps`;
@@ -290,7 +438,7 @@ class ScreenTimeTracker {
       }
       if (sessionInfo) {
         if (this.currentStatus && !this.currentStatus.is_active) {
-          sessionInfo.textContent = `Paused session for ${this.currentStatus.day_key}`;
+          sessionInfo.textContent = `Paused session for ${this.currentStatus.day_key}${this.projectSuffix()}`;
         } else {
           sessionInfo.textContent = 'No active session';
         }
@@ -336,13 +484,73 @@ class ScreenTimeTracker {
   }
 
   private startStatusUpdates(): void {
-    window.setInterval(async () => {
-      // Always check the backend status to stay in sync
-      await this.loadCurrentStatus();
-    }, 200); // Update 5x per second for smooth display
+    // The backend pushes the live status every second while a session exists.
+    listen<CurrentStatus>('status-updated', (event) => this.applyStatus(event.payload));
+
+    // Discrete changes (including ones from the tray, popover or lock screen) also
+    // change the laps list, so reload everything.
+    for (const name of ['session-started', 'lap-ended', 'session-paused', 'session-resumed', 'day-ended']) {
+      listen(name, () => this.loadCurrentStatus());
+    }
+
+    listen<DayAutoClosed>('day-auto-closed', () => this.showDayAutoClosed());
+
+    // Notifications are turned off for the app, so its alerts land here instead.
+    listen<{ title: string; body: string }>('alert-undelivered', (event) => {
+      this.showNotification(`${event.payload.title}: ${event.payload.body}`, 'success');
+    });
+
+    listen<DayRecord>('day-auto-ended', (event) => {
+      const { date, total_duration } = event.payload;
+      this.showNotification(`${date} was ended automatically with ${this.formatTime(total_duration)} tracked`, 'success');
+    });
+
+    // "Review" on the welcome-back notification: show the laps around the gap.
+    listen<{ gap_seconds: number }>('review-gap', (event) => {
+      const minutes = Math.round(event.payload.gap_seconds / 60);
+      this.showNotification(`You were away for ${minutes}m; that time was not tracked`, 'success');
+      this.loadCurrentStatus();
+    });
+
+    // Laps are stamped in wall time, so a clock or timezone change can make them look odd.
+    listen<{ skew_seconds: number; lap_split: boolean }>('clock-changed', (event) => {
+      const minutes = Math.round(Math.abs(event.payload.skew_seconds) / 60);
+      const split = event.payload.lap_split ? '; the current lap was split there' : '';
+      this.showNotification(`The system clock moved ${minutes}m${split}`, 'success');
+      this.loadCurrentStatus();
+    });
+
+    listen<{ previous_offset_secs: number; offset_secs: number; day_key: string | null }>('timezone-changed', (event) => {
+      const hours = (event.payload.offset_secs - event.payload.previous_offset_secs) / 3600;
+      this.showNotification(`Timezone changed by ${hours > 0 ? '+' : ''}${hours}h; lap times are shown in the new local time`, 'success');
+      this.loadCurrentStatus();
+    });
   }
 
 
+  // A panicked background loop stops tracking while this window keeps responding, so
+  // check now and then and say so instead of silently showing a frozen total.
+  private startHealthChecks(): void {
+    // Raised (throttled) by the backend when state.json cannot be written.
+    listen<TrackerError>('persistence-error', (event) => {
+      this.showNotification(`Your tracked time is not being saved: ${event.payload.message}`, 'error');
+    });
+
+    let warned = false;
+    window.setInterval(async () => {
+      try {
+        const health = await invoke<HealthReport>('get_health');
+        const healthy = health.monitoring_alive && health.autosave_alive;
+        if (!healthy && !warned) {
+          this.showNotification('Background tracking has stopped - please restart the app', 'error');
+        }
+        warned = !healthy;
+      } catch (error) {
+        console.error('Failed to check health:', error);
+      }
+    }, 60000);
+  }
+
   private async loadCurrentDayLaps(): Promise<void> {
     try {
       const laps = await invoke<Lap[]>('get_current_day_laps');
@@ -456,7 +664,7 @@ class ScreenTimeTracker {
         await this.loadCurrentStatus();
       } catch (error) {
         console.error('Failed to start day from notification:', error);
-        this.showNotification(`Failed to start tracking: ${error}`, 'error');
+        this.showNotification(`Failed to start tracking: ${errorMessage(error)}`, 'error');
       }
     });
   }
@@ -0,0 +1,3 @@
+// Why a session is paused; mirrors the backend's PauseCause (snake_case on the wire).
+// Shared by the main window and the popover so a new cause has to be handled in both.
+export type PauseCause = 'user' | 'lock' | 'sleep' | 'display_sleep' | 'logout' | 'restart';
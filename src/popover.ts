@@ -1,4 +1,18 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import type { PauseCause } from "./pause";
+
+interface PomodoroState {
+  phase: 'work' | 'short_break' | 'long_break';
+  phase_remaining_seconds: number;
+  completed_work_intervals: number;
+  config: {
+    work_seconds: number;
+    short_break_seconds: number;
+    long_break_seconds: number;
+    long_break_every: number;
+  };
+}
 
 interface CurrentStatus {
   day_key: string;
@@ -6,6 +20,18 @@ interface CurrentStatus {
   current_lap_start_timestamp: number;
   total_session_duration: number;
   is_active: boolean;
+  active_project: string | null;
+  current_continuous_work_seconds: number;
+  goal_seconds: number | null;
+  goal_progress_percent: number | null;
+  goal_remaining_seconds: number | null;
+  overtime: boolean;
+  pomodoro_state: PomodoroState | null;
+  paused_cause: PauseCause | null;
+  paused_since: number | null;
+  user_paused: boolean;
+  effective_max_continuous_work_secs: number | null;
+  effective_gap_threshold_secs: number;
 }
 
 // Compact menu-bar popover: a quick glance at today's timer plus the essential
@@ -15,7 +41,7 @@ class Popover {
 
   constructor() {
     this.render();
-    this.startPolling();
+    this.startListening();
   }
 
   private render(): void {
@@ -47,10 +73,16 @@ class Popover {
     document.getElementById("pop-expand")?.addEventListener("click", () => this.expand());
   }
 
-  private startPolling(): void {
+  private startListening(): void {
     this.refresh();
-    // Only runs while the popover window is open, so 1s is plenty and cheap.
-    window.setInterval(() => this.refresh(), 1000);
+    listen<CurrentStatus>("status-updated", (event) => {
+      this.status = event.payload;
+      this.update();
+    });
+    // status-updated stops once the day ends, so pick up the cleared session here.
+    for (const name of ["session-started", "session-paused", "session-resumed", "day-ended"]) {
+      listen(name, () => this.refresh());
+    }
   }
 
   private async refresh(): Promise<void> {
@@ -89,11 +121,27 @@ class Popover {
 
     if (lapTime) lapTime.textContent = this.formatTime(live);
     if (totalTime) totalTime.textContent = this.formatTime(total);
-    if (status) status.textContent = active ? "Tracking · today" : "Paused · today";
+    if (status) status.textContent = active ? "Tracking · today" : this.pausedLabel(this.status);
     if (toggle) toggle.textContent = active ? "Pause" : "Resume";
     if (lapBtn) lapBtn.disabled = false;
   }
 
+  // "Paused 14 min ago by screen lock"
+  private pausedLabel(status: CurrentStatus): string {
+    const causes: Record<PauseCause, string> = {
+      user: "you",
+      lock: "screen lock",
+      sleep: "sleep",
+      display_sleep: "display sleep",
+      logout: "logout",
+      restart: "app restart",
+    };
+    if (!status.paused_cause || !status.paused_since) return "Paused · today";
+    const minutes = Math.max(0, Math.floor((Date.now() / 1000 - status.paused_since) / 60));
+    const ago = minutes === 0 ? "just now" : `${minutes} min ago`;
+    return `Paused ${ago} by ${causes[status.paused_cause]}`;
+  }
+
   private async toggle(): Promise<void> {
     try {
       if (this.status?.is_active) {
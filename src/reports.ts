@@ -16,6 +16,13 @@ interface Lap {
   start_time: number;
   end_time?: number;
   duration?: number;
+  tags: string[];
+  project: string | null;
+  color: string | null;
+  app_breakdown: Record<string, number>;
+  window_titles: string[];
+  note: string | null;
+  is_synthetic: boolean;
 }
 
 interface DayRecord {
@@ -23,6 +30,7 @@ interface DayRecord {
   total_duration: number;
   laps: Lap[];
   is_active: boolean;
+  metadata: Record<string, string>;
 }
 
 type Mode = "week" | "month" | "year";
@@ -238,7 +246,7 @@ export class ReportsView {
 
     for (const rec of this.records) {
       for (const lap of rec.laps) {
-        if (lap.duration == null || lap.end_time == null) continue;
+        if (lap.duration == null || lap.end_time == null || lap.is_synthetic) continue;
         if (lap.duration < minLapSeconds) continue;
         let s = lap.start_time * 1000;
         let e = lap.end_time * 1000;
@@ -267,7 +275,7 @@ export class ReportsView {
       const rec = this.byDate.get(d.key);
       if (!rec) continue;
       for (const lap of rec.laps) {
-        if (lap.duration != null && lap.duration > 0) out.push({ lap, dayKey: d.key });
+        if (lap.duration != null && lap.duration > 0 && !lap.is_synthetic) out.push({ lap, dayKey: d.key });
       }
     }
     return out;
@@ -483,7 +491,7 @@ export class ReportsView {
   private renderDayDetail(key: string): string {
     const rec = this.byDate.get(key);
     const laps = rec?.laps ?? [];
-    const completed = laps.filter((l) => l.duration != null && l.duration > 0);
+    const completed = laps.filter((l) => l.duration != null && l.duration > 0 && !l.is_synthetic);
     const total = rec?.total_duration ?? 0;
     const activeBadge = rec?.is_active ? '<span class="history-badge active">Active</span>' : "";
 
@@ -497,7 +505,7 @@ export class ReportsView {
               const ongoing = lap.duration == null;
               const dur = ongoing ? "Ongoing" : ReportsView.fmtClock(lap.duration || 0);
               return `<div class="history-lap ${ongoing ? "ongoing" : ""}">
-                <span class="history-lap-num">Lap ${i + 1}</span>
+                <span class="history-lap-num">${lap.is_synthetic ? lap.note ?? "Break" : `Lap ${i + 1}`}</span>
                 <span class="history-lap-period">${start.toLocaleTimeString()} - ${end ? end.toLocaleTimeString() : "Ongoing"}</span>
                 <span class="history-lap-duration">${dur}</span>
               </div>`;